@@ -13,6 +13,7 @@ use amethyst_core::{
         Builder, Dispatcher, DispatcherBuilder, Read, ReadExpect, System, VecStorage, World,
         WorldExt, Write,
     },
+    shrev::EventChannel,
     Time,
 };
 use amethyst_error::{format_err, Error, ResultExt};
@@ -33,6 +34,7 @@ impl App {
         world.register::<MeshHandle>();
 
         world.insert(AssetStorage::<MeshAsset>::new());
+        world.insert(EventChannel::<AssetEvent<MeshAsset>>::new());
         world.insert(Loader::new(path, pool.clone()));
         world.insert(Time::default());
         world.insert(pool);
@@ -102,14 +104,16 @@ impl<'a> System<'a> for RenderingSystem {
         Read<'a, Time>,
         ReadExpect<'a, Arc<ThreadPool>>,
         Option<Read<'a, HotReloadStrategy>>,
+        Write<'a, EventChannel<AssetEvent<MeshAsset>>>,
         /* texture storage, transforms, .. */
     );
 
-    fn run(&mut self, (mut mesh_storage, time, pool, strategy): Self::SystemData) {
+    fn run(&mut self, (mut mesh_storage, time, pool, strategy, mut events): Self::SystemData) {
         use std::ops::Deref;
 
         let strategy = strategy.as_ref().map(Deref::deref);
 
+        mesh_storage.track_events();
         mesh_storage.process(
             |vertex_data| {
                 // Upload vertex data to GPU and give back an asset
@@ -120,6 +124,7 @@ impl<'a> System<'a> for RenderingSystem {
             &**pool,
             strategy,
         );
+        mesh_storage.write_events(&mut events);
     }
 }
 
@@ -5,7 +5,7 @@ use std::{str::from_utf8, sync::Arc, thread::sleep, time::Duration};
 use rayon::ThreadPoolBuilder;
 
 use amethyst_assets::*;
-use amethyst_core::ecs::prelude::VecStorage;
+use amethyst_core::{ecs::prelude::VecStorage, shrev::EventChannel};
 use amethyst_error::Error;
 
 #[derive(Clone, Debug)]
@@ -45,6 +45,9 @@ fn main() {
 
     let loader = Loader::new(&path, pool.clone());
     let mut storage: AssetStorage<DummyAsset> = AssetStorage::new();
+    storage.track_events();
+    let mut events = EventChannel::new();
+    let mut reader = events.register_reader();
 
     let mut progress = ProgressCounter::new();
 
@@ -77,6 +80,10 @@ fn main() {
             &*pool,
             Some(&strategy),
         );
+        storage.write_events(&mut events);
+        for event in events.read(&mut reader) {
+            println!("event: {:?}", event);
+        }
     }
 
     println!("dummy: {:?}", storage.get(&dummy));
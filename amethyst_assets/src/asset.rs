@@ -1,4 +1,6 @@
-use crate::{storage::ProcessingState, FormatRegisteredData, Handle, Reload, SingleFile, Source};
+use crate::{
+    storage::ProcessingState, AssetKey, FormatRegisteredData, Handle, Reload, SingleFile, Source,
+};
 use amethyst_core::ecs::storage::UnprotectedStorage;
 use amethyst_error::{Error, ResultExt};
 use std::{fmt::Debug, ops::Deref, sync::Arc};
@@ -29,6 +31,22 @@ pub trait Asset: Send + Sync + 'static {
 
     /// The ECS storage type to be used. You'll want to use `DenseVecStorage` in most cases.
     type HandleStorage: UnprotectedStorage<Handle<Self>> + Send + Sync;
+
+    /// Returns the assets this asset depends on.
+    ///
+    /// When one of them is hot reloaded, this asset is reloaded as well.
+    fn dependencies(&self) -> Vec<AssetKey> {
+        Vec::new()
+    }
+
+    /// Returns an estimate of the memory used by this asset in bytes,
+    /// including the memory it owns on the heap.
+    ///
+    /// Reported by `AssetStorage::usage` and checked against its memory budget.
+    /// Defaults to the size of the asset type.
+    fn size_hint(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
 }
 
 /// Defines a way to process asset's data into the asset. This allows
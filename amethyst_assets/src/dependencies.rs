@@ -0,0 +1,346 @@
+//! Tracks which loaded assets depend on which, so hot reloading cascades to dependents.
+
+use std::{any::TypeId, sync::Arc};
+
+use fnv::{FnvHashMap, FnvHashSet};
+use parking_lot::Mutex;
+
+use crate::{Asset, Handle};
+
+/// Identifies a loaded asset across all asset storages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AssetKey {
+    type_id: TypeId,
+    type_name: &'static str,
+    id: u32,
+}
+
+impl AssetKey {
+    /// Returns the key of the asset the handle points at.
+    pub fn of<A: Asset>(handle: &Handle<A>) -> Self {
+        Self::new::<A>(handle.id())
+    }
+
+    pub(crate) fn new<A: Asset>(id: u32) -> Self {
+        AssetKey {
+            type_id: TypeId::of::<A>(),
+            type_name: A::NAME,
+            id,
+        }
+    }
+
+    /// Returns the `Asset::NAME` of the asset type.
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Returns the id of the handle.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+/// An asset which was hot reloaded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReloadedAsset {
+    /// The key of the asset.
+    pub key: AssetKey,
+    /// The name the asset was loaded with, usually its path.
+    pub name: String,
+}
+
+/// Written to the `EventChannel<HotReloadEvent>` by the `HotReloadSystem` once all reloads
+/// caused by changed assets have finished.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HotReloadEvent {
+    /// The assets which were reloaded because they changed, in the order they finished.
+    pub changed: Vec<ReloadedAsset>,
+    /// The assets which were reloaded because something they depend on changed,
+    /// every asset after its dependencies.
+    pub dependents: Vec<ReloadedAsset>,
+}
+
+/// The dependencies between loaded assets.
+///
+/// An asset depends on the assets returned by `Asset::dependencies` when it finished loading,
+/// for a `Prefab` these are all sub assets loaded by `PrefabData::load_sub_assets`.
+/// Dependencies are recorded by the asset storages while a `HotReloadStrategy` is present,
+/// which shares this graph among its clones.
+///
+/// Once an asset was hot reloaded, its dependents are reloaded as well, each one only after
+/// all of its reloading dependencies have finished.
+#[derive(Clone, Debug, Default)]
+pub struct AssetDependencies {
+    graph: Arc<Mutex<Graph>>,
+}
+
+#[derive(Debug, Default)]
+struct Graph {
+    names: FnvHashMap<AssetKey, String>,
+    dependencies: FnvHashMap<AssetKey, Vec<AssetKey>>,
+    dependents: FnvHashMap<AssetKey, FnvHashSet<AssetKey>>,
+    // dependents waiting to be reloaded
+    pending: FnvHashSet<AssetKey>,
+    in_flight: FnvHashSet<AssetKey>,
+    // the in flight reloads which were caused by a dependency
+    cascading: FnvHashSet<AssetKey>,
+    changed: Vec<ReloadedAsset>,
+    reloaded_dependents: Vec<ReloadedAsset>,
+}
+
+impl AssetDependencies {
+    /// Returns the assets the given asset depends on.
+    pub fn dependencies_of(&self, key: AssetKey) -> Vec<AssetKey> {
+        self.graph
+            .lock()
+            .dependencies
+            .get(&key)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Returns the assets which directly depend on the given asset.
+    pub fn dependents_of(&self, key: AssetKey) -> Vec<AssetKey> {
+        self.graph
+            .lock()
+            .dependents
+            .get(&key)
+            .map(|dependents| dependents.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the name the given asset was loaded with.
+    pub fn name(&self, key: AssetKey) -> Option<String> {
+        self.graph.lock().names.get(&key).cloned()
+    }
+
+    /// Records a loaded asset, replacing the dependencies recorded before.
+    pub(crate) fn record(&self, key: AssetKey, name: &str, dependencies: Vec<AssetKey>) {
+        let mut graph = self.graph.lock();
+        graph.names.insert(key, name.to_owned());
+
+        let dependencies = dependencies
+            .into_iter()
+            .filter(|dependency| *dependency != key)
+            .collect::<Vec<_>>();
+        for dependency in &dependencies {
+            graph
+                .dependents
+                .entry(*dependency)
+                .or_insert_with(FnvHashSet::default)
+                .insert(key);
+        }
+        if let Some(old) = graph.dependencies.insert(key, dependencies) {
+            for dependency in old {
+                let still_depends = graph.dependencies[&key].contains(&dependency);
+                if !still_depends {
+                    graph.unlink(dependency, key);
+                }
+            }
+        }
+    }
+
+    /// Forgets an asset which was removed from its storage.
+    pub(crate) fn remove(&self, key: AssetKey) {
+        let mut graph = self.graph.lock();
+        graph.names.remove(&key);
+        for dependency in graph.dependencies.remove(&key).unwrap_or_default() {
+            graph.unlink(dependency, key);
+        }
+        for dependent in graph.dependents.remove(&key).unwrap_or_default() {
+            if let Some(dependencies) = graph.dependencies.get_mut(&dependent) {
+                dependencies.retain(|dependency| *dependency != key);
+            }
+        }
+        graph.pending.remove(&key);
+        graph.in_flight.remove(&key);
+        graph.cascading.remove(&key);
+    }
+
+    /// Marks an asset whose source changed as reloading.
+    pub(crate) fn reload_started(&self, key: AssetKey) {
+        self.graph.lock().in_flight.insert(key);
+    }
+
+    /// Marks a reload as finished, queueing the dependents of the asset if it succeeded.
+    pub(crate) fn reload_finished(&self, key: AssetKey, success: bool) {
+        let mut graph = self.graph.lock();
+        graph.in_flight.remove(&key);
+        let cascaded = graph.cascading.remove(&key);
+        if !success {
+            return;
+        }
+
+        let reloaded = ReloadedAsset {
+            key,
+            name: graph.names.get(&key).cloned().unwrap_or_default(),
+        };
+        if cascaded {
+            graph.reloaded_dependents.push(reloaded);
+        } else {
+            graph.changed.push(reloaded);
+        }
+
+        let dependents = graph.dependents.get(&key).cloned().unwrap_or_default();
+        graph.pending.extend(dependents);
+    }
+
+    /// Returns the handle ids of the waiting dependents of asset type `A` which
+    /// no longer wait for one of their dependencies, marking them as reloading.
+    pub(crate) fn take_ready<A: Asset>(&self) -> Vec<u32> {
+        let mut graph = self.graph.lock();
+        if graph.pending.is_empty() {
+            return Vec::new();
+        }
+
+        let type_id = TypeId::of::<A>();
+        let ready = graph
+            .pending
+            .iter()
+            .filter(|key| key.type_id == type_id && !graph.is_blocked(**key))
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in &ready {
+            graph.pending.remove(key);
+            graph.in_flight.insert(*key);
+            graph.cascading.insert(*key);
+        }
+
+        ready.into_iter().map(|key| key.id).collect()
+    }
+
+    /// Returns the reloaded assets once all reloads have finished.
+    pub(crate) fn take_event(&self) -> Option<HotReloadEvent> {
+        let mut graph = self.graph.lock();
+        if !graph.pending.is_empty()
+            || !graph.in_flight.is_empty()
+            || (graph.changed.is_empty() && graph.reloaded_dependents.is_empty())
+        {
+            return None;
+        }
+
+        Some(HotReloadEvent {
+            changed: graph.changed.drain(..).collect(),
+            dependents: graph.reloaded_dependents.drain(..).collect(),
+        })
+    }
+}
+
+impl Graph {
+    fn unlink(&mut self, dependency: AssetKey, dependent: AssetKey) {
+        if let Some(dependents) = self.dependents.get_mut(&dependency) {
+            dependents.remove(&dependent);
+            if dependents.is_empty() {
+                self.dependents.remove(&dependency);
+            }
+        }
+    }
+
+    /// Returns `true` if `key` transitively depends on `other`.
+    fn depends_on(&self, key: AssetKey, other: AssetKey) -> bool {
+        let mut visited = FnvHashSet::default();
+        let mut stack = self.dependencies.get(&key).cloned().unwrap_or_default();
+        while let Some(next) = stack.pop() {
+            if next == other {
+                return true;
+            }
+            if visited.insert(next) {
+                if let Some(dependencies) = self.dependencies.get(&next) {
+                    stack.extend(dependencies);
+                }
+            }
+        }
+        false
+    }
+
+    /// A waiting dependent is blocked while it is reloading or one of its dependencies is
+    /// waiting or reloading. Dependencies in a cycle with the dependent don't block it.
+    fn is_blocked(&self, key: AssetKey) -> bool {
+        self.in_flight.contains(&key)
+            || self.pending.iter().chain(&self.in_flight).any(|&other| {
+                other != key && self.depends_on(key, other) && !self.depends_on(other, key)
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use amethyst_core::ecs::prelude::VecStorage;
+
+    use crate::{Asset, Handle};
+
+    use super::{AssetDependencies, AssetKey, HotReloadEvent, ReloadedAsset};
+
+    struct Texture;
+    struct Prefab;
+
+    impl Asset for Texture {
+        const NAME: &'static str = "Texture";
+        type Data = ();
+        type HandleStorage = VecStorage<Handle<Self>>;
+    }
+
+    impl Asset for Prefab {
+        const NAME: &'static str = "Prefab";
+        type Data = ();
+        type HandleStorage = VecStorage<Handle<Self>>;
+    }
+
+    fn reloaded(key: AssetKey, name: &str) -> ReloadedAsset {
+        ReloadedAsset {
+            key,
+            name: name.to_owned(),
+        }
+    }
+
+    #[test]
+    fn dependents_reload_after_their_dependencies() {
+        let texture = AssetKey::new::<Texture>(0);
+        let level = AssetKey::new::<Prefab>(0);
+        let player = AssetKey::new::<Prefab>(1);
+
+        // the level uses the texture directly and through the player
+        let dependencies = AssetDependencies::default();
+        dependencies.record(texture, "player.png", vec![]);
+        dependencies.record(player, "player.ron", vec![texture]);
+        dependencies.record(level, "level.ron", vec![texture, player]);
+        assert_eq!(dependencies.dependents_of(player), vec![level]);
+
+        dependencies.reload_started(texture);
+        assert!(dependencies.take_ready::<Prefab>().is_empty());
+        dependencies.reload_finished(texture, true);
+        assert!(dependencies.take_ready::<Texture>().is_empty());
+        assert_eq!(dependencies.take_ready::<Prefab>(), vec![1]);
+        assert!(dependencies.take_ready::<Prefab>().is_empty());
+        assert_eq!(dependencies.take_event(), None);
+
+        dependencies.reload_finished(player, true);
+        assert_eq!(dependencies.take_ready::<Prefab>(), vec![0]);
+        dependencies.reload_finished(level, true);
+        assert!(dependencies.take_ready::<Prefab>().is_empty());
+
+        assert_eq!(
+            dependencies.take_event(),
+            Some(HotReloadEvent {
+                changed: vec![reloaded(texture, "player.png")],
+                dependents: vec![reloaded(player, "player.ron"), reloaded(level, "level.ron")],
+            })
+        );
+        assert_eq!(dependencies.take_event(), None);
+    }
+
+    #[test]
+    fn removed_assets_are_forgotten() {
+        let texture = AssetKey::new::<Texture>(0);
+        let player = AssetKey::new::<Prefab>(0);
+
+        let dependencies = AssetDependencies::default();
+        dependencies.record(texture, "player.png", vec![]);
+        dependencies.record(player, "player.ron", vec![texture]);
+        dependencies.remove(player);
+
+        assert!(dependencies.dependents_of(texture).is_empty());
+        dependencies.reload_finished(texture, true);
+        assert!(dependencies.take_ready::<Prefab>().is_empty());
+    }
+}
@@ -10,6 +10,8 @@ pub enum Error {
     Format(&'static str),
     #[error(display = "Asset was loaded but no handle to it was saved.")]
     UnusedHandle,
+    #[error(display = "Loading was cancelled because every handle to the asset was dropped.")]
+    Cancelled,
     #[error(display = "Some error has occurred")]
     #[doc(hidden)]
     __Nonexhaustive,
@@ -5,7 +5,7 @@ use amethyst_core::ecs::{
     Read, ReadExpect, World,
 };
 
-use crate::{Asset, AssetStorage, Format, Handle, Loader, Progress};
+use crate::{Asset, AssetStorage, Format, Handle, LoadPriority, Loader, Progress};
 
 /// Helper type for loading assets
 #[derive(SystemData)]
@@ -34,6 +34,25 @@ where
         self.loader.load(name, format, progress, &*self.storage)
     }
 
+    /// Loads an asset with a given format and priority from the default (directory) source.
+    ///
+    /// See `Loader::load_from_with_priority` for more information.
+    pub fn load_with_priority<F, N, P>(
+        &self,
+        name: N,
+        format: F,
+        priority: LoadPriority,
+        progress: P,
+    ) -> Handle<A>
+    where
+        F: Format<A::Data>,
+        N: Into<String>,
+        P: Progress,
+    {
+        self.loader
+            .load_with_priority(name, format, priority, progress, &*self.storage)
+    }
+
     /// Loads an asset with a given id and format from a custom source.
     /// The actual work is done in a worker thread, thus this method immediately returns a handle.
     ///
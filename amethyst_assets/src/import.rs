@@ -0,0 +1,534 @@
+//! Imports assets ahead of time and caches the resulting `Asset::Data`.
+
+use std::{
+    any::{Any, TypeId},
+    fs,
+    hash::Hasher,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use fnv::{FnvHashMap, FnvHasher};
+use log::{debug, warn};
+use serde::{de::DeserializeOwned, Serialize};
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use amethyst_error::{format_err, Error, ResultExt};
+
+use crate::{
+    error, source::files_below, Directory, Format, FormatValue, Reload, SingleFile, Source,
+};
+
+/// Identifies a cached artifact.
+const MAGIC: [u8; 4] = *b"AMIC";
+/// The version of the artifact layout, part of every key.
+const CACHE_VERSION: u16 = 1;
+/// The version of the engine, part of every key so an engine update rebuilds the cache.
+const ENGINE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Converts data of a registered type from and to the bytes of an artifact.
+struct Codec<D> {
+    name: &'static str,
+    serialize: fn(&D) -> Result<Vec<u8>, Error>,
+    deserialize: fn(&[u8]) -> Result<D, Error>,
+}
+
+fn serialize_data<D: Serialize>(data: &D) -> Result<Vec<u8>, Error> {
+    Ok(bincode::serialize(data)?)
+}
+
+fn deserialize_data<D: DeserializeOwned>(bytes: &[u8]) -> Result<D, Error> {
+    Ok(bincode::deserialize(bytes)?)
+}
+
+/// A cache of imported asset data on disk.
+///
+/// Only the data types registered with `with_data` are cached. An artifact is keyed by a hash of
+/// the bytes of the source file, the name and options of the format, the name of the data type
+/// and the engine version, so changing any of them imports the asset again.
+///
+/// Once set with `Loader::set_import_cache`, the `Loader` uses an up to date artifact instead
+/// of running `Format::import`, and caches the data of assets it had to import.
+/// The cache can be built ahead of time with an `ImportPipeline`.
+///
+/// Only the file with the name of the asset is hashed, formats which read other files as well
+/// should not be cached.
+pub struct ImportCache {
+    dir: PathBuf,
+    codecs: FnvHashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl ImportCache {
+    /// Creates a cache storing its artifacts in the given directory.
+    pub fn new<P>(dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        ImportCache {
+            dir: dir.into(),
+            codecs: Default::default(),
+        }
+    }
+
+    /// Caches data of type `D`, identified by the given name.
+    ///
+    /// The name is part of the key of the artifacts, so the loading game
+    /// and the `ImportPipeline` building the cache have to use the same one.
+    pub fn with_data<D>(mut self, name: &'static str) -> Self
+    where
+        D: Serialize + DeserializeOwned + Send + Sync + 'static,
+    {
+        self.register::<D>(name);
+        self
+    }
+
+    /// Caches data of type `D`, identified by the given name.
+    pub fn register<D>(&mut self, name: &'static str)
+    where
+        D: Serialize + DeserializeOwned + Send + Sync + 'static,
+    {
+        let codec = Codec::<D> {
+            name,
+            serialize: serialize_data::<D>,
+            deserialize: deserialize_data::<D>,
+        };
+        self.codecs.insert(TypeId::of::<D>(), Box::new(codec));
+    }
+
+    /// Returns `true` if data of type `D` is cached.
+    pub fn caches<D: 'static>(&self) -> bool {
+        self.codecs.contains_key(&TypeId::of::<D>())
+    }
+
+    /// Returns the directory of the artifacts.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn codec<D: 'static>(&self) -> Option<&Codec<D>> {
+        self.codecs
+            .get(&TypeId::of::<D>())
+            .and_then(|codec| codec.downcast_ref())
+    }
+
+    /// Imports an asset, using its artifact if it is up to date and caching the data otherwise.
+    ///
+    /// Assets whose data type isn't cached are imported with the format.
+    pub(crate) fn import<D, F>(
+        &self,
+        name: String,
+        source: Arc<dyn Source>,
+        format: &F,
+        create_reload: Option<Box<dyn Format<D>>>,
+    ) -> Result<FormatValue<D>, Error>
+    where
+        D: 'static,
+        F: Format<D>,
+    {
+        #[cfg(feature = "profiler")]
+        profile_scope!("import_cached_asset");
+
+        let codec = match self.codec::<D>() {
+            Some(codec) => codec,
+            None => return format.import(name, source, create_reload),
+        };
+
+        let (bytes, modified) = source
+            .load_with_metadata(&name)
+            .with_context(|_| error::Error::Source)?;
+        let key = key(&bytes, format, codec.name);
+
+        match self.read(key, codec) {
+            Ok(data) => {
+                debug!("Using the cached import of {:?}", name);
+                let reload = create_reload.map(|format| {
+                    Box::new(SingleFile::new(format, modified, name, source)) as Box<dyn Reload<D>>
+                });
+                return Ok(FormatValue { data, reload });
+            }
+            Err(e) => debug!("No cached import of {:?}: {}", name, e),
+        }
+
+        let source = Preloaded::new(name.clone(), bytes, modified, source);
+        let value = format.import(name.clone(), source, create_reload)?;
+        if let Err(e) = self.write(key, codec, &value.data) {
+            warn!("Failed to cache the import of {:?}: {}", name, e);
+        }
+
+        Ok(value)
+    }
+
+    /// Imports an asset into the cache unless its artifact is up to date.
+    ///
+    /// Returns `true` if the asset was imported.
+    fn prebuild<D, F>(&self, name: &str, source: Arc<dyn Source>, format: &F) -> Result<bool, Error>
+    where
+        D: 'static,
+        F: Format<D>,
+    {
+        let codec = self
+            .codec::<D>()
+            .ok_or_else(|| format_err!("Data of {:?} is not cached", name))?;
+        let (bytes, modified) = source
+            .load_with_metadata(name)
+            .with_context(|_| error::Error::Source)?;
+        let key = key(&bytes, format, codec.name);
+        if self.artifact(key).is_file() {
+            return Ok(false);
+        }
+
+        let source = Preloaded::new(name.to_owned(), bytes, modified, source);
+        let value = format.import(name.to_owned(), source, None)?;
+        self.write(key, codec, &value.data)?;
+
+        Ok(true)
+    }
+
+    fn artifact(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{:016x}.bin", key))
+    }
+
+    fn read<D>(&self, key: u64, codec: &Codec<D>) -> Result<D, Error> {
+        let loc = self.artifact(key);
+        let bytes =
+            fs::read(&loc).with_context(|_| format_err!("Failed to read artifact {:?}", loc))?;
+
+        let header = MAGIC.len() + 2 + 8;
+        if bytes.len() < header
+            || bytes[..4] != MAGIC
+            || bytes[4..6] != CACHE_VERSION.to_le_bytes()
+            || bytes[6..14] != key.to_le_bytes()
+        {
+            return Err(format_err!("Artifact {:?} is invalid", loc));
+        }
+
+        let mut data = Vec::new();
+        DeflateDecoder::new(&bytes[header..])
+            .read_to_end(&mut data)
+            .with_context(|_| format_err!("Failed to inflate artifact {:?}", loc))?;
+        (codec.deserialize)(&data)
+    }
+
+    fn write<D>(&self, key: u64, codec: &Codec<D>, data: &D) -> Result<(), Error> {
+        let data = (codec.serialize)(data)?;
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(&data)?;
+        let compressed = encoder.finish()?;
+
+        let mut bytes = Vec::with_capacity(14 + compressed.len());
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&CACHE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&key.to_le_bytes());
+        bytes.extend_from_slice(&compressed);
+
+        // written next to the artifact and renamed, so readers never see a partial artifact
+        fs::create_dir_all(&self.dir)
+            .with_context(|_| format_err!("Failed to create cache directory {:?}", self.dir))?;
+        let loc = self.artifact(key);
+        let partial = loc.with_extension(format!(
+            "{}.{}.partial",
+            process::id(),
+            NEXT_PARTIAL.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&partial, &bytes)
+            .and_then(|_| fs::rename(&partial, &loc))
+            .with_context(|_| format_err!("Failed to write artifact {:?}", loc))?;
+
+        Ok(())
+    }
+}
+
+/// Numbers the partial artifacts of this process, so concurrent writes of the same artifact
+/// don't share a file.
+static NEXT_PARTIAL: AtomicUsize = AtomicUsize::new(0);
+
+/// A source handing out the already read bytes of an asset the first time they are loaded.
+///
+/// Lets the format import the bytes which were hashed for the key without reading the file
+/// again, while a `Reload` created by the format still reads the current file.
+struct Preloaded {
+    name: String,
+    bytes: Mutex<Option<(Vec<u8>, u64)>>,
+    inner: Arc<dyn Source>,
+}
+
+impl Preloaded {
+    fn new(name: String, bytes: Vec<u8>, modified: u64, inner: Arc<dyn Source>) -> Arc<dyn Source> {
+        Arc::new(Preloaded {
+            name,
+            bytes: Mutex::new(Some((bytes, modified))),
+            inner,
+        })
+    }
+
+    fn take(&self, path: &str) -> Option<(Vec<u8>, u64)> {
+        if path == self.name {
+            self.bytes.lock().expect("Preloaded bytes poisoned").take()
+        } else {
+            None
+        }
+    }
+}
+
+impl Source for Preloaded {
+    fn modified(&self, path: &str) -> Result<u64, Error> {
+        self.inner.modified(path)
+    }
+
+    fn load(&self, path: &str) -> Result<Vec<u8>, Error> {
+        match self.take(path) {
+            Some((bytes, _)) => Ok(bytes),
+            None => self.inner.load(path),
+        }
+    }
+
+    fn load_with_metadata(&self, path: &str) -> Result<(Vec<u8>, u64), Error> {
+        match self.take(path) {
+            Some(loaded) => Ok(loaded),
+            None => self.inner.load_with_metadata(path),
+        }
+    }
+}
+
+/// Hashes everything an import depends on.
+fn key<D: 'static, F: Format<D>>(bytes: &[u8], format: &F, data_name: &str) -> u64 {
+    let mut hasher = FnvHasher::default();
+    for part in &[
+        ENGINE_VERSION.as_bytes(),
+        &CACHE_VERSION.to_le_bytes()[..],
+        data_name.as_bytes(),
+        format.name().as_bytes(),
+        format!("{:?}", format).as_bytes(),
+        bytes,
+    ] {
+        hasher.write(&(part.len() as u64).to_le_bytes());
+        hasher.write(part);
+    }
+    hasher.finish()
+}
+
+type Importer = Box<dyn Fn(&ImportCache, &str, Arc<dyn Source>) -> Result<bool, Error>>;
+
+/// Builds an `ImportCache` ahead of time, so the assets don't have to be imported at runtime.
+///
+/// Every file with a registered extension is imported with the format of the extension.
+///
+/// ```no_run
+/// use amethyst_assets::{ImportCache, ImportPipeline, RonFormat};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Deserialize, Serialize)]
+/// struct Level {
+///     enemies: Vec<String>,
+/// }
+///
+/// let report = ImportPipeline::new(ImportCache::new("cache"))
+///     .with_format::<Level, _>("level", "level", RonFormat)
+///     .build_directory("assets")
+///     .expect("Failed to read the assets");
+/// assert!(report.failed.is_empty());
+/// ```
+pub struct ImportPipeline {
+    cache: ImportCache,
+    importers: FnvHashMap<String, Importer>,
+}
+
+/// The result of `ImportPipeline::build_directory`.
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    /// The assets which were imported.
+    pub imported: Vec<String>,
+    /// The assets whose artifacts were up to date.
+    pub up_to_date: Vec<String>,
+    /// The assets which could not be imported.
+    pub failed: Vec<(String, Error)>,
+    /// The files without a registered extension.
+    pub skipped: Vec<String>,
+}
+
+impl ImportPipeline {
+    /// Creates a pipeline filling the given cache.
+    pub fn new(cache: ImportCache) -> Self {
+        ImportPipeline {
+            cache,
+            importers: Default::default(),
+        }
+    }
+
+    /// Imports the files with the given extension as data of type `D` using `format`.
+    ///
+    /// `data_name` registers `D` with the cache, see `ImportCache::with_data`.
+    pub fn with_format<D, F>(mut self, extension: &str, data_name: &'static str, format: F) -> Self
+    where
+        D: Serialize + DeserializeOwned + Send + Sync + 'static,
+        F: Format<D>,
+    {
+        self.cache.register::<D>(data_name);
+        self.importers.insert(
+            extension.to_lowercase(),
+            Box::new(move |cache, name, source| cache.prebuild::<D, F>(name, source, &format)),
+        );
+        self
+    }
+
+    /// Imports every file below the given directory whose artifact is not up to date.
+    ///
+    /// Fails if the directory can't be read, failed imports are listed in the report.
+    pub fn build_directory<P>(&self, dir: P) -> Result<ImportReport, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let dir = dir.as_ref();
+        let source = Arc::new(Directory::new(dir)) as Arc<dyn Source>;
+
+        let mut files = files_below(dir)?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        files.sort();
+
+        let mut report = ImportReport::default();
+        for name in files {
+            let extension = Path::new(&name)
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase());
+            let importer = match extension.and_then(|extension| self.importers.get(&extension)) {
+                Some(importer) => importer,
+                None => {
+                    report.skipped.push(name);
+                    continue;
+                }
+            };
+
+            match importer(&self.cache, &name, source.clone()) {
+                Ok(true) => report.imported.push(name),
+                Ok(false) => report.up_to_date.push(name),
+                Err(e) => report.failed.push((name, e)),
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Returns the cache, with the data types of the registered formats.
+    pub fn into_cache(self) -> ImportCache {
+        self.cache
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        env, fs, process,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use amethyst_error::Error;
+
+    use crate::{Directory, RonFormat, Source};
+
+    use super::{ImportCache, ImportPipeline};
+
+    #[test]
+    fn prebuilt_artifacts_are_used() {
+        let root = env::temp_dir().join(format!("amethyst_assets_import_{}", process::id()));
+        let assets = root.join("assets");
+        fs::create_dir_all(assets.join("levels")).expect("Failed to create the assets");
+        fs::write(assets.join("levels/one.ron"), b"[1, 2, 3]").expect("Failed to write");
+        fs::write(assets.join("readme.txt"), b"not an asset").expect("Failed to write");
+
+        let pipeline = ImportPipeline::new(ImportCache::new(root.join("cache")))
+            .with_format::<Vec<u32>, _>("ron", "numbers", RonFormat);
+        let report = pipeline.build_directory(&assets).unwrap();
+        assert_eq!(report.imported, vec!["levels/one.ron".to_string()]);
+        assert_eq!(report.skipped, vec!["readme.txt".to_string()]);
+        assert!(report.failed.is_empty());
+
+        let report = pipeline.build_directory(&assets).unwrap();
+        assert_eq!(report.up_to_date, vec!["levels/one.ron".to_string()]);
+
+        // an artifact with the same key is used instead of importing the file
+        let cache = pipeline.into_cache();
+        let source = Arc::new(Directory::new(&assets)) as Arc<dyn Source>;
+        let artifact = fs::read_dir(cache.dir())
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        let mut numbers = vec![4, 5, 6];
+        cache
+            .write(
+                cache_key(&artifact),
+                cache.codec::<Vec<u32>>().unwrap(),
+                &numbers,
+            )
+            .unwrap();
+        let value = cache
+            .import::<Vec<u32>, _>("levels/one.ron".into(), source.clone(), &RonFormat, None)
+            .unwrap();
+        assert_eq!(value.data, numbers);
+
+        // changing the file imports it again
+        fs::write(assets.join("levels/one.ron"), b"[7]").expect("Failed to write");
+        numbers = vec![7];
+        let value = cache
+            .import::<Vec<u32>, _>("levels/one.ron".into(), source, &RonFormat, None)
+            .unwrap();
+        assert_eq!(value.data, numbers);
+
+        fs::remove_dir_all(&root).expect("Failed to remove the test directory");
+    }
+
+    struct Counting {
+        inner: Directory,
+        loads: AtomicUsize,
+    }
+
+    impl Source for Counting {
+        fn modified(&self, path: &str) -> Result<u64, Error> {
+            self.inner.modified(path)
+        }
+
+        fn load(&self, path: &str) -> Result<Vec<u8>, Error> {
+            self.loads.fetch_add(1, Ordering::Relaxed);
+            self.inner.load(path)
+        }
+    }
+
+    #[test]
+    fn cache_miss_reads_the_source_once() {
+        let root = env::temp_dir().join(format!("amethyst_assets_import_once_{}", process::id()));
+        fs::create_dir_all(&root).expect("Failed to create the assets");
+        fs::write(root.join("one.ron"), b"[1, 2, 3]").expect("Failed to write");
+
+        let source = Arc::new(Counting {
+            inner: Directory::new(&root),
+            loads: AtomicUsize::new(0),
+        });
+        let cache = ImportCache::new(root.join("cache")).with_data::<Vec<u32>>("numbers");
+        let value = cache
+            .import::<Vec<u32>, _>("one.ron".into(), source.clone(), &RonFormat, None)
+            .unwrap();
+        assert_eq!(value.data, vec![1, 2, 3]);
+        assert_eq!(source.loads.load(Ordering::Relaxed), 1);
+
+        fs::remove_dir_all(&root).expect("Failed to remove the test directory");
+    }
+
+    fn cache_key(artifact: &std::path::Path) -> u64 {
+        let stem = artifact.file_stem().unwrap().to_string_lossy();
+        u64::from_str_radix(&stem, 16).unwrap()
+    }
+}
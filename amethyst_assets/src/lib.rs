@@ -14,33 +14,43 @@ pub use crate::formats::JsonFormat;
 pub use crate::{
     asset::{Asset, Format, FormatValue, ProcessableAsset, SerializableFormat},
     cache::Cache,
+    dependencies::{AssetDependencies, AssetKey, HotReloadEvent, ReloadedAsset},
     dyn_format::FormatRegisteredData,
     formats::RonFormat,
     helper::AssetLoaderSystemData,
-    loader::Loader,
+    import::{ImportCache, ImportPipeline, ImportReport},
+    loader::{LoadPriority, Loader},
+    manifest::{Manifest, ManifestAsset, ManifestEntry, ManifestLoader, ManifestLoaderSystem},
     prefab::{
-        AssetPrefab, Prefab, PrefabData, PrefabLoader, PrefabLoaderSystem, PrefabLoaderSystemDesc,
+        AssetPrefab, Prefab, PrefabData, PrefabEntityRef, PrefabInstance, PrefabLoader,
+        PrefabLoaderSystem, PrefabLoaderSystemDesc, PrefabOverride, PrefabReference, PrefabSpawned,
+        SavePrefabData,
     },
     progress::{Completion, Progress, ProgressCounter, Tracker},
     reload::{HotReloadBundle, HotReloadStrategy, HotReloadSystem, Reload, SingleFile},
-    source::{Directory, Source},
-    storage::{AssetStorage, Handle, ProcessingState, Processor, WeakHandle},
+    source::{Directory, LayeredSource, PakBuilder, PakCompression, PakSource, Source},
+    storage::{AssetEvent, AssetStorage, Handle, ProcessingState, Processor, WeakHandle},
+    usage::{AssetUsage, AssetUsageReport, AssetUsageSystem},
 };
 
 pub use rayon::ThreadPool;
 
 mod asset;
 mod cache;
+mod dependencies;
 mod dyn_format;
 mod error;
 mod formats;
 mod helper;
+mod import;
 mod loader;
+mod manifest;
 mod prefab;
 mod progress;
 mod reload;
 mod source;
 mod storage;
+mod usage;
 
 // used in macros. Private API otherwise.
 #[doc(hidden)]
@@ -1,7 +1,8 @@
-use std::{borrow::Borrow, hash::Hash, path::PathBuf, sync::Arc};
+use std::{borrow::Borrow, collections::VecDeque, hash::Hash, path::PathBuf, sync::Arc};
 
 use fnv::FnvHashMap;
 use log::debug;
+use parking_lot::Mutex;
 use rayon::ThreadPool;
 
 use amethyst_error::ResultExt;
@@ -11,13 +12,48 @@ use thread_profiler::profile_scope;
 use crate::{
     error::Error,
     storage::{AssetStorage, Handle, Processed},
-    Asset, Directory, Format, FormatValue, Progress, Source,
+    Asset, AssetKey, Directory, Format, FormatValue, ImportCache, Progress, Source,
 };
 
+/// The priority of a load request.
+///
+/// Pending loads are started from the highest priority down, in the order they were requested.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LoadPriority {
+    /// Needed right now, e.g. by the current level.
+    Critical,
+    /// The priority of `Loader::load` and `Loader::load_from`.
+    Normal,
+    /// Prefetching which should not delay other loads.
+    Background,
+}
+
+impl LoadPriority {
+    fn index(self) -> usize {
+        match self {
+            LoadPriority::Critical => 0,
+            LoadPriority::Normal => 1,
+            LoadPriority::Background => 2,
+        }
+    }
+}
+
+impl Default for LoadPriority {
+    fn default() -> Self {
+        LoadPriority::Normal
+    }
+}
+
 /// The asset loader, holding the sources and a reference to the `ThreadPool`.
+///
+/// At most `max_loads_in_flight` loads run at the same time, which defaults to the number of
+/// threads of the pool. The others wait in a queue ordered by their `LoadPriority`, and are
+/// cancelled if every handle to their asset is dropped before they start.
 pub struct Loader {
     hot_reload: bool,
+    import_cache: Option<Arc<ImportCache>>,
     pool: Arc<ThreadPool>,
+    queue: Arc<LoadQueue>,
     sources: FnvHashMap<String, Arc<dyn Source>>,
 }
 
@@ -38,6 +74,8 @@ impl Loader {
     {
         let mut loader = Loader {
             hot_reload: true,
+            import_cache: None,
+            queue: Arc::new(LoadQueue::new(pool.clone())),
             pool,
             sources: Default::default(),
         };
@@ -73,6 +111,33 @@ impl Loader {
         self.hot_reload = value;
     }
 
+    /// Sets the cache of imported asset data.
+    ///
+    /// Assets whose data type is cached are loaded from their artifact if it is up to date,
+    /// the others are imported and added to the cache.
+    pub fn set_import_cache(&mut self, cache: ImportCache) {
+        self.import_cache = Some(Arc::new(cache));
+    }
+
+    /// Sets the maximum number of loads running at the same time, at least one.
+    ///
+    /// Lowering it keeps background loads from occupying every thread of the pool.
+    pub fn set_max_loads_in_flight(&mut self, max: usize) {
+        self.queue.state.lock().max_in_flight = max.max(1);
+        self.queue.start_pending();
+    }
+
+    /// Returns the number of loads waiting to be started.
+    pub fn pending_loads(&self) -> usize {
+        self.queue
+            .state
+            .lock()
+            .pending
+            .iter()
+            .map(VecDeque::len)
+            .sum()
+    }
+
     /// Loads an asset with a given format from the default (directory) source.
     /// If you want to load from a custom source instead, use `load_from`.
     ///
@@ -95,6 +160,26 @@ impl Loader {
         self.load_from::<A, F, _, _, _>(name, format, "", progress, storage)
     }
 
+    /// Loads an asset with a given format and priority from the default (directory) source.
+    ///
+    /// See `load_from` for more information.
+    pub fn load_with_priority<A, F, N, P>(
+        &self,
+        name: N,
+        format: F,
+        priority: LoadPriority,
+        progress: P,
+        storage: &AssetStorage<A>,
+    ) -> Handle<A>
+    where
+        A: Asset,
+        F: Format<A::Data>,
+        N: Into<String>,
+        P: Progress,
+    {
+        self.load_from_with_priority::<A, F, _, _, _>(name, format, "", priority, progress, storage)
+    }
+
     /// Loads an asset with a given id and format from a custom source.
     /// The actual work is done in a worker thread, thus this method immediately returns a handle.
     ///
@@ -113,6 +198,41 @@ impl Loader {
         name: N,
         format: F,
         source: &S,
+        progress: P,
+        storage: &AssetStorage<A>,
+    ) -> Handle<A>
+    where
+        A: Asset,
+        F: Format<A::Data>,
+        N: Into<String>,
+        P: Progress,
+        S: AsRef<str> + Eq + Hash + ?Sized,
+        String: Borrow<S>,
+    {
+        self.load_from_with_priority::<A, F, _, _, _>(
+            name,
+            format,
+            source,
+            LoadPriority::Normal,
+            progress,
+            storage,
+        )
+    }
+
+    /// Loads an asset with a given id, format and priority from a custom source.
+    ///
+    /// The load waits in the queue of the `Loader` until no more than `max_loads_in_flight`
+    /// loads are running and all pending loads with a higher priority have started.
+    /// If every handle to the asset is dropped before that, the load is cancelled and the
+    /// progress is notified of the failure.
+    ///
+    /// See `load_from` for the other parameters.
+    pub fn load_from_with_priority<A, F, N, P, S>(
+        &self,
+        name: N,
+        format: F,
+        source: &S,
+        priority: LoadPriority,
         mut progress: P,
         storage: &AssetStorage<A>,
     ) -> Handle<A>
@@ -138,22 +258,26 @@ impl Loader {
         };
 
         let handle = storage.allocate();
+        let id = handle.id();
 
         debug!(
-            "{:?}: Loading asset {:?} with format {:?} from source {:?} (handle id: {:?})",
+            "{:?}: Loading asset {:?} with format {:?} from source {:?} with priority {:?} (handle id: {:?})",
             A::NAME,
             name,
             format_name,
             source_name,
+            priority,
             handle,
         );
 
         progress.add_assets(1);
+        progress.track_asset(AssetKey::of(&handle));
         let tracker = progress.create_tracker();
 
         let source = self.source(source);
-        let handle_clone = handle.clone();
+        let weak_handle = handle.downgrade();
         let processed = storage.processed.clone();
+        let import_cache = self.import_cache.clone();
 
         let hot_reload = if self.hot_reload {
             Some(objekt::clone_box(&format) as Box<dyn Format<A::Data>>)
@@ -164,10 +288,22 @@ impl Loader {
         let cl = move || {
             #[cfg(feature = "profiler")]
             profile_scope!("load_asset_from_worker");
-            let data = format
-                .import(name.clone(), source, hot_reload)
-                .with_context(|_| Error::Format(format_name));
             let tracker = Box::new(tracker) as Box<dyn Tracker>;
+            let handle = match weak_handle.upgrade() {
+                Some(handle) => handle,
+                None => {
+                    debug!("{:?}: Cancelled loading asset {:?}", A::NAME, name);
+                    processed.push(Processed::Cancelled(id));
+                    tracker.fail(id, A::NAME, name, Error::Cancelled.into());
+                    return;
+                }
+            };
+
+            let data = match import_cache {
+                Some(cache) => cache.import(name.clone(), source, &format, hot_reload),
+                None => format.import(name.clone(), source, hot_reload),
+            }
+            .with_context(|_| Error::Format(format_name));
 
             processed.push(Processed::NewAsset {
                 data,
@@ -176,9 +312,9 @@ impl Loader {
                 tracker,
             });
         };
-        self.pool.spawn(cl);
+        self.queue.push(priority, Box::new(cl));
 
-        handle_clone
+        handle
     }
 
     /// Load an asset from data and return a handle.
@@ -192,10 +328,11 @@ impl Loader {
         A: Asset,
         P: Progress,
     {
+        let handle = storage.allocate();
         progress.add_assets(1);
+        progress.track_asset(AssetKey::of(&handle));
         let tracker = progress.create_tracker();
         let tracker = Box::new(tracker);
-        let handle = storage.allocate();
         storage.processed.push(Processed::NewAsset {
             data: Ok(FormatValue::data(data)),
             handle: handle.clone(),
@@ -218,10 +355,11 @@ impl Loader {
         P: Progress,
         F: FnOnce() -> A::Data + Send + Sync + 'static,
     {
+        let handle = storage.allocate();
         progress.add_assets(1);
+        progress.track_asset(AssetKey::of(&handle));
         let tracker = progress.create_tracker();
         let tracker = Box::new(tracker);
-        let handle = storage.allocate();
         let processed = storage.processed.clone();
 
         self.pool.spawn({
@@ -239,6 +377,19 @@ impl Loader {
         handle
     }
 
+    /// Returns the paths the sources reported as changed since the last call,
+    /// `None` if a source doesn't know its changes.
+    pub(crate) fn take_changes(&self) -> Option<Vec<String>> {
+        // every source is asked, so none of them keeps old changes
+        self.sources
+            .values()
+            .map(|source| source.take_changes())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .map(|changes| changes.concat())
+    }
+
     fn source(&self, source: &str) -> Arc<dyn Source> {
         self.sources
             .get(source)
@@ -246,3 +397,143 @@ impl Loader {
             .clone()
     }
 }
+
+type LoadJob = Box<dyn FnOnce() + Send>;
+
+/// The loads waiting for a free slot, started on the pool from the highest priority down.
+struct LoadQueue {
+    pool: Arc<ThreadPool>,
+    state: Mutex<LoadQueueState>,
+}
+
+struct LoadQueueState {
+    pending: [VecDeque<LoadJob>; 3],
+    in_flight: usize,
+    max_in_flight: usize,
+}
+
+impl LoadQueue {
+    fn new(pool: Arc<ThreadPool>) -> Self {
+        let max_in_flight = pool.current_num_threads().max(1);
+        LoadQueue {
+            pool,
+            state: Mutex::new(LoadQueueState {
+                pending: Default::default(),
+                in_flight: 0,
+                max_in_flight,
+            }),
+        }
+    }
+
+    fn push(self: &Arc<Self>, priority: LoadPriority, job: LoadJob) {
+        self.state.lock().pending[priority.index()].push_back(job);
+        self.start_pending();
+    }
+
+    /// Starts pending loads until the maximum number of loads is running.
+    fn start_pending(self: &Arc<Self>) {
+        let mut state = self.state.lock();
+        while state.in_flight < state.max_in_flight {
+            let job = match state.pending.iter_mut().find_map(VecDeque::pop_front) {
+                Some(job) => job,
+                None => break,
+            };
+            state.in_flight += 1;
+
+            let queue = self.clone();
+            self.pool.spawn(move || {
+                job();
+                queue.state.lock().in_flight -= 1;
+                queue.start_pending();
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{mpsc::channel, Arc},
+        thread::sleep,
+        time::{Duration, Instant},
+    };
+
+    use parking_lot::Mutex;
+    use rayon::ThreadPoolBuilder;
+
+    use amethyst_core::ecs::prelude::VecStorage;
+
+    use crate::{Asset, AssetStorage, Handle, ProcessingState, ProgressCounter, RonFormat};
+
+    use super::{LoadPriority, Loader};
+
+    struct Text;
+
+    impl Asset for Text {
+        const NAME: &'static str = "Text";
+        type Data = String;
+        type HandleStorage = VecStorage<Handle<Self>>;
+    }
+
+    fn wait_for(done: impl Fn() -> bool) {
+        let start = Instant::now();
+        while !done() {
+            assert!(start.elapsed() < Duration::from_secs(5), "Timed out");
+            sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn pending_loads_start_by_priority_and_are_cancelled() {
+        let pool = Arc::new(ThreadPoolBuilder::new().num_threads(2).build().unwrap());
+        let mut loader = Loader::new(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/tests/assets"),
+            pool.clone(),
+        );
+        loader.set_max_loads_in_flight(1);
+
+        // occupy the only slot until all loads are queued
+        let (release, blocked) = channel::<()>();
+        loader.queue.push(
+            LoadPriority::Critical,
+            Box::new(move || blocked.recv().unwrap()),
+        );
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        for &priority in &[
+            LoadPriority::Background,
+            LoadPriority::Normal,
+            LoadPriority::Critical,
+        ] {
+            let order = order.clone();
+            loader
+                .queue
+                .push(priority, Box::new(move || order.lock().push(priority)));
+        }
+
+        let mut storage = AssetStorage::<Text>::new();
+        let mut progress = ProgressCounter::new();
+        let handle = loader.load("subdir/asset", RonFormat, &mut progress, &storage);
+        let id = handle.id();
+        drop(handle);
+        assert_eq!(loader.pending_loads(), 4);
+
+        release.send(()).unwrap();
+        wait_for(|| order.lock().len() == 3 && progress.num_loading() == 0);
+
+        assert_eq!(
+            *order.lock(),
+            vec![
+                LoadPriority::Critical,
+                LoadPriority::Normal,
+                LoadPriority::Background
+            ]
+        );
+        assert_eq!(progress.num_failed(), 1);
+
+        // the id of the cancelled load is reused
+        storage.process(|_| Ok(ProcessingState::Loaded(Text)), 0, &pool, None);
+        assert!(storage.processed.pop().is_err());
+        assert_eq!(storage.allocate().id(), id);
+    }
+}
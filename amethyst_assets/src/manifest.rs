@@ -0,0 +1,449 @@
+//! Manifests listing assets which are loaded and unloaded as a group.
+
+use std::{any::Any, collections::BTreeMap, fmt, marker::PhantomData, ops::Deref};
+
+use derivative::Derivative;
+use fnv::FnvHashMap;
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use amethyst_core::{
+    ecs::prelude::{
+        DenseVecStorage, Read, ReadExpect, ResourceId, System, SystemData, World, Write,
+    },
+    shrev::EventChannel,
+    ArcThreadPool, SystemDesc, Time,
+};
+use amethyst_error::{format_err, Error, ResultExt};
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{
+    Asset, AssetEvent, AssetKey, AssetStorage, Completion, Format, Handle, HotReloadStrategy,
+    Loader, ProcessingState, Progress, ProgressCounter, SerializableFormat,
+};
+
+/// An asset listed in a `Manifest`.
+pub trait ManifestEntry<'a> {
+    /// `SystemData` needed to load the asset
+    type SystemData: SystemData<'a>;
+
+    /// Starts loading the asset, adding it to the given progress.
+    ///
+    /// Returns the handle of the asset, which is kept alive by the manifest and
+    /// can be retrieved with `Manifest::get`.
+    fn load(
+        &self,
+        progress: &mut ProgressCounter,
+        system_data: &mut Self::SystemData,
+    ) -> Result<Box<dyn Any + Send + Sync>, Error>;
+}
+
+/// A `ManifestEntry` loading an asset of type `A` from a path using `Format` `F`.
+///
+/// ### Type parameters:
+///
+/// - `A`: `Asset`,
+/// - `F`: `Format` for loading `A`
+#[derive(Derivative, Deserialize, Serialize)]
+#[derivative(Clone(bound = "F: Clone"), Debug(bound = "F: fmt::Debug"))]
+pub struct ManifestAsset<A, F = Box<dyn SerializableFormat<<A as Asset>::Data>>>
+where
+    A: Asset,
+    F: Format<A::Data>,
+{
+    /// The name of the asset in the default source, usually its path.
+    pub path: String,
+    /// The format of the asset.
+    pub format: F,
+    #[serde(skip)]
+    #[derivative(Debug = "ignore")]
+    marker: PhantomData<A>,
+}
+
+impl<A, F> ManifestAsset<A, F>
+where
+    A: Asset,
+    F: Format<A::Data>,
+{
+    /// Creates an entry loading the asset at `path` with `format`.
+    pub fn new<N: Into<String>>(path: N, format: F) -> Self {
+        ManifestAsset {
+            path: path.into(),
+            format,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, A, F> ManifestEntry<'a> for ManifestAsset<A, F>
+where
+    A: Asset,
+    F: Format<A::Data> + Clone,
+{
+    type SystemData = (ReadExpect<'a, Loader>, Read<'a, AssetStorage<A>>);
+
+    fn load(
+        &self,
+        progress: &mut ProgressCounter,
+        (loader, storage): &mut Self::SystemData,
+    ) -> Result<Box<dyn Any + Send + Sync>, Error> {
+        let handle = loader.load(self.path.clone(), self.format.clone(), progress, storage);
+        Ok(Box::new(handle))
+    }
+}
+
+/// A group of named assets, loaded with a single handle.
+///
+/// A manifest finishes loading once all of its assets are loaded, so the `Progress` passed when
+/// loading the manifest covers the whole group, and it fails if one of them fails.
+/// It holds the handles of its assets, so dropping every handle to the manifest unloads the
+/// assets which aren't used elsewhere as well.
+///
+/// Manifests of `T` are processed by the `ManifestLoaderSystem<T>`. `T` is usually an enum with
+/// a `ManifestAsset` for every asset type, or a single `ManifestAsset` if all assets have the
+/// same type.
+///
+/// ### Example:
+///
+/// ```rust,ignore
+/// #[derive(Deserialize, Serialize)]
+/// enum LevelAsset {
+///     Texture(ManifestAsset<Texture>),
+///     Mesh(ManifestAsset<Mesh>),
+/// }
+///
+/// impl<'a> ManifestEntry<'a> for LevelAsset {
+///     type SystemData = (
+///         <ManifestAsset<Texture> as ManifestEntry<'a>>::SystemData,
+///         <ManifestAsset<Mesh> as ManifestEntry<'a>>::SystemData,
+///     );
+///
+///     fn load(
+///         &self,
+///         progress: &mut ProgressCounter,
+///         (textures, meshes): &mut Self::SystemData,
+///     ) -> Result<Box<dyn Any + Send + Sync>, Error> {
+///         match self {
+///             LevelAsset::Texture(texture) => texture.load(progress, textures),
+///             LevelAsset::Mesh(mesh) => mesh.load(progress, meshes),
+///         }
+///     }
+/// }
+/// ```
+///
+/// A manifest of `LevelAsset`s is then written as
+///
+/// ```ron
+/// (
+///     assets: {
+///         "player": Texture((path: "textures/player.png", format: ("IMAGE", ()))),
+///         "ground": Mesh((path: "meshes/ground.obj", format: ("OBJ", ()))),
+///     },
+/// )
+/// ```
+///
+/// ### Type parameters:
+///
+/// - `T`: `ManifestEntry`
+#[derive(Derivative, Deserialize, Serialize)]
+#[derivative(Default(bound = ""))]
+pub struct Manifest<T> {
+    assets: BTreeMap<String, T>,
+    #[serde(skip)]
+    handles: FnvHashMap<String, Box<dyn Any + Send + Sync>>,
+    #[serde(skip)]
+    counter: Option<ProgressCounter>,
+}
+
+impl<T> Manifest<T> {
+    /// Creates an empty manifest.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds an asset to the manifest, replacing the asset with the same name.
+    pub fn add<N: Into<String>>(&mut self, name: N, asset: T) {
+        self.assets.insert(name.into(), asset);
+    }
+
+    /// Returns the entry of the asset with the given name.
+    pub fn entry(&self, name: &str) -> Option<&T> {
+        self.assets.get(name)
+    }
+
+    /// Returns the handle of the asset with the given name,
+    /// if the manifest was loaded and the asset has type `A`.
+    pub fn get<A: Asset>(&self, name: &str) -> Option<&Handle<A>> {
+        self.handles
+            .get(name)
+            .and_then(|handle| handle.downcast_ref())
+    }
+
+    /// Returns the names of the assets, in sorted order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.assets.keys().map(String::as_str)
+    }
+
+    /// Returns the number of assets in the manifest.
+    pub fn len(&self) -> usize {
+        self.assets.len()
+    }
+
+    /// Returns `true` if the manifest lists no assets.
+    pub fn is_empty(&self) -> bool {
+        self.assets.is_empty()
+    }
+
+    /// Returns the progress of loading the assets, once loading started.
+    pub fn progress(&self) -> Option<&ProgressCounter> {
+        self.counter.as_ref()
+    }
+
+    /// Starts loading all assets which weren't loaded yet.
+    fn load_assets<'a>(&mut self, system_data: &mut T::SystemData) -> Result<(), Error>
+    where
+        T: ManifestEntry<'a>,
+    {
+        let counter = self.counter.get_or_insert_with(ProgressCounter::new);
+        for (name, asset) in &self.assets {
+            if self.handles.contains_key(name) {
+                continue;
+            }
+            let handle = asset
+                .load(counter, system_data)
+                .with_context(|_| format_err!("Failed to load manifest asset {:?}", name))?;
+            self.handles.insert(name.clone(), handle);
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> fmt::Debug for Manifest<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Manifest")
+            .field("assets", &self.assets)
+            .field("loaded", &self.handles.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl<T> Asset for Manifest<T>
+where
+    T: Send + Sync + 'static,
+{
+    const NAME: &'static str = "MANIFEST";
+    type Data = Self;
+    type HandleStorage = DenseVecStorage<Handle<Self>>;
+
+    /// The assets listed in the manifest.
+    fn dependencies(&self) -> Vec<AssetKey> {
+        self.counter
+            .as_ref()
+            .map(|counter| counter.tracked_assets().to_vec())
+            .unwrap_or_default()
+    }
+}
+
+/// Helper structure for loading manifests.
+///
+/// ### Example
+///
+/// ```rust,ignore
+/// let manifest = world.exec(|loader: ManifestLoader<'_, LevelAsset>| {
+///     loader.load("levels/forest.ron", RonFormat, ())
+/// });
+/// ```
+#[derive(SystemData)]
+pub struct ManifestLoader<'a, T>
+where
+    T: Send + Sync + 'static,
+{
+    loader: ReadExpect<'a, Loader>,
+    storage: Read<'a, AssetStorage<Manifest<T>>>,
+}
+
+impl<'a, T> ManifestLoader<'a, T>
+where
+    T: Send + Sync + 'static,
+{
+    /// Loads a manifest from the default source.
+    pub fn load<F, N, P>(&self, name: N, format: F, progress: P) -> Handle<Manifest<T>>
+    where
+        F: Format<Manifest<T>>,
+        N: Into<String>,
+        P: Progress,
+    {
+        self.loader.load(name, format, progress, &self.storage)
+    }
+}
+
+/// System that loads the assets of `Manifest`s of `T`.
+///
+/// ### Type parameters:
+///
+/// - `T`: `ManifestEntry`
+#[derive(Derivative)]
+#[derivative(Default(bound = ""), Debug(bound = ""))]
+pub struct ManifestLoaderSystem<T> {
+    marker: PhantomData<T>,
+}
+
+impl<T> ManifestLoaderSystem<T> {
+    /// Creates a new `ManifestLoaderSystem`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<'a, 'b, T> SystemDesc<'a, 'b, ManifestLoaderSystem<T>> for ManifestLoaderSystem<T>
+where
+    T: for<'c> ManifestEntry<'c> + Send + Sync + 'static,
+{
+    fn build(self, world: &mut World) -> ManifestLoaderSystem<T> {
+        <ManifestLoaderSystem<T> as System<'_>>::SystemData::setup(world);
+        self
+    }
+}
+
+impl<'a, T> System<'a> for ManifestLoaderSystem<T>
+where
+    T: ManifestEntry<'a> + Send + Sync + 'static,
+{
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Write<'a, AssetStorage<Manifest<T>>>,
+        Read<'a, Time>,
+        ReadExpect<'a, ArcThreadPool>,
+        Option<Read<'a, HotReloadStrategy>>,
+        Write<'a, EventChannel<AssetEvent<Manifest<T>>>>,
+        T::SystemData,
+    );
+
+    fn run(
+        &mut self,
+        (mut storage, time, pool, strategy, mut events, mut entry_data): Self::SystemData,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("manifest_loader_system");
+
+        storage.track_events();
+        storage.process(
+            |mut manifest| {
+                manifest.load_assets(&mut entry_data)?;
+                let counter = manifest
+                    .progress()
+                    .expect("Unreachable: Loading assets creates the progress counter");
+                match counter.complete() {
+                    Completion::Complete => Ok(ProcessingState::Loaded(manifest)),
+                    Completion::Failed => {
+                        error!("Failed loading manifest assets: {:?}", counter.errors());
+                        Err(Error::from_string("Failed loading manifest assets"))
+                    }
+                    Completion::Loading => Ok(ProcessingState::Loading(manifest)),
+                }
+            },
+            time.frame_number(),
+            &**pool,
+            strategy.as_ref().map(Deref::deref),
+        );
+        storage.write_events(&mut events);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, fs, process,
+        sync::Arc,
+        thread::sleep,
+        time::{Duration, Instant},
+    };
+
+    use rayon::ThreadPoolBuilder;
+    use serde::Deserialize;
+
+    use amethyst_core::{
+        ecs::{RunNow, VecStorage, World, WorldExt},
+        SystemDesc, Time,
+    };
+
+    use crate::{Processor, RonFormat};
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Text(String);
+
+    impl Asset for Text {
+        const NAME: &'static str = "Text";
+        type Data = Self;
+        type HandleStorage = VecStorage<Handle<Self>>;
+    }
+
+    type TextManifest = Manifest<ManifestAsset<Text, RonFormat>>;
+
+    #[test]
+    fn manifest_loads_and_unloads_its_assets() {
+        let dir = env::temp_dir().join(format!("amethyst_assets_manifest_{}", process::id()));
+        fs::create_dir_all(&dir).expect("Failed to create the assets");
+        fs::write(dir.join("greeting.ron"), b"(\"hello\")").expect("Failed to write");
+
+        let mut world = World::new();
+        let pool = Arc::new(ThreadPoolBuilder::default().build().unwrap());
+        world.insert(pool.clone());
+        world.insert(Loader::new(&dir, pool));
+        world.insert(Time::default());
+        let mut manifests =
+            ManifestLoaderSystem::<ManifestAsset<Text, RonFormat>>::new().build(&mut world);
+        let mut texts = Processor::<Text>::new().build(&mut world);
+
+        let mut manifest = TextManifest::new();
+        manifest.add("greeting", ManifestAsset::new("greeting.ron", RonFormat));
+        let mut progress = ProgressCounter::new();
+        let handle = world.read_resource::<Loader>().load_from_data(
+            manifest,
+            &mut progress,
+            &world.read_resource::<AssetStorage<TextManifest>>(),
+        );
+
+        let start = Instant::now();
+        while !progress.is_complete() {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "Loading timed out"
+            );
+            manifests.run_now(&world);
+            texts.run_now(&world);
+            sleep(Duration::from_millis(10));
+        }
+
+        let greeting = {
+            let manifests = world.read_resource::<AssetStorage<TextManifest>>();
+            let manifest = manifests.get(&handle).expect("Manifest was not loaded");
+            assert!(manifest.get::<TextManifest>("greeting").is_none());
+            manifest
+                .get::<Text>("greeting")
+                .expect("Manifest has no greeting")
+                .downgrade()
+        };
+        assert_eq!(
+            world
+                .read_resource::<AssetStorage<Text>>()
+                .get(&greeting.upgrade().unwrap()),
+            Some(&Text("hello".into()))
+        );
+
+        drop(handle);
+        manifests.run_now(&world);
+        texts.run_now(&world);
+        assert!(greeting.is_dead());
+
+        fs::remove_dir_all(&dir).expect("Failed to remove the test directory");
+    }
+}
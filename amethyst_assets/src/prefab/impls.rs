@@ -1,10 +1,12 @@
+use std::collections::HashMap;
+
 use amethyst_core::{
-    ecs::{Entity, WriteStorage},
+    ecs::{Entity, ReadStorage, WriteStorage},
     Named, Transform,
 };
 use amethyst_error::Error;
 
-use crate::{PrefabData, ProgressCounter};
+use crate::{PrefabData, ProgressCounter, SavePrefabData};
 
 impl<'a, T> PrefabData<'a> for Option<T>
 where
@@ -77,6 +79,45 @@ impl<'a> PrefabData<'a> for Named {
     }
 }
 
+impl<'a, T> SavePrefabData<'a> for Option<T>
+where
+    T: SavePrefabData<'a>,
+{
+    type SystemData = <T as SavePrefabData<'a>>::SystemData;
+
+    fn save_entity(
+        entity: Entity,
+        system_data: &Self::SystemData,
+        entities: &HashMap<Entity, usize>,
+    ) -> Result<Option<Self>, Error> {
+        Ok(Some(T::save_entity(entity, system_data, entities)?))
+    }
+}
+
+impl<'a> SavePrefabData<'a> for Transform {
+    type SystemData = ReadStorage<'a, Transform>;
+
+    fn save_entity(
+        entity: Entity,
+        storage: &Self::SystemData,
+        _: &HashMap<Entity, usize>,
+    ) -> Result<Option<Self>, Error> {
+        Ok(storage.get(entity).cloned())
+    }
+}
+
+impl<'a> SavePrefabData<'a> for Named {
+    type SystemData = (ReadStorage<'a, Named>,);
+
+    fn save_entity(
+        entity: Entity,
+        storages: &Self::SystemData,
+        _: &HashMap<Entity, usize>,
+    ) -> Result<Option<Self>, Error> {
+        Ok(storages.0.get(entity).cloned())
+    }
+}
+
 macro_rules! impl_data {
     ( $($ty:ident:$i:tt),* ) => {
         #[allow(unused)]
@@ -116,6 +157,33 @@ macro_rules! impl_data {
                 Ok(ret)
             }
         }
+
+        #[allow(unused)]
+        impl<'a, $($ty),*> SavePrefabData<'a> for ( $( $ty , )* )
+            where $( $ty : SavePrefabData<'a> ),*
+        {
+            type SystemData = (
+                $(
+                    <$ty as SavePrefabData<'a>>::SystemData,
+                )*
+            );
+
+            fn save_entity(
+                entity: Entity,
+                system_data: &Self::SystemData,
+                entities: &HashMap<Entity, usize>,
+            ) -> Result<Option<Self>, Error> {
+                #![allow(unused_variables)]
+                Ok(Some((
+                    $(
+                        match $ty::save_entity(entity, &system_data.$i, entities)? {
+                            Some(data) => data,
+                            None => return Ok(None),
+                        },
+                    )*
+                )))
+            }
+        }
     };
 }
 
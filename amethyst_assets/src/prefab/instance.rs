@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+use derivative::Derivative;
+
+use amethyst_core::ecs::{Component, DenseVecStorage, Entity};
+use amethyst_error::Error;
+
+use crate::ProgressCounter;
+
+use super::{PrefabData, PrefabEntityRef, PrefabOverride};
+
+/// Per-instance parameters of a prefab, placed on the same entity as the `Handle<Prefab<T>>`.
+///
+/// When the prefab is spawned, the sub assets of the overrides are loaded and the data of each
+/// override is added to the selected entity after the data of the prefab, so it replaces the
+/// components it contains and keeps the others. Like a prefab override, an override selecting
+/// an entity the prefab does not have fails the instance, which is then not spawned.
+///
+/// ### Example:
+///
+/// ```rust,ignore
+/// world
+///     .create_entity()
+///     .with(handle)
+///     .with(
+///         PrefabInstance::new()
+///             .with_override(PrefabEntityRef::Index(0), MyPrefabData::at(position))
+///             .with_override(PrefabEntityRef::Name("flag".into()), MyPrefabData::team(color)),
+///     )
+///     .build();
+/// ```
+///
+/// ### Type parameters:
+///
+/// - `T`: `PrefabData`
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct PrefabInstance<T> {
+    overrides: Vec<PrefabOverride<T>>,
+    #[derivative(Debug = "ignore")]
+    counter: Option<ProgressCounter>,
+}
+
+impl<T> Default for PrefabInstance<T> {
+    fn default() -> Self {
+        PrefabInstance::new()
+    }
+}
+
+impl<T> PrefabInstance<T> {
+    /// Create instance parameters without overrides
+    pub fn new() -> Self {
+        PrefabInstance {
+            overrides: Vec::new(),
+            counter: None,
+        }
+    }
+
+    /// Add data to the given entity of the spawned prefab
+    pub fn with_override(mut self, entity: PrefabEntityRef, data: T) -> Self {
+        self.add_override(entity, data);
+        self
+    }
+
+    /// Add data to the given entity of the spawned prefab
+    pub fn add_override(&mut self, entity: PrefabEntityRef, data: T) {
+        self.overrides.push(PrefabOverride { entity, data });
+    }
+
+    /// Get immutable access to all overrides of the instance
+    pub fn overrides(&self) -> impl Iterator<Item = &PrefabOverride<T>> {
+        self.overrides.iter()
+    }
+
+    /// Check if sub asset loading have been triggered
+    pub fn loading(&self) -> bool {
+        self.counter.is_some()
+    }
+
+    /// Get the `ProgressCounter` for the sub asset loading.
+    ///
+    /// ### Panics
+    ///
+    /// If sub asset loading has not been triggered.
+    pub fn progress(&self) -> &ProgressCounter {
+        self.counter
+            .as_ref()
+            .expect("Sub asset loading has not been triggered")
+    }
+
+    /// Trigger sub asset loading for the data of the overrides
+    pub fn load_sub_assets<'a>(
+        &mut self,
+        system_data: &mut <T as PrefabData<'a>>::SystemData,
+    ) -> Result<bool, Error>
+    where
+        T: PrefabData<'a>,
+    {
+        let mut ret = false;
+        let mut progress = ProgressCounter::default();
+        for over in &mut self.overrides {
+            if over.data.load_sub_assets(&mut progress, system_data)? {
+                ret = true;
+            }
+        }
+        self.counter = Some(progress);
+        Ok(ret)
+    }
+}
+
+impl<T> Component for PrefabInstance<T>
+where
+    T: Send + Sync + 'static,
+{
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Event sent by the `PrefabLoaderSystem` after it spawned a prefab.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrefabSpawned {
+    /// The entity the `Handle<Prefab<T>>` is placed on, which is the main entity of the prefab.
+    pub root: Entity,
+    /// The created entities, by index of the prefab entity after the base and nested prefabs
+    /// were resolved. The first entry is `root`.
+    pub entities: Vec<Entity>,
+    /// The indices in `entities` of the created entities, by name of the prefab entity.
+    ///
+    /// Entities of a nested prefab are named `instance/entity`. When several entities have
+    /// the same name, the first one is used.
+    pub names: HashMap<String, usize>,
+}
+
+impl PrefabSpawned {
+    /// Returns the created entity selected by `entity`.
+    pub fn get(&self, entity: &PrefabEntityRef) -> Option<Entity> {
+        entity
+            .index(self.entities.len(), |name| self.names.get(name).cloned())
+            .map(|index| self.entities[index])
+    }
+}
@@ -1,6 +1,6 @@
 use std::marker::PhantomData;
 
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use amethyst_core::ecs::prelude::{
     Component, DenseVecStorage, Entity, FlaggedStorage, Read, ReadExpect, ResourceId, SystemData,
@@ -9,12 +9,23 @@ use amethyst_core::ecs::prelude::{
 use amethyst_error::Error;
 
 use crate::{
-    Asset, AssetStorage, Format, Handle, Loader, Progress, ProgressCounter, SerializableFormat,
+    Asset, AssetKey, AssetStorage, Format, Handle, Loader, Progress, ProgressCounter,
+    SerializableFormat,
 };
 
-pub use self::system::{PrefabLoaderSystem, PrefabLoaderSystemDesc};
+pub use self::{
+    instance::{PrefabInstance, PrefabSpawned},
+    reference::{PrefabEntityRef, PrefabOverride, PrefabReference},
+    save::SavePrefabData,
+    system::{PrefabLoaderSystem, PrefabLoaderSystemDesc},
+};
+
+use self::reference::Resolution;
 
 mod impls;
+mod instance;
+mod reference;
+mod save;
 mod system;
 
 /// Trait for loading a prefabs data for a single entity
@@ -101,16 +112,59 @@ pub trait PrefabData<'a> {
 /// }
 /// ```
 ///
+/// ### Inheritance
+///
+/// A prefab can be based on another prefab, whose entities are created first, followed by the
+/// entities of the prefab itself. Parent indices refer to this combined list. An entity can also
+/// instantiate another prefab, which creates the entities of the nested prefab with its main
+/// entity being the instantiating entity. Finally, overrides replace the data of entities
+/// selected by index or name.
+///
+/// The referenced prefabs are loaded and resolved by the `PrefabLoaderSystem` before the prefab
+/// finishes loading. A prefab referencing itself, directly or through other prefabs, fails
+/// to load.
+///
+/// ```ron
+/// #![enable(implicit_some)]
+/// Prefab(
+///     base: "prefab/enemy_base.ron",
+///     entities: [
+///         (parent: 0, prefab: "prefab/torch.ron", name: "torch"),
+///     ],
+///     overrides: [
+///         (entity: Name("sprite"), data: (sprite: (sheet: 1, sprite_number: 3))),
+///         (entity: Name("torch/flame"), data: (light: (color: (1.0, 0.2, 0.1, 1.0)))),
+///     ],
+/// )
+/// ```
+///
+/// ### Saving
+///
+/// When `T` also implements `SavePrefabData`, `Prefab::save` and `Prefab::save_hierarchy` create
+/// a prefab from the components of existing entities, which `Prefab::to_ron` writes in the
+/// format loaded by `RonFormat`.
+///
 /// ### Type parameters:
 ///
 /// - `T`: `PrefabData`
 #[derive(Default, Deserialize, Serialize)]
+#[serde(bound(deserialize = "T: DeserializeOwned + Send + Sync + 'static"))]
 pub struct Prefab<T> {
     #[serde(skip)]
     tag: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    base: Option<PrefabReference<T>>,
+    #[serde(default)]
     entities: Vec<PrefabEntity<T>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    overrides: Vec<PrefabOverride<T>>,
     #[serde(skip)]
     counter: Option<ProgressCounter>,
+    #[serde(skip)]
+    resolved: Option<Resolution<T>>,
+    // the name of the prefab, when it was loaded as a base or nested prefab
+    #[serde(skip)]
+    reference_name: Option<String>,
 }
 
 /// Prefab data container for a single entity
@@ -119,10 +173,17 @@ pub struct Prefab<T> {
 ///
 /// - `T`: `PrefabData`
 #[derive(Debug, Deserialize, Serialize)]
-#[serde(default)]
+#[serde(
+    default,
+    bound(deserialize = "T: DeserializeOwned + Send + Sync + 'static")
+)]
 pub struct PrefabEntity<T> {
     parent: Option<usize>,
     data: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prefab: Option<PrefabReference<T>>,
 }
 
 impl<T> Default for PrefabEntity<T> {
@@ -134,7 +195,12 @@ impl<T> Default for PrefabEntity<T> {
 impl<T> PrefabEntity<T> {
     /// New prefab entity
     pub fn new(parent: Option<usize>, data: Option<T>) -> Self {
-        PrefabEntity { parent, data }
+        PrefabEntity {
+            parent,
+            data,
+            name: None,
+            prefab: None,
+        }
     }
 
     /// Set parent index
@@ -142,6 +208,33 @@ impl<T> PrefabEntity<T> {
         self.parent = Some(parent);
     }
 
+    /// Get parent index
+    pub fn parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    /// Set the name, used to select the entity in `PrefabOverride`s
+    pub fn set_name<N: Into<String>>(&mut self, name: N) {
+        self.name = Some(name.into());
+    }
+
+    /// Get the name
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_ref().map(String::as_str)
+    }
+
+    /// Instantiate the given prefab on this entity
+    ///
+    /// The data of this entity replaces the data of the main entity of the nested prefab.
+    pub fn set_prefab(&mut self, prefab: PrefabReference<T>) {
+        self.prefab = Some(prefab);
+    }
+
+    /// Get the nested prefab instantiated on this entity
+    pub fn prefab(&self) -> Option<&PrefabReference<T>> {
+        self.prefab.as_ref()
+    }
+
     /// Set data
     pub fn set_data(&mut self, data: T) {
         self.data = Some(data);
@@ -194,28 +287,70 @@ impl<T> PrefabEntity<T> {
 impl<T> Prefab<T> {
     /// Create new empty prefab
     pub fn new() -> Self {
-        Prefab {
-            tag: None,
-            entities: vec![PrefabEntity::default()],
-            counter: None,
-        }
+        Self::with_entities(vec![PrefabEntity::default()])
     }
 
     /// Create a prefab with data for only the main `Entity`
     pub fn new_main(data: T) -> Self {
+        Self::with_entities(vec![PrefabEntity::new(None, Some(data))])
+    }
+
+    /// Create a prefab based on the given prefab, with no entities of its own
+    pub fn with_base(base: PrefabReference<T>) -> Self {
+        let mut prefab = Self::with_entities(Vec::new());
+        prefab.base = Some(base);
+        prefab
+    }
+
+    fn with_entities(entities: Vec<PrefabEntity<T>>) -> Self {
         Prefab {
             tag: None,
-            entities: vec![PrefabEntity::new(None, Some(data))],
+            base: None,
+            entities,
+            overrides: Vec::new(),
             counter: None,
+            resolved: None,
+            reference_name: None,
         }
     }
 
+    /// Get the base prefab
+    pub fn base(&self) -> Option<&PrefabReference<T>> {
+        self.base.as_ref()
+    }
+
+    /// Replace the data of an entity, after the base and nested prefabs were resolved
+    pub fn add_override(&mut self, entity: PrefabEntityRef, data: T) {
+        self.overrides.push(PrefabOverride { entity, data });
+    }
+
+    /// Get immutable access to all overrides in the prefab
+    pub fn overrides(&self) -> impl Iterator<Item = &PrefabOverride<T>> {
+        self.overrides.iter()
+    }
+
     /// Set main `Entity` data
+    ///
+    /// With a base, the main entity is the one of the base: `Some(data)` overrides its data and
+    /// `None` keeps the data of the base.
     pub fn main(&mut self, data: Option<T>) {
-        self.entities[0].data = data;
+        if self.base.is_some() {
+            let main = PrefabEntityRef::Index(0);
+            self.overrides.retain(|over| over.entity != main);
+            if let Some(data) = data {
+                self.add_override(main, data);
+            }
+        } else if let Some(entity) = self.entities.first_mut() {
+            entity.data = data;
+        } else {
+            self.entities.push(PrefabEntity::new(None, data));
+        }
     }
 
     /// Add a new entity to the prefab, with optional data and parent.
+    ///
+    /// Returns the index of the entity among the entities of this prefab. With a base, parent
+    /// indices refer to the entities of the base followed by the entities of this prefab.
     pub fn add(&mut self, parent: Option<usize>, data: Option<T>) -> usize {
         let index = self.entities.len();
         self.entities.push(PrefabEntity::new(parent, data));
@@ -223,6 +358,8 @@ impl<T> Prefab<T> {
     }
 
     /// Number of entities in the prefab, including the main entity
+    ///
+    /// Entities of the base and nested prefabs are not included.
     pub fn len(&self) -> usize {
         self.entities.len()
     }
@@ -288,7 +425,7 @@ impl<T> Prefab<T> {
             .expect("Sub asset loading has not been triggered")
     }
 
-    /// Trigger sub asset loading for the asset
+    /// Trigger sub asset loading for the asset, including the data of its overrides
     pub fn load_sub_assets<'a>(
         &mut self,
         system_data: &mut <T as PrefabData<'a>>::SystemData,
@@ -303,6 +440,11 @@ impl<T> Prefab<T> {
                 ret = true;
             }
         }
+        for over in &mut self.overrides {
+            if over.data.load_sub_assets(&mut progress, system_data)? {
+                ret = true;
+            }
+        }
         self.counter = Some(progress);
         Ok(ret)
     }
@@ -345,6 +487,17 @@ where
     const NAME: &'static str = "PREFAB";
     type Data = Self;
     type HandleStorage = FlaggedStorage<Handle<Self>, DenseVecStorage<Handle<Self>>>;
+
+    /// The sub assets loaded by `load_sub_assets` and the referenced prefabs.
+    fn dependencies(&self) -> Vec<AssetKey> {
+        let mut dependencies = self
+            .counter
+            .as_ref()
+            .map(|counter| counter.tracked_assets().to_vec())
+            .unwrap_or_default();
+        dependencies.extend(self.reference_keys());
+        dependencies
+    }
 }
 
 /// Convenience `PrefabData` for loading assets of type `A` using `Format` `F`.
@@ -465,16 +618,22 @@ where
 
 #[cfg(test)]
 mod tests {
-    use std::sync::Arc;
+    use std::{
+        env, fs, process,
+        sync::Arc,
+        thread::sleep,
+        time::{Duration, Instant},
+    };
 
     use rayon::ThreadPoolBuilder;
 
     use amethyst_core::{
-        ecs::{Builder, RunNow, World, WorldExt},
+        ecs::{Builder, Join, RunNow, VecStorage, World, WorldExt},
+        shrev::EventChannel,
         SystemDesc, Time, Transform,
     };
 
-    use crate::Loader;
+    use crate::{Loader, RonFormat};
 
     use super::*;
 
@@ -505,4 +664,219 @@ mod tests {
         );
         assert!(world.read_storage::<Transform>().get(root_entity).is_some());
     }
+
+    #[test]
+    fn prefab_spawns_without_loader() {
+        let mut world = World::new();
+        let pool = Arc::new(ThreadPoolBuilder::default().build().unwrap());
+        world.insert(pool);
+        world.insert(Time::default());
+        let mut system = PrefabLoaderSystemDesc::<MyPrefab>::default().build(&mut world);
+        RunNow::setup(&mut system, &mut world);
+
+        let handle = world
+            .write_resource::<AssetStorage<Prefab<MyPrefab>>>()
+            .insert(Prefab::new_main(Transform::default()));
+        let root_entity = world.create_entity().with(handle).build();
+        system.run_now(&world);
+        assert!(world.read_storage::<Transform>().get(root_entity).is_some());
+    }
+
+    #[test]
+    fn main_of_prefab_with_base_overrides_the_base() {
+        let mut prefab = Prefab::with_base(PrefabReference::new("base.ron"));
+        prefab.main(Some(Transform::default()));
+        prefab.main(Some(Transform::default()));
+        assert_eq!(prefab.len(), 0);
+        assert_eq!(
+            prefab
+                .overrides()
+                .map(|over| over.entity.clone())
+                .collect::<Vec<_>>(),
+            vec![PrefabEntityRef::Index(0)]
+        );
+        prefab.main(None);
+        assert_eq!(prefab.overrides().count(), 0);
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn test_prefab_instance() {
+        let mut world = World::new();
+        let pool = Arc::new(ThreadPoolBuilder::default().build().unwrap());
+        world.insert(pool.clone());
+        world.insert(Loader::new(".", pool));
+        world.insert(Time::default());
+        let mut system = PrefabLoaderSystemDesc::<MyPrefab>::default().build(&mut world);
+        RunNow::setup(&mut system, &mut world);
+        let mut reader = world
+            .write_resource::<EventChannel<PrefabSpawned>>()
+            .register_reader();
+
+        let mut prefab = Prefab::new_main(Transform::default());
+        let child = prefab.add(Some(0), Some(Transform::default()));
+        prefab.entity(child).unwrap().set_name("child");
+
+        let handle = world.read_resource::<Loader>().load_from_data(
+            prefab,
+            (),
+            &world.read_resource::<AssetStorage<Prefab<MyPrefab>>>(),
+        );
+        let mut moved = Transform::default();
+        moved.set_translation_x(1.0);
+        let root_entity = world
+            .create_entity()
+            .with(handle)
+            .with(PrefabInstance::new().with_override(PrefabEntityRef::Name("child".into()), moved))
+            .build();
+        system.run_now(&world);
+
+        let events = world
+            .read_resource::<EventChannel<PrefabSpawned>>()
+            .read(&mut reader)
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].root, root_entity);
+        assert_eq!(events[0].entities.len(), 2);
+        let child = events[0].get(&PrefabEntityRef::Name("child".into()));
+        assert_eq!(child, Some(events[0].entities[1]));
+
+        let transforms = world.read_storage::<Transform>();
+        assert_eq!(transforms.get(root_entity).unwrap().translation().x, 0.0);
+        assert_eq!(transforms.get(child.unwrap()).unwrap().translation().x, 1.0);
+    }
+
+    #[test]
+    fn prefab_instance_with_unknown_entity_fails() {
+        let mut world = World::new();
+        let pool = Arc::new(ThreadPoolBuilder::default().build().unwrap());
+        world.insert(pool.clone());
+        world.insert(Loader::new(".", pool));
+        world.insert(Time::default());
+        let mut system = PrefabLoaderSystemDesc::<MyPrefab>::default().build(&mut world);
+        RunNow::setup(&mut system, &mut world);
+        let mut reader = world
+            .write_resource::<EventChannel<PrefabSpawned>>()
+            .register_reader();
+
+        let handle = world.read_resource::<Loader>().load_from_data(
+            Prefab::new_main(Transform::default()),
+            (),
+            &world.read_resource::<AssetStorage<Prefab<MyPrefab>>>(),
+        );
+        let mut moved = Transform::default();
+        moved.set_translation_x(1.0);
+        let root_entity = world
+            .create_entity()
+            .with(handle)
+            .with(
+                PrefabInstance::new()
+                    .with_override(PrefabEntityRef::Name("missing".into()), moved.clone())
+                    .with_override(PrefabEntityRef::Index(1), moved),
+            )
+            .build();
+        system.run_now(&world);
+
+        let events = world
+            .read_resource::<EventChannel<PrefabSpawned>>()
+            .read(&mut reader)
+            .cloned()
+            .collect::<Vec<_>>();
+        assert!(events.is_empty());
+        assert!(world.read_storage::<Transform>().get(root_entity).is_none());
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Text(String);
+
+    impl Asset for Text {
+        const NAME: &'static str = "Text";
+        type Data = Self;
+        type HandleStorage = VecStorage<Handle<Self>>;
+    }
+
+    #[test]
+    fn prefab_instance_loads_sub_assets_of_overrides() {
+        let dir = env::temp_dir().join(format!("amethyst_assets_prefab_sign_{}", process::id()));
+        fs::create_dir_all(&dir).expect("Failed to create the assets");
+        fs::write(dir.join("greeting.ron"), b"(\"hello\")").expect("Failed to write");
+
+        let mut world = World::new();
+        let pool = Arc::new(ThreadPoolBuilder::default().build().unwrap());
+        world.insert(pool.clone());
+        world.insert(Loader::new(&dir, pool));
+        world.insert(Time::default());
+        let mut system =
+            PrefabLoaderSystemDesc::<AssetPrefab<Text, RonFormat>>::default().build(&mut world);
+        RunNow::setup(&mut system, &mut world);
+
+        let mut prefab = Prefab::new();
+        let sign = prefab.add(Some(0), None);
+        prefab.entity(sign).unwrap().set_name("sign");
+        let handle = world.read_resource::<Loader>().load_from_data(
+            prefab,
+            (),
+            &world.read_resource::<AssetStorage<Prefab<AssetPrefab<Text, RonFormat>>>>(),
+        );
+        let root_entity = world
+            .create_entity()
+            .with(handle)
+            .with(PrefabInstance::new().with_override(
+                PrefabEntityRef::Name("sign".into()),
+                AssetPrefab::File("greeting.ron".into(), RonFormat),
+            ))
+            .build();
+        system.run_now(&world);
+
+        let instances = world.read_storage::<PrefabInstance<AssetPrefab<Text, RonFormat>>>();
+        let instance = instances.get(root_entity).unwrap();
+        assert!(instance.loading());
+        assert_eq!(instance.progress().num_assets(), 1);
+        let texts = world.read_storage::<Handle<Text>>();
+        assert!(texts.get(root_entity).is_none());
+        assert_eq!((&texts).join().count(), 1);
+
+        fs::remove_dir_all(&dir).expect("Failed to remove the test directory");
+    }
+
+    #[test]
+    fn cyclic_references_fail_to_load() {
+        let dir = env::temp_dir().join(format!("amethyst_assets_prefab_cycle_{}", process::id()));
+        fs::create_dir_all(&dir).expect("Failed to create the prefabs");
+        fs::write(dir.join("itself.ron"), b"(base: \"itself.ron\")").expect("Failed to write");
+        fs::write(dir.join("ping.ron"), b"(base: \"pong.ron\")").expect("Failed to write");
+        fs::write(dir.join("pong.ron"), b"(base: \"ping.ron\")").expect("Failed to write");
+
+        let mut world = World::new();
+        let pool = Arc::new(ThreadPoolBuilder::default().build().unwrap());
+        world.insert(pool.clone());
+        world.insert(Loader::new(&dir, pool));
+        world.insert(Time::default());
+        let mut system = PrefabLoaderSystemDesc::<MyPrefab>::default().build(&mut world);
+        RunNow::setup(&mut system, &mut world);
+
+        for name in &["itself.ron", "ping.ron"] {
+            let mut progress = ProgressCounter::new();
+            let _handle = world.read_resource::<Loader>().load_from_data(
+                Prefab::<MyPrefab>::with_base(PrefabReference::new(*name)),
+                &mut progress,
+                &world.read_resource::<AssetStorage<Prefab<MyPrefab>>>(),
+            );
+
+            let start = Instant::now();
+            while progress.num_loading() > 0 {
+                assert!(
+                    start.elapsed() < Duration::from_secs(5),
+                    "Loading {:?} timed out",
+                    name
+                );
+                system.run_now(&world);
+                sleep(Duration::from_millis(10));
+            }
+            assert_eq!(progress.num_failed(), 1, "Loading {:?} did not fail", name);
+        }
+
+        fs::remove_dir_all(&dir).expect("Failed to remove the test directory");
+    }
 }
@@ -0,0 +1,447 @@
+use std::{fmt, sync::Arc};
+
+use derivative::Derivative;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+
+use amethyst_error::{format_err, Error};
+
+use crate::{AssetKey, AssetStorage, Format, FormatValue, Handle, RonFormat, Source};
+
+use super::Prefab;
+
+/// A reference to another prefab, used as the base of a prefab or as a nested prefab instance.
+///
+/// Deserialized from the name of the prefab in the default source,
+/// and loaded with `RonFormat` unless created with another format.
+pub struct PrefabReference<T> {
+    name: String,
+    format: Box<dyn Format<Prefab<T>>>,
+}
+
+impl<T> PrefabReference<T>
+where
+    T: DeserializeOwned + Send + Sync + 'static,
+{
+    /// Creates a reference to the prefab with the given name, loaded with `RonFormat`.
+    pub fn new<N: Into<String>>(name: N) -> Self {
+        Self::with_format(name, RonFormat)
+    }
+}
+
+impl<T> PrefabReference<T> {
+    /// Creates a reference to the prefab with the given name, loaded with `format`.
+    pub fn with_format<N, F>(name: N, format: F) -> Self
+    where
+        N: Into<String>,
+        F: Format<Prefab<T>>,
+    {
+        PrefabReference {
+            name: name.into(),
+            format: Box::new(format),
+        }
+    }
+
+    /// Returns the name of the referenced prefab.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(super) fn format(&self) -> Box<dyn Format<Prefab<T>>> {
+        self.format.clone()
+    }
+}
+
+/// Loads a referenced prefab with the format of the reference,
+/// remembering its name so reference cycles can be detected.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
+pub(super) struct ReferenceFormat<T>(pub(super) Box<dyn Format<Prefab<T>>>);
+
+impl<T> Format<Prefab<T>> for ReferenceFormat<T>
+where
+    T: Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        self.0.name()
+    }
+
+    fn import(
+        &self,
+        name: String,
+        source: Arc<dyn Source>,
+        create_reload: Option<Box<dyn Format<Prefab<T>>>>,
+    ) -> Result<FormatValue<Prefab<T>>, Error> {
+        let mut value = self.0.import(name.clone(), source, create_reload)?;
+        value.data.reference_name = Some(name);
+        Ok(value)
+    }
+}
+
+impl<T> fmt::Debug for PrefabReference<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PrefabReference")
+            .field(&self.name)
+            .field(&self.format.name())
+            .finish()
+    }
+}
+
+impl<T> Serialize for PrefabReference<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.name.serialize(serializer)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for PrefabReference<T>
+where
+    T: DeserializeOwned + Send + Sync + 'static,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(PrefabReference::new)
+    }
+}
+
+/// Selects an entity of a prefab, after its base and nested prefabs were resolved.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum PrefabEntityRef {
+    /// The entity with the given index, the entities of the base come first.
+    Index(usize),
+    /// The first entity with the given name.
+    ///
+    /// Entities of a nested prefab are named `instance/entity`,
+    /// where `instance` is the name of the entity the prefab is instantiated on.
+    Name(String),
+}
+
+impl PrefabEntityRef {
+    /// Returns the index of the selected entity among `len` entities,
+    /// `by_name` returns the index of the first entity with the given name.
+    pub(super) fn index<F>(&self, len: usize, by_name: F) -> Option<usize>
+    where
+        F: FnOnce(&str) -> Option<usize>,
+    {
+        match *self {
+            PrefabEntityRef::Index(index) if index < len => Some(index),
+            PrefabEntityRef::Index(_) => None,
+            PrefabEntityRef::Name(ref name) => by_name(name),
+        }
+    }
+
+    /// Returns the index of the selected entity among the resolved entities of a prefab.
+    pub(super) fn find<T>(&self, entities: &[ResolvedEntity<T>]) -> Option<usize> {
+        self.index(entities.len(), |name| {
+            entities
+                .iter()
+                .position(|entity| entity.name.as_ref().map(String::as_str) == Some(name))
+        })
+    }
+}
+
+/// Replaces the data of an entity inherited from a base or nested prefab.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PrefabOverride<T> {
+    /// The entity whose data is replaced.
+    pub entity: PrefabEntityRef,
+    /// The new data of the entity.
+    pub data: T,
+}
+
+/// Where the data of a resolved entity is stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum DataSlot {
+    Entity(usize),
+    Override(usize),
+}
+
+/// The data of a resolved entity, in the prefab itself or in one it references.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""))]
+pub(super) struct DataSource<T> {
+    prefab: Option<Handle<Prefab<T>>>,
+    slot: DataSlot,
+}
+
+/// An entity created when spawning a prefab.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""))]
+pub(super) struct ResolvedEntity<T> {
+    pub(super) parent: Option<usize>,
+    pub(super) name: Option<String>,
+    data: Option<DataSource<T>>,
+}
+
+impl<T> ResolvedEntity<T> {
+    /// Points data stored in the prefab itself at the given handle of the prefab.
+    fn rebase(&self, handle: &Handle<Prefab<T>>) -> Self {
+        ResolvedEntity {
+            parent: self.parent,
+            name: self.name.clone(),
+            data: self.data.as_ref().map(|data| data.rebase(handle)),
+        }
+    }
+}
+
+impl<T> DataSource<T> {
+    fn rebase(&self, handle: &Handle<Prefab<T>>) -> Self {
+        DataSource {
+            prefab: self.prefab.clone().or_else(|| Some(handle.clone())),
+            slot: self.slot,
+        }
+    }
+}
+
+/// The entities of a prefab after its base and nested prefabs were resolved.
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""))]
+pub(super) struct Resolution<T> {
+    pub(super) entities: Arc<Vec<ResolvedEntity<T>>>,
+    references: Vec<Handle<Prefab<T>>>,
+}
+
+/// A loaded prefab a reference points at.
+pub(super) type LoadedReference<T> = (Handle<Prefab<T>>, Arc<Vec<ResolvedEntity<T>>>);
+
+impl<T> Prefab<T> {
+    /// Returns the keys of the loaded prefabs this prefab references.
+    pub(super) fn reference_keys(&self) -> Vec<AssetKey>
+    where
+        T: Send + Sync + 'static,
+    {
+        self.resolved
+            .as_ref()
+            .map(|resolution| resolution.references.iter().map(AssetKey::of).collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the entities to create when spawning the prefab.
+    pub(super) fn resolved_entities(&self) -> Arc<Vec<ResolvedEntity<T>>> {
+        match self.resolved {
+            Some(ref resolution) => resolution.entities.clone(),
+            None => Arc::new(self.own_entities()),
+        }
+    }
+
+    /// Returns the data of a resolved entity.
+    ///
+    /// Fails when a referenced prefab no longer has the data, which happens when it was
+    /// hot reloaded with fewer entities or overrides.
+    pub(super) fn entity_data<'s>(
+        &'s self,
+        storage: &'s AssetStorage<Prefab<T>>,
+        entity: &ResolvedEntity<T>,
+    ) -> Result<Option<&'s T>, Error>
+    where
+        T: Send + Sync + 'static,
+    {
+        let source = match entity.data {
+            Some(ref source) => source,
+            None => return Ok(None),
+        };
+        let prefab = match source.prefab {
+            Some(ref handle) => storage
+                .get(handle)
+                .ok_or_else(|| format_err!("Referenced prefab is not loaded"))?,
+            None => self,
+        };
+        let data = match source.slot {
+            DataSlot::Entity(index) => prefab
+                .entities
+                .get(index)
+                .and_then(|entity| entity.data.as_ref()),
+            DataSlot::Override(index) => prefab.overrides.get(index).map(|over| &over.data),
+        };
+        data.map(Some)
+            .ok_or_else(|| format_err!("Referenced prefab has no data in {:?}", source.slot))
+    }
+
+    fn own_entities(&self) -> Vec<ResolvedEntity<T>> {
+        self.entities
+            .iter()
+            .enumerate()
+            .map(|(index, entity)| ResolvedEntity {
+                parent: entity.parent,
+                name: entity.name.clone(),
+                data: entity.data.as_ref().map(|_| DataSource {
+                    prefab: None,
+                    slot: DataSlot::Entity(index),
+                }),
+            })
+            .collect()
+    }
+
+    /// Resolves the base, nested prefabs and overrides of this prefab.
+    ///
+    /// `lookup` returns the referenced prefab once it is loaded.
+    /// Returns `Ok(false)` while a referenced prefab is still loading.
+    pub(super) fn resolve<F>(&mut self, mut lookup: F) -> Result<bool, Error>
+    where
+        F: FnMut(&PrefabReference<T>) -> Result<Option<LoadedReference<T>>, Error>,
+    {
+        if self.resolved.is_some() {
+            return Ok(true);
+        }
+
+        // all references are looked up before waiting, so they load at the same time
+        let base = match self.base {
+            Some(ref base) => Some(lookup(base)?),
+            None => None,
+        };
+        let mut nested = Vec::new();
+        for (index, entity) in self.entities.iter().enumerate() {
+            if let Some(ref reference) = entity.prefab {
+                nested.push((index, lookup(reference)?));
+            }
+        }
+        if let Some(None) = base {
+            return Ok(false);
+        }
+        if nested.iter().any(|(_, reference)| reference.is_none()) {
+            return Ok(false);
+        }
+
+        let mut resolved = Vec::new();
+        let mut references = Vec::new();
+        if let Some(Some((handle, entities))) = base {
+            resolved.extend(entities.iter().map(|entity| entity.rebase(&handle)));
+            references.push(handle);
+        }
+        let offset = resolved.len();
+        resolved.extend(self.own_entities());
+
+        for (index, reference) in nested {
+            let (handle, entities) = reference.expect("Unreachable: Waited for nested prefabs");
+            let instance = offset + index;
+            let start = resolved.len();
+            let prefix = resolved[instance].name.clone();
+            if let Some(main) = entities.first() {
+                if resolved[instance].data.is_none() {
+                    resolved[instance].data = main.rebase(&handle).data;
+                }
+            }
+            for entity in entities.iter().skip(1) {
+                let mut entity = entity.rebase(&handle);
+                entity.parent = entity.parent.map(|parent| {
+                    if parent == 0 {
+                        instance
+                    } else {
+                        start + parent - 1
+                    }
+                });
+                if let (Some(prefix), Some(name)) = (&prefix, &entity.name) {
+                    entity.name = Some(format!("{}/{}", prefix, name));
+                }
+                resolved.push(entity);
+            }
+            references.push(handle);
+        }
+
+        for (index, over) in self.overrides.iter().enumerate() {
+            let target = over.entity.find(&resolved).ok_or_else(|| {
+                format_err!("Prefab override targets unknown entity {:?}", over.entity)
+            })?;
+            resolved[target].data = Some(DataSource {
+                prefab: None,
+                slot: DataSlot::Override(index),
+            });
+        }
+
+        if let Some(parent) = resolved
+            .iter()
+            .filter_map(|entity| entity.parent)
+            .find(|parent| *parent >= resolved.len())
+        {
+            return Err(format_err!("Prefab entity has unknown parent {}", parent));
+        }
+
+        self.resolved = Some(Resolution {
+            entities: Arc::new(resolved),
+            references,
+        });
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AssetStorage, Prefab, PrefabEntityRef, PrefabReference};
+
+    #[test]
+    fn prefab_resolves_base_nested_prefabs_and_overrides() {
+        let mut storage = AssetStorage::<Prefab<u32>>::new();
+
+        let mut base = Prefab::new_main(1);
+        base.add(Some(0), Some(2));
+        base.entity(1).unwrap().set_name("child");
+        let base = storage.insert(base);
+
+        let mut torch = Prefab::new_main(10);
+        torch.add(Some(0), Some(11));
+        torch.entity(1).unwrap().set_name("flame");
+        let torch = storage.insert(torch);
+
+        // a torch held by the child of the base
+        let mut prefab = Prefab::with_base(PrefabReference::new("base.ron"));
+        let instance = prefab.add(Some(1), None);
+        prefab.entity(instance).unwrap().set_name("torch");
+        prefab
+            .entity(instance)
+            .unwrap()
+            .set_prefab(PrefabReference::new("torch.ron"));
+        prefab.add_override(PrefabEntityRef::Name("child".into()), 20);
+        prefab.add_override(PrefabEntityRef::Name("torch/flame".into()), 30);
+
+        assert!(!prefab.resolve(|_| Ok(None)).unwrap());
+        assert!(prefab
+            .resolve(|reference| {
+                let handle = match reference.name() {
+                    "base.ron" => base.clone(),
+                    "torch.ron" => torch.clone(),
+                    other => panic!("Unexpected reference {:?}", other),
+                };
+                let entities = storage.get(&handle).unwrap().resolved_entities();
+                Ok(Some((handle, entities)))
+            })
+            .unwrap());
+
+        let resolved = prefab.resolved_entities();
+        assert_eq!(
+            resolved
+                .iter()
+                .map(|entity| entity.parent)
+                .collect::<Vec<_>>(),
+            vec![None, Some(0), Some(1), Some(2)]
+        );
+        assert_eq!(
+            resolved
+                .iter()
+                .map(|entity| prefab.entity_data(&storage, entity).unwrap().cloned())
+                .collect::<Vec<_>>(),
+            vec![Some(1), Some(20), Some(10), Some(30)]
+        );
+    }
+
+    #[test]
+    fn reloaded_base_with_fewer_entities_fails() {
+        let mut storage = AssetStorage::<Prefab<u32>>::new();
+
+        let mut base = Prefab::new_main(1);
+        base.add(Some(0), Some(2));
+        let base = storage.insert(base);
+
+        let mut prefab = Prefab::<u32>::with_base(PrefabReference::new("base.ron"));
+        assert!(prefab
+            .resolve(|_| {
+                let entities = storage.get(&base).unwrap().resolved_entities();
+                Ok(Some((base.clone(), entities)))
+            })
+            .unwrap());
+
+        storage.replace(&base, Prefab::new_main(1));
+        let resolved = prefab.resolved_entities();
+        assert_eq!(
+            prefab.entity_data(&storage, &resolved[0]).unwrap(),
+            Some(&1)
+        );
+        assert!(prefab.entity_data(&storage, &resolved[1]).is_err());
+    }
+}
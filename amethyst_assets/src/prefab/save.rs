@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+
+use ron::ser::PrettyConfig;
+use serde::Serialize;
+
+use amethyst_core::{
+    ecs::{Entity, ReadStorage, SystemData},
+    Parent, ParentHierarchy,
+};
+use amethyst_error::{Error, ResultExt};
+
+use super::{Prefab, PrefabEntity};
+
+/// Trait for saving the components of a single entity as prefab data,
+/// the reverse of `PrefabData::add_to_entity`.
+pub trait SavePrefabData<'a>: Sized {
+    /// `SystemData` needed to read the components
+    type SystemData: SystemData<'a>;
+
+    /// Read the data for this prefab from the given `Entity`
+    ///
+    /// ### Parameters:
+    ///
+    /// - `entity`: `Entity` to read components from
+    /// - `system_data`: `SystemData` needed to read the components
+    /// - `entities`: The prefab index of every entity being saved, so components linking to
+    ///               other entities can store the index of the linked entity, which is its
+    ///               position in the `entities` given to `PrefabData::add_to_entity` on load.
+    ///
+    /// ### Returns
+    ///
+    /// - `Err(error)` - if an `Error` occurs
+    /// - `Ok(None)` - if the entity does not have the components needed for the data
+    /// - `Ok(Some(data))` - the prefab data of the entity
+    fn save_entity(
+        entity: Entity,
+        system_data: &Self::SystemData,
+        entities: &HashMap<Entity, usize>,
+    ) -> Result<Option<Self>, Error>;
+}
+
+impl<T> Prefab<T> {
+    /// Create a prefab from the components of the given entities.
+    ///
+    /// The main entity of the prefab has no data, the given entities are added below it. An
+    /// entity whose `Parent` is also saved keeps it as its parent, all other entities are
+    /// parented to the main entity. Entities are reordered so parents come before their children.
+    pub fn save<'a>(
+        entities: &[Entity],
+        parents: &ReadStorage<'_, Parent>,
+        system_data: &<T as SavePrefabData<'a>>::SystemData,
+    ) -> Result<Self, Error>
+    where
+        T: SavePrefabData<'a>,
+    {
+        let mut ordered = Vec::with_capacity(entities.len());
+        let mut indices = HashMap::with_capacity(entities.len());
+        for entity in entities {
+            visit(*entity, entities, parents, &mut indices, &mut ordered);
+        }
+        let mut prefab = Prefab::new();
+        prefab.add_saved(&ordered, &indices, parents, system_data)?;
+        Ok(prefab)
+    }
+
+    /// Create a prefab from the components of `root` and all its descendants.
+    ///
+    /// The data of `root` is saved on the main entity of the prefab.
+    pub fn save_hierarchy<'a>(
+        root: Entity,
+        hierarchy: &ParentHierarchy,
+        parents: &ReadStorage<'_, Parent>,
+        system_data: &<T as SavePrefabData<'a>>::SystemData,
+    ) -> Result<Self, Error>
+    where
+        T: SavePrefabData<'a>,
+    {
+        // breadth first, so parents come before their children
+        let mut ordered = vec![root];
+        let mut next = 0;
+        while next < ordered.len() {
+            let children = hierarchy.children(ordered[next]);
+            ordered.extend_from_slice(children);
+            next += 1;
+        }
+        let indices = ordered
+            .iter()
+            .enumerate()
+            .map(|(index, entity)| (*entity, index))
+            .collect::<HashMap<_, _>>();
+        let mut prefab = Prefab::with_entities(Vec::new());
+        prefab.add_saved(&ordered, &indices, parents, system_data)?;
+        Ok(prefab)
+    }
+
+    fn add_saved<'a>(
+        &mut self,
+        ordered: &[Entity],
+        indices: &HashMap<Entity, usize>,
+        parents: &ReadStorage<'_, Parent>,
+        system_data: &<T as SavePrefabData<'a>>::SystemData,
+    ) -> Result<(), Error>
+    where
+        T: SavePrefabData<'a>,
+    {
+        let offset = self.entities.len();
+        let prefab_indices = indices
+            .iter()
+            .map(|(entity, index)| (*entity, offset + index))
+            .collect::<HashMap<_, _>>();
+        for entity in ordered {
+            let parent = match parents.get(*entity) {
+                Some(parent) => prefab_indices.get(&parent.entity).cloned(),
+                None => None,
+            };
+            let data = T::save_entity(*entity, system_data, &prefab_indices)?;
+            self.entities.push(PrefabEntity::new(
+                parent.or_else(|| if offset > 0 { Some(0) } else { None }),
+                data,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Serialize the prefab in the RON format read by `RonFormat`.
+    pub fn to_ron(&self) -> Result<String, Error>
+    where
+        T: Serialize,
+    {
+        ron::ser::to_string_pretty(self, PrettyConfig::default())
+            .with_context(|_| Error::from_string("Failed to serialize prefab"))
+    }
+}
+
+/// Adds `entity` to `ordered` after its ancestors among the saved entities.
+fn visit(
+    entity: Entity,
+    entities: &[Entity],
+    parents: &ReadStorage<'_, Parent>,
+    indices: &mut HashMap<Entity, usize>,
+    ordered: &mut Vec<Entity>,
+) {
+    if indices.contains_key(&entity) {
+        return;
+    }
+    // reserved before visiting the parent, which stops at cycles
+    indices.insert(entity, usize::max_value());
+    if let Some(parent) = parents.get(entity) {
+        if entities.contains(&parent.entity) {
+            visit(parent.entity, entities, parents, indices, ordered);
+        }
+    }
+    indices.insert(entity, ordered.len());
+    ordered.push(entity);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use rayon::ThreadPoolBuilder;
+    use serde::{Deserialize, Serialize};
+
+    use amethyst_core::{
+        ecs::{
+            Builder, Component, DenseVecStorage, Entity, ReadStorage, RunNow, World, WorldExt,
+            WriteStorage,
+        },
+        shrev::EventChannel,
+        Named, Parent, SystemDesc, Time,
+    };
+    use amethyst_error::Error;
+
+    use crate::{
+        AssetStorage, Format, Loader, Prefab, PrefabData, PrefabLoaderSystemDesc, PrefabSpawned,
+        RonFormat, SavePrefabData,
+    };
+
+    struct Link(Entity);
+
+    impl Component for Link {
+        type Storage = DenseVecStorage<Self>;
+    }
+
+    // the prefab index of the linked entity
+    #[derive(Deserialize, Serialize)]
+    struct LinkData(Option<usize>);
+
+    impl<'a> PrefabData<'a> for LinkData {
+        type SystemData = WriteStorage<'a, Link>;
+        type Result = ();
+
+        fn add_to_entity(
+            &self,
+            entity: Entity,
+            links: &mut Self::SystemData,
+            entities: &[Entity],
+            _: &[Entity],
+        ) -> Result<(), Error> {
+            if let Some(index) = self.0 {
+                links.insert(entity, Link(entities[index]))?;
+            }
+            Ok(())
+        }
+    }
+
+    impl<'a> SavePrefabData<'a> for LinkData {
+        type SystemData = ReadStorage<'a, Link>;
+
+        fn save_entity(
+            entity: Entity,
+            links: &Self::SystemData,
+            entities: &HashMap<Entity, usize>,
+        ) -> Result<Option<Self>, Error> {
+            Ok(Some(LinkData(
+                links
+                    .get(entity)
+                    .and_then(|link| entities.get(&link.0).cloned()),
+            )))
+        }
+    }
+
+    #[test]
+    fn saved_prefab_keeps_hierarchy() {
+        let mut world = World::new();
+        world.register::<Named>();
+        world.register::<Parent>();
+        let wheel = world.create_entity().with(Named::new("wheel")).build();
+        let car = world.create_entity().with(Named::new("car")).build();
+        world
+            .write_storage::<Parent>()
+            .insert(wheel, Parent::new(car))
+            .unwrap();
+        let unnamed = world.create_entity().build();
+
+        let prefab = Prefab::<Option<Named>>::save(
+            &[wheel, car, unnamed],
+            &world.read_storage(),
+            &world.system_data(),
+        )
+        .unwrap();
+        let ron = prefab.to_ron().unwrap();
+        let prefab: Prefab<Option<Named>> = RonFormat.import_simple(ron.into_bytes()).unwrap();
+
+        assert_eq!(
+            prefab
+                .entities()
+                .map(|entity| (
+                    entity.parent(),
+                    entity
+                        .data()
+                        .and_then(Option::as_ref)
+                        .map(|named| named.name.to_string())
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (None, None),
+                (Some(0), Some("car".to_string())),
+                (Some(1), Some("wheel".to_string())),
+                (Some(0), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn saved_links_point_to_the_loaded_entities() {
+        let mut world = World::new();
+        let pool = Arc::new(ThreadPoolBuilder::default().build().unwrap());
+        world.insert(pool.clone());
+        world.insert(Loader::new(".", pool));
+        world.insert(Time::default());
+        world.register::<Parent>();
+        let mut system = PrefabLoaderSystemDesc::<LinkData>::default().build(&mut world);
+        RunNow::setup(&mut system, &mut world);
+        let mut reader = world
+            .write_resource::<EventChannel<PrefabSpawned>>()
+            .register_reader();
+
+        let target = world.create_entity().build();
+        let source = world.create_entity().with(Link(target)).build();
+        let prefab = Prefab::<LinkData>::save(
+            &[source, target],
+            &world.read_storage(),
+            &world.system_data(),
+        )
+        .unwrap();
+        let ron = prefab.to_ron().unwrap();
+        let prefab: Prefab<LinkData> = RonFormat.import_simple(ron.into_bytes()).unwrap();
+
+        let handle = world.read_resource::<Loader>().load_from_data(
+            prefab,
+            (),
+            &world.read_resource::<AssetStorage<Prefab<LinkData>>>(),
+        );
+        world.create_entity().with(handle).build();
+        system.run_now(&world);
+
+        let events = world
+            .read_resource::<EventChannel<PrefabSpawned>>()
+            .read(&mut reader)
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(events.len(), 1);
+        let entities = &events[0].entities;
+        assert_eq!(entities.len(), 3);
+        let links = world.read_storage::<Link>();
+        assert_eq!(links.get(entities[1]).map(|link| link.0), Some(entities[2]));
+        assert!(links.get(entities[2]).is_none());
+    }
+}
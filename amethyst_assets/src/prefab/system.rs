@@ -1,4 +1,9 @@
-use std::{collections::HashMap, marker::PhantomData, ops::Deref};
+use std::{
+    collections::{HashMap, HashSet},
+    marker::PhantomData,
+    ops::Deref,
+    sync::Arc,
+};
 
 use derivative::Derivative;
 use log::error;
@@ -8,6 +13,7 @@ use amethyst_core::{
         storage::ComponentEvent, BitSet, Entities, Entity, Join, Read, ReadExpect, ReadStorage,
         ReaderId, System, SystemData, World, Write, WriteStorage,
     },
+    shrev::EventChannel,
     ArcThreadPool, Parent, SystemDesc, Time,
 };
 use amethyst_error::{format_err, Error, ResultExt};
@@ -15,9 +21,15 @@ use amethyst_error::{format_err, Error, ResultExt};
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
 
-use crate::{AssetStorage, Completion, Handle, HotReloadStrategy, ProcessingState};
+use crate::{
+    AssetEvent, AssetStorage, Completion, Format, Handle, HotReloadStrategy, Loader,
+    ProcessingState, ProgressCounter,
+};
 
-use super::{Prefab, PrefabData, PrefabTag};
+use super::{
+    reference::{ReferenceFormat, ResolvedEntity},
+    Prefab, PrefabData, PrefabInstance, PrefabSpawned, PrefabTag,
+};
 
 /// Builds a `PrefabLoaderSystem`.
 #[derive(Derivative, Debug)]
@@ -41,6 +53,12 @@ where
 
 /// System that load `Prefab`s for `PrefabData` `T`.
 ///
+/// Also loads the base and nested prefabs referenced by a `Prefab`, which finishes loading once
+/// they are resolved.
+///
+/// When spawning a prefab, the overrides of a `PrefabInstance` on the entity are added after the
+/// data of the prefab, and a `PrefabSpawned` event with the created entities is sent.
+///
 /// ### Type parameters:
 ///
 /// - `T`: `PrefabData`
@@ -51,6 +69,16 @@ pub struct PrefabLoaderSystem<T> {
     to_process: BitSet,
     insert_reader: ReaderId<ComponentEvent>,
     next_tag: u64,
+    // the referenced prefabs loading prefabs are waiting for, by name
+    references: HashMap<String, ReferenceLoad<T>>,
+    // the names of the prefabs referenced by each loading referenced prefab
+    reference_graph: HashMap<String, HashSet<String>>,
+}
+
+struct ReferenceLoad<T> {
+    handle: Handle<Prefab<T>>,
+    progress: ProgressCounter,
+    entities: Option<Arc<Vec<ResolvedEntity<T>>>>,
 }
 
 impl<'a, T> PrefabLoaderSystem<T>
@@ -66,6 +94,8 @@ where
             to_process: BitSet::default(),
             insert_reader,
             next_tag: 0,
+            references: HashMap::new(),
+            reference_graph: HashMap::new(),
         }
     }
 }
@@ -77,13 +107,17 @@ where
     #[allow(clippy::type_complexity)]
     type SystemData = (
         Entities<'a>,
+        Option<Read<'a, Loader>>,
         Write<'a, AssetStorage<Prefab<T>>>,
         ReadStorage<'a, Handle<Prefab<T>>>,
         Read<'a, Time>,
         ReadExpect<'a, ArcThreadPool>,
         Option<Read<'a, HotReloadStrategy>>,
+        Write<'a, EventChannel<AssetEvent<Prefab<T>>>>,
         WriteStorage<'a, Parent>,
         WriteStorage<'a, PrefabTag<T>>,
+        WriteStorage<'a, PrefabInstance<T>>,
+        Write<'a, EventChannel<PrefabSpawned>>,
         T::SystemData,
     );
 
@@ -93,40 +127,89 @@ where
 
         let (
             entities,
+            loader,
             mut prefab_storage,
             prefab_handles,
             time,
             pool,
             strategy,
+            mut events,
             mut parents,
             mut tags,
+            mut instances,
+            mut spawned,
             mut prefab_system_data,
         ) = data;
         let strategy = strategy.as_ref().map(Deref::deref);
+        let loader = loader.as_ref().map(Deref::deref);
+        let next_tag = &mut self.next_tag;
+        let references = &self.references;
+        let graph = &mut self.reference_graph;
+        let mut used = HashSet::new();
+        let mut requests = HashMap::<String, Box<dyn Format<Prefab<T>>>>::new();
+        prefab_storage.track_events();
         prefab_storage.process(
             |mut d| {
-                d.tag = Some(self.next_tag);
-                self.next_tag += 1;
-                if !d.loading()
-                    && !d
-                        .load_sub_assets(&mut prefab_system_data)
-                        .with_context(|_| format_err!("Failed starting sub asset loading"))?
-                {
-                    return Ok(ProcessingState::Loaded(d));
+                d.tag = Some(*next_tag);
+                *next_tag += 1;
+                if !d.loading() {
+                    d.load_sub_assets(&mut prefab_system_data)
+                        .with_context(|_| format_err!("Failed starting sub asset loading"))?;
                 }
+                let own = d.reference_name.clone();
+                if let Some(ref own) = own {
+                    graph.insert(own.clone(), HashSet::new());
+                }
+                let resolved = d.resolve(|reference| {
+                    if let Some(ref own) = own {
+                        graph
+                            .get_mut(own)
+                            .expect("Unreachable: Inserted before resolving")
+                            .insert(reference.name().to_owned());
+                        if reaches(graph, reference.name(), own) {
+                            return Err(format_err!(
+                                "Prefab {:?} references itself through {:?}",
+                                own,
+                                reference.name()
+                            ));
+                        }
+                    }
+                    used.insert(reference.name().to_owned());
+                    match references.get(reference.name()) {
+                        Some(load) if load.progress.complete() == Completion::Failed => Err(
+                            format_err!("Failed loading referenced prefab {:?}", reference.name()),
+                        ),
+                        Some(load) => Ok(load
+                            .entities
+                            .clone()
+                            .map(|entities| (load.handle.clone(), entities))),
+                        None if loader.is_some() => {
+                            requests
+                                .entry(reference.name().to_owned())
+                                .or_insert_with(|| reference.format());
+                            Ok(None)
+                        }
+                        None => Err(format_err!(
+                            "Loading referenced prefab {:?} needs a `Loader`",
+                            reference.name()
+                        )),
+                    }
+                })?;
                 match d.progress().complete() {
-                    Completion::Complete => Ok(ProcessingState::Loaded(d)),
+                    Completion::Complete if resolved => Ok(ProcessingState::Loaded(d)),
+                    Completion::Complete | Completion::Loading => Ok(ProcessingState::Loading(d)),
                     Completion::Failed => {
                         error!("Failed loading sub asset: {:?}", d.progress().errors());
                         Err(Error::from_string("Failed loading sub asset"))
                     }
-                    Completion::Loading => Ok(ProcessingState::Loading(d)),
                 }
             },
             time.frame_number(),
             &**pool,
             strategy,
         );
+        self.load_references(used, requests, loader, &prefab_storage);
+        prefab_storage.write_events(&mut events);
         prefab_handles
             .channel()
             .read(&mut self.insert_reader)
@@ -139,12 +222,57 @@ where
         for (root_entity, handle, _) in (&*entities, &prefab_handles, &self.to_process).join() {
             if let Some(prefab) = prefab_storage.get(handle) {
                 self.finished.push(root_entity);
+                let resolved = prefab.resolved_entities();
+                let data = match resolved
+                    .iter()
+                    .map(|entity| prefab.entity_data(&prefab_storage, entity))
+                    .collect::<Result<Vec<_>, Error>>()
+                {
+                    Ok(data) => data,
+                    Err(e) => {
+                        error!("Failed spawning prefab: {}", e);
+                        continue;
+                    }
+                };
+                let mut sub_assets_loaded = true;
+                if let Some(instance) = instances.get_mut(root_entity) {
+                    if !instance.loading() {
+                        if let Err(e) = instance.load_sub_assets(&mut prefab_system_data) {
+                            error!("Failed starting prefab instance sub asset loading: {}", e);
+                            sub_assets_loaded = false;
+                        }
+                    }
+                }
+                let overrides = match instances
+                    .get(root_entity)
+                    .filter(|_| sub_assets_loaded)
+                    .into_iter()
+                    .flat_map(|i| i.overrides())
+                    .map(|over| {
+                        over.entity
+                            .find(&resolved)
+                            .map(|index| (index, &over.data))
+                            .ok_or_else(|| {
+                                format_err!(
+                                    "Prefab instance override targets unknown entity {:?}",
+                                    over.entity
+                                )
+                            })
+                    })
+                    .collect::<Result<Vec<_>, Error>>()
+                {
+                    Ok(overrides) => overrides,
+                    Err(e) => {
+                        error!("Failed spawning prefab: {}", e);
+                        continue;
+                    }
+                };
                 // create entities
                 self.entities.clear();
                 self.entities.push(root_entity);
 
                 let mut children = HashMap::new();
-                for entity_data in prefab.entities.iter().skip(1) {
+                for entity_data in resolved.iter().skip(1) {
                     let new_entity = entities.create();
                     self.entities.push(new_entity);
                     if let Some(parent) = entity_data.parent {
@@ -173,8 +301,8 @@ where
                     .expect("Unable to insert `PrefabTag` for prefab entity");
                 }
                 // create components
-                for (index, entity_data) in prefab.entities.iter().enumerate() {
-                    if let Some(ref prefab_data) = &entity_data.data {
+                for (index, prefab_data) in data.into_iter().enumerate() {
+                    if let Some(prefab_data) = prefab_data {
                         prefab_data
                             .add_to_entity(
                                 self.entities[index],
@@ -188,6 +316,30 @@ where
                             .expect("Unable to add prefab system data to entity");
                     }
                 }
+                // add instance overrides
+                for (index, data) in overrides {
+                    data.add_to_entity(
+                        self.entities[index],
+                        &mut prefab_system_data,
+                        &self.entities,
+                        children
+                            .get(&index)
+                            .map(|children| &children[..])
+                            .unwrap_or(&[]),
+                    )
+                    .expect("Unable to add prefab instance data to entity");
+                }
+                let mut names = HashMap::new();
+                for (index, entity_data) in resolved.iter().enumerate() {
+                    if let Some(ref name) = entity_data.name {
+                        names.entry(name.clone()).or_insert(index);
+                    }
+                }
+                spawned.single_write(PrefabSpawned {
+                    root: root_entity,
+                    entities: self.entities.clone(),
+                    names,
+                });
             }
         }
 
@@ -196,3 +348,67 @@ where
         }
     }
 }
+
+impl<T> PrefabLoaderSystem<T>
+where
+    T: Send + Sync + 'static,
+{
+    /// Starts loading the requested references, and forgets the ones no prefab waits for.
+    ///
+    /// References are only requested when there is a `Loader`.
+    fn load_references(
+        &mut self,
+        used: HashSet<String>,
+        requests: HashMap<String, Box<dyn Format<Prefab<T>>>>,
+        loader: Option<&Loader>,
+        storage: &AssetStorage<Prefab<T>>,
+    ) {
+        self.references.retain(|name, _| used.contains(name));
+        let references = &self.references;
+        self.reference_graph
+            .retain(|name, _| references.contains_key(name));
+        if let Some(loader) = loader {
+            for (name, format) in requests {
+                let mut progress = ProgressCounter::new();
+                let handle = loader.load(
+                    name.clone(),
+                    ReferenceFormat(format),
+                    &mut progress,
+                    storage,
+                );
+                self.references.insert(
+                    name,
+                    ReferenceLoad {
+                        handle,
+                        progress,
+                        entities: None,
+                    },
+                );
+            }
+        }
+
+        for load in self.references.values_mut() {
+            if load.entities.is_none() {
+                load.entities = storage.get(&load.handle).map(Prefab::resolved_entities);
+            }
+        }
+    }
+}
+
+/// Returns `true` if the prefab named `from` is or references the prefab named `to`,
+/// directly or through other prefabs.
+fn reaches(graph: &HashMap<String, HashSet<String>>, from: &str, to: &str) -> bool {
+    let mut visited = HashSet::new();
+    let mut pending = vec![from];
+    while let Some(name) = pending.pop() {
+        if name == to {
+            return true;
+        }
+        if visited.insert(name) {
+            if let Some(next) = graph.get(name) {
+                pending.extend(next.iter().map(String::as_str));
+            }
+        }
+    }
+    false
+}
@@ -7,6 +7,8 @@ use amethyst_error::Error;
 use log::error;
 use parking_lot::Mutex;
 
+use crate::AssetKey;
+
 /// Completion status, returned by `ProgressCounter::complete`.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Completion {
@@ -29,6 +31,9 @@ pub trait Progress {
     /// put in the queue.
     fn add_assets(&mut self, num: usize);
 
+    /// Called by the `Loader` with the key of every asset added to the progress.
+    fn track_asset(&mut self, _key: AssetKey) {}
+
     /// Creates a `Tracker`.
     fn create_tracker(self) -> Self::Tracker;
 }
@@ -45,6 +50,7 @@ impl Progress for () {
 /// in order to check how many assets are loaded.
 #[derive(Default)]
 pub struct ProgressCounter {
+    assets: Vec<AssetKey>,
     errors: Arc<Mutex<Vec<AssetErrorMeta>>>,
     num_assets: usize,
     num_failed: Arc<AtomicUsize>,
@@ -63,6 +69,11 @@ impl ProgressCounter {
         lock.drain(..).collect()
     }
 
+    /// Returns the keys of the assets this struct is tracking.
+    pub fn tracked_assets(&self) -> &[AssetKey] {
+        &self.assets
+    }
+
     /// Returns the number of assets this struct is tracking.
     pub fn num_assets(&self) -> usize {
         self.num_assets
@@ -108,6 +119,10 @@ impl<'a> Progress for &'a mut ProgressCounter {
         self.num_assets += num;
     }
 
+    fn track_asset(&mut self, key: AssetKey) {
+        self.assets.push(key);
+    }
+
     fn create_tracker(self) -> Self::Tracker {
         let errors = self.errors.clone();
         let num_failed = self.num_failed.clone();
@@ -1,11 +1,12 @@
 //! Defines the `Reload` trait.
 
-use std::{sync::Arc, time::Instant};
+use std::{collections::HashSet, iter, mem, sync::Arc, time::Instant};
 
 use derive_new::new;
 
 use amethyst_core::{
     ecs::prelude::{DispatcherBuilder, Read, System, SystemData, World, Write},
+    shrev::EventChannel,
     SystemBundle, SystemDesc, Time,
 };
 use amethyst_error::Error;
@@ -13,7 +14,7 @@ use amethyst_error::Error;
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
 
-use crate::{Format, FormatValue, Loader, Source};
+use crate::{AssetDependencies, Format, FormatValue, HotReloadEvent, Loader, Source};
 
 /// This bundle activates hot reload for the `Loader`,
 /// adds a `HotReloadStrategy` and the `HotReloadSystem`.
@@ -58,9 +59,20 @@ impl<'a, 'b> SystemBundle<'a, 'b> for HotReloadBundle {
 /// world.insert(HotReloadStrategy::every(2));
 /// # }
 /// ```
+///
+/// The strategy also holds the `AssetDependencies` recorded while it is present,
+/// so reloading an asset reloads the assets depending on it as well.
+///
+/// When every source of the `Loader` reports its changes, like `Directory::watched`,
+/// only the assets at the changed paths are checked instead of all of them.
 #[derive(Clone, Debug)]
 pub struct HotReloadStrategy {
     inner: HotReloadStrategyInner,
+    dependencies: AssetDependencies,
+    // the changes since the last reload, `None` if they are unknown
+    pending: Option<HashSet<String>>,
+    // the changes checked by the current reload
+    changed: Option<HashSet<String>>,
 }
 
 impl HotReloadStrategy {
@@ -74,6 +86,9 @@ impl HotReloadStrategy {
                 last: Instant::now(),
                 frame_number: MAX,
             },
+            dependencies: Default::default(),
+            pending: Some(HashSet::new()),
+            changed: None,
         }
     }
 
@@ -86,6 +101,9 @@ impl HotReloadStrategy {
                 triggered: false,
                 frame_number: MAX,
             },
+            dependencies: Default::default(),
+            pending: Some(HashSet::new()),
+            changed: None,
         }
     }
 
@@ -93,6 +111,9 @@ impl HotReloadStrategy {
     pub fn never() -> Self {
         HotReloadStrategy {
             inner: HotReloadStrategyInner::Never,
+            dependencies: Default::default(),
+            pending: Some(HashSet::new()),
+            changed: None,
         }
     }
 
@@ -107,6 +128,11 @@ impl HotReloadStrategy {
         }
     }
 
+    /// Returns the dependencies between the loaded assets.
+    pub fn dependencies(&self) -> &AssetDependencies {
+        &self.dependencies
+    }
+
     /// Crate-internal method to check if reload is necessary.
     /// `reload_counter` is a per-storage value which is only used
     /// for and by this method.
@@ -117,6 +143,32 @@ impl HotReloadStrategy {
             HotReloadStrategyInner::Never => false,
         }
     }
+
+    /// Crate-internal method to check if the asset at `path` may have changed
+    /// since the previous reload.
+    pub(crate) fn may_have_changed(&self, path: &str) -> bool {
+        match self.changed {
+            Some(ref changed) => path
+                .match_indices('/')
+                .map(|(end, _)| &path[..end])
+                .chain(iter::once(path))
+                .any(|changed_path| changed.contains(changed_path)),
+            None => true,
+        }
+    }
+
+    /// Adds the changes reported by the sources, `None` if they are unknown.
+    fn add_changes(&mut self, changes: Option<Vec<String>>) {
+        match (self.pending.as_mut(), changes) {
+            (Some(pending), Some(changes)) => pending.extend(changes),
+            _ => self.pending = None,
+        }
+    }
+
+    /// Makes the pending changes the ones checked by the next reload.
+    fn start_reload(&mut self) {
+        self.changed = mem::replace(&mut self.pending, Some(HashSet::new()));
+    }
 }
 
 impl Default for HotReloadStrategy {
@@ -158,37 +210,60 @@ impl<'a, 'b> SystemDesc<'a, 'b, HotReloadSystem> for HotReloadSystemDesc {
 }
 
 /// System for updating `HotReloadStrategy`.
+///
+/// Collects the paths the sources of the `Loader` reported as changed, so the next reload
+/// only checks the assets at those paths if every source knows its changes.
+///
+/// Once the reloads of changed assets and their dependents have finished,
+/// a `HotReloadEvent` listing them is written to the `EventChannel<HotReloadEvent>`.
 #[derive(Debug, new)]
 pub struct HotReloadSystem;
 
 impl<'a> System<'a> for HotReloadSystem {
-    type SystemData = (Read<'a, Time>, Write<'a, HotReloadStrategy>);
-
-    fn run(&mut self, (time, mut strategy): Self::SystemData) {
+    type SystemData = (
+        Read<'a, Time>,
+        Option<Read<'a, Loader>>,
+        Write<'a, HotReloadStrategy>,
+        Write<'a, EventChannel<HotReloadEvent>>,
+    );
+
+    fn run(&mut self, (time, loader, mut strategy, mut events): Self::SystemData) {
         #[cfg(feature = "profiler")]
         profile_scope!("hot_reload_system");
 
-        match strategy.inner {
+        if let Some(event) = strategy.dependencies.take_event() {
+            events.single_write(event);
+        }
+
+        strategy.add_changes(loader.and_then(|loader| loader.take_changes()));
+        let reload = match strategy.inner {
             HotReloadStrategyInner::Trigger {
                 ref mut triggered,
                 ref mut frame_number,
             } => {
-                if *triggered {
+                let reload = *triggered;
+                if reload {
                     *frame_number = time.frame_number() + 1;
                 }
                 *triggered = false;
+                reload
             }
             HotReloadStrategyInner::Every {
                 interval,
                 ref mut last,
                 ref mut frame_number,
             } => {
-                if last.elapsed().as_secs() > u64::from(interval) {
+                let reload = last.elapsed().as_secs() > u64::from(interval);
+                if reload {
                     *frame_number = time.frame_number() + 1;
                     *last = Instant::now();
                 }
+                reload
             }
-            HotReloadStrategyInner::Never => {}
+            HotReloadStrategyInner::Never => false,
+        };
+        if reload {
+            strategy.start_reload();
         }
     }
 }
@@ -289,3 +364,27 @@ impl<D: 'static> Reload<D> for SingleFile<D> {
         format.import(path, source, Some(objekt::clone(&format)))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::HotReloadStrategy;
+
+    #[test]
+    fn only_changed_paths_are_checked() {
+        let mut strategy = HotReloadStrategy::when_triggered();
+        assert!(strategy.may_have_changed("texture/player.png"));
+
+        strategy.add_changes(Some(vec!["mesh".into(), "texture/enemy.png".into()]));
+        strategy.start_reload();
+        assert!(strategy.may_have_changed("mesh/cube.obj"));
+        assert!(strategy.may_have_changed("texture/enemy.png"));
+        assert!(!strategy.may_have_changed("texture/player.png"));
+        assert!(!strategy.may_have_changed("meshes/cube.obj"));
+
+        // a source which doesn't know its changes makes every asset checked again
+        strategy.add_changes(Some(Vec::new()));
+        strategy.add_changes(None);
+        strategy.start_reload();
+        assert!(strategy.may_have_changed("texture/player.png"));
+    }
+}
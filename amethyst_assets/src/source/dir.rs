@@ -1,3 +1,5 @@
+#[cfg(feature = "watch")]
+use std::time::Duration;
 use std::{
     fs::File,
     path::{Path, PathBuf},
@@ -9,6 +11,8 @@ use thread_profiler::profile_scope;
 
 use amethyst_error::{format_err, Error, ResultExt};
 
+#[cfg(feature = "watch")]
+use super::watch::DirectoryWatch;
 use crate::{error, source::Source};
 
 /// Directory source.
@@ -17,9 +21,14 @@ use crate::{error, source::Source};
 /// inside the `Loader`, which is automatically used when you call
 /// `load`. In case you want another, second, directory for assets,
 /// you can instantiate one yourself, too. Please use `Loader::load_from` then.
+///
+/// With the `watch` feature, `Directory::watched` creates a source which is notified of
+/// changed files, so hot reloading doesn't have to check every asset on disk.
 #[derive(Debug)]
 pub struct Directory {
     loc: PathBuf,
+    #[cfg(feature = "watch")]
+    watch: Option<DirectoryWatch>,
 }
 
 impl Directory {
@@ -28,7 +37,43 @@ impl Directory {
     where
         P: Into<PathBuf>,
     {
-        Directory { loc: loc.into() }
+        Directory {
+            loc: loc.into(),
+            #[cfg(feature = "watch")]
+            watch: None,
+        }
+    }
+
+    /// Creates a new directory storage which watches the directory for changes,
+    /// using inotify on Linux and the native notifications on other platforms.
+    ///
+    /// The modification times of the assets are cached until a file changes, so `modified` only
+    /// touches the disk for changed assets. Changes are reported once a file didn't change for
+    /// `debounce`, so editors writing a file in several steps cause a single reload.
+    ///
+    /// ```no_run
+    /// use std::{sync::Arc, time::Duration};
+    ///
+    /// use amethyst_assets::{Directory, Loader};
+    /// use rayon::ThreadPoolBuilder;
+    ///
+    /// let pool = Arc::new(ThreadPoolBuilder::new().build().unwrap());
+    /// let source = Directory::watched("assets", Duration::from_millis(200))
+    ///     .expect("Failed to watch the assets");
+    /// let loader = Loader::with_default_source(source, pool);
+    /// ```
+    #[cfg(feature = "watch")]
+    pub fn watched<P>(loc: P, debounce: Duration) -> Result<Self, Error>
+    where
+        P: Into<PathBuf>,
+    {
+        let loc = loc.into();
+        let watch = DirectoryWatch::new(&loc, debounce)?;
+
+        Ok(Directory {
+            loc,
+            watch: Some(watch),
+        })
     }
 
     fn path(&self, s_path: &str) -> PathBuf {
@@ -37,12 +82,8 @@ impl Directory {
 
         path
     }
-}
 
-impl Source for Directory {
-    fn modified(&self, path: &str) -> Result<u64, Error> {
-        #[cfg(feature = "profiler")]
-        profile_scope!("dir_modified_asset");
+    fn stat(&self, path: &str) -> Result<u64, Error> {
         use std::fs::metadata;
 
         let path = self.path(path);
@@ -57,6 +98,33 @@ impl Source for Directory {
             })
             .map(|d| d.as_secs())
     }
+}
+
+impl Source for Directory {
+    fn modified(&self, path: &str) -> Result<u64, Error> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("dir_modified_asset");
+
+        #[cfg(feature = "watch")]
+        {
+            if let Some(ref watch) = self.watch {
+                return watch.modified(path, || self.stat(path));
+            }
+        }
+
+        self.stat(path)
+    }
+
+    fn take_changes(&self) -> Option<Vec<String>> {
+        #[cfg(feature = "watch")]
+        {
+            if let Some(ref watch) = self.watch {
+                return watch.take_changes();
+            }
+        }
+
+        None
+    }
 
     fn load(&self, path: &str) -> Result<Vec<u8>, Error> {
         #[cfg(feature = "profiler")]
@@ -0,0 +1,179 @@
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use amethyst_error::{format_err, Error, ResultExt};
+use log::debug;
+
+use crate::{error, source::Source};
+
+/// Layered source.
+///
+/// Stacks multiple sources, each path is served by the topmost layer which has it.
+/// This allows mods and patches to override single assets without touching the base assets.
+///
+/// Layers are added from the bottom to the top:
+///
+/// ```no_run
+/// use amethyst_assets::{Directory, LayeredSource, PakSource};
+///
+/// let source = LayeredSource::new()
+///     .with_layer("base", Directory::new("assets"))
+///     .with_layer("dlc", PakSource::open("dlc.pak").expect("Failed to open dlc.pak"))
+///     .with_layer("mods", Directory::new("mods"));
+///
+/// // "mods" if the mod folder has the texture, otherwise "dlc" or "base".
+/// let layer = source.resolve("texture/player.png");
+/// ```
+#[derive(Default)]
+pub struct LayeredSource {
+    // the layers with their names, from the bottom to the top.
+    layers: Vec<(String, Box<dyn Source>)>,
+}
+
+impl LayeredSource {
+    /// Creates a source without any layers.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds a layer on top of the existing ones.
+    pub fn with_layer<N, S>(mut self, name: N, source: S) -> Self
+    where
+        N: Into<String>,
+        S: Source,
+    {
+        self.add_layer(name, source);
+        self
+    }
+
+    /// Adds a layer on top of the existing ones.
+    pub fn add_layer<N, S>(&mut self, name: N, source: S)
+    where
+        N: Into<String>,
+        S: Source,
+    {
+        self.layers.push((name.into(), Box::new(source)));
+    }
+
+    /// Returns the names of the layers, from the top to the bottom.
+    pub fn layers(&self) -> impl Iterator<Item = &str> {
+        self.layers.iter().rev().map(|(name, _)| name.as_str())
+    }
+
+    /// Returns the name of the layer serving the given path, or `None` if no layer has it.
+    pub fn resolve(&self, path: &str) -> Option<&str> {
+        self.find(path).map(|(name, _, _)| name)
+    }
+
+    /// Returns the topmost layer which has the given path, with its modification time.
+    fn find(&self, path: &str) -> Option<(&str, &dyn Source, u64)> {
+        self.layers.iter().rev().find_map(|(name, source)| {
+            source
+                .modified(path)
+                .ok()
+                .map(|modified| (name.as_str(), &**source, modified))
+        })
+    }
+
+    fn missing(&self, path: &str) -> Error {
+        format_err!(
+            "None of the layers {:?} has {:?}",
+            self.layers().collect::<Vec<_>>(),
+            path
+        )
+    }
+}
+
+impl Source for LayeredSource {
+    fn modified(&self, path: &str) -> Result<u64, Error> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("layered_modified_asset");
+
+        self.find(path)
+            .map(|(_, _, modified)| modified)
+            .ok_or_else(|| self.missing(path))
+    }
+
+    fn load(&self, path: &str) -> Result<Vec<u8>, Error> {
+        self.load_with_metadata(path).map(|(bytes, _)| bytes)
+    }
+
+    fn load_with_metadata(&self, path: &str) -> Result<(Vec<u8>, u64), Error> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("layered_load_asset");
+
+        let (name, source, modified) = self
+            .find(path)
+            .ok_or_else(|| self.missing(path))
+            .with_context(|_| error::Error::Source)?;
+        debug!("Loading {:?} from layer {:?}", path, name);
+
+        let bytes = source
+            .load(path)
+            .with_context(|_| format_err!("Failed to load {:?} from layer {:?}", path, name))?;
+
+        Ok((bytes, modified))
+    }
+
+    fn take_changes(&self) -> Option<Vec<String>> {
+        // every layer is asked, so none of them keeps old changes
+        self.layers
+            .iter()
+            .map(|(_, source)| source.take_changes())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .map(|changes| changes.concat())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use amethyst_error::{format_err, Error};
+
+    use crate::source::Source;
+
+    use super::LayeredSource;
+
+    struct Memory(HashMap<&'static str, &'static [u8]>);
+
+    impl Source for Memory {
+        fn modified(&self, path: &str) -> Result<u64, Error> {
+            self.0
+                .get(path)
+                .map(|_| 0)
+                .ok_or_else(|| format_err!("No asset {:?}", path))
+        }
+
+        fn load(&self, path: &str) -> Result<Vec<u8>, Error> {
+            self.0
+                .get(path)
+                .map(|bytes| bytes.to_vec())
+                .ok_or_else(|| format_err!("No asset {:?}", path))
+        }
+    }
+
+    #[test]
+    fn topmost_layer_serves_path() {
+        let base = Memory(
+            vec![("texture", &b"base"[..]), ("prefab", &b"base"[..])]
+                .into_iter()
+                .collect(),
+        );
+        let modded = Memory(vec![("texture", &b"mod"[..])].into_iter().collect());
+        let source = LayeredSource::new()
+            .with_layer("base", base)
+            .with_layer("mod", modded);
+
+        assert_eq!(source.layers().collect::<Vec<_>>(), vec!["mod", "base"]);
+        assert_eq!(source.resolve("texture"), Some("mod"));
+        assert_eq!(source.resolve("prefab"), Some("base"));
+        assert_eq!(source.resolve("missing"), None);
+
+        assert_eq!(source.load("texture").unwrap(), b"mod".to_vec());
+        assert_eq!(source.load("prefab").unwrap(), b"base".to_vec());
+        assert!(source.load("missing").is_err());
+    }
+}
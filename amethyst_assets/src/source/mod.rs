@@ -1,11 +1,24 @@
-use amethyst_error::Error;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
-pub use self::dir::Directory;
+use amethyst_error::{format_err, Error, ResultExt};
+
+pub use self::{
+    dir::Directory,
+    layered::LayeredSource,
+    pak::{PakBuilder, PakCompression, PakSource},
+};
 
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
 
 mod dir;
+mod layered;
+mod pak;
+#[cfg(feature = "watch")]
+mod watch;
 
 /// A trait for asset sources, which provides
 /// methods for loading bytes.
@@ -32,4 +45,43 @@ pub trait Source: Send + Sync + 'static {
 
         Ok((b, m))
     }
+
+    /// Returns the paths changed since the last call, if the source is notified of changes.
+    ///
+    /// A changed directory stands for everything below it. The default implementation returns
+    /// `None`, meaning the changes are unknown, so hot reloading checks every asset with
+    /// `modified`.
+    fn take_changes(&self) -> Option<Vec<String>> {
+        None
+    }
+}
+
+/// Returns every file below the given directory, with its path relative to the directory
+/// using `/` as separator.
+pub(crate) fn files_below(dir: &Path) -> Result<Vec<(String, PathBuf)>, Error> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(current) = pending.pop() {
+        let entries = fs::read_dir(&current)
+            .with_context(|_| format_err!("Failed to read directory {:?}", current))?;
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+
+            let relative = path
+                .strip_prefix(dir)
+                .expect("Entry is below the directory")
+                .iter()
+                .map(|part| part.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            files.push((relative, path));
+        }
+    }
+
+    Ok(files)
 }
@@ -0,0 +1,404 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use parking_lot::Mutex;
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use amethyst_error::{format_err, Error, ResultExt};
+
+use crate::{
+    error,
+    source::{files_below, Source},
+};
+
+/// Identifies a pak archive.
+const MAGIC: [u8; 4] = *b"APAK";
+/// The version of the archive layout written by `PakBuilder`.
+const VERSION: u16 = 1;
+/// The size of the magic, the version and the offset of the index.
+const HEADER_SIZE: u64 = 4 + 2 + 8;
+
+/// How an entry of a pak archive is stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PakCompression {
+    /// The entry is stored as it is.
+    None,
+    /// The entry is compressed with deflate.
+    Deflate,
+}
+
+impl PakCompression {
+    fn from_byte(byte: u8) -> Result<Self, Error> {
+        match byte {
+            0 => Ok(PakCompression::None),
+            1 => Ok(PakCompression::Deflate),
+            _ => Err(format_err!("Unknown pak compression {}", byte)),
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            PakCompression::None => 0,
+            PakCompression::Deflate => 1,
+        }
+    }
+}
+
+/// The location of an entry in the archive.
+#[derive(Clone, Debug)]
+struct PakEntry {
+    offset: u64,
+    stored_size: u64,
+    size: u64,
+    modified: u64,
+    compression: PakCompression,
+}
+
+/// Pak archive source.
+///
+/// Reads assets from a single archive written by a `PakBuilder`, which is a lot faster to ship
+/// and open than thousands of small files. The index of the archive is read once when it is opened,
+/// so looking up an asset does not touch the disk.
+///
+/// The modification time of an asset is the one recorded when it was added to the archive.
+/// Register the source with `Loader::add_source` and load from it with `Loader::load_from`.
+#[derive(Debug)]
+pub struct PakSource {
+    loc: PathBuf,
+    file: Mutex<File>,
+    index: HashMap<String, PakEntry>,
+}
+
+impl PakSource {
+    /// Opens the archive at the given location and reads its index.
+    pub fn open<P>(loc: P) -> Result<Self, Error>
+    where
+        P: Into<PathBuf>,
+    {
+        let loc = loc.into();
+        let mut file = File::open(&loc)
+            .with_context(|_| format_err!("Failed to open pak archive {:?}", loc))?;
+        let index = read_index(&mut file)
+            .with_context(|_| format_err!("Failed to read the index of pak archive {:?}", loc))?;
+
+        Ok(PakSource {
+            loc,
+            file: Mutex::new(file),
+            index,
+        })
+    }
+
+    /// Returns `true` if the archive contains an asset with the given path.
+    pub fn contains(&self, path: &str) -> bool {
+        self.index.contains_key(path)
+    }
+
+    /// Returns an iterator over the paths of all assets in the archive.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.index.keys().map(String::as_str)
+    }
+
+    fn entry(&self, path: &str) -> Result<&PakEntry, Error> {
+        self.index
+            .get(path)
+            .ok_or_else(|| format_err!("No entry {:?} in pak archive {:?}", path, self.loc))
+    }
+}
+
+impl Source for PakSource {
+    fn modified(&self, path: &str) -> Result<u64, Error> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("pak_modified_asset");
+
+        Ok(self.entry(path)?.modified)
+    }
+
+    fn load(&self, path: &str) -> Result<Vec<u8>, Error> {
+        #[cfg(feature = "profiler")]
+        profile_scope!("pak_load_asset");
+
+        let entry = self.entry(path).with_context(|_| error::Error::Source)?;
+
+        let mut stored = vec![0; entry.stored_size as usize];
+        {
+            let mut file = self.file.lock();
+            file.seek(SeekFrom::Start(entry.offset))
+                .and_then(|_| file.read_exact(&mut stored))
+                .with_context(|_| format_err!("Failed to read entry {:?} of {:?}", path, self.loc))
+                .with_context(|_| error::Error::Source)?;
+        }
+
+        match entry.compression {
+            PakCompression::None => Ok(stored),
+            PakCompression::Deflate => {
+                let mut data = Vec::with_capacity(entry.size as usize);
+                DeflateDecoder::new(&stored[..])
+                    .read_to_end(&mut data)
+                    .with_context(|_| format_err!("Failed to inflate entry {:?}", path))
+                    .with_context(|_| error::Error::Source)?;
+                Ok(data)
+            }
+        }
+    }
+}
+
+fn read_index(file: &mut File) -> Result<HashMap<String, PakEntry>, Error> {
+    let mut magic = [0; 4];
+    file.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(format_err!("Not a pak archive"));
+    }
+    let version = read_u16(file)?;
+    if version != VERSION {
+        return Err(format_err!("Unsupported pak version {}", version));
+    }
+    let index_offset = read_u64(file)?;
+
+    let mut index = Vec::new();
+    file.seek(SeekFrom::Start(index_offset))?;
+    file.read_to_end(&mut index)?;
+
+    let mut reader = &index[..];
+    let count = read_u32(&mut reader)?;
+    let mut entries = HashMap::with_capacity(count as usize);
+    for _ in 0..count {
+        let mut path = vec![0; read_u16(&mut reader)? as usize];
+        reader.read_exact(&mut path)?;
+        let path = String::from_utf8(path)?;
+
+        let offset = read_u64(&mut reader)?;
+        let stored_size = read_u64(&mut reader)?;
+        let size = read_u64(&mut reader)?;
+        let modified = read_u64(&mut reader)?;
+        let mut compression = [0; 1];
+        reader.read_exact(&mut compression)?;
+        let compression = PakCompression::from_byte(compression[0])?;
+
+        entries.insert(
+            path,
+            PakEntry {
+                offset,
+                stored_size,
+                size,
+                modified,
+                compression,
+            },
+        );
+    }
+
+    Ok(entries)
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> std::io::Result<u16> {
+    let mut buf = [0; 2];
+    reader.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> std::io::Result<u32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> std::io::Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// Writes the archives read by `PakSource`.
+///
+/// ```no_run
+/// use amethyst_assets::{PakBuilder, PakCompression};
+///
+/// let mut pak = PakBuilder::new();
+/// pak.add_directory("assets", PakCompression::Deflate)
+///     .expect("Failed to add the assets");
+/// pak.write_to_file("assets.pak").expect("Failed to write the archive");
+/// ```
+#[derive(Debug, Default)]
+pub struct PakBuilder {
+    entries: Vec<(String, Vec<u8>, u64, PakCompression)>,
+}
+
+impl PakBuilder {
+    /// Creates an empty `PakBuilder`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds an asset with the given path, which should use `/` as separator,
+    /// and its modification time as seconds since `UNIX_EPOCH`.
+    pub fn add<P>(&mut self, path: P, data: Vec<u8>, modified: u64, compression: PakCompression)
+    where
+        P: Into<String>,
+    {
+        self.entries
+            .push((path.into(), data, modified, compression));
+    }
+
+    /// Adds every file below the given directory, with its path relative to the directory.
+    pub fn add_directory<P>(&mut self, dir: P, compression: PakCompression) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        for (relative, path) in files_below(dir.as_ref())? {
+            let modified = fs::metadata(&path)?
+                .modified()?
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            let data =
+                fs::read(&path).with_context(|_| format_err!("Failed to read file {:?}", path))?;
+
+            self.add(relative, data, modified, compression);
+        }
+
+        Ok(())
+    }
+
+    /// Writes the archive.
+    ///
+    /// Nothing is written if a path is used by more than one entry or is longer than `u16::MAX` bytes.
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        self.validate()?;
+
+        let mut stored = Vec::with_capacity(self.entries.len());
+        for (path, data, _, compression) in &self.entries {
+            stored.push(match compression {
+                PakCompression::None => data.clone(),
+                PakCompression::Deflate => {
+                    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                    encoder
+                        .write_all(data)
+                        .and_then(|_| encoder.finish())
+                        .with_context(|_| format_err!("Failed to deflate {:?}", path))?
+                }
+            });
+        }
+
+        let data_size = stored.iter().map(|data| data.len() as u64).sum::<u64>();
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&(HEADER_SIZE + data_size).to_le_bytes())?;
+        for data in &stored {
+            writer.write_all(data)?;
+        }
+
+        let mut offset = HEADER_SIZE;
+        writer.write_all(&(self.entries.len() as u32).to_le_bytes())?;
+        for ((path, data, modified, compression), stored) in self.entries.iter().zip(&stored) {
+            writer.write_all(&(path.len() as u16).to_le_bytes())?;
+            writer.write_all(path.as_bytes())?;
+            writer.write_all(&offset.to_le_bytes())?;
+            writer.write_all(&(stored.len() as u64).to_le_bytes())?;
+            writer.write_all(&(data.len() as u64).to_le_bytes())?;
+            writer.write_all(&modified.to_le_bytes())?;
+            writer.write_all(&[compression.to_byte()])?;
+            offset += stored.len() as u64;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the archive to a file at the given location.
+    ///
+    /// The archive is built in memory first, so the file is left alone if `write` fails.
+    pub fn write_to_file<P>(&self, loc: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        let loc = loc.as_ref();
+        let mut bytes = Vec::new();
+        self.write(&mut bytes)?;
+
+        fs::write(loc, bytes)
+            .with_context(|_| format_err!("Failed to write pak archive {:?}", loc))?;
+        Ok(())
+    }
+
+    /// Checks that every path fits in the index and is used by a single entry.
+    fn validate(&self) -> Result<(), Error> {
+        let mut paths = HashSet::with_capacity(self.entries.len());
+        for (path, ..) in &self.entries {
+            if path.len() > u16::max_value() as usize {
+                return Err(format_err!("Path {:?} is too long for a pak archive", path));
+            }
+            if !paths.insert(path.as_str()) {
+                return Err(format_err!("Path {:?} was added more than once", path));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, path::Path, process};
+
+    use crate::source::Source;
+
+    use super::{PakBuilder, PakCompression, PakSource};
+
+    #[test]
+    fn loads_assets_from_archive() {
+        let loc = env::temp_dir().join(format!("amethyst_assets_pak_{}.pak", process::id()));
+
+        let mut pak = PakBuilder::new();
+        pak.add_directory(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/assets"),
+            PakCompression::None,
+        )
+        .expect("Failed to add tests/assets");
+        pak.add(
+            "compressed",
+            vec![7; 4096],
+            1_000_000,
+            PakCompression::Deflate,
+        );
+        pak.write_to_file(&loc)
+            .expect("Failed to write the archive");
+
+        let source = PakSource::open(&loc).expect("Failed to open the archive");
+
+        assert_eq!(
+            b"data".to_vec(),
+            source
+                .load("subdir/asset")
+                .expect("Failed to load subdir/asset")
+        );
+        assert_eq!(vec![7; 4096], source.load("compressed").unwrap());
+        assert_eq!(1_000_000, source.modified("compressed").unwrap());
+        assert!(source.load("missing").is_err());
+
+        drop(source);
+        std::fs::remove_file(&loc).expect("Failed to remove the archive");
+    }
+
+    #[test]
+    fn invalid_entries_are_rejected_before_writing() {
+        let mut duplicate = PakBuilder::new();
+        duplicate.add("asset", vec![1], 0, PakCompression::None);
+        duplicate.add("asset", vec![2], 0, PakCompression::None);
+
+        let mut too_long = PakBuilder::new();
+        too_long.add("asset", vec![1], 0, PakCompression::None);
+        too_long.add("a".repeat(70_000), vec![2], 0, PakCompression::None);
+
+        for pak in &[duplicate, too_long] {
+            let mut written = Vec::new();
+            assert!(pak.write(&mut written).is_err());
+            assert!(written.is_empty());
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver},
+    time::Duration,
+};
+
+use fnv::{FnvHashMap, FnvHashSet};
+use log::warn;
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use parking_lot::Mutex;
+
+use amethyst_error::{format_err, Error, ResultExt};
+
+/// Watches a directory for changes and caches the modification times of the assets in it,
+/// so only the assets which changed are checked on disk again.
+///
+/// The changed paths are collected until they are taken by hot reloading, which then only
+/// checks the assets below them.
+pub(super) struct DirectoryWatch {
+    root: PathBuf,
+    state: Mutex<WatchState>,
+}
+
+struct WatchState {
+    // dropping the watcher stops the notifications
+    _watcher: RecommendedWatcher,
+    events: Receiver<DebouncedEvent>,
+    modified: FnvHashMap<String, u64>,
+    // `None` if a change could not be attributed to a path
+    changes: Option<FnvHashSet<String>>,
+}
+
+impl DirectoryWatch {
+    pub(super) fn new(loc: &Path, debounce: Duration) -> Result<Self, Error> {
+        let root = loc
+            .canonicalize()
+            .with_context(|_| format_err!("Failed to find directory {:?}", loc))?;
+
+        let (sender, events) = channel();
+        let mut watcher: RecommendedWatcher = Watcher::new(sender, debounce)
+            .with_context(|_| format_err!("Failed to create a file watcher"))?;
+        watcher
+            .watch(&root, RecursiveMode::Recursive)
+            .with_context(|_| format_err!("Failed to watch directory {:?}", root))?;
+
+        Ok(DirectoryWatch {
+            root,
+            state: Mutex::new(WatchState {
+                _watcher: watcher,
+                events,
+                modified: Default::default(),
+                changes: Some(Default::default()),
+            }),
+        })
+    }
+
+    /// Returns the cached modification time of the asset,
+    /// calling `stat` if it changed since it was last checked.
+    pub(super) fn modified<F>(&self, path: &str, stat: F) -> Result<u64, Error>
+    where
+        F: FnOnce() -> Result<u64, Error>,
+    {
+        let mut state = self.state.lock();
+        state.apply_events(&self.root);
+
+        if let Some(modified) = state.modified.get(path) {
+            return Ok(*modified);
+        }
+        let modified = stat()?;
+        state.modified.insert(path.to_owned(), modified);
+
+        Ok(modified)
+    }
+
+    /// Returns the paths changed since the last call, `None` if the changes are unknown.
+    pub(super) fn take_changes(&self) -> Option<Vec<String>> {
+        let mut state = self.state.lock();
+        state.apply_events(&self.root);
+
+        state
+            .changes
+            .replace(Default::default())
+            .map(|changes| changes.into_iter().collect())
+    }
+}
+
+impl fmt::Debug for DirectoryWatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DirectoryWatch")
+            .field("root", &self.root)
+            .finish()
+    }
+}
+
+impl WatchState {
+    fn apply_events(&mut self, root: &Path) {
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                // the debounced events follow once the file stopped changing.
+                DebouncedEvent::NoticeWrite(_) | DebouncedEvent::NoticeRemove(_) => {}
+                DebouncedEvent::Create(path)
+                | DebouncedEvent::Write(path)
+                | DebouncedEvent::Chmod(path)
+                | DebouncedEvent::Remove(path) => self.invalidate(root, &path),
+                DebouncedEvent::Rename(from, to) => {
+                    self.invalidate(root, &from);
+                    self.invalidate(root, &to);
+                }
+                DebouncedEvent::Rescan => self.forget_all(),
+                DebouncedEvent::Error(error, path) => {
+                    warn!(
+                        "Error while watching {:?}: {}, checking all assets again",
+                        path.as_ref().map_or(root, PathBuf::as_path),
+                        error
+                    );
+                    self.forget_all();
+                }
+            }
+        }
+    }
+
+    /// Forgets the modification time of the changed path, and everything below it
+    /// if it is a directory.
+    fn invalidate(&mut self, root: &Path, changed: &Path) {
+        let relative = match changed.strip_prefix(root) {
+            Ok(relative) => relative
+                .iter()
+                .map(|part| part.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/"),
+            Err(_) => return,
+        };
+        if relative.is_empty() {
+            self.forget_all();
+            return;
+        }
+        let prefix = format!("{}/", relative);
+
+        self.modified
+            .retain(|path, _| *path != relative && !path.starts_with(&prefix));
+        if let Some(ref mut changes) = self.changes {
+            changes.insert(relative);
+        }
+    }
+
+    fn forget_all(&mut self) {
+        self.modified.clear();
+        self.changes = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        env, fs, process,
+        thread::sleep,
+        time::{Duration, Instant},
+    };
+
+    use crate::source::{Directory, Source};
+
+    #[test]
+    fn changed_assets_are_checked_again() {
+        let dir = env::temp_dir().join(format!("amethyst_assets_watch_{}", process::id()));
+        fs::create_dir_all(dir.join("subdir")).expect("Failed to create the directory");
+        fs::write(dir.join("subdir/asset"), b"data").expect("Failed to write the asset");
+
+        let directory = Directory::watched(&dir, Duration::from_millis(50))
+            .expect("Failed to watch the directory");
+        assert!(directory.modified("subdir/asset").is_ok());
+        assert_eq!(directory.take_changes(), Some(Vec::new()));
+
+        fs::remove_file(dir.join("subdir/asset")).expect("Failed to remove the asset");
+        let start = Instant::now();
+        while directory.modified("subdir/asset").is_ok() {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "The removal was not noticed"
+            );
+            sleep(Duration::from_millis(10));
+        }
+        let changes = directory.take_changes().expect("The changes are known");
+        assert!(changes.contains(&"subdir/asset".to_string()));
+
+        drop(directory);
+        fs::remove_dir_all(&dir).expect("Failed to remove the directory");
+    }
+}
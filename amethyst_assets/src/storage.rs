@@ -8,15 +8,17 @@ use std::{
 
 use crossbeam_queue::SegQueue;
 use derivative::Derivative;
+use fnv::FnvHashMap;
 use log::{debug, error, trace, warn};
 use rayon::ThreadPool;
 
 use amethyst_core::{
     ecs::{
-        hibitset::BitSet,
+        hibitset::{BitSet, BitSetLike},
         prelude::{Component, Read, ReadExpect, System, SystemData, VecStorage, World, Write},
         storage::UnprotectedStorage,
     },
+    shrev::EventChannel,
     SystemDesc, Time,
 };
 use amethyst_error::{Error, ResultExt};
@@ -26,9 +28,11 @@ use thread_profiler::profile_scope;
 
 use crate::{
     asset::{Asset, FormatValue, ProcessableAsset},
+    dependencies::{AssetDependencies, AssetKey},
     error,
     progress::Tracker,
     reload::{HotReloadStrategy, Reload},
+    usage::AssetUsage,
 };
 
 /// An `Allocator`, holding a counter for producing unique IDs.
@@ -46,6 +50,10 @@ impl Allocator {
 
 /// An asset storage, storing the actual assets and allocating
 /// handles to them.
+///
+/// Assets are unloaded by `process` once all of their handles were dropped, after an optional
+/// grace period during which they can still be retrieved by upgrading a `WeakHandle`,
+/// e.g. from a `Cache`.
 pub struct AssetStorage<A: Asset> {
     assets: VecStorage<(A, u32)>,
     bitset: BitSet,
@@ -54,6 +62,54 @@ pub struct AssetStorage<A: Asset> {
     pub(crate) processed: Arc<SegQueue<Processed<A>>>,
     reloads: Vec<(WeakHandle<A>, Box<dyn Reload<A::Data>>)>,
     unused_handles: SegQueue<Handle<A>>,
+    // passed on to the event channel of the asset type by `write_events`
+    events: Vec<AssetEvent<A>>,
+    tracks_events: bool,
+    unload_grace_period: u64,
+    // the frame at which the assets without handles were first noticed
+    unused_since: FnvHashMap<u32, u64>,
+    memory_budget: Option<usize>,
+    usage: AssetUsage,
+}
+
+/// Describes a change of an asset in an `AssetStorage`.
+///
+/// The systems processing the storage write the events to the `EventChannel<AssetEvent<A>>`
+/// resource of the asset type, so they can be read without borrowing the storage mutably.
+/// The events hold `WeakHandle`s, so they don't keep the assets alive.
+///
+/// ## Examples
+///
+/// ```
+/// # use amethyst_assets::{AssetEvent, Prefab};
+/// # use amethyst_core::shrev::EventChannel;
+/// #
+/// # let mut channel = EventChannel::<AssetEvent<Prefab<()>>>::new();
+/// let mut reader = channel.register_reader();
+/// // ...
+/// for event in channel.read(&mut reader) {
+///     match event {
+///         AssetEvent::Loaded(handle) | AssetEvent::Reloaded(handle) => {
+///             if let Some(handle) = handle.upgrade() {
+///                 println!("Asset {} changed", handle.id());
+///             }
+///         }
+///         AssetEvent::Failed(_, error) => println!("Asset failed to load: {}", error),
+///         AssetEvent::Unloaded(id) => println!("Asset {} was unloaded", id),
+///     }
+/// }
+/// ```
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""))]
+pub enum AssetEvent<A> {
+    /// The asset was loaded and can be retrieved with the handle.
+    Loaded(WeakHandle<A>),
+    /// The asset was replaced by a hot reload.
+    Reloaded(WeakHandle<A>),
+    /// Loading the asset failed. If it was a hot reload, the previous asset is still available.
+    Failed(WeakHandle<A>, Error),
+    /// The asset with the given handle id was removed from the storage.
+    Unloaded(u32),
 }
 
 /// Returned by processor systems, describes the loading state of the asset.
@@ -73,6 +129,44 @@ impl<A: Asset> AssetStorage<A> {
         Default::default()
     }
 
+    /// Keeps the `AssetEvent`s of the following calls to `process` and `unload_all`
+    /// until they are passed on by `write_events`.
+    ///
+    /// Without it the events are dropped, so storages which are processed without
+    /// writing their events don't collect them forever.
+    pub fn track_events(&mut self) {
+        self.tracks_events = true;
+    }
+
+    /// Writes the `AssetEvent`s since the last call to the given channel.
+    ///
+    /// Systems processing the storage call `track_events` before `process` and this after it.
+    pub fn write_events(&mut self, channel: &mut EventChannel<AssetEvent<A>>) {
+        channel.drain_vec_write(&mut self.events);
+    }
+
+    /// Sets the number of frames an asset stays loaded after all of its handles were dropped.
+    ///
+    /// Defaults to zero, unloading unused assets in the next call to `process`.
+    /// A grace period keeps assets which are dropped and loaded again shortly after,
+    /// like those shared by consecutive levels, from being reloaded.
+    pub fn set_unload_grace_period(&mut self, frames: u64) {
+        self.unload_grace_period = frames;
+    }
+
+    /// Sets the memory budget of this storage in bytes, as estimated by `Asset::size_hint`.
+    ///
+    /// While the assets use more memory than the budget allows, unused assets are unloaded
+    /// before their grace period is over, the longest unused first.
+    pub fn set_memory_budget(&mut self, bytes: Option<usize>) {
+        self.memory_budget = bytes;
+    }
+
+    /// Returns the number of assets and their memory usage as of the last call to `process`.
+    pub fn usage(&self) -> AssetUsage {
+        self.usage
+    }
+
     /// Allocate a new handle.
     pub(crate) fn allocate(&self) -> Handle<A> {
         self.unused_handles
@@ -92,6 +186,10 @@ impl<A: Asset> AssetStorage<A> {
     /// Trying to retreive any data using old handle will return `None`.
     pub fn unload_all(&mut self) {
         unsafe { self.assets.clean(&self.bitset) }
+        if self.tracks_events {
+            self.events
+                .extend((&self.bitset).iter().map(AssetEvent::Unloaded));
+        }
         self.bitset.clear();
     }
 
@@ -242,6 +340,8 @@ impl<A: Asset> AssetStorage<A> {
         D: FnMut(A),
         F: FnMut(A::Data) -> Result<ProcessingState<A>, Error>,
     {
+        let dependencies = strategy.map(HotReloadStrategy::dependencies);
+
         {
             let mut requeue = Vec::new();
             while let Ok(processed) = self.processed.pop() {
@@ -249,9 +349,17 @@ impl<A: Asset> AssetStorage<A> {
                 let bitset = &mut self.bitset;
                 let handles = &mut self.handles;
                 let reloads = &mut self.reloads;
+                let events = &mut self.events;
 
                 let f = &mut f;
                 let (reload_obj, handle) = match processed {
+                    Processed::Cancelled(id) => {
+                        self.unused_handles.push(Handle {
+                            id: Arc::new(id),
+                            marker: PhantomData,
+                        });
+                        continue;
+                    }
                     Processed::NewAsset {
                         data,
                         handle,
@@ -281,7 +389,7 @@ impl<A: Asset> AssetStorage<A> {
                                     tracker.fail(
                                         handle.id(),
                                         A::NAME,
-                                        name,
+                                        name.clone(),
                                         Error::from(error::Error::UnusedHandle),
                                     );
                                 } else {
@@ -313,6 +421,7 @@ impl<A: Asset> AssetStorage<A> {
                                     handle,
                                     e,
                                 );
+                                events.push(AssetEvent::Failed(handle.downgrade(), copy_error(&e)));
                                 tracker.fail(handle.id(), A::NAME, name, e);
 
                                 continue;
@@ -323,6 +432,15 @@ impl<A: Asset> AssetStorage<A> {
                         bitset.add(id);
                         handles.push(handle.clone());
 
+                        if let Some(dependencies) = dependencies {
+                            dependencies.record(
+                                AssetKey::new::<A>(id),
+                                &name,
+                                asset.dependencies(),
+                            );
+                        }
+                        events.push(AssetEvent::Loaded(handle.downgrade()));
+
                         // NOTE: the loader has to ensure that a handle will be used
                         // together with a `Data` only once.
                         unsafe {
@@ -368,7 +486,12 @@ impl<A: Asset> AssetStorage<A> {
                                     e,
                                 );
 
+                                events.push(AssetEvent::Failed(handle.downgrade(), e));
                                 reloads.push((handle.downgrade(), old_reload));
+                                if let Some(dependencies) = dependencies {
+                                    dependencies
+                                        .reload_finished(AssetKey::new::<A>(handle.id()), false);
+                                }
 
                                 continue;
                             }
@@ -380,9 +503,15 @@ impl<A: Asset> AssetStorage<A> {
                             "Expected handle {:?} to be valid, but the asset storage says otherwise",
                             handle,
                         );
+                        if let Some(dependencies) = dependencies {
+                            let key = AssetKey::new::<A>(id);
+                            dependencies.record(key, &name, asset.dependencies());
+                            dependencies.reload_finished(key, true);
+                        }
                         let data = unsafe { self.assets.get_mut(id) };
                         data.1 += 1;
                         drop_fn(std::mem::replace(&mut data.0, asset));
+                        events.push(AssetEvent::Reloaded(handle.downgrade()));
 
                         (reload_obj, handle)
                     }
@@ -400,78 +529,232 @@ impl<A: Asset> AssetStorage<A> {
         }
 
         let mut count = 0;
-        let mut skip = 0;
-        while let Some(i) = self.handles.iter().skip(skip).position(Handle::is_unique) {
-            count += 1;
-            // Re-normalize index
-            let i = skip + i;
-            skip = i;
-            let handle = self.handles.swap_remove(i);
-            let id = handle.id();
-            unsafe {
-                let (asset, _) = self.assets.remove(id);
-                drop_fn(asset);
+        let mut i = 0;
+        while i < self.handles.len() {
+            let id = self.handles[i].id();
+            if !self.handles[i].is_unique() {
+                self.unused_since.remove(&id);
+                i += 1;
+                continue;
             }
-            self.bitset.remove(id);
 
-            // Can't reuse old handle here, because otherwise weak handles would still be valid.
-            // TODO: maybe just store u32?
-            self.unused_handles.push(Handle {
-                id: Arc::new(id),
-                marker: PhantomData,
-            });
+            let since = *self.unused_since.entry(id).or_insert(frame_number);
+            if frame_number.saturating_sub(since) < self.unload_grace_period {
+                i += 1;
+                continue;
+            }
+            self.free(i, &mut drop_fn, dependencies);
+            count += 1;
+        }
+
+        let was_over_budget = self.usage.is_over_budget();
+        self.update_usage();
+        if let Some(budget) = self.memory_budget {
+            count += self.enforce_budget(budget, &mut drop_fn, dependencies);
+            if self.usage.is_over_budget() && !was_over_budget {
+                warn!(
+                    "{:?}: Assets use {} bytes, more than the budget of {} bytes",
+                    A::NAME,
+                    self.usage.bytes,
+                    budget,
+                );
+            }
         }
         if count != 0 {
             debug!("{:?}: Freed {} handle ids", A::NAME, count,);
         }
 
-        if strategy
-            .map(|s| s.needs_reload(frame_number))
-            .unwrap_or(false)
-        {
+        if let Some(strategy) = strategy.filter(|s| s.needs_reload(frame_number)) {
             trace!("{:?}: Testing for asset reloads..", A::NAME);
-            self.hot_reload(pool);
+            self.hot_reload(pool, strategy);
+        }
+
+        if let Some(dependencies) = dependencies {
+            self.reload_dependents(pool, dependencies);
+        }
+
+        if !self.tracks_events {
+            self.events.clear();
+        }
+    }
+
+    /// Removes the asset of the handle at the given index, which must be unique.
+    fn free<D>(&mut self, index: usize, drop_fn: &mut D, dependencies: Option<&AssetDependencies>)
+    where
+        D: FnMut(A),
+    {
+        let handle = self.handles.swap_remove(index);
+        let id = handle.id();
+        // the asset is already gone if the storage was cleared by `unload_all`
+        if self.bitset.remove(id) {
+            unsafe {
+                let (asset, _) = self.assets.remove(id);
+                drop_fn(asset);
+            }
+            self.events.push(AssetEvent::Unloaded(id));
+        }
+        self.unused_since.remove(&id);
+        if let Some(dependencies) = dependencies {
+            dependencies.remove(AssetKey::new::<A>(id));
+        }
+
+        // Can't reuse old handle here, because otherwise weak handles would still be valid.
+        // TODO: maybe just store u32?
+        self.unused_handles.push(Handle {
+            id: Arc::new(id),
+            marker: PhantomData,
+        });
+    }
+
+    /// Unloads unused assets, the longest unused first, until the budget is kept.
+    /// Returns the number of unloaded assets.
+    fn enforce_budget<D>(
+        &mut self,
+        budget: usize,
+        drop_fn: &mut D,
+        dependencies: Option<&AssetDependencies>,
+    ) -> usize
+    where
+        D: FnMut(A),
+    {
+        let mut unused = self
+            .unused_since
+            .iter()
+            .map(|(&id, &since)| (since, id))
+            .collect::<Vec<_>>();
+        unused.sort();
+
+        let mut count = 0;
+        for (_, id) in unused {
+            if self.usage.bytes <= budget {
+                break;
+            }
+            let index = match self.handles.iter().position(|handle| handle.id() == id) {
+                Some(index) if self.bitset.contains(id) => index,
+                _ => continue,
+            };
+            let size = unsafe { self.assets.get(id).0.size_hint() };
+            self.free(index, drop_fn, dependencies);
+            self.usage.count -= 1;
+            self.usage.bytes -= size;
+            self.usage.unused -= 1;
+            count += 1;
         }
+
+        count
     }
 
-    fn hot_reload(&mut self, pool: &ThreadPool) {
+    fn update_usage(&mut self) {
+        let (assets, bitset) = (&self.assets, &self.bitset);
+        let (count, bytes) = self
+            .handles
+            .iter()
+            .filter(|handle| bitset.contains(handle.id()))
+            .fold((0, 0), |(count, bytes), handle| {
+                let size = unsafe { assets.get(handle.id()).0.size_hint() };
+                (count + 1, bytes + size)
+            });
+
+        self.usage = AssetUsage {
+            count,
+            bytes,
+            unused: self.unused_since.len(),
+            budget: self.memory_budget,
+        };
+    }
+
+    fn hot_reload(&mut self, pool: &ThreadPool, strategy: &HotReloadStrategy) {
         self.reloads.retain(|&(ref handle, _)| !handle.is_dead());
         while let Some(p) = self
             .reloads
             .iter()
-            .position(|&(_, ref rel)| rel.needs_reload())
+            .position(|&(_, ref rel)| strategy.may_have_changed(&rel.name()) && rel.needs_reload())
         {
             let (handle, rel): (WeakHandle<_>, Box<dyn Reload<_>>) = self.reloads.swap_remove(p);
 
-            let name = rel.name();
-            let format = rel.format();
-            let handle = handle.upgrade();
-
             debug!(
                 "{:?}: Asset {:?} (handle id: {:?}) needs a reload using format {:?}",
                 A::NAME,
-                name,
-                handle,
-                format,
+                rel.name(),
+                handle.upgrade(),
+                rel.format(),
             );
 
-            if let Some(handle) = handle {
-                let processed = self.processed.clone();
-                pool.spawn(move || {
-                    let old_reload = rel.clone();
-                    let data = rel.reload().with_context(|_| error::Error::Format(format));
+            if let Some(handle) = handle.upgrade() {
+                strategy
+                    .dependencies()
+                    .reload_started(AssetKey::of(&handle));
+                self.spawn_reload(pool, handle, rel);
+            }
+        }
+    }
 
-                    let p = Processed::HotReload {
-                        data,
-                        name,
+    /// Reloads the assets whose dependencies have been reloaded.
+    fn reload_dependents(&mut self, pool: &ThreadPool, dependencies: &AssetDependencies) {
+        for id in dependencies.take_ready::<A>() {
+            let position = self.reloads.iter().position(|&(ref handle, _)| {
+                handle.upgrade().map(|handle| handle.id()) == Some(id)
+            });
+            let reload = position
+                .map(|p| self.reloads.swap_remove(p))
+                .and_then(|(handle, rel)| handle.upgrade().map(|handle| (handle, rel)));
+
+            match reload {
+                Some((handle, rel)) => {
+                    debug!(
+                        "{:?}: Asset {:?} (handle id: {:?}) is reloaded because a dependency changed",
+                        A::NAME,
+                        rel.name(),
                         handle,
-                        old_reload,
-                    };
-                    processed.push(p);
-                });
+                    );
+                    self.spawn_reload(pool, handle, rel);
+                }
+                None => {
+                    debug!(
+                        "{:?}: Asset with handle id {:?} can not be reloaded for its changed dependency",
+                        A::NAME,
+                        id,
+                    );
+                    dependencies.reload_finished(AssetKey::new::<A>(id), false);
+                }
             }
         }
     }
+
+    fn spawn_reload(&self, pool: &ThreadPool, handle: Handle<A>, rel: Box<dyn Reload<A::Data>>) {
+        let name = rel.name();
+        let format = rel.format();
+        let processed = self.processed.clone();
+        pool.spawn(move || {
+            let old_reload = rel.clone();
+            let data = rel.reload().with_context(|_| error::Error::Format(format));
+
+            let p = Processed::HotReload {
+                data,
+                name,
+                handle,
+                old_reload,
+            };
+            processed.push(p);
+        });
+    }
+}
+
+/// Copies the messages of an error and its causes, for the `AssetEvent::Failed` of an error
+/// which is passed on to the `Tracker`.
+fn copy_error(error: &Error) -> Error {
+    let messages = error.causes().map(ToString::to_string).collect::<Vec<_>>();
+    messages
+        .into_iter()
+        .rev()
+        .fold(None, |source: Option<Error>, message| {
+            let error = Error::from_string(message);
+            Some(match source {
+                Some(source) => error.with_source(source),
+                None => error,
+            })
+        })
+        .expect("An error is its own first cause")
 }
 
 impl<A: Asset> Default for AssetStorage<A> {
@@ -484,6 +767,12 @@ impl<A: Asset> Default for AssetStorage<A> {
             processed: Arc::new(SegQueue::new()),
             reloads: Default::default(),
             unused_handles: SegQueue::new(),
+            events: Vec::new(),
+            tracks_events: false,
+            unload_grace_period: 0,
+            unused_since: Default::default(),
+            memory_budget: None,
+            usage: Default::default(),
         }
     }
 }
@@ -535,20 +824,23 @@ where
         ReadExpect<'a, Arc<ThreadPool>>,
         Read<'a, Time>,
         Option<Read<'a, HotReloadStrategy>>,
+        Write<'a, EventChannel<AssetEvent<A>>>,
     );
 
-    fn run(&mut self, (mut storage, pool, time, strategy): Self::SystemData) {
+    fn run(&mut self, (mut storage, pool, time, strategy, mut events): Self::SystemData) {
         #[cfg(feature = "profiler")]
         profile_scope!("processor_system");
 
         use std::ops::Deref;
 
+        storage.track_events();
         storage.process(
             ProcessableAsset::process,
             time.frame_number(),
             &**pool,
             strategy.as_ref().map(Deref::deref),
         );
+        storage.write_events(&mut events);
     }
 }
 
@@ -611,12 +903,14 @@ pub(crate) enum Processed<A: Asset> {
         name: String,
         old_reload: Box<dyn Reload<A::Data>>,
     },
+    /// A load whose handles were all dropped before it started, returning its id.
+    Cancelled(u32),
 }
 
 /// A weak handle, which is useful if you don't directly need the asset
 /// like in caches. This way, the asset can still get dropped (if you want that).
 #[derive(Derivative)]
-#[derivative(Clone(bound = ""))]
+#[derivative(Clone(bound = ""), Debug(bound = ""))]
 pub struct WeakHandle<A> {
     id: Weak<u32>,
     marker: PhantomData<A>,
@@ -638,3 +932,190 @@ impl<A> WeakHandle<A> {
         self.upgrade().is_none()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use rayon::ThreadPoolBuilder;
+
+    use amethyst_core::ecs::prelude::VecStorage;
+    use amethyst_error::Error;
+
+    use crate::{Asset, FormatValue};
+
+    use super::*;
+
+    struct Text;
+
+    impl Asset for Text {
+        const NAME: &'static str = "Text";
+        type Data = ();
+        type HandleStorage = VecStorage<Handle<Self>>;
+    }
+
+    struct Blob(usize);
+
+    impl Asset for Blob {
+        const NAME: &'static str = "Blob";
+        type Data = ();
+        type HandleStorage = VecStorage<Handle<Self>>;
+
+        fn size_hint(&self) -> usize {
+            self.0
+        }
+    }
+
+    fn process_blobs(storage: &mut AssetStorage<Blob>, pool: &ThreadPool, frame_number: u64) {
+        storage.process(|()| unreachable!(), frame_number, pool, None);
+    }
+
+    fn push(storage: &AssetStorage<Text>, data: Result<(), Error>) -> Handle<Text> {
+        let handle = storage.allocate();
+        storage.processed.push(Processed::NewAsset {
+            data: data.map(FormatValue::data),
+            handle: handle.clone(),
+            name: "text".into(),
+            tracker: Box::new(()),
+        });
+        handle
+    }
+
+    #[test]
+    fn process_writes_asset_events() {
+        let pool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let mut storage = AssetStorage::<Text>::new();
+        storage.track_events();
+        let mut channel = EventChannel::new();
+        let mut reader = channel.register_reader();
+
+        let loaded = push(&storage, Ok(()));
+        let failed = push(&storage, Err(Error::from_string("broken")));
+        storage.process(|_| Ok(ProcessingState::Loaded(Text)), 0, &pool, None);
+        storage.write_events(&mut channel);
+
+        {
+            let events = channel.read(&mut reader).collect::<Vec<_>>();
+            assert_eq!(events.len(), 2);
+            match *events[0] {
+                AssetEvent::Loaded(ref handle) => {
+                    assert_eq!(handle.upgrade(), Some(loaded.clone()))
+                }
+                ref other => panic!("Expected `Loaded`, got {:?}", other),
+            }
+            match *events[1] {
+                AssetEvent::Failed(ref handle, ref error) => {
+                    assert_eq!(handle.upgrade(), Some(failed.clone()));
+                    assert!(error.causes().any(|cause| cause.to_string() == "broken"));
+                }
+                ref other => panic!("Expected `Failed`, got {:?}", other),
+            }
+        }
+
+        let id = loaded.id();
+        drop(loaded);
+        storage.process(|_| Ok(ProcessingState::Loaded(Text)), 1, &pool, None);
+        storage.write_events(&mut channel);
+
+        let events = channel.read(&mut reader).collect::<Vec<_>>();
+        match events[..] {
+            [AssetEvent::Unloaded(unloaded)] => assert_eq!(*unloaded, id),
+            ref other => panic!("Expected `Unloaded`, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn untracked_events_are_dropped() {
+        let pool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let mut storage = AssetStorage::<Text>::new();
+        let mut channel = EventChannel::new();
+        let mut reader = channel.register_reader();
+
+        let _loaded = push(&storage, Ok(()));
+        storage.process(|_| Ok(ProcessingState::Loaded(Text)), 0, &pool, None);
+        storage.unload_all();
+        assert!(storage.events.is_empty());
+
+        storage.track_events();
+        storage.write_events(&mut channel);
+        assert_eq!(channel.read(&mut reader).count(), 0);
+    }
+
+    #[test]
+    fn unload_all_writes_one_unloaded_event() {
+        let pool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let mut storage = AssetStorage::<Text>::new();
+        storage.track_events();
+        let mut channel = EventChannel::new();
+        let mut reader = channel.register_reader();
+
+        let handle = storage.insert(Text);
+        let id = handle.id();
+        storage.unload_all();
+        drop(handle);
+        storage.process(|_| Ok(ProcessingState::Loaded(Text)), 0, &pool, None);
+        storage.write_events(&mut channel);
+
+        let events = channel.read(&mut reader).collect::<Vec<_>>();
+        match events[..] {
+            [AssetEvent::Unloaded(unloaded)] => assert_eq!(*unloaded, id),
+            ref other => panic!("Expected one `Unloaded`, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unused_assets_are_kept_for_the_grace_period() {
+        let pool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let mut storage = AssetStorage::<Blob>::new();
+        storage.set_unload_grace_period(2);
+
+        let weak = storage.insert(Blob(10)).downgrade();
+        process_blobs(&mut storage, &pool, 0);
+        assert_eq!(
+            storage.usage(),
+            AssetUsage {
+                count: 1,
+                bytes: 10,
+                unused: 1,
+                budget: None,
+            }
+        );
+
+        // upgrading the weak handle keeps the asset
+        let handle = weak.upgrade().expect("Asset was unloaded too early");
+        process_blobs(&mut storage, &pool, 5);
+        assert_eq!(storage.usage().unused, 0);
+
+        drop(handle);
+        process_blobs(&mut storage, &pool, 6);
+        process_blobs(&mut storage, &pool, 7);
+        assert!(!weak.is_dead());
+        process_blobs(&mut storage, &pool, 8);
+        assert!(weak.is_dead());
+        assert_eq!(storage.usage().count, 0);
+    }
+
+    #[test]
+    fn memory_budget_unloads_the_longest_unused_first() {
+        let pool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let mut storage = AssetStorage::<Blob>::new();
+        storage.set_unload_grace_period(100);
+        storage.set_memory_budget(Some(25));
+
+        let oldest = storage.insert(Blob(10)).downgrade();
+        process_blobs(&mut storage, &pool, 0);
+        let newer = storage.insert(Blob(10)).downgrade();
+        let _used = storage.insert(Blob(10));
+        process_blobs(&mut storage, &pool, 1);
+
+        assert!(oldest.is_dead());
+        assert!(!newer.is_dead());
+        assert_eq!(
+            storage.usage(),
+            AssetUsage {
+                count: 2,
+                bytes: 20,
+                unused: 1,
+                budget: Some(25),
+            }
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Reports how many assets of each type are resident and how much memory they use.
+
+use std::{any::TypeId, marker::PhantomData};
+
+use derivative::Derivative;
+use fnv::FnvHashMap;
+
+use amethyst_core::{
+    ecs::prelude::{Read, System, SystemData, World, Write},
+    SystemDesc,
+};
+
+use crate::{Asset, AssetStorage};
+
+/// The resident assets of one asset type, see `AssetStorage::usage`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AssetUsage {
+    /// The number of assets in the storage.
+    pub count: usize,
+    /// The memory used by the assets in bytes, as estimated by `Asset::size_hint`.
+    pub bytes: usize,
+    /// The number of assets without handles, which are unloaded once their grace period is over.
+    pub unused: usize,
+    /// The memory budget of the storage in bytes, see `AssetStorage::set_memory_budget`.
+    pub budget: Option<usize>,
+}
+
+impl AssetUsage {
+    /// Returns `true` if the assets use more memory than the budget allows.
+    pub fn is_over_budget(&self) -> bool {
+        self.budget.map_or(false, |budget| self.bytes > budget)
+    }
+}
+
+/// A resource reporting the `AssetUsage` of the asset types with an `AssetUsageSystem`.
+#[derive(Debug, Default)]
+pub struct AssetUsageReport {
+    types: FnvHashMap<TypeId, (&'static str, AssetUsage)>,
+}
+
+impl AssetUsageReport {
+    /// Records the current usage of the given storage.
+    pub fn update<A: Asset>(&mut self, storage: &AssetStorage<A>) {
+        self.types
+            .insert(TypeId::of::<A>(), (A::NAME, storage.usage()));
+    }
+
+    /// Returns the usage of asset type `A`, if it was recorded.
+    pub fn get<A: Asset>(&self) -> Option<AssetUsage> {
+        self.types.get(&TypeId::of::<A>()).map(|(_, usage)| *usage)
+    }
+
+    /// Returns the `Asset::NAME` and usage of every recorded asset type.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, AssetUsage)> + '_ {
+        self.types.values().cloned()
+    }
+
+    /// Returns the asset types which use more memory than their budget allows.
+    pub fn over_budget(&self) -> impl Iterator<Item = (&'static str, AssetUsage)> + '_ {
+        self.iter().filter(|(_, usage)| usage.is_over_budget())
+    }
+
+    /// Returns the number of resident assets of all types.
+    pub fn total_count(&self) -> usize {
+        self.iter().map(|(_, usage)| usage.count).sum()
+    }
+
+    /// Returns the memory used by the assets of all types in bytes.
+    pub fn total_bytes(&self) -> usize {
+        self.iter().map(|(_, usage)| usage.bytes).sum()
+    }
+}
+
+/// Records the `AssetUsage` of the storage of `A` in the `AssetUsageReport`.
+///
+/// Add one for every asset type which should be reported, after the system processing
+/// the storage. Only these systems write the report, so the processors keep running
+/// in parallel.
+#[derive(Derivative)]
+#[derivative(Default(bound = ""), Debug(bound = ""))]
+pub struct AssetUsageSystem<A> {
+    marker: PhantomData<A>,
+}
+
+impl<A> AssetUsageSystem<A> {
+    /// Creates a system reporting the usage of assets of type `A`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<'a, 'b, A> SystemDesc<'a, 'b, AssetUsageSystem<A>> for AssetUsageSystem<A>
+where
+    A: Asset,
+{
+    fn build(self, world: &mut World) -> AssetUsageSystem<A> {
+        <AssetUsageSystem<A> as System<'_>>::SystemData::setup(world);
+        self
+    }
+}
+
+impl<'a, A> System<'a> for AssetUsageSystem<A>
+where
+    A: Asset,
+{
+    type SystemData = (Read<'a, AssetStorage<A>>, Write<'a, AssetUsageReport>);
+
+    fn run(&mut self, (storage, mut report): Self::SystemData) {
+        report.update(&*storage);
+    }
+}
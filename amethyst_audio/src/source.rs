@@ -28,6 +28,10 @@ impl Asset for Source {
     const NAME: &'static str = "audio::Source";
     type Data = AudioData;
     type HandleStorage = VecStorage<SourceHandle>;
+
+    fn size_hint(&self) -> usize {
+        std::mem::size_of::<Self>() + self.bytes.capacity()
+    }
 }
 
 impl ProcessableAsset for Source {
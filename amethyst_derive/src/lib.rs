@@ -41,6 +41,17 @@ pub fn prefab_data_derive(input: TokenStream) -> TokenStream {
     gen.into()
 }
 
+/// Deriving `SavePrefabData` requires that `amethyst::ecs::{Entity, ReadStorage}`,
+/// `amethyst::assets::SavePrefabData` and `amethyst::error::Error` are imported and visible in
+/// the current scope. Only structs are supported, fields use the same `prefab` attributes as
+/// the `PrefabData` derive.
+#[proc_macro_derive(SavePrefabData, attributes(prefab))]
+pub fn save_prefab_data_derive(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    let gen = prefab_data::impl_save_prefab_data(&ast);
+    gen.into()
+}
+
 /// This allows the use of an enum as an ID for the `Widgets` resource. One
 /// variant has to be marked as the default variant with `#[widget_id_default]
 /// and will be used when a `Widget` is added to the resource without an
@@ -288,6 +288,99 @@ fn impl_prefab_data_aggregate(ast: &DeriveInput) -> TokenStream {
     }
 }
 
+pub fn impl_save_prefab_data(ast: &DeriveInput) -> TokenStream {
+    let base = &ast.ident;
+    let (_, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let lf_tokens = gen_def_lt_tokens(&ast.generics);
+    let ty_tokens = gen_def_ty_params(&ast.generics);
+
+    if is_component_prefab(&ast.attrs[..]) {
+        return quote! {
+            impl<'pfd, #lf_tokens #ty_tokens> SavePrefabData<'pfd> for #base #ty_generics #where_clause {
+                type SystemData = ReadStorage<'pfd, #base #ty_generics>;
+
+                fn save_entity(entity: Entity,
+                               system_data: &Self::SystemData,
+                               _: &::std::collections::HashMap<Entity, usize>) -> ::std::result::Result<Option<Self>, Error> {
+                    Ok(system_data.get(entity).cloned())
+                }
+            }
+        };
+    }
+
+    let fields = match &ast.data {
+        Data::Struct(ref s) => &s.fields,
+        _ => panic!("SavePrefabData aggregate derive only support structs"),
+    };
+    let mut data_types: Vec<(Type, bool)> = Vec::new();
+    let mut values = Vec::new();
+    for field in fields.iter() {
+        let is_component = is_component_prefab(&field.attrs[..]);
+        let i = match data_types
+            .iter()
+            .position(|t| t.0 == field.ty && t.1 == is_component)
+        {
+            Some(i) => i,
+            None => {
+                data_types.push((field.ty.clone(), is_component));
+                data_types.len() - 1
+            }
+        };
+        let tuple_index = Literal::usize_unsuffixed(i);
+        let ty = &field.ty;
+        let value = if is_component {
+            quote! {
+                system_data.#tuple_index.get(entity).cloned()
+            }
+        } else {
+            quote! {
+                <#ty as SavePrefabData<'pfd>>::save_entity(entity, &system_data.#tuple_index, entities)?
+            }
+        };
+        let value = quote! {
+            match #value {
+                Some(data) => data,
+                None => return Ok(None),
+            }
+        };
+        values.push(match &field.ident {
+            Some(name) => quote! { #name: #value },
+            None => value,
+        });
+    }
+    let construct = match fields {
+        Fields::Named(_) => quote! { #base { #(#values,)* } },
+        Fields::Unnamed(_) => quote! { #base ( #(#values,)* ) },
+        Fields::Unit => quote! { #base },
+    };
+    let system_data = data_types.iter().map(|(ty, is_component)| {
+        if *is_component {
+            quote! {
+                 ReadStorage<'pfd, #ty>
+            }
+        } else {
+            quote! {
+                <#ty as SavePrefabData<'pfd>>::SystemData
+            }
+        }
+    });
+
+    quote! {
+        impl<'pfd, #lf_tokens #ty_tokens> SavePrefabData<'pfd> for #base #ty_generics #where_clause {
+            type SystemData = (
+                #(#system_data,)*
+            );
+
+            #[allow(unused_variables)]
+            fn save_entity(entity: Entity,
+                           system_data: &Self::SystemData,
+                           entities: &::std::collections::HashMap<Entity, usize>) -> ::std::result::Result<Option<Self>, Error> {
+                Ok(Some(#construct))
+            }
+        }
+    }
+}
+
 fn gen_def_lt_tokens(generics: &Generics) -> TokenStream {
     let lts: Vec<_> = generics
         .lifetimes()
@@ -3,11 +3,13 @@
     clippy::block_in_if_condition_stmt,
     clippy::unneeded_field_pattern
 )]
-use amethyst_derive::{EventReader, PrefabData};
+use std::collections::HashMap;
 
-use amethyst_assets::{PrefabData, ProgressCounter};
+use amethyst_derive::{EventReader, PrefabData, SavePrefabData};
+
+use amethyst_assets::{PrefabData, ProgressCounter, SavePrefabData};
 use amethyst_core::{
-    ecs::{Component, DenseVecStorage, Entity, Read, SystemData, World, WriteStorage},
+    ecs::{Component, DenseVecStorage, Entity, Read, ReadStorage, SystemData, World, WriteStorage},
     shrev::{EventChannel, ReaderId},
     EventReader,
 };
@@ -42,7 +44,7 @@ where
     Four(TestEvent3<T2>),
 }
 
-#[derive(Clone, PrefabData, Default)]
+#[derive(Clone, PrefabData, SavePrefabData, Default)]
 #[prefab(Component)]
 pub struct Stuff<T>
 where
@@ -58,7 +60,7 @@ where
     type Storage = DenseVecStorage<Self>;
 }
 
-#[derive(Clone, PrefabData)]
+#[derive(Clone, PrefabData, SavePrefabData)]
 pub struct OuterPrefab<T>
 where
     T: Default + Clone + Send + Sync + 'static,
@@ -75,13 +77,13 @@ impl Component for External {
     type Storage = DenseVecStorage<Self>;
 }
 
-#[derive(PrefabData, Clone)]
+#[derive(PrefabData, SavePrefabData, Clone)]
 pub struct Outer {
     #[prefab(Component)]
     external: External,
 }
 
-#[derive(PrefabData, Clone)]
+#[derive(PrefabData, SavePrefabData, Clone)]
 pub struct OuterTuple(#[prefab(Component)] External);
 
 #[derive(PrefabData, Clone)]
@@ -102,7 +104,10 @@ pub enum EnumPrefab {
 mod tests {
     use super::*;
     use amethyst_assets::{AssetStorage, Loader, Prefab, PrefabLoaderSystemDesc};
-    use amethyst_core::ecs::{world::EntitiesRes, Builder, Join, WorldExt};
+    use amethyst_core::{
+        ecs::{world::EntitiesRes, Builder, Join, WorldExt},
+        Parent,
+    };
     use amethyst_test::prelude::*;
 
     macro_rules! assert_prefab {
@@ -223,4 +228,42 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn save_struct_prefabs() {
+        let mut world = World::new();
+        world.register::<External>();
+        world.register::<Stuff<u32>>();
+        world.register::<Parent>();
+        let saved = world.create_entity().with(External { inner: 5 }).build();
+        let child = world
+            .create_entity()
+            .with(Stuff { inner: 6 })
+            .with(Parent::new(saved))
+            .build();
+
+        let outer = Prefab::<Option<OuterTuple>>::save(
+            &[child, saved],
+            &world.read_storage(),
+            &world.system_data(),
+        )
+        .unwrap();
+        let data = outer
+            .entities()
+            .map(|entity| {
+                entity
+                    .data()
+                    .map(|data| data.as_ref().map(|data| (data.0).inner))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(data, vec![None, Some(Some(5)), Some(None)]);
+
+        let stuff = <OuterPrefab<u32> as SavePrefabData<'_>>::save_entity(
+            child,
+            &world.system_data(),
+            &HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(stuff.map(|stuff| stuff.inner.inner), Some(6));
+    }
 }
@@ -5,13 +5,24 @@ use serde::{de::DeserializeOwned, Serialize};
 use amethyst_core::{bundle::SystemBundle, ecs::World, shred::DispatcherBuilder};
 use amethyst_error::{Error, ResultExt};
 
-use crate::{server::ServerConfig, NetSocketSystem};
+use crate::{
+    serializer::{BincodeSerializer, NetSerializer},
+    server::ServerConfig,
+    transport::NetworkTransport,
+    NetSocketSystem,
+};
 
 /// A convenience bundle to create the infrastructure needed to send and receive network messages.
+///
+/// Events are encoded with `bincode` unless another `NetSerializer` is set `with_serializer`.
 #[allow(missing_debug_implementations)] // TODO: Revisit for laminar
-pub struct NetworkBundle<T> {
+pub struct NetworkBundle<T, S = BincodeSerializer> {
     /// the configuration used for the networking crate.
     config: ServerConfig,
+    /// the transport to use instead of a laminar UDP socket.
+    transport: Option<Box<dyn NetworkTransport>>,
+    /// the wire format of the events.
+    serializer: S,
     _data: PhantomData<T>,
 }
 
@@ -28,6 +39,8 @@ impl<T> NetworkBundle<T> {
 
         NetworkBundle {
             config,
+            transport: None,
+            serializer: BincodeSerializer,
             _data: PhantomData,
         }
     }
@@ -36,14 +49,43 @@ impl<T> NetworkBundle<T> {
     pub fn from_config(config: ServerConfig) -> NetworkBundle<T> {
         NetworkBundle {
             config,
+            transport: None,
+            serializer: BincodeSerializer,
             _data: PhantomData,
         }
     }
 }
 
-impl<'a, 'b, T> SystemBundle<'a, 'b> for NetworkBundle<T>
+impl<T, S> NetworkBundle<T, S> {
+    /// Sends and receives packets over the given transport instead of a laminar UDP socket.
+    pub fn with_transport<N>(mut self, transport: N) -> Self
+    where
+        N: NetworkTransport + 'static,
+    {
+        self.transport = Some(Box::new(transport));
+        self
+    }
+
+    /// Encodes the events with the given serializer instead of `bincode`.
+    ///
+    /// The remote ends have to use the same serializer to be able to decode the events.
+    pub fn with_serializer<N>(self, serializer: N) -> NetworkBundle<T, N>
+    where
+        N: NetSerializer,
+    {
+        NetworkBundle {
+            config: self.config,
+            transport: self.transport,
+            serializer,
+            _data: PhantomData,
+        }
+    }
+}
+
+impl<'a, 'b, T, S> SystemBundle<'a, 'b> for NetworkBundle<T, S>
 where
     T: Send + Sync + PartialEq + Serialize + Clone + DeserializeOwned + 'static,
+    S: NetSerializer,
 {
     /// Build the networking bundle by adding the networking system to the application.
     fn build(
@@ -51,9 +93,16 @@ where
         _world: &mut World,
         builder: &mut DispatcherBuilder<'_, '_>,
     ) -> Result<(), Error> {
-        let socket_system = NetSocketSystem::<T>::new(self.config)
-            .with_context(|_| Error::from_string("Failed to open network system."))?;
-        builder.add(socket_system, "net_socket", &[]);
+        let socket_system = match self.transport {
+            Some(transport) => NetSocketSystem::<T>::with_boxed_transport(self.config, transport),
+            None => NetSocketSystem::<T>::new(self.config)
+                .with_context(|_| Error::from_string("Failed to open network system."))?,
+        };
+        builder.add(
+            socket_system.with_serializer(self.serializer),
+            "net_socket",
+            &[],
+        );
 
         Ok(())
     }
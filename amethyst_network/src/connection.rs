@@ -3,14 +3,16 @@
 use serde::{Deserialize, Serialize};
 use shrev::{EventChannel, EventIterator, ReaderId};
 use std::{
+    collections::{HashMap, HashSet},
     fmt::{Debug, Formatter},
+    marker::PhantomData,
     net::SocketAddr,
 };
 use uuid::Uuid;
 
-use amethyst_core::ecs::{Component, VecStorage};
+use amethyst_core::ecs::{Component, VecStorage, World};
 
-use crate::NetEvent;
+use crate::{DisconnectReason, NetEvent};
 
 // TODO: Think about relationship between NetConnection and NetIdentity.
 
@@ -40,6 +42,12 @@ pub struct NetConnection<E: 'static> {
     /// The buffer used by `NetSocketSystem` that allows it to immediately send events upon receiving a new `NetConnection`.
     #[serde(skip)]
     send_reader: ReaderId<NetEvent<E>>,
+    /// The id of the next remote procedure call made on this connection.
+    #[serde(skip)]
+    pub(crate) next_request_id: u64,
+    /// The readers of the consumers listed in the `ConnectionReaders`, by name of the consumer.
+    #[serde(skip)]
+    consumer_readers: HashMap<String, ReaderId<NetEvent<E>>>,
 }
 
 impl<E: Send + Sync + 'static> NetConnection<E> {
@@ -56,6 +64,8 @@ impl<E: Send + Sync + 'static> NetConnection<E> {
             send_buffer,
             receive_buffer: EventChannel::<NetEvent<E>>::new(),
             send_reader,
+            next_request_id: 0,
+            consumer_readers: HashMap::new(),
         }
     }
 
@@ -96,6 +106,19 @@ impl<E: Send + Sync + 'static> NetConnection<E> {
         self.send_buffer.single_write(event);
     }
 
+    /// Gracefully closes this connection.
+    ///
+    /// The events queued so far are still sent, followed by a `NetEvent::Disconnect` with the given reason.
+    /// Both ends will then receive a `NetEvent::Disconnected` carrying that reason.
+    pub fn disconnect(&mut self, reason: DisconnectReason) {
+        if self.state == ConnectionState::Disconnected {
+            return;
+        }
+
+        self.queue(NetEvent::Disconnect { reason });
+        self.state = ConnectionState::Disconnecting;
+    }
+
     /// Returns an iterator over the received events.
     ///
     /// - `reader_id`: the reader id of the registered reader.
@@ -120,6 +143,85 @@ impl<E: Send + Sync + 'static> NetConnection<E> {
     pub fn register_reader(&mut self) -> ReaderId<NetEvent<E>> {
         self.receive_buffer.register_reader()
     }
+
+    /// Returns an iterator over the received events the given consumer did not read yet.
+    ///
+    /// The consumer has to be registered on the `ConnectionReaders`, so its reader is registered
+    /// before the first event is received. An unregistered consumer only sees the events received
+    /// after its first call.
+    pub fn received_events_for(&mut self, consumer: &str) -> EventIterator<'_, NetEvent<E>> {
+        if !self.consumer_readers.contains_key(consumer) {
+            let reader = self.receive_buffer.register_reader();
+            self.consumer_readers.insert(consumer.to_string(), reader);
+        }
+
+        let reader = self
+            .consumer_readers
+            .get_mut(consumer)
+            .expect("Reader was just registered");
+        self.receive_buffer.read(reader)
+    }
+
+    /// Registers the readers of all consumers which do not have one yet.
+    pub(crate) fn register_consumers(&mut self, readers: &ConnectionReaders<E>) {
+        for consumer in &readers.consumers {
+            if !self.consumer_readers.contains_key(consumer) {
+                let reader = self.receive_buffer.register_reader();
+                self.consumer_readers.insert(consumer.clone(), reader);
+            }
+        }
+    }
+}
+
+/// Resource listing the consumers of the received events of every `NetConnection<E>`.
+///
+/// The `NetSocketSystem` registers a reader for every consumer on every connection before it
+/// queues the first received event, so consumers reading with `NetConnection::received_events_for`
+/// also see the events received in the frame the connection was created.
+/// Systems add themselves in `System::setup`.
+pub struct ConnectionReaders<E> {
+    consumers: HashSet<String>,
+    _marker: PhantomData<E>,
+}
+
+impl<E> ConnectionReaders<E> {
+    /// Adds a consumer, which reads the events of every connection under the given name.
+    pub fn register(&mut self, consumer: impl Into<String>) {
+        self.consumers.insert(consumer.into());
+    }
+
+    /// Returns an iterator over the names of all consumers.
+    pub fn consumers(&self) -> impl Iterator<Item = &str> {
+        self.consumers.iter().map(String::as_str)
+    }
+}
+
+/// Adds the given consumer to the `ConnectionReaders<E>` of the world.
+pub(crate) fn register_consumer<E>(world: &mut World, consumer: impl Into<String>)
+where
+    E: Send + Sync + 'static,
+{
+    world
+        .entry::<ConnectionReaders<E>>()
+        .or_insert_with(ConnectionReaders::default)
+        .register(consumer);
+}
+
+impl<E> Default for ConnectionReaders<E> {
+    fn default() -> Self {
+        ConnectionReaders {
+            consumers: HashSet::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<E> Debug for ConnectionReaders<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConnectionReaders")
+            .field("consumers", &self.consumers)
+            .finish()
+    }
 }
 
 impl<E> PartialEq for NetConnection<E> {
@@ -137,10 +239,12 @@ impl<E: Send + Sync + 'static> Component for NetConnection<E> {
 ///The state of the connection.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ConnectionState {
-    /// The connection is established.
+    /// The handshake completed and the connection is established.
     Connected,
-    /// The connection is being established.
+    /// The connection is being established, the handshake is sent until it is answered.
     Connecting,
+    /// The connection is being closed, the queued events are sent one last time.
+    Disconnecting,
     /// The connection has been dropped.
     Disconnected,
 }
@@ -177,7 +281,10 @@ impl Component for NetIdentity {
 
 #[cfg(test)]
 mod tests {
-    use crate::{connection::NetConnection, net_event::NetEvent};
+    use crate::{
+        connection::{ConnectionReaders, ConnectionState, NetConnection},
+        net_event::{DisconnectReason, NetEvent},
+    };
 
     #[test]
     fn can_read_received_events() {
@@ -218,6 +325,35 @@ mod tests {
         );
     }
 
+    #[test]
+    fn registered_consumer_reads_events_received_before_first_read() {
+        let mut readers = ConnectionReaders::default();
+        readers.register("consumer");
+
+        let mut connection = test_connection();
+        connection.register_consumers(&readers);
+        connection
+            .receive_buffer
+            .single_write(NetEvent::Connected("127.0.0.1:0".parse().unwrap()));
+
+        assert_eq!(connection.received_events_for("consumer").len(), 1);
+        assert_eq!(connection.received_events_for("consumer").len(), 0);
+    }
+
+    #[test]
+    fn disconnect_queues_disconnect_event() {
+        let mut connection = test_connection();
+        connection.disconnect(DisconnectReason::Closed);
+
+        assert_eq!(connection.state, ConnectionState::Disconnecting);
+        assert_eq!(
+            connection.send_buffer_early_read().next(),
+            Some(&NetEvent::Disconnect {
+                reason: DisconnectReason::Closed
+            })
+        );
+    }
+
     fn test_connection() -> NetConnection<String> {
         NetConnection::new("127.0.0.1:0".parse().unwrap())
     }
@@ -0,0 +1,138 @@
+//! The host side of the discovery.
+
+use std::{
+    io::ErrorKind,
+    marker::PhantomData,
+    net::{Ipv4Addr, UdpSocket},
+};
+
+use log::warn;
+use serde::{de::DeserializeOwned, Serialize};
+
+use amethyst_core::ecs::{Read, System};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use super::{decode, encode, DiscoveryConfig, DiscoveryMessage, MAX_DATAGRAM_SIZE};
+use crate::{
+    error::Result,
+    serializer::{BincodeSerializer, NetSerializer},
+};
+
+/// The info a host announces to the clients searching for servers.
+///
+/// Nothing is announced while the info is `None`, which is the default.
+#[derive(Clone, Debug)]
+pub struct HostedServer<I> {
+    /// The info sent to clients, for example the name, player count and map of the server.
+    ///
+    /// An info which does not fit in a single discovery datagram is not announced.
+    pub info: Option<I>,
+}
+
+impl<I> Default for HostedServer<I> {
+    fn default() -> Self {
+        HostedServer { info: None }
+    }
+}
+
+/// Answers the queries of `LanDiscoverySystem`s with the info of the `HostedServer` resource.
+///
+/// - `I` corresponds to the server info.
+/// - `S` corresponds to the wire format of the datagrams.
+#[allow(missing_debug_implementations)]
+pub struct LanAnnounceSystem<I, S = BincodeSerializer> {
+    socket: UdpSocket,
+    config: DiscoveryConfig,
+    // the port of the socket clients should connect to.
+    game_port: u16,
+    buffer: Vec<u8>,
+    // the wire format of the datagrams.
+    serializer: S,
+    _marker: PhantomData<I>,
+}
+
+impl<I> LanAnnounceSystem<I> {
+    /// Creates a `LanAnnounceSystem` listening for queries on the port of the configuration.
+    ///
+    /// `game_port` is the port of the `NetSocketSystem` clients should connect to.
+    pub fn new(config: DiscoveryConfig, game_port: u16) -> Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, config.port))?;
+        socket.set_nonblocking(true)?;
+
+        Ok(LanAnnounceSystem {
+            socket,
+            config,
+            game_port,
+            buffer: vec![0; MAX_DATAGRAM_SIZE],
+            serializer: BincodeSerializer,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<I, S> LanAnnounceSystem<I, S> {
+    /// Encodes the datagrams with the given serializer instead of `bincode`.
+    ///
+    /// The `LanDiscoverySystem`s have to use the same serializer to be able to decode the announcements.
+    pub fn with_serializer<N>(self, serializer: N) -> LanAnnounceSystem<I, N>
+    where
+        N: NetSerializer,
+    {
+        LanAnnounceSystem {
+            socket: self.socket,
+            config: self.config,
+            game_port: self.game_port,
+            buffer: self.buffer,
+            serializer,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, I, S> System<'a> for LanAnnounceSystem<I, S>
+where
+    I: Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+    S: NetSerializer,
+{
+    type SystemData = Read<'a, HostedServer<I>>;
+
+    fn run(&mut self, hosted: Self::SystemData) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("lan_announce_system");
+
+        loop {
+            let (len, from) = match self.socket.recv_from(&mut self.buffer) {
+                Ok(received) => received,
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    warn!("Failed to receive a discovery query: {}", e);
+                    break;
+                }
+            };
+
+            match decode::<I, S>(&self.config, &self.serializer, &self.buffer[..len]) {
+                Some(DiscoveryMessage::Query) => {}
+                _ => continue,
+            }
+
+            let info = match hosted.info {
+                Some(ref info) => info.clone(),
+                None => continue,
+            };
+
+            let announce = DiscoveryMessage::Announce {
+                port: self.game_port,
+                info,
+            };
+            match encode(&self.config, &self.serializer, announce) {
+                Ok(data) => {
+                    if let Err(e) = self.socket.send_to(&data, from) {
+                        warn!("Failed to announce the server to {}: {}", from, e);
+                    }
+                }
+                Err(e) => warn!("Failed to serialize the server info: {}", e),
+            }
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    time::Duration,
+};
+
+/// The configuration of the discovery systems, which has to match on hosts and clients.
+#[derive(Clone, Debug)]
+pub struct DiscoveryConfig {
+    /// The port on which hosts listen for queries.
+    /// This value is by default 23460.
+    pub port: u16,
+    /// The address to which clients send their queries.
+    /// This value is by default the broadcast address 255.255.255.255.
+    pub broadcast_addr: IpAddr,
+    /// The interval at which clients query for hosts.
+    /// This value is by default 1 second.
+    pub query_interval: Duration,
+    /// The time after which a host that stopped answering is considered lost.
+    /// This value is by default 3 seconds.
+    pub expiry: Duration,
+    /// The version of the game protocol, hosts and clients of other versions ignore each other.
+    /// This value is by default 0.
+    pub protocol_version: u32,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        DiscoveryConfig {
+            port: 23460,
+            broadcast_addr: IpAddr::V4(Ipv4Addr::BROADCAST),
+            query_interval: Duration::from_secs(1),
+            expiry: Duration::from_secs(3),
+            protocol_version: 0,
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! The client side of the discovery.
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    io::ErrorKind,
+    marker::PhantomData,
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+    time::Instant,
+};
+
+use log::warn;
+use serde::{de::DeserializeOwned, Serialize};
+
+use amethyst_core::{
+    ecs::{System, Write},
+    shrev::EventChannel,
+};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use super::{decode, encode, DiscoveryConfig, DiscoveryMessage, MAX_DATAGRAM_SIZE};
+use crate::{
+    error::Result,
+    serializer::{BincodeSerializer, NetSerializer},
+};
+
+/// A server which answered the queries of this client.
+#[derive(Clone, Debug)]
+pub struct DiscoveredServer<I> {
+    /// The address of the game socket of the server.
+    pub addr: SocketAddr,
+    /// The info the server announced.
+    pub info: I,
+    /// When the server answered last.
+    pub last_seen: Instant,
+}
+
+/// The servers found on the local network, by the address of their game socket.
+#[derive(Clone, Debug)]
+pub struct DiscoveredServers<I> {
+    servers: HashMap<SocketAddr, DiscoveredServer<I>>,
+}
+
+impl<I> DiscoveredServers<I> {
+    /// Returns the server with the given game address, if it was found.
+    pub fn get(&self, addr: SocketAddr) -> Option<&DiscoveredServer<I>> {
+        self.servers.get(&addr)
+    }
+
+    /// Returns an iterator over all servers which are currently answering.
+    pub fn iter(&self) -> impl Iterator<Item = &DiscoveredServer<I>> {
+        self.servers.values()
+    }
+
+    /// Returns the number of servers which are currently answering.
+    pub fn len(&self) -> usize {
+        self.servers.len()
+    }
+
+    /// Returns `true` if no server is currently answering.
+    pub fn is_empty(&self) -> bool {
+        self.servers.is_empty()
+    }
+}
+
+impl<I> Default for DiscoveredServers<I> {
+    fn default() -> Self {
+        DiscoveredServers {
+            servers: HashMap::new(),
+        }
+    }
+}
+
+/// Written to the `EventChannel<DiscoveryEvent<I>>` when the `DiscoveredServers` change.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiscoveryEvent<I> {
+    /// A server answered for the first time.
+    Found {
+        /// The address of the game socket of the server.
+        addr: SocketAddr,
+        /// The info the server announced.
+        info: I,
+    },
+    /// A server announced other info than before.
+    Updated {
+        /// The address of the game socket of the server.
+        addr: SocketAddr,
+        /// The info the server announced.
+        info: I,
+    },
+    /// A server did not answer within the `expiry` of the configuration.
+    Lost {
+        /// The address of the game socket of the server.
+        addr: SocketAddr,
+    },
+}
+
+/// Broadcasts queries for servers and keeps the answers in the `DiscoveredServers` resource.
+///
+/// - `I` corresponds to the server info.
+/// - `S` corresponds to the wire format of the datagrams.
+#[allow(missing_debug_implementations)]
+pub struct LanDiscoverySystem<I, S = BincodeSerializer> {
+    socket: UdpSocket,
+    config: DiscoveryConfig,
+    last_query: Option<Instant>,
+    buffer: Vec<u8>,
+    // the wire format of the datagrams.
+    serializer: S,
+    _marker: PhantomData<I>,
+}
+
+impl<I> LanDiscoverySystem<I> {
+    /// Creates a `LanDiscoverySystem` querying for servers on the port of the configuration.
+    pub fn new(config: DiscoveryConfig) -> Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        socket.set_broadcast(true)?;
+        socket.set_nonblocking(true)?;
+
+        Ok(LanDiscoverySystem {
+            socket,
+            config,
+            last_query: None,
+            buffer: vec![0; MAX_DATAGRAM_SIZE],
+            serializer: BincodeSerializer,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<I, S> LanDiscoverySystem<I, S> {
+    /// Encodes the datagrams with the given serializer instead of `bincode`.
+    ///
+    /// The `LanAnnounceSystem`s have to use the same serializer to be able to decode the queries.
+    pub fn with_serializer<N>(self, serializer: N) -> LanDiscoverySystem<I, N>
+    where
+        N: NetSerializer,
+    {
+        LanDiscoverySystem {
+            socket: self.socket,
+            config: self.config,
+            last_query: self.last_query,
+            buffer: self.buffer,
+            serializer,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<I, S> LanDiscoverySystem<I, S>
+where
+    S: NetSerializer,
+{
+    fn query(&mut self, now: Instant)
+    where
+        I: Serialize,
+    {
+        let interval = self.config.query_interval;
+        if self
+            .last_query
+            .map_or(false, |last| now.duration_since(last) < interval)
+        {
+            return;
+        }
+        self.last_query = Some(now);
+
+        let target = SocketAddr::new(self.config.broadcast_addr, self.config.port);
+        match encode::<I, S>(&self.config, &self.serializer, DiscoveryMessage::Query) {
+            Ok(data) => {
+                if let Err(e) = self.socket.send_to(&data, target) {
+                    warn!("Failed to send a discovery query to {}: {}", target, e);
+                }
+            }
+            Err(e) => warn!("Failed to serialize a discovery query: {}", e),
+        }
+    }
+}
+
+impl<'a, I, S> System<'a> for LanDiscoverySystem<I, S>
+where
+    I: Serialize + DeserializeOwned + Clone + PartialEq + Send + Sync + 'static,
+    S: NetSerializer,
+{
+    type SystemData = (
+        Write<'a, DiscoveredServers<I>>,
+        Write<'a, EventChannel<DiscoveryEvent<I>>>,
+    );
+
+    fn run(&mut self, (mut discovered, mut events): Self::SystemData) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("lan_discovery_system");
+
+        let now = Instant::now();
+        self.query(now);
+
+        loop {
+            let (len, from) = match self.socket.recv_from(&mut self.buffer) {
+                Ok(received) => received,
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    warn!("Failed to receive a server announcement: {}", e);
+                    break;
+                }
+            };
+
+            let (port, info) =
+                match decode::<I, S>(&self.config, &self.serializer, &self.buffer[..len]) {
+                    Some(DiscoveryMessage::Announce { port, info }) => (port, info),
+                    _ => continue,
+                };
+            let addr = SocketAddr::new(from.ip(), port);
+
+            match discovered.servers.entry(addr) {
+                Entry::Occupied(mut entry) => {
+                    let server = entry.get_mut();
+                    server.last_seen = now;
+                    if server.info != info {
+                        server.info = info.clone();
+                        events.single_write(DiscoveryEvent::Updated { addr, info });
+                    }
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(DiscoveredServer {
+                        addr,
+                        info: info.clone(),
+                        last_seen: now,
+                    });
+                    events.single_write(DiscoveryEvent::Found { addr, info });
+                }
+            }
+        }
+
+        let expiry = self.config.expiry;
+        let lost = discovered
+            .servers
+            .values()
+            .filter(|server| now.duration_since(server.last_seen) > expiry)
+            .map(|server| server.addr)
+            .collect::<Vec<_>>();
+        for addr in lost {
+            discovered.servers.remove(&addr);
+            events.single_write(DiscoveryEvent::Lost { addr });
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//! Discovery of servers on the local network.
+//!
+//! A client running a `LanDiscoverySystem` broadcasts a query on the discovery port every `query_interval`.
+//! Every host running a `LanAnnounceSystem` answers the query with the port of its game socket and
+//! the server info set on its `HostedServer` resource, which can be any serializable type.
+//!
+//! The client keeps the answering servers in the `DiscoveredServers` resource and writes a `DiscoveryEvent`
+//! when a server is found, its info changes or it stops answering.
+//!
+//! The datagrams are encoded with `bincode`, unless the systems are created `with_serializer`.
+//! Both sides have to use the same serializer.
+
+pub use self::{
+    announce::{HostedServer, LanAnnounceSystem},
+    config::DiscoveryConfig,
+    discover::{DiscoveredServer, DiscoveredServers, DiscoveryEvent, LanDiscoverySystem},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    serializer::NetSerializer,
+};
+
+mod announce;
+mod config;
+mod discover;
+
+/// Identifies the datagrams of the discovery protocol among other traffic on the port.
+const MAGIC: [u8; 4] = *b"AMDS";
+
+/// The largest datagram the discovery systems send and read.
+///
+/// Server infos which do not fit in a datagram of this size are not announced.
+const MAX_DATAGRAM_SIZE: usize = 1500;
+
+/// The messages exchanged by the discovery systems.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum DiscoveryMessage<I> {
+    /// Asks every host on the network to announce itself.
+    Query,
+    /// Answers a query with the port of the game socket and the server info.
+    Announce { port: u16, info: I },
+}
+
+#[derive(Serialize, Deserialize)]
+struct Datagram<I> {
+    magic: [u8; 4],
+    protocol_version: u32,
+    message: DiscoveryMessage<I>,
+}
+
+/// Encodes a datagram, returning an error if it is larger than `MAX_DATAGRAM_SIZE`.
+fn encode<I: Serialize, S: NetSerializer>(
+    config: &DiscoveryConfig,
+    serializer: &S,
+    message: DiscoveryMessage<I>,
+) -> Result<Vec<u8>> {
+    let data = serializer.serialize(&Datagram {
+        magic: MAGIC,
+        protocol_version: config.protocol_version,
+        message,
+    })?;
+
+    if data.len() > MAX_DATAGRAM_SIZE {
+        return Err(Error::EncodeError(format!(
+            "The discovery datagram of {} bytes exceeds the limit of {} bytes",
+            data.len(),
+            MAX_DATAGRAM_SIZE
+        )));
+    }
+
+    Ok(data)
+}
+
+/// Decodes a datagram, returning `None` for foreign traffic and other protocol versions.
+fn decode<I: DeserializeOwned, S: NetSerializer>(
+    config: &DiscoveryConfig,
+    serializer: &S,
+    data: &[u8],
+) -> Option<DiscoveryMessage<I>> {
+    match serializer.deserialize::<Datagram<I>>(data) {
+        Ok(ref datagram)
+            if datagram.magic != MAGIC || datagram.protocol_version != config.protocol_version =>
+        {
+            None
+        }
+        Ok(datagram) => Some(datagram.message),
+        Err(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serializer::{BincodeSerializer, Compressed};
+
+    #[test]
+    fn other_protocol_versions_are_ignored() {
+        let config = DiscoveryConfig::default();
+        let other = DiscoveryConfig {
+            protocol_version: 1,
+            ..Default::default()
+        };
+
+        let data = encode(
+            &config,
+            &BincodeSerializer,
+            DiscoveryMessage::Announce {
+                port: 1234,
+                info: "server".to_string(),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            decode::<String, _>(&config, &BincodeSerializer, &data),
+            Some(DiscoveryMessage::Announce {
+                port: 1234,
+                info: "server".to_string(),
+            })
+        );
+        assert_eq!(decode::<String, _>(&other, &BincodeSerializer, &data), None);
+        assert_eq!(
+            decode::<String, _>(&config, &BincodeSerializer, b"garbage"),
+            None
+        );
+        assert_eq!(
+            decode::<String, _>(&config, &Compressed::new(BincodeSerializer, 64), &data),
+            None
+        );
+    }
+
+    #[test]
+    fn oversized_info_is_not_encoded() {
+        let config = DiscoveryConfig::default();
+
+        let announce = DiscoveryMessage::Announce {
+            port: 1234,
+            info: "x".repeat(MAX_DATAGRAM_SIZE),
+        };
+
+        assert!(encode(&config, &BincodeSerializer, announce).is_err());
+    }
+}
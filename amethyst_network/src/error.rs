@@ -20,6 +20,12 @@ pub enum Error {
     /// Error that could occur when serializing whit `bincode`
     #[error(display = "Serialization error occurred")]
     SerializeError(#[cause] bincode::Error),
+    /// Error that could occur when a `NetSerializer` fails to encode a value.
+    #[error(display = "Encoding error occurred: {}", _0)]
+    EncodeError(String),
+    /// Error that could occur when a payload received from a remote end can not be decoded.
+    #[error(display = "Decoding error occurred: {}", _0)]
+    DecodeError(String),
     /// Error that could occur when sending an `ServerSocketEvent` to some channel.
     #[error(display = "Channel send error occurred")]
     ChannelSendError(#[cause] crossbeam_channel::SendError<laminar::Packet>),
@@ -0,0 +1,57 @@
+use std::collections::HashSet;
+
+use amethyst_core::ecs::{Component, DenseVecStorage, Entity};
+
+/// Identifies a group, or room, entities and connections can be part of.
+pub type InterestGroup = u32;
+
+/// Component for entities with a `NetConnection`, describing what the connection is interested in.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Interest {
+    /// The entity the connection focuses on, usually the entity controlled by the remote player.
+    /// This entity is always relevant.
+    pub focus: Option<Entity>,
+    /// The distance from the focused entity within which entities are relevant.
+    /// All distances are relevant if this is `None`, or if either entity has no `Transform`.
+    pub radius: Option<f32>,
+    /// The groups the connection is part of, entities with `InterestGroups` have to share one of them.
+    pub groups: HashSet<InterestGroup>,
+}
+
+impl Interest {
+    /// Creates an interest in everything within the given distance from the focused entity.
+    pub fn around(focus: Entity, radius: f32) -> Self {
+        Interest {
+            focus: Some(focus),
+            radius: Some(radius),
+            groups: HashSet::new(),
+        }
+    }
+
+    /// Adds the connection to the given group.
+    pub fn with_group(mut self, group: InterestGroup) -> Self {
+        self.groups.insert(group);
+        self
+    }
+}
+
+impl Component for Interest {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Component restricting the relevancy of an entity to the connections sharing one of its groups.
+///
+/// Entities without this component are relevant regardless of the groups of a connection.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InterestGroups(pub HashSet<InterestGroup>);
+
+impl InterestGroups {
+    /// Returns `true` if the entity is part of any of the given groups.
+    pub fn intersects(&self, groups: &HashSet<InterestGroup>) -> bool {
+        !self.0.is_disjoint(groups)
+    }
+}
+
+impl Component for InterestGroups {
+    type Storage = DenseVecStorage<Self>;
+}
@@ -0,0 +1,23 @@
+//! Interest management, limiting what the server sends to every connection to what is relevant to it.
+//!
+//! The `InterestSystem` computes the `Relevancy` of every `Replicated` entity for every connection
+//! whose entity carries an `Interest` component, based on:
+//!
+//! - The distance between the `Transform` of the entity the connection focuses on and the entity.
+//! - The `InterestGroups` of the entity, which have to share a group with the `Interest` of the connection.
+//! - An optional custom predicate.
+//!
+//! The `ReplicationServerSystem` only replicates relevant entities, spawning and despawning them on
+//! connections as they become relevant or irrelevant. Other events concerning an entity can be
+//! queued to the connections it is relevant to with `queue_relevant`.
+//! Connections without an `Interest` see everything.
+
+pub use self::{
+    component::{Interest, InterestGroup, InterestGroups},
+    relevancy::{queue_relevant, Relevancy},
+    system::{InterestPredicate, InterestSystem},
+};
+
+mod component;
+mod relevancy;
+mod system;
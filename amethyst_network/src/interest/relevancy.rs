@@ -0,0 +1,87 @@
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+};
+
+use amethyst_core::ecs::Entity;
+
+use crate::{ConnectionState, NetConnection, NetEvent};
+
+/// Resource holding the entities relevant to every connection, computed by the `InterestSystem`.
+///
+/// Connections without an `Interest` are not filtered, every entity is relevant to them.
+#[derive(Clone, Debug, Default)]
+pub struct Relevancy {
+    relevant: HashMap<SocketAddr, HashSet<Entity>>,
+}
+
+impl Relevancy {
+    /// Returns `true` if the entity is relevant to the connection with the given address.
+    pub fn is_relevant(&self, addr: SocketAddr, entity: Entity) -> bool {
+        self.relevant
+            .get(&addr)
+            .map_or(true, |relevant| relevant.contains(&entity))
+    }
+
+    /// Returns `true` if the connection with the given address is filtered.
+    pub fn is_filtered(&self, addr: SocketAddr) -> bool {
+        self.relevant.contains_key(&addr)
+    }
+
+    /// Returns the entities relevant to the connection with the given address,
+    /// or `None` if every entity is relevant to it.
+    pub fn relevant(&self, addr: SocketAddr) -> Option<&HashSet<Entity>> {
+        self.relevant.get(&addr)
+    }
+
+    pub(crate) fn set(&mut self, addr: SocketAddr, relevant: HashSet<Entity>) {
+        self.relevant.insert(addr, relevant);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.relevant.clear();
+    }
+}
+
+/// Queues the event on every connection the entity is relevant to.
+pub fn queue_relevant<'a, E, I>(
+    relevancy: &Relevancy,
+    connections: I,
+    entity: Entity,
+    event: NetEvent<E>,
+) where
+    E: Clone + Send + Sync + 'static,
+    I: IntoIterator<Item = &'a mut NetConnection<E>>,
+{
+    for connection in connections {
+        if connection.state != ConnectionState::Disconnected
+            && relevancy.is_relevant(connection.target_addr, entity)
+        {
+            connection.queue(event.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Relevancy;
+    use amethyst_core::ecs::{Builder, World, WorldExt};
+    use std::{collections::HashSet, net::SocketAddr};
+
+    #[test]
+    fn unfiltered_connections_see_everything() {
+        let mut world = World::new();
+        let visible = world.create_entity().build();
+        let hidden = world.create_entity().build();
+
+        let filtered: SocketAddr = "127.0.0.1:1".parse().unwrap();
+        let unfiltered: SocketAddr = "127.0.0.1:2".parse().unwrap();
+
+        let mut relevancy = Relevancy::default();
+        relevancy.set(filtered, vec![visible].into_iter().collect::<HashSet<_>>());
+
+        assert!(relevancy.is_relevant(filtered, visible));
+        assert!(!relevancy.is_relevant(filtered, hidden));
+        assert!(relevancy.is_relevant(unfiltered, hidden));
+    }
+}
@@ -0,0 +1,162 @@
+use std::{collections::HashSet, marker::PhantomData, sync::Arc};
+
+use amethyst_core::{
+    ecs::{Entities, Entity, Join, ReadStorage, System, Write},
+    math::Vector3,
+    Transform,
+};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use super::{Interest, InterestGroups, Relevancy};
+use crate::{replication::Replicated, ConnectionState, NetConnection};
+
+/// A custom relevancy check, called with the connection entity and a replicated entity.
+///
+/// An entity is only relevant if the predicate returns `true`, in addition to the distance and group checks.
+pub type InterestPredicate = Arc<dyn Fn(Entity, Entity) -> bool + Send + Sync>;
+
+/// Computes the `Relevancy` of all `Replicated` entities for every connection with an `Interest`.
+///
+/// Add this system before the `ReplicationServerSystem`, and after the systems moving entities.
+///
+/// - `E` corresponds to the network event type.
+#[allow(missing_debug_implementations)]
+pub struct InterestSystem<E> {
+    predicate: Option<InterestPredicate>,
+    _marker: PhantomData<E>,
+}
+
+impl<E> InterestSystem<E> {
+    /// Creates a new `InterestSystem` checking distances and groups.
+    pub fn new() -> Self {
+        InterestSystem {
+            predicate: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Additionally checks the relevancy of every entity with the given predicate.
+    pub fn with_predicate(mut self, predicate: InterestPredicate) -> Self {
+        self.predicate = Some(predicate);
+        self
+    }
+}
+
+impl<'a, E> System<'a> for InterestSystem<E>
+where
+    E: Send + Sync + 'static,
+{
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, NetConnection<E>>,
+        ReadStorage<'a, Interest>,
+        ReadStorage<'a, Replicated>,
+        ReadStorage<'a, InterestGroups>,
+        ReadStorage<'a, Transform>,
+        Write<'a, Relevancy>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, connections, interests, replicated, groups, transforms, mut relevancy): Self::SystemData,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("interest_system");
+
+        relevancy.clear();
+
+        for (connection_entity, connection, interest) in
+            (&entities, &connections, &interests).join()
+        {
+            if connection.state == ConnectionState::Disconnected {
+                continue;
+            }
+
+            let focus_position = interest
+                .focus
+                .and_then(|focus| transforms.get(focus))
+                .map(position);
+
+            let relevant = (&entities, &replicated, groups.maybe(), transforms.maybe())
+                .join()
+                .filter(|(entity, _, entity_groups, transform)| {
+                    if Some(*entity) == interest.focus {
+                        return true;
+                    }
+
+                    if let Some(entity_groups) = entity_groups {
+                        if !entity_groups.intersects(&interest.groups) {
+                            return false;
+                        }
+                    }
+
+                    if let (Some(radius), Some(focus), Some(transform)) =
+                        (interest.radius, focus_position, transform)
+                    {
+                        if (position(transform) - focus).norm() > radius {
+                            return false;
+                        }
+                    }
+
+                    self.predicate
+                        .as_ref()
+                        .map_or(true, |predicate| predicate(connection_entity, *entity))
+                })
+                .map(|(entity, _, _, _)| entity)
+                .collect::<HashSet<_>>();
+
+            relevancy.set(connection.target_addr, relevant);
+        }
+    }
+}
+
+/// Returns the global position of the transform.
+fn position(transform: &Transform) -> Vector3<f32> {
+    transform.global_matrix().column(3).xyz()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InterestSystem;
+    use crate::{
+        interest::{Interest, InterestGroups, Relevancy},
+        replication::Replicated,
+        NetConnection,
+    };
+    use amethyst_core::ecs::{Builder, RunNow, System, World, WorldExt};
+    use std::{collections::HashSet, net::SocketAddr};
+
+    #[test]
+    fn entities_outside_of_the_groups_are_irrelevant() {
+        let mut world = World::new();
+        let mut system = InterestSystem::<String>::new();
+        System::setup(&mut system, &mut world);
+
+        let in_group = world
+            .create_entity()
+            .with(Replicated)
+            .with(InterestGroups(vec![1].into_iter().collect::<HashSet<_>>()))
+            .build();
+        let other_group = world
+            .create_entity()
+            .with(Replicated)
+            .with(InterestGroups(vec![2].into_iter().collect::<HashSet<_>>()))
+            .build();
+        let public = world.create_entity().with(Replicated).build();
+
+        let addr: SocketAddr = "127.0.0.1:1".parse().unwrap();
+        world
+            .create_entity()
+            .with(NetConnection::<String>::new(addr))
+            .with(Interest::default().with_group(1))
+            .build();
+
+        system.run_now(&world);
+
+        let relevancy = world.read_resource::<Relevancy>();
+        assert!(relevancy.is_relevant(addr, in_group));
+        assert!(!relevancy.is_relevant(addr, other_group));
+        assert!(relevancy.is_relevant(addr, public));
+    }
+}
@@ -11,83 +11,140 @@
 
 pub use crate::{
     bundle::NetworkBundle,
-    connection::{ConnectionState, NetConnection, NetIdentity},
+    connection::{ConnectionReaders, ConnectionState, NetConnection, NetIdentity},
+    discovery::{
+        DiscoveredServer, DiscoveredServers, DiscoveryConfig, DiscoveryEvent, HostedServer,
+        LanAnnounceSystem, LanDiscoverySystem,
+    },
     error::Result,
-    net_event::{NetEvent, NetPacket},
+    interest::{
+        queue_relevant, Interest, InterestGroup, InterestGroups, InterestPredicate, InterestSystem,
+        Relevancy,
+    },
+    lobby::{
+        Lobby, LobbyClientSystem, LobbyError, LobbyEvent, LobbyMessage, LobbyServerSystem,
+        LobbyView, Member, Room, RoomId, RoomSummary,
+    },
+    net_event::{DeliveryGuarantee, DisconnectReason, NetEvent, NetPacket, RefusedReason},
     network_socket::NetSocketSystem,
-    server::{Host, ServerConfig},
+    prediction::{
+        replay, ActionState, ClientInputs, InputFrame, InputHistory, InputSamplingSystem,
+        Predicted, PredictedComponents, PredictedInput, PredictionBundle, PredictionClientSystem,
+        PredictionConfig, PredictionHistory, PredictionMessage, PredictionServerSystem,
+        ProcessedTicks, ReconcileSystem, Reconciliation, RecordPredictionSystem, SimulationTick,
+    },
+    replication::{
+        AuthoritativeState, ComponentId, NetworkEntities, NetworkId, Replicated, ReplicationBundle,
+        ReplicationClientSystem, ReplicationConfig, ReplicationMessage, ReplicationRegistry,
+        ReplicationRole, ReplicationServerSystem, Snapshot,
+    },
+    rpc::{
+        broadcast, RequestId, Rpc, RpcBundle, RpcContext, RpcHandlerSystem, RpcMessage,
+        RpcResponse, RpcResponseSystem,
+    },
+    serializer::{BincodeSerializer, Compressed, NetSerializer, UndecodablePayload},
+    server::{Host, ServerConfig, TokenValidator},
+    stats::{ConnectionStats, NetworkStats},
+    transport::{
+        ConditionedTransport, LoopbackNetwork, LoopbackTransport, NetworkConditions,
+        NetworkTransport, TcpTransport,
+    },
 };
 
+#[cfg(feature = "msgpack")]
+pub use crate::serializer::MessagePackSerializer;
+
 use std::net::SocketAddr;
 
-use bincode::{deserialize, serialize};
 use laminar::Packet;
 use serde::{de::DeserializeOwned, Serialize};
 
 mod bundle;
 mod connection;
+mod discovery;
 mod error;
+mod interest;
+mod lobby;
 mod net_event;
 mod network_socket;
+mod prediction;
+mod replication;
+mod rpc;
+mod serializer;
 mod server;
+mod stats;
 mod test;
+mod transport;
 
 /// Attempts to serialize the given `NetEvent` and returns a laminar packet.
-/// Reliable ordered will be used by default.
-fn serialize_event<E>(event: NetEvent<E>, addr: SocketAddr) -> Result<Packet>
+///
+/// The whole event is put on the wire so the receiving end is able to tell user packets apart
+/// from the events produced by the engine.
+fn serialize_event<E, S>(event: NetEvent<E>, addr: SocketAddr, serializer: &S) -> Result<Packet>
 where
     E: Serialize,
+    S: NetSerializer,
 {
-    match serialize(&event) {
-        Ok(packet) => Ok(Packet::reliable_ordered(addr, packet, None)),
-        Err(e) => Err(e.into()),
-    }
+    let (delivery, ordering) = event.guarantees();
+    let payload = serializer.serialize(&event)?;
+
+    Ok(into_laminar_packet(addr, payload, delivery, ordering))
 }
 
 /// Attempts to serialize the given packet and returns a laminar packet.
-fn serialize_packet<T>(packet: NetPacket<T>, addr: SocketAddr) -> Result<Packet>
+fn serialize_packet<T, S>(packet: NetPacket<T>, addr: SocketAddr, serializer: &S) -> Result<Packet>
 where
     T: Serialize,
+    S: NetSerializer,
 {
-    let ser = serialize(&packet.content());
-    match ser {
-        Ok(payload) => Ok(match packet.delivery_guarantee() {
-            net_event::DeliveryGuarantee::Unreliable => match packet.ordering_guarantee() {
-                net_event::OrderingGuarantee::None => Packet::unreliable(addr, payload),
-                net_event::OrderingGuarantee::Sequenced(s) => {
-                    Packet::unreliable_sequenced(addr, payload, s)
-                }
-                _ => unreachable!(
-                    "Can not apply the guarantees: {:?}, {:?} to the packet.",
-                    packet.ordering_guarantee(),
-                    packet.delivery_guarantee()
-                ),
-            },
-            net_event::DeliveryGuarantee::Reliable => match packet.ordering_guarantee() {
-                net_event::OrderingGuarantee::None => Packet::reliable_unordered(addr, payload),
-                net_event::OrderingGuarantee::Sequenced(s) => {
-                    Packet::reliable_sequenced(addr, payload, s)
-                }
-                net_event::OrderingGuarantee::Ordered(o) => {
-                    Packet::reliable_ordered(addr, payload, o)
-                }
-            },
-        }),
-        Err(e) => Err(e.into()),
+    serialize_event(NetEvent::Packet(packet), addr, serializer)
+}
+
+/// Wraps the given payload in a laminar packet with the given guarantees.
+fn into_laminar_packet(
+    addr: SocketAddr,
+    payload: Vec<u8>,
+    delivery: net_event::DeliveryGuarantee,
+    ordering: net_event::OrderingGuarantee,
+) -> Packet {
+    match delivery {
+        net_event::DeliveryGuarantee::Unreliable => match ordering {
+            net_event::OrderingGuarantee::None => Packet::unreliable(addr, payload),
+            net_event::OrderingGuarantee::Sequenced(s) => {
+                Packet::unreliable_sequenced(addr, payload, s)
+            }
+            _ => unreachable!(
+                "Can not apply the guarantees: {:?}, {:?} to the packet.",
+                ordering, delivery
+            ),
+        },
+        net_event::DeliveryGuarantee::Reliable => match ordering {
+            net_event::OrderingGuarantee::None => Packet::reliable_unordered(addr, payload),
+            net_event::OrderingGuarantee::Sequenced(s) => {
+                Packet::reliable_sequenced(addr, payload, s)
+            }
+            net_event::OrderingGuarantee::Ordered(o) => Packet::reliable_ordered(addr, payload, o),
+        },
     }
 }
 
 // Attempts to deserialize an event from the raw byte data.
-fn deserialize_event<T>(data: &[u8]) -> Result<T>
+fn deserialize_event<T, S>(data: &[u8], serializer: &S) -> Result<T>
 where
     T: DeserializeOwned,
+    S: NetSerializer,
 {
-    Ok(deserialize::<T>(data)?)
+    serializer.deserialize::<T>(data)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{deserialize_event, net_event::NetPacket, serialize_packet};
+    use crate::{
+        deserialize_event,
+        net_event::{NetEvent, NetPacket},
+        replication::ReplicationMessage,
+        serialize_event, serialize_packet, BincodeSerializer,
+    };
     use laminar::{DeliveryGuarantee, OrderingGuarantee};
     use std::net::SocketAddr;
 
@@ -102,11 +159,11 @@ mod tests {
 
         let addr: SocketAddr = "127.0.0.1:1234".parse().unwrap();
 
-        let serialized_packet1 = serialize_packet(packet1, addr).unwrap();
-        let serialized_packet2 = serialize_packet(packet2, addr).unwrap();
-        let serialized_packet3 = serialize_packet(packet3, addr).unwrap();
-        let serialized_packet4 = serialize_packet(packet4, addr).unwrap();
-        let serialized_packet5 = serialize_packet(packet5, addr).unwrap();
+        let serialized_packet1 = serialize_packet(packet1, addr, &BincodeSerializer).unwrap();
+        let serialized_packet2 = serialize_packet(packet2, addr, &BincodeSerializer).unwrap();
+        let serialized_packet3 = serialize_packet(packet3, addr, &BincodeSerializer).unwrap();
+        let serialized_packet4 = serialize_packet(packet4, addr, &BincodeSerializer).unwrap();
+        let serialized_packet5 = serialize_packet(packet5, addr, &BincodeSerializer).unwrap();
 
         // assure correct guarantees
         assert!(
@@ -133,9 +190,28 @@ mod tests {
 
     #[test]
     fn can_deserialize_event() {
-        let result =
-            deserialize_event::<NetPacket<String>>(&[3, 0, 0, 0, 0, 0, 0, 0, 97, 98, 99]).unwrap();
+        let result = deserialize_event::<NetPacket<String>, _>(
+            &[3, 0, 0, 0, 0, 0, 0, 0, 97, 98, 99],
+            &BincodeSerializer,
+        )
+        .unwrap();
 
         assert_eq!(result.content(), &"abc".to_string());
     }
+
+    #[test]
+    fn can_roundtrip_replication_event() {
+        let event = NetEvent::<String>::Replication(NetPacket::unreliable_sequenced(
+            ReplicationMessage::Ack(3),
+            Some(1),
+        ));
+
+        let addr: SocketAddr = "127.0.0.1:1234".parse().unwrap();
+        let packet = serialize_event(event.clone(), addr, &BincodeSerializer).unwrap();
+
+        assert_eq!(
+            NetEvent::<String>::from_packet(packet, &BincodeSerializer).unwrap(),
+            event
+        );
+    }
 }
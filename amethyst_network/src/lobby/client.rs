@@ -0,0 +1,162 @@
+//! The client side of the lobby.
+
+use std::{marker::PhantomData, net::SocketAddr};
+
+use amethyst_core::{
+    ecs::{Join, System, SystemData, World, Write, WriteStorage},
+    shrev::EventChannel,
+};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use super::{LobbyEvent, LobbyMessage, Room, RoomSummary};
+use crate::{connection::register_consumer, ConnectionState, NetConnection, NetEvent};
+
+/// The lobby as seen by a client, kept up to date by the `LobbyClientSystem`.
+#[derive(Clone, Debug, Default)]
+pub struct LobbyView {
+    /// The address of this client as seen by the server, known once it joined a room.
+    pub you: Option<SocketAddr>,
+    /// The room this client is in.
+    pub room: Option<Room>,
+    /// The rooms of the server, as of the last answer to `LobbyMessage::List`.
+    pub rooms: Vec<RoomSummary>,
+}
+
+impl LobbyView {
+    /// Returns `true` if this client leads its room.
+    pub fn is_leader(&self) -> bool {
+        match (self.you, &self.room) {
+            (Some(you), Some(room)) => room.leader == you,
+            _ => false,
+        }
+    }
+}
+
+/// Applies the lobby answers of the server to the `LobbyView` resource.
+///
+/// The changes to the room of this client are written to the `EventChannel<LobbyEvent>`.
+///
+/// - `E` corresponds to the network event type.
+#[allow(missing_debug_implementations)]
+pub struct LobbyClientSystem<E: 'static> {
+    _marker: PhantomData<E>,
+}
+
+impl<E: 'static> LobbyClientSystem<E> {
+    /// Creates a new `LobbyClientSystem`.
+    pub fn new() -> Self {
+        LobbyClientSystem {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, E> System<'a> for LobbyClientSystem<E>
+where
+    E: Send + Sync + 'static,
+{
+    type SystemData = (
+        Write<'a, LobbyView>,
+        Write<'a, EventChannel<LobbyEvent>>,
+        WriteStorage<'a, NetConnection<E>>,
+    );
+
+    fn run(&mut self, (mut view, mut events, mut connections): Self::SystemData) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("lobby_client_system");
+
+        for connection in (&mut connections).join() {
+            if connection.state == ConnectionState::Disconnected {
+                continue;
+            }
+
+            for event in connection.received_events_for("lobby_client") {
+                let message = match event {
+                    NetEvent::Lobby(packet) => packet.content(),
+                    _ => continue,
+                };
+
+                match message {
+                    LobbyMessage::Rooms(rooms) => view.rooms = rooms.clone(),
+                    LobbyMessage::Room { you, room } => {
+                        events.iter_write(changes(*you, view.room.as_ref(), room.as_ref()));
+                        view.you = Some(*you);
+                        view.room = room.clone();
+                    }
+                    LobbyMessage::Refused(error) => {
+                        events.single_write(LobbyEvent::Refused(error.clone()))
+                    }
+                    // requests of clients are not handled.
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        register_consumer::<E>(world, "lobby_client");
+    }
+}
+
+/// Returns the events describing the change of the room of a client.
+fn changes(you: SocketAddr, old: Option<&Room>, new: Option<&Room>) -> Vec<LobbyEvent> {
+    let mut events = Vec::new();
+
+    let (old, new) = match (old, new) {
+        (Some(old), Some(new)) if old.id == new.id => (old, new),
+        (old, new) => {
+            if let Some(old) = old {
+                events.push(LobbyEvent::Left {
+                    room: old.id,
+                    addr: you,
+                });
+            }
+            if let Some(new) = new {
+                events.push(LobbyEvent::Joined {
+                    room: new.id,
+                    addr: you,
+                });
+            }
+            return events;
+        }
+    };
+
+    let room = new.id;
+    for member in &old.members {
+        if !new.contains(member.addr) {
+            events.push(LobbyEvent::Left {
+                room,
+                addr: member.addr,
+            });
+        }
+    }
+    for member in &new.members {
+        match old.member(member.addr) {
+            None => events.push(LobbyEvent::Joined {
+                room,
+                addr: member.addr,
+            }),
+            Some(previous) if previous.ready != member.ready => {
+                events.push(LobbyEvent::ReadyChanged {
+                    room,
+                    addr: member.addr,
+                    ready: member.ready,
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    if old.leader != new.leader {
+        events.push(LobbyEvent::LeaderChanged {
+            room,
+            leader: new.leader,
+        });
+    }
+    if !old.all_ready() && new.all_ready() {
+        events.push(LobbyEvent::AllReady(room));
+    }
+
+    events
+}
@@ -0,0 +1,100 @@
+use std::net::SocketAddr;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Room, RoomId, RoomSummary};
+
+/// The messages exchanged by the lobby systems.
+///
+/// Clients send the requests with `NetConnection::lobby_request`, the server answers with the other variants.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LobbyMessage {
+    /// Asks the server for the summaries of all rooms.
+    List,
+    /// Creates a room led by the requesting client.
+    Create {
+        /// The name of the room.
+        name: String,
+        /// The number of members at which the room is full, `None` if any number can join.
+        max_members: Option<usize>,
+    },
+    /// Joins the room with the given id.
+    Join(RoomId),
+    /// Leaves the current room.
+    Leave,
+    /// Sets whether the requesting client is ready to start the game.
+    SetReady(bool),
+    /// Makes another member the leader of the room, only the current leader may do this.
+    Promote(SocketAddr),
+    /// The answer to `List`.
+    Rooms(Vec<RoomSummary>),
+    /// The room of a client, sent whenever it changes.
+    Room {
+        /// The address of the receiving client, as seen by the server.
+        you: SocketAddr,
+        /// The room the client is in, `None` after it left.
+        room: Option<Room>,
+    },
+    /// A request could not be fulfilled.
+    Refused(LobbyError),
+}
+
+/// Why a lobby request was refused.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LobbyError {
+    /// There is no room with the given id.
+    UnknownRoom(RoomId),
+    /// The room has reached its `max_members`.
+    RoomFull(RoomId),
+    /// The client has to leave its current room first.
+    AlreadyInRoom(RoomId),
+    /// The client, or the member to promote, is not in a room.
+    NotInRoom,
+    /// Only the leader of the room may do this.
+    NotLeader,
+}
+
+/// Written to the `EventChannel<LobbyEvent>` when the lobby changes.
+///
+/// On the server the events describe every room, on a client only the room it is in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LobbyEvent {
+    /// A room was created.
+    Created(RoomId),
+    /// A member joined a room.
+    Joined {
+        /// The room which was joined.
+        room: RoomId,
+        /// The address of the member.
+        addr: SocketAddr,
+    },
+    /// A member left a room, or was removed from it because its connection closed.
+    Left {
+        /// The room which was left.
+        room: RoomId,
+        /// The address of the member.
+        addr: SocketAddr,
+    },
+    /// A member changed its ready state.
+    ReadyChanged {
+        /// The room of the member.
+        room: RoomId,
+        /// The address of the member.
+        addr: SocketAddr,
+        /// Whether the member is ready now.
+        ready: bool,
+    },
+    /// All members of a room are ready.
+    AllReady(RoomId),
+    /// Another member leads the room, either because it was promoted or because the leader left.
+    LeaderChanged {
+        /// The room which changed its leader.
+        room: RoomId,
+        /// The address of the new leader.
+        leader: SocketAddr,
+    },
+    /// The last member left a room, which was removed.
+    Closed(RoomId),
+    /// The server refused a request of this client.
+    Refused(LobbyError),
+}
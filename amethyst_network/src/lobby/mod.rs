@@ -0,0 +1,35 @@
+//! Rooms which clients gather in before starting a game.
+//!
+//! The server keeps the rooms in the `Lobby` resource, which is run by the `LobbyServerSystem`.
+//! Clients send their requests with `NetConnection::lobby_request`: they create, join and leave rooms,
+//! set whether they are ready and pass on the lead of their room. When the leader leaves,
+//! the member which joined next takes over, and the room is closed when its last member leaves.
+//!
+//! Every client receives the new state of its room when it changes, which the `LobbyClientSystem`
+//! keeps in the `LobbyView` resource. Both ends write the changes to the `EventChannel<LobbyEvent>`.
+
+pub use self::{
+    client::{LobbyClientSystem, LobbyView},
+    message::{LobbyError, LobbyEvent, LobbyMessage},
+    room::{Member, Room, RoomId, RoomSummary},
+    server::{Lobby, LobbyServerSystem},
+};
+
+use crate::{NetConnection, NetEvent, NetPacket};
+
+mod client;
+mod message;
+mod room;
+mod server;
+
+impl<E: Send + Sync + 'static> NetConnection<E> {
+    /// Sends a lobby request to the server.
+    pub fn lobby_request(&mut self, request: LobbyMessage) {
+        self.queue(NetEvent::Lobby(lobby_packet(request)));
+    }
+}
+
+/// Lobby messages are sent reliable ordered, so requests and room updates are applied in the order they were sent.
+fn lobby_packet(message: LobbyMessage) -> NetPacket<LobbyMessage> {
+    NetPacket::reliable_ordered(message, None)
+}
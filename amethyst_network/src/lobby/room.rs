@@ -0,0 +1,77 @@
+use std::net::SocketAddr;
+
+use serde::{Deserialize, Serialize};
+
+/// Identifies a room of the `Lobby`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct RoomId(pub u32);
+
+/// A member of a room.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Member {
+    /// The address of the member, as seen by the server.
+    pub addr: SocketAddr,
+    /// Whether the member is ready to start the game.
+    pub ready: bool,
+}
+
+/// A room which clients can join to gather before starting a game.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Room {
+    /// The id of the room.
+    pub id: RoomId,
+    /// The name of the room.
+    pub name: String,
+    /// The member leading the room, who is allowed to promote another member to leader.
+    pub leader: SocketAddr,
+    /// The members of the room, in the order in which they joined.
+    pub members: Vec<Member>,
+    /// The number of members at which the room is full, `None` if any number can join.
+    pub max_members: Option<usize>,
+}
+
+impl Room {
+    /// Returns the member with the given address.
+    pub fn member(&self, addr: SocketAddr) -> Option<&Member> {
+        self.members.iter().find(|member| member.addr == addr)
+    }
+
+    /// Returns `true` if the given address is a member of the room.
+    pub fn contains(&self, addr: SocketAddr) -> bool {
+        self.member(addr).is_some()
+    }
+
+    /// Returns `true` if no more members can join the room.
+    pub fn is_full(&self) -> bool {
+        self.max_members
+            .map_or(false, |max| self.members.len() >= max)
+    }
+
+    /// Returns `true` if the room has members and all of them are ready.
+    pub fn all_ready(&self) -> bool {
+        !self.members.is_empty() && self.members.iter().all(|member| member.ready)
+    }
+
+    /// Returns the summary of this room shown to clients browsing the lobby.
+    pub fn summary(&self) -> RoomSummary {
+        RoomSummary {
+            id: self.id,
+            name: self.name.clone(),
+            members: self.members.len(),
+            max_members: self.max_members,
+        }
+    }
+}
+
+/// The summary of a room shown to clients browsing the lobby.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoomSummary {
+    /// The id of the room.
+    pub id: RoomId,
+    /// The name of the room.
+    pub name: String,
+    /// The number of members in the room.
+    pub members: usize,
+    /// The number of members at which the room is full, `None` if any number can join.
+    pub max_members: Option<usize>,
+}
@@ -0,0 +1,399 @@
+//! The server side of the lobby.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    marker::PhantomData,
+    net::SocketAddr,
+};
+
+use amethyst_core::{
+    ecs::{Join, System, SystemData, World, Write, WriteStorage},
+    shrev::EventChannel,
+};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use super::{lobby_packet, LobbyError, LobbyEvent, LobbyMessage, Member, Room, RoomId};
+use crate::{connection::register_consumer, ConnectionState, NetConnection, NetEvent};
+
+/// The rooms of the server.
+///
+/// The `LobbyServerSystem` modifies the lobby on behalf of the clients, but game code can modify it as well.
+/// Every change is written to the `EventChannel<LobbyEvent>` and sent to the affected clients
+/// the next time the `LobbyServerSystem` runs.
+#[derive(Clone, Debug, Default)]
+pub struct Lobby {
+    rooms: BTreeMap<RoomId, Room>,
+    next_id: u32,
+    // the events which were not written to the channel yet.
+    events: Vec<LobbyEvent>,
+    // the clients whose room changed since the last run.
+    changed: HashSet<SocketAddr>,
+}
+
+impl Lobby {
+    /// Returns the room with the given id.
+    pub fn room(&self, id: RoomId) -> Option<&Room> {
+        self.rooms.get(&id)
+    }
+
+    /// Returns the room the given client is in.
+    pub fn room_of(&self, addr: SocketAddr) -> Option<&Room> {
+        self.rooms.values().find(|room| room.contains(addr))
+    }
+
+    /// Returns an iterator over all rooms, ordered by id.
+    pub fn rooms(&self) -> impl Iterator<Item = &Room> {
+        self.rooms.values()
+    }
+
+    /// Creates a room led by the given client.
+    pub fn create(
+        &mut self,
+        leader: SocketAddr,
+        name: impl Into<String>,
+        max_members: Option<usize>,
+    ) -> Result<RoomId, LobbyError> {
+        if let Some(room) = self.room_of(leader) {
+            return Err(LobbyError::AlreadyInRoom(room.id));
+        }
+
+        let id = RoomId(self.next_id);
+        self.next_id += 1;
+
+        self.rooms.insert(
+            id,
+            Room {
+                id,
+                name: name.into(),
+                leader,
+                members: vec![Member {
+                    addr: leader,
+                    ready: false,
+                }],
+                max_members,
+            },
+        );
+        self.events.push(LobbyEvent::Created(id));
+        self.events.push(LobbyEvent::Joined {
+            room: id,
+            addr: leader,
+        });
+        self.changed.insert(leader);
+
+        Ok(id)
+    }
+
+    /// Adds the given client to a room.
+    pub fn join(&mut self, id: RoomId, addr: SocketAddr) -> Result<(), LobbyError> {
+        if let Some(room) = self.room_of(addr) {
+            return Err(LobbyError::AlreadyInRoom(room.id));
+        }
+
+        let room = self.rooms.get_mut(&id).ok_or(LobbyError::UnknownRoom(id))?;
+        if room.is_full() {
+            return Err(LobbyError::RoomFull(id));
+        }
+
+        room.members.push(Member { addr, ready: false });
+        self.events.push(LobbyEvent::Joined { room: id, addr });
+        self.changed
+            .extend(room.members.iter().map(|member| member.addr));
+
+        Ok(())
+    }
+
+    /// Removes the given client from its room.
+    ///
+    /// If the client led the room, the member which joined first after it becomes the leader.
+    /// The room is closed when its last member leaves.
+    pub fn leave(&mut self, addr: SocketAddr) -> Result<RoomId, LobbyError> {
+        let id = self.room_of(addr).ok_or(LobbyError::NotInRoom)?.id;
+        let room = self.rooms.get_mut(&id).expect("Room was just found");
+
+        let was_ready = room.all_ready();
+        room.members.retain(|member| member.addr != addr);
+        self.events.push(LobbyEvent::Left { room: id, addr });
+        self.changed.insert(addr);
+        self.changed
+            .extend(room.members.iter().map(|member| member.addr));
+
+        if room.members.is_empty() {
+            self.rooms.remove(&id);
+            self.events.push(LobbyEvent::Closed(id));
+            return Ok(id);
+        }
+
+        if room.leader == addr {
+            room.leader = room.members[0].addr;
+            self.events.push(LobbyEvent::LeaderChanged {
+                room: id,
+                leader: room.leader,
+            });
+        }
+        if !was_ready && room.all_ready() {
+            self.events.push(LobbyEvent::AllReady(id));
+        }
+
+        Ok(id)
+    }
+
+    /// Sets whether the given client is ready to start the game.
+    pub fn set_ready(&mut self, addr: SocketAddr, ready: bool) -> Result<(), LobbyError> {
+        let id = self.room_of(addr).ok_or(LobbyError::NotInRoom)?.id;
+        let room = self.rooms.get_mut(&id).expect("Room was just found");
+
+        let member = room
+            .members
+            .iter_mut()
+            .find(|member| member.addr == addr)
+            .expect("Member was just found");
+        if member.ready == ready {
+            return Ok(());
+        }
+
+        member.ready = ready;
+        self.events.push(LobbyEvent::ReadyChanged {
+            room: id,
+            addr,
+            ready,
+        });
+        if room.all_ready() {
+            self.events.push(LobbyEvent::AllReady(id));
+        }
+        self.changed
+            .extend(room.members.iter().map(|member| member.addr));
+
+        Ok(())
+    }
+
+    /// Makes the given client the leader of its room.
+    pub fn promote(&mut self, addr: SocketAddr) -> Result<(), LobbyError> {
+        let id = self.room_of(addr).ok_or(LobbyError::NotInRoom)?.id;
+        let room = self.rooms.get_mut(&id).expect("Room was just found");
+
+        if room.leader != addr {
+            room.leader = addr;
+            self.events.push(LobbyEvent::LeaderChanged {
+                room: id,
+                leader: addr,
+            });
+            self.changed
+                .extend(room.members.iter().map(|member| member.addr));
+        }
+
+        Ok(())
+    }
+
+    /// Handles a request of the given client, returning the answer to send back to it.
+    fn handle(&mut self, addr: SocketAddr, request: &LobbyMessage) -> Option<LobbyMessage> {
+        let result = match request {
+            LobbyMessage::List => {
+                return Some(LobbyMessage::Rooms(
+                    self.rooms.values().map(Room::summary).collect(),
+                ))
+            }
+            LobbyMessage::Create { name, max_members } => {
+                self.create(addr, name.clone(), *max_members).map(|_| ())
+            }
+            LobbyMessage::Join(id) => self.join(*id, addr),
+            LobbyMessage::Leave => self.leave(addr).map(|_| ()),
+            LobbyMessage::SetReady(ready) => self.set_ready(addr, *ready),
+            LobbyMessage::Promote(member) => {
+                let allowed = match self.room_of(addr) {
+                    Some(room) if room.leader != addr => Err(LobbyError::NotLeader),
+                    Some(room) if room.contains(*member) => Ok(()),
+                    _ => Err(LobbyError::NotInRoom),
+                };
+                allowed.and_then(|_| self.promote(*member))
+            }
+            // answers of the server are not handled.
+            _ => Ok(()),
+        };
+
+        result.err().map(LobbyMessage::Refused)
+    }
+}
+
+/// Runs the `Lobby` on the server.
+///
+/// This system handles the lobby requests of all connections, removes the clients whose
+/// connection closed from their room and sends every client the new state of its room when it changed.
+///
+/// - `E` corresponds to the network event type.
+#[allow(missing_debug_implementations)]
+pub struct LobbyServerSystem<E: 'static> {
+    _marker: PhantomData<E>,
+}
+
+impl<E: 'static> LobbyServerSystem<E> {
+    /// Creates a new `LobbyServerSystem`.
+    pub fn new() -> Self {
+        LobbyServerSystem {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, E> System<'a> for LobbyServerSystem<E>
+where
+    E: Send + Sync + 'static,
+{
+    type SystemData = (
+        Write<'a, Lobby>,
+        Write<'a, EventChannel<LobbyEvent>>,
+        WriteStorage<'a, NetConnection<E>>,
+    );
+
+    fn run(&mut self, (mut lobby, mut events, mut connections): Self::SystemData) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("lobby_server_system");
+
+        let mut active = HashSet::new();
+
+        for connection in (&mut connections).join() {
+            if connection.state == ConnectionState::Disconnected {
+                continue;
+            }
+
+            let addr = connection.target_addr;
+            active.insert(addr);
+
+            let mut answers = Vec::new();
+            for event in connection.received_events_for("lobby_server") {
+                if let NetEvent::Lobby(packet) = event {
+                    answers.extend(lobby.handle(addr, packet.content()));
+                }
+            }
+
+            for answer in answers {
+                connection.queue(NetEvent::Lobby(lobby_packet(answer)));
+            }
+        }
+
+        let gone = lobby
+            .rooms()
+            .flat_map(|room| room.members.iter().map(|member| member.addr))
+            .filter(|addr| !active.contains(addr))
+            .collect::<Vec<_>>();
+        for addr in gone {
+            lobby.leave(addr).expect("Member was just found");
+        }
+
+        events.iter_write(lobby.events.drain(..));
+
+        let changed = lobby.changed.drain().collect::<HashSet<_>>();
+        for connection in (&mut connections).join() {
+            let addr = connection.target_addr;
+            if active.contains(&addr) && changed.contains(&addr) {
+                connection.queue(NetEvent::Lobby(lobby_packet(LobbyMessage::Room {
+                    you: addr,
+                    room: lobby.room_of(addr).cloned(),
+                })));
+            }
+        }
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        register_consumer::<E>(world, "lobby_server");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leader_migrates_and_empty_rooms_close() {
+        let first: SocketAddr = "127.0.0.1:1".parse().unwrap();
+        let second: SocketAddr = "127.0.0.1:2".parse().unwrap();
+
+        let mut lobby = Lobby::default();
+        let id = lobby.create(first, "room", Some(2)).unwrap();
+        lobby.join(id, second).unwrap();
+        assert_eq!(
+            lobby.join(id, "127.0.0.1:3".parse().unwrap()),
+            Err(LobbyError::RoomFull(id))
+        );
+
+        lobby.set_ready(first, true).unwrap();
+        lobby.leave(first).unwrap();
+        assert_eq!(lobby.room(id).unwrap().leader, second);
+
+        lobby.set_ready(second, true).unwrap();
+        lobby.leave(second).unwrap();
+        assert!(lobby.room(id).is_none());
+
+        assert_eq!(
+            lobby.events,
+            vec![
+                LobbyEvent::Created(id),
+                LobbyEvent::Joined {
+                    room: id,
+                    addr: first
+                },
+                LobbyEvent::Joined {
+                    room: id,
+                    addr: second
+                },
+                LobbyEvent::ReadyChanged {
+                    room: id,
+                    addr: first,
+                    ready: true
+                },
+                LobbyEvent::Left {
+                    room: id,
+                    addr: first
+                },
+                LobbyEvent::LeaderChanged {
+                    room: id,
+                    leader: second
+                },
+                LobbyEvent::ReadyChanged {
+                    room: id,
+                    addr: second,
+                    ready: true
+                },
+                LobbyEvent::AllReady(id),
+                LobbyEvent::Left {
+                    room: id,
+                    addr: second
+                },
+                LobbyEvent::Closed(id),
+            ]
+        );
+    }
+
+    #[test]
+    fn all_ready_is_sent_once_everyone_is_ready() {
+        let first: SocketAddr = "127.0.0.1:1".parse().unwrap();
+        let second: SocketAddr = "127.0.0.1:2".parse().unwrap();
+        let third: SocketAddr = "127.0.0.1:3".parse().unwrap();
+
+        let mut lobby = Lobby::default();
+        let id = lobby.create(first, "room", None).unwrap();
+        lobby.join(id, second).unwrap();
+        lobby.join(id, third).unwrap();
+        lobby.set_ready(first, true).unwrap();
+        lobby.set_ready(second, true).unwrap();
+        // the only member who isn't ready leaving makes the room ready
+        lobby.leave(third).unwrap();
+        // a ready member leaving doesn't
+        lobby.leave(second).unwrap();
+
+        let all_ready = lobby
+            .events
+            .iter()
+            .filter(|event| **event == LobbyEvent::AllReady(id))
+            .count();
+        assert_eq!(all_ready, 1);
+        assert_eq!(
+            lobby.events.last(),
+            Some(&LobbyEvent::Left {
+                room: id,
+                addr: second
+            })
+        );
+    }
+}
@@ -2,56 +2,146 @@
 //! NetEvent are passed through the network
 //! NetOwnedEvent are passed through the ECS, and contains the event's source (remote connection, usually).
 
-use crate::Result;
+use crate::{
+    lobby::LobbyMessage, prediction::PredictionMessage, replication::ReplicationMessage,
+    rpc::RpcMessage, serializer::NetSerializer, Result,
+};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::net::SocketAddr;
 
 /// Network events which you can send or and receive from an endpoint.
-// TODO, Connect, connection refused, disconnect, disconnected
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum NetEvent<T> {
-    /// Will be fired when a client connected.
+    /// The handshake sent by the `NetSocketSystem` for every `NetConnection` that is still connecting.
+    ///
+    /// The receiving end answers with `Connected` if the protocol version matches its own
+    /// and the token is accepted, or with `ConnectionRefused` otherwise.
+    Connect {
+        /// The protocol version of the connecting end.
+        protocol_version: u32,
+        /// The optional authentication token of the connecting end.
+        token: Option<Vec<u8>>,
+    },
+    /// Will be fired when the handshake with a remote end completed.
     /// When this event occurs the `NetConnection` with this address was already automatically added to the world.
     Connected(SocketAddr),
-    /// Will be fired when a client was disconnected.
+    /// Will be fired on both ends when a handshake was refused.
+    /// The `NetConnection` is `Disconnected` afterwards.
+    ConnectionRefused {
+        /// Why the handshake was refused.
+        reason: RefusedReason,
+    },
+    /// Sent to the remote end to gracefully close a connection, see `NetConnection::disconnect`.
+    Disconnect {
+        /// Why the connection is being closed.
+        reason: DisconnectReason,
+    },
+    /// Will be fired on both ends when a connection was closed or timed out.
     /// If this happens consider removing the `NetConnection` with this address from the world.
-    Disconnected(SocketAddr),
+    Disconnected {
+        /// The address of the remote end.
+        addr: SocketAddr,
+        /// Why the connection was closed.
+        reason: DisconnectReason,
+    },
+    /// Keeps an idle connection alive, consumed by the `NetSocketSystem`.
+    Heartbeat,
+    /// Measures the round trip time of a connection, consumed by the `NetSocketSystem`.
+    ///
+    /// The receiving end answers with a `Pong` carrying the same sequence number.
+    Ping(u32),
+    /// The answer to a `Ping`, consumed by the `NetSocketSystem`.
+    Pong(u32),
     /// Send a packet to all connected clients
     Packet(NetPacket<T>),
+    /// Entity state replication data, produced and consumed by the replication systems.
+    Replication(NetPacket<ReplicationMessage>),
+    /// Tick stamped input, produced and consumed by the prediction systems.
+    Prediction(NetPacket<PredictionMessage>),
+    /// Remote procedure calls and their responses, produced and consumed by the rpc systems.
+    Rpc(NetPacket<RpcMessage>),
+    /// Lobby requests and room updates, produced and consumed by the lobby systems.
+    Lobby(NetPacket<LobbyMessage>),
     #[doc(hidden)]
     __Nonexhaustive,
 }
 
+/// The reason why a handshake was refused.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RefusedReason {
+    /// The protocol versions of both ends differ.
+    ProtocolMismatch {
+        /// The protocol version of the refusing end.
+        expected: u32,
+        /// The protocol version of the connecting end.
+        received: u32,
+    },
+    /// The authentication token was missing or rejected.
+    Unauthorized,
+}
+
+/// The reason why a connection was closed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DisconnectReason {
+    /// The connection was closed without a specific reason.
+    Closed,
+    /// Nothing was received from the remote end within the configured timeout.
+    Timeout,
+    /// The remote end removed this end, with a message explaining why.
+    Kicked(String),
+}
+
+impl<T> NetEvent<T> {
+    /// Returns the guarantees with which this event should be transmitted.
+    ///
+    /// Events carrying a packet use the guarantees of that packet, heartbeats and pings are unreliable,
+    /// all other events are reliable ordered.
+    pub(crate) fn guarantees(&self) -> (DeliveryGuarantee, OrderingGuarantee) {
+        match self {
+            NetEvent::Packet(packet) => (packet.delivery_guarantee(), packet.ordering_guarantee()),
+            NetEvent::Replication(packet) => {
+                (packet.delivery_guarantee(), packet.ordering_guarantee())
+            }
+            NetEvent::Prediction(packet) => {
+                (packet.delivery_guarantee(), packet.ordering_guarantee())
+            }
+            NetEvent::Rpc(packet) => (packet.delivery_guarantee(), packet.ordering_guarantee()),
+            NetEvent::Lobby(packet) => (packet.delivery_guarantee(), packet.ordering_guarantee()),
+            NetEvent::Heartbeat | NetEvent::Ping(_) | NetEvent::Pong(_) => {
+                (DeliveryGuarantee::Unreliable, OrderingGuarantee::None)
+            }
+            _ => (
+                DeliveryGuarantee::Reliable,
+                OrderingGuarantee::Ordered(None),
+            ),
+        }
+    }
+}
+
 impl<T> NetEvent<T>
 where
     T: Serialize + DeserializeOwned,
 {
-    pub(crate) fn from_packet(packet: laminar::Packet) -> Result<Self> {
-        match crate::deserialize_event::<T>(packet.payload()) {
-            Ok(event) => {
-                let net_event: NetEvent<T> = NetEvent::Packet(match packet.delivery_guarantee() {
-                    laminar::DeliveryGuarantee::Unreliable => match packet.order_guarantee() {
-                        laminar::OrderingGuarantee::None => NetPacket::<T>::unreliable(event),
-                        laminar::OrderingGuarantee::Sequenced(s) => {
-                            NetPacket::unreliable_sequenced(event, s)
-                        }
-                        _ => panic!("This is in no way possible"),
-                    },
-                    laminar::DeliveryGuarantee::Reliable => match packet.order_guarantee() {
-                        laminar::OrderingGuarantee::None => NetPacket::reliable_unordered(event),
-                        laminar::OrderingGuarantee::Sequenced(s) => {
-                            NetPacket::reliable_sequenced(event, s)
-                        }
-                        laminar::OrderingGuarantee::Ordered(o) => {
-                            NetPacket::reliable_ordered(event, o)
-                        }
-                    },
-                });
-
-                Ok(net_event)
-            }
-            Err(e) => Err(e),
+    pub(crate) fn from_packet<S: NetSerializer>(
+        packet: laminar::Packet,
+        serializer: &S,
+    ) -> Result<Self> {
+        let mut event = crate::deserialize_event::<NetEvent<T>, S>(packet.payload(), serializer)?;
+
+        // The guarantees are not part of the payload, restore them from the laminar packet.
+        let delivery = DeliveryGuarantee::from(packet.delivery_guarantee());
+        let ordering = OrderingGuarantee::from(packet.order_guarantee());
+
+        match event {
+            NetEvent::Packet(ref mut packet) => packet.set_guarantees(delivery, ordering),
+            NetEvent::Replication(ref mut packet) => packet.set_guarantees(delivery, ordering),
+            NetEvent::Prediction(ref mut packet) => packet.set_guarantees(delivery, ordering),
+            NetEvent::Rpc(ref mut packet) => packet.set_guarantees(delivery, ordering),
+            NetEvent::Lobby(ref mut packet) => packet.set_guarantees(delivery, ordering),
+            _ => {}
         }
+
+        Ok(event)
     }
 }
 
@@ -68,7 +158,7 @@ pub(crate) enum OrderingGuarantee {
 
 /// Enum to specify how a packet should be delivered.
 #[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialOrd, PartialEq, Eq)]
-pub(crate) enum DeliveryGuarantee {
+pub enum DeliveryGuarantee {
     /// Packet may or may not be delivered
     Unreliable,
     /// Packet will be delivered
@@ -95,6 +185,15 @@ impl From<OrderingGuarantee> for laminar::OrderingGuarantee {
     }
 }
 
+impl From<laminar::DeliveryGuarantee> for DeliveryGuarantee {
+    fn from(delivery: laminar::DeliveryGuarantee) -> Self {
+        match delivery {
+            laminar::DeliveryGuarantee::Unreliable => DeliveryGuarantee::Unreliable,
+            laminar::DeliveryGuarantee::Reliable => DeliveryGuarantee::Reliable,
+        }
+    }
+}
+
 impl From<DeliveryGuarantee> for laminar::DeliveryGuarantee {
     fn from(delivery: DeliveryGuarantee) -> Self {
         match delivery {
@@ -298,6 +397,16 @@ impl<T> NetPacket<T> {
     pub(crate) fn delivery_guarantee(&self) -> DeliveryGuarantee {
         self.delivery_guarantee
     }
+
+    /// Overrides the guarantees, used when reconstructing a packet that was received.
+    pub(crate) fn set_guarantees(
+        &mut self,
+        delivery_guarantee: DeliveryGuarantee,
+        ordering_guarantee: OrderingGuarantee,
+    ) {
+        self.delivery_guarantee = delivery_guarantee;
+        self.ordering_guarantee = ordering_guarantee;
+    }
 }
 
 #[cfg(test)]
@@ -1,28 +1,76 @@
 //! The network send and receive System
 
-use std::{clone::Clone, net::SocketAddr, thread};
+use std::{
+    clone::Clone,
+    collections::{HashMap, HashSet},
+    marker::PhantomData,
+    net::SocketAddr,
+    time::Instant,
+};
 
-use amethyst_core::ecs::{Entities, Join, System, WriteStorage};
+use amethyst_core::{
+    ecs::{Entities, Join, Read, System, SystemData, World, Write, WriteStorage},
+    shrev::EventChannel,
+};
 
-use crossbeam_channel::{Receiver, Sender};
-use laminar::{Packet, SocketEvent};
+use laminar::SocketEvent;
 use log::{error, warn};
 use serde::{de::DeserializeOwned, Serialize};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
 
 use super::{
     error::Result,
     serialize_event, serialize_packet,
+    serializer::{BincodeSerializer, NetSerializer, UndecodablePayload},
     server::{Host, ServerConfig},
-    ConnectionState, NetConnection, NetEvent,
+    stats::{ConnectionStats, NetworkStats},
+    transport::{ConditionedTransport, NetworkTransport},
+    ConnectionReaders, ConnectionState, DisconnectReason, NetConnection, NetEvent, RefusedReason,
 };
 use std::io::{Error, ErrorKind};
 
-enum InternalSocketEvent<E> {
-    SendEvents {
-        target: SocketAddr,
-        events: Vec<NetEvent<E>>,
-    },
-    Stop,
+/// The number of events a connecting end may send before its handshake completes.
+///
+/// They are delivered once the connection is established, further events are dropped.
+const MAX_EARLY_EVENTS: usize = 256;
+
+/// The session bookkeeping of a single connection.
+struct Session<E> {
+    // when this end started to wait for the handshake to complete.
+    started: Instant,
+    // when the handshake was sent last.
+    last_handshake: Option<Instant>,
+    // when anything was sent to the remote end last.
+    last_sent: Instant,
+    // when anything was received from the remote end last.
+    last_received: Instant,
+    // when the last ping was sent.
+    last_ping: Option<Instant>,
+    // the sequence number of the next ping.
+    next_ping: u32,
+    // the ping which was not answered yet, and when it was sent.
+    pending_ping: Option<(u32, Instant)>,
+    // the traffic statistics of the connection.
+    stats: ConnectionStats,
+    // the events received before the handshake completed, delivered once it is established.
+    early: Vec<NetEvent<E>>,
+}
+
+impl<E> Session<E> {
+    fn new(now: Instant) -> Self {
+        Session {
+            started: now,
+            last_handshake: None,
+            last_sent: now,
+            last_received: now,
+            last_ping: None,
+            next_ping: 0,
+            pending_ping: None,
+            stats: ConnectionStats::default(),
+            early: Vec::new(),
+        }
+    }
 }
 
 /// The System managing the network state from `NetConnections`.
@@ -31,24 +79,46 @@ enum InternalSocketEvent<E> {
 ///
 /// - Reading to send packets from `NetConnection` and sending those over to some remote endpoint.
 /// - Listening for incoming packets and queue the received packets (`NetEvent::Packet(...)`) on the accompanying `NetConnection`.
+/// - Running the session of every `NetConnection`: the handshake, heartbeats, timeouts and graceful disconnects.
+///
+/// Packets are exchanged over a `NetworkTransport`, which is a laminar UDP socket unless created `with_transport`.
+///
+/// The traffic of every connection is measured in the `NetworkStats` resource.
+///
+/// The readers of the consumers listed in the `ConnectionReaders` are registered on every connection
+/// before the first received event is queued on it.
+///
+/// Events are encoded with the `NetSerializer` `S`, `BincodeSerializer` unless created `with_serializer`.
+/// The serializer is inserted as a resource, through which the other network systems encode their payloads.
+/// A received payload which can not be decoded is dropped and reported to the `EventChannel<UndecodablePayload>`.
 ///
 /// This system is able to create a `NetConnection` and add those to the world when a new client connects.
 /// (This behavior might not be desired and can therefore be deactivated in the configuration).
 ///
-/// In both cases when a client connects and disconnects a `NetEvent::Connected` or `NetEvent::Disconnected` will be queued on accompanying `NetConnection`
+/// A `NetConnection` starts out `Connecting`, during which a `NetEvent::Connect` handshake is sent to the remote end.
+/// Once the handshake is answered a `NetEvent::Connected` is queued on the accompanying `NetConnection`.
+/// Events received from the remote end before that are held back until then, and dropped if the handshake is refused.
+/// A new handshake from the end of a refused or disconnected `NetConnection` opens it again.
+/// When a connection is refused, closed or times out, a `NetEvent::ConnectionRefused` or `NetEvent::Disconnected` is queued instead.
 ///
 /// - `T` corresponds to the network event type.
+/// - `S` corresponds to the wire format of the events.
 #[allow(missing_debug_implementations)]
-pub struct NetSocketSystem<E: 'static>
+pub struct NetSocketSystem<E: 'static, S = BincodeSerializer>
 where
     E: PartialEq,
 {
-    // sender on which you can queue packets to send to some endpoint.
-    event_sender: Sender<InternalSocketEvent<E>>,
-    // receiver from which you can read received packets.
-    event_receiver: Receiver<laminar::SocketEvent>,
+    // the transport over which packets are sent to and received from remote endpoints.
+    transport: Box<dyn NetworkTransport>,
     // the configuration with which you can configure the network behaviour.
     config: ServerConfig,
+    // the sessions of all connections which are not disconnected.
+    sessions: HashMap<SocketAddr, Session<E>>,
+    // the wire format of the events.
+    serializer: S,
+    // the number of undecodable payloads received from any address.
+    undecodable: u64,
+    _marker: PhantomData<E>,
 }
 
 impl<E> NetSocketSystem<E>
@@ -64,113 +134,429 @@ where
 
         let server = Host::run(&config)?;
 
-        let udp_send_handle = server.udp_send_handle();
-        let udp_receive_handle = server.udp_receive_handle();
+        Ok(Self::with_transport(config, server))
+    }
 
-        let event_sender = NetSocketSystem::<E>::start_sending(udp_send_handle);
+    /// Creates a `NetSocketSystem` sending and receiving packets over the given transport.
+    ///
+    /// The `udp_socket_addr` of the configuration is not used, the transport is bound already.
+    pub fn with_transport<T>(config: ServerConfig, transport: T) -> Self
+    where
+        T: NetworkTransport + 'static,
+    {
+        Self::with_boxed_transport(config, Box::new(transport))
+    }
 
-        Ok(NetSocketSystem {
-            event_sender,
-            event_receiver: udp_receive_handle,
+    pub(crate) fn with_boxed_transport(
+        config: ServerConfig,
+        transport: Box<dyn NetworkTransport>,
+    ) -> Self {
+        let transport: Box<dyn NetworkTransport> = match config.network_conditions {
+            Some(ref conditions) => {
+                Box::new(ConditionedTransport::new(transport, conditions.clone()))
+            }
+            None => transport,
+        };
+
+        NetSocketSystem {
+            transport,
             config,
-        })
+            sessions: HashMap::new(),
+            serializer: BincodeSerializer,
+            undecodable: 0,
+            _marker: PhantomData,
+        }
     }
+}
 
-    /// Start a thread to send all queued packets.
-    fn start_sending(sender: Sender<Packet>) -> Sender<InternalSocketEvent<E>> {
-        let (event_sender, event_receiver) = crossbeam_channel::unbounded();
-
-        thread::spawn(move || loop {
-            for control_event in event_receiver.try_iter() {
-                match control_event {
-                    InternalSocketEvent::SendEvents { target, events } => {
-                        for ev in events {
-                            let serialize_result = match ev {
-                                NetEvent::Packet(packet) => serialize_packet(packet, target),
-                                NetEvent::Connected(addr) => serialize_event(ev, addr),
-                                NetEvent::Disconnected(addr) => serialize_event(ev, addr),
-                                NetEvent::__Nonexhaustive => {
-                                    Err(Error::new(ErrorKind::Other, "Net event does not exist.")
-                                        .into())
-                                }
-                            };
+impl<E, S> NetSocketSystem<E, S>
+where
+    E: Serialize + PartialEq + Send + 'static,
+    S: NetSerializer,
+{
+    /// Encodes the events with the given serializer instead of the current one.
+    ///
+    /// The remote ends have to use the same serializer to be able to decode the events.
+    pub fn with_serializer<N>(self, serializer: N) -> NetSocketSystem<E, N>
+    where
+        N: NetSerializer,
+    {
+        NetSocketSystem {
+            transport: self.transport,
+            config: self.config,
+            sessions: self.sessions,
+            serializer,
+            undecodable: self.undecodable,
+            _marker: PhantomData,
+        }
+    }
 
-                            match serialize_result {
-                                Ok(packet) => match sender.send(packet) {
-                                    Ok(_qty) => {}
-                                    Err(e) => {
-                                        error!("Failed to send data to network socket: {}", e)
-                                    }
-                                },
-                                Err(e) => error!("Cannot serialize packet. Reason: {}", e),
+    fn send(&mut self, target: SocketAddr, events: Vec<NetEvent<E>>, now: Instant) {
+        if events.is_empty() {
+            return;
+        }
+
+        let mut stats = self.sessions.get_mut(&target).map(|session| {
+            session.last_sent = now;
+            &mut session.stats
+        });
+
+        for ev in events {
+            let serialize_result = match ev {
+                NetEvent::Packet(packet) => serialize_packet(packet, target, &self.serializer),
+                NetEvent::__Nonexhaustive => {
+                    Err(Error::new(ErrorKind::Other, "Net event does not exist.").into())
+                }
+                ev => serialize_event(ev, target, &self.serializer),
+            };
+
+            let sent = match serialize_result {
+                Ok(packet) => {
+                    let size = packet.payload().len();
+                    match self.transport.send(packet) {
+                        Ok(()) => Some(size),
+                        Err(e) => {
+                            if let Some(ref mut stats) = stats {
+                                stats.send_errors += 1;
                             }
+                            error!("Failed to send data to network socket: {}", e);
+                            None
                         }
                     }
-                    InternalSocketEvent::Stop => {
-                        break;
+                }
+                Err(e) => {
+                    error!("Cannot serialize packet. Reason: {}", e);
+                    None
+                }
+            };
+
+            if let Some(ref mut stats) = stats {
+                match sent {
+                    Some(size) => stats.record_sent(size),
+                    None => stats.dropped += 1,
+                }
+            }
+        }
+    }
+
+    /// Checks the handshake of a connecting end against the configuration.
+    fn check_handshake(
+        &self,
+        addr: SocketAddr,
+        protocol_version: u32,
+        token: Option<&[u8]>,
+    ) -> std::result::Result<(), RefusedReason> {
+        if protocol_version != self.config.protocol_version {
+            return Err(RefusedReason::ProtocolMismatch {
+                expected: self.config.protocol_version,
+                received: protocol_version,
+            });
+        }
+
+        match self.config.token_validator {
+            Some(ref validator) if !validator(addr, token) => Err(RefusedReason::Unauthorized),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<E, S> NetSocketSystem<E, S>
+where
+    E: Serialize + Clone + PartialEq + Send + Sync + 'static,
+    S: NetSerializer,
+{
+    /// Sends the queued events of a connection and keeps its session alive.
+    fn update_session(&mut self, connection: &mut NetConnection<E>, now: Instant) {
+        let addr = connection.target_addr;
+        let session = self
+            .sessions
+            .entry(addr)
+            .or_insert_with(|| Session::new(now));
+
+        match connection.state {
+            ConnectionState::Connecting => {
+                if now.duration_since(session.started) > self.config.handshake_timeout {
+                    close(connection, DisconnectReason::Timeout);
+                    return;
+                }
+
+                let interval = self.config.heartbeat_interval;
+                let mut events = Vec::new();
+                if session
+                    .last_handshake
+                    .map_or(true, |last| now.duration_since(last) >= interval)
+                {
+                    if session.last_handshake.is_some() {
+                        session.stats.resent += 1;
+                    }
+                    session.last_handshake = Some(now);
+                    events.push(NetEvent::Connect {
+                        protocol_version: self.config.protocol_version,
+                        token: self.config.auth_token.clone(),
+                    });
+                }
+                events.extend(connection.send_buffer_early_read().cloned());
+                session.stats.queued = events.len();
+
+                self.send(addr, events, now);
+            }
+            ConnectionState::Connected => {
+                if now.duration_since(session.last_received) > self.config.connection_timeout {
+                    close(connection, DisconnectReason::Timeout);
+                    return;
+                }
+
+                let heartbeat =
+                    now.duration_since(session.last_sent) >= self.config.heartbeat_interval;
+
+                let mut events = connection
+                    .send_buffer_early_read()
+                    .cloned()
+                    .collect::<Vec<_>>();
+                session.stats.queued = events.len();
+
+                let ping_interval = self.config.ping_interval;
+                if session
+                    .last_ping
+                    .map_or(true, |last| now.duration_since(last) >= ping_interval)
+                {
+                    // a ping which was not answered before the next one is sent counts as lost.
+                    if session.pending_ping.is_some() {
+                        session.stats.record_ping(true);
                     }
+                    session.last_ping = Some(now);
+                    session.pending_ping = Some((session.next_ping, now));
+                    events.push(NetEvent::Ping(session.next_ping));
+                    session.next_ping = session.next_ping.wrapping_add(1);
+                } else if events.is_empty() && heartbeat {
+                    events.push(NetEvent::Heartbeat);
                 }
+
+                self.send(addr, events, now);
             }
-        });
+            ConnectionState::Disconnecting => {
+                let events = connection
+                    .send_buffer_early_read()
+                    .cloned()
+                    .collect::<Vec<_>>();
+                let reason = events
+                    .iter()
+                    .filter_map(|event| match event {
+                        NetEvent::Disconnect { reason } => Some(reason.clone()),
+                        _ => None,
+                    })
+                    .last()
+                    .unwrap_or(DisconnectReason::Closed);
 
-        event_sender
+                self.send(addr, events, now);
+                close(connection, reason);
+            }
+            ConnectionState::Disconnected => {}
+        }
+    }
+
+    /// Handles an event received from the remote end of a connection.
+    fn receive(&mut self, connection: &mut NetConnection<E>, event: NetEvent<E>, now: Instant) {
+        let addr = connection.target_addr;
+        self.sessions
+            .entry(addr)
+            .or_insert_with(|| Session::new(now))
+            .last_received = now;
+
+        match event {
+            NetEvent::Connect {
+                protocol_version,
+                token,
+            } => match self.check_handshake(
+                addr,
+                protocol_version,
+                token.as_ref().map(Vec::as_slice),
+            ) {
+                Ok(()) => {
+                    self.send(addr, vec![NetEvent::Connected(addr)], now);
+                    let early = self.take_early(addr);
+                    establish(connection, early);
+                }
+                Err(reason) => {
+                    warn!("Refused the connection of {}: {:?}", addr, reason);
+                    self.send(
+                        addr,
+                        vec![NetEvent::ConnectionRefused {
+                            reason: reason.clone(),
+                        }],
+                        now,
+                    );
+                    refuse(connection, reason);
+                }
+            },
+            NetEvent::Connected(_) => {
+                let early = self.take_early(addr);
+                establish(connection, early);
+            }
+            NetEvent::ConnectionRefused { reason } => refuse(connection, reason),
+            NetEvent::Disconnect { reason } => close(connection, reason),
+            NetEvent::Heartbeat => {}
+            NetEvent::Ping(sequence) => self.send(addr, vec![NetEvent::Pong(sequence)], now),
+            NetEvent::Pong(sequence) => {
+                let session = self
+                    .sessions
+                    .get_mut(&addr)
+                    .expect("Session was just created");
+                if let Some((pending, sent)) = session.pending_ping {
+                    if pending == sequence {
+                        session.pending_ping = None;
+                        session.stats.record_rtt(now.duration_since(sent));
+                        session.stats.record_ping(false);
+                    }
+                }
+            }
+            event => {
+                if connection.state == ConnectionState::Connecting {
+                    // the remote end is not authenticated until the handshake completed.
+                    let session = self
+                        .sessions
+                        .get_mut(&addr)
+                        .expect("Session was just created");
+                    if session.early.len() < MAX_EARLY_EVENTS {
+                        session.early.push(event);
+                    }
+                } else {
+                    connection.receive_buffer.single_write(event);
+                }
+            }
+        }
+    }
+
+    /// Takes the events which were received from the given address before its handshake completed.
+    fn take_early(&mut self, addr: SocketAddr) -> Vec<NetEvent<E>> {
+        self.sessions
+            .get_mut(&addr)
+            .map(|session| std::mem::replace(&mut session.early, Vec::new()))
+            .unwrap_or_default()
     }
 }
 
-impl<'a, E> System<'a> for NetSocketSystem<E>
+/// Marks the connection as established once its handshake was answered,
+/// and delivers the events received before.
+fn establish<E: Send + Sync + 'static>(connection: &mut NetConnection<E>, early: Vec<NetEvent<E>>) {
+    if connection.state == ConnectionState::Connecting {
+        connection.state = ConnectionState::Connected;
+        connection
+            .receive_buffer
+            .single_write(NetEvent::Connected(connection.target_addr));
+        connection.receive_buffer.iter_write(early);
+    }
+}
+
+/// Opens a refused or disconnected connection again when the remote end sends a new handshake.
+fn reopen<E: Send + Sync + 'static>(connection: &mut NetConnection<E>) {
+    connection.state = ConnectionState::Connecting;
+}
+
+/// Drops the connection after its handshake was refused.
+fn refuse<E: Send + Sync + 'static>(connection: &mut NetConnection<E>, reason: RefusedReason) {
+    connection.state = ConnectionState::Disconnected;
+    connection
+        .receive_buffer
+        .single_write(NetEvent::ConnectionRefused { reason });
+}
+
+/// Drops the connection for the given reason.
+fn close<E: Send + Sync + 'static>(connection: &mut NetConnection<E>, reason: DisconnectReason) {
+    connection.state = ConnectionState::Disconnected;
+    // we can't remove the entity from the world here because it could still have events in it's buffer.
+    connection
+        .receive_buffer
+        .single_write(NetEvent::Disconnected {
+            addr: connection.target_addr,
+            reason,
+        });
+}
+
+impl<'a, E, S> System<'a> for NetSocketSystem<E, S>
 where
     E: Send + Sync + Serialize + Clone + DeserializeOwned + PartialEq + 'static,
+    S: NetSerializer,
 {
-    type SystemData = (WriteStorage<'a, NetConnection<E>>, Entities<'a>);
+    type SystemData = (
+        WriteStorage<'a, NetConnection<E>>,
+        Entities<'a>,
+        Read<'a, ConnectionReaders<E>>,
+        Write<'a, EventChannel<UndecodablePayload>>,
+        Write<'a, NetworkStats>,
+    );
 
-    fn run(&mut self, (mut net_connections, entities): Self::SystemData) {
+    fn run(
+        &mut self,
+        (mut net_connections, entities, readers, mut undecodable, mut stats): Self::SystemData,
+    ) {
         #[cfg(feature = "profiler")]
         profile_scope!("net_socket_system");
 
+        let now = Instant::now();
+
         for connection in (&mut net_connections).join() {
-            match connection.state {
-                ConnectionState::Connected | ConnectionState::Connecting => {
-                    self.event_sender
-                        .send(InternalSocketEvent::SendEvents {
-                            target: connection.target_addr,
-                            events: connection.send_buffer_early_read().cloned().collect(),
-                        })
-                        .expect("Unreachable: Channel will be alive until a stop event is sent");
-                }
-                ConnectionState::Disconnected => {
-                    self.event_sender
-                        .send(InternalSocketEvent::Stop)
-                        .expect("Already sent a stop event to the channel");
-                }
-            }
+            connection.register_consumers(&readers);
+            self.update_session(connection, now);
         }
 
-        for (counter, socket_event) in self.event_receiver.try_iter().enumerate() {
+        let mut counter = 0;
+        while let Some(socket_event) = self.transport.receive() {
             match socket_event {
                 SocketEvent::Packet(packet) => {
                     let from_addr = packet.addr();
+                    let size = packet.payload().len();
+                    let decoded = NetEvent::<E>::from_packet(packet, &self.serializer);
 
-                    match NetEvent::<E>::from_packet(packet) {
+                    if let Some(session) = self.sessions.get_mut(&from_addr) {
+                        session.stats.record_received(size);
+                        if decoded.is_err() {
+                            session.stats.undecodable += 1;
+                        }
+                    }
+
+                    match decoded {
                         Ok(event) => {
                             for connection in (&mut net_connections).join() {
-                                if connection.target_addr == from_addr {
-                                    connection.receive_buffer.single_write(event.clone());
+                                if connection.target_addr != from_addr {
+                                    continue;
                                 }
+
+                                if connection.state == ConnectionState::Disconnected {
+                                    match event {
+                                        NetEvent::Connect { .. } => {
+                                            self.sessions.remove(&from_addr);
+                                            reopen(connection);
+                                        }
+                                        _ => continue,
+                                    }
+                                }
+
+                                self.receive(connection, event.clone(), now);
                             }
                         }
-                        Err(e) => error!(
-                            "Failed to deserialize an incoming network event: {} From source: {:?}",
-                            e, from_addr
-                        ),
+                        Err(e) => {
+                            self.undecodable += 1;
+                            error!(
+                                "Dropped an undecodable payload of {} bytes from {}: {}",
+                                size, from_addr, e
+                            );
+                            undecodable.single_write(UndecodablePayload {
+                                addr: from_addr,
+                                size,
+                                reason: e.to_string(),
+                            });
+                        }
                     }
                 }
                 SocketEvent::Connect(addr) => {
-                    if self.config.create_net_connection_on_connect {
-                        let mut connection: NetConnection<E> = NetConnection::new(addr);
-                        connection
-                            .receive_buffer
-                            .single_write(NetEvent::Connected(addr));
+                    let exists = (&net_connections)
+                        .join()
+                        .any(|connection| connection.target_addr == addr);
 
+                    if self.config.create_net_connection_on_connect && !exists {
+                        let mut connection = NetConnection::<E>::new(addr);
+                        connection.register_consumers(&readers);
                         entities
                             .build_entity()
                             .with(connection, &mut net_connections)
@@ -179,11 +565,10 @@ where
                 }
                 SocketEvent::Timeout(timeout_addr) => {
                     for connection in (&mut net_connections).join() {
-                        if connection.target_addr == timeout_addr {
-                            // we can't remove the entity from the world here because it could still have events in it's buffer.
-                            connection
-                                .receive_buffer
-                                .single_write(NetEvent::Disconnected(timeout_addr));
+                        if connection.target_addr == timeout_addr
+                            && connection.state != ConnectionState::Disconnected
+                        {
+                            close(connection, DisconnectReason::Timeout);
                         }
                     }
                 }
@@ -195,6 +580,27 @@ where
             if counter >= self.config.max_throughput as usize {
                 break;
             }
+            counter += 1;
+        }
+
+        let active = (&net_connections)
+            .join()
+            .filter(|connection| connection.state != ConnectionState::Disconnected)
+            .map(|connection| connection.target_addr)
+            .collect::<HashSet<_>>();
+        self.sessions.retain(|addr, _| active.contains(addr));
+
+        let interval = self.config.stats_interval;
+        stats.connections.clear();
+        for (addr, session) in &mut self.sessions {
+            session.stats.update_rates(now, interval);
+            stats.connections.insert(*addr, session.stats.clone());
         }
+        stats.undecodable = self.undecodable;
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        world.insert(self.serializer.clone());
     }
 }
@@ -0,0 +1,180 @@
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+
+use amethyst_core::{
+    bundle::SystemBundle,
+    ecs::{Component, World},
+    shred::DispatcherBuilder,
+};
+use amethyst_error::Error;
+use amethyst_input::BindingTypes;
+
+use super::{
+    reconcile::RewindComponent, PredictedComponents, PredictionClientSystem, PredictionConfig,
+    ReconcileSystem,
+};
+use crate::{
+    replication::{ComponentId, ReplicationRegistry},
+    serializer::{BincodeSerializer, NetSerializer},
+};
+
+/// Adds the system reconciling a single predicted component type to the dispatcher.
+type AddReconcileSystem =
+    for<'a, 'b, 'c> fn(&mut DispatcherBuilder<'a, 'b>, ComponentId, &'static str, &[&'c str]);
+
+/// A bundle adding the per frame systems of the client side prediction.
+///
+/// The `PredictionClientSystem` will be registered with name "prediction_client", after the "replication_client",
+/// and a `ReconcileSystem` with name "prediction_reconcile_{name}" for every predicted component.
+///
+/// The systems running on the fixed update, `InputSamplingSystem` and `RecordPredictionSystem`,
+/// have to be added to the dispatcher running the simulation instead.
+///
+/// The authoritative components are decoded with the `NetSerializer` `S`, which has to be
+/// the serializer of the `NetSocketSystem`.
+///
+/// ## Errors
+///
+/// Returns an error if a predicted component was not registered on the `ReplicationBundle`,
+/// which has to be added before this bundle.
+#[allow(missing_debug_implementations)]
+pub struct PredictionBundle<'a, T, S = BincodeSerializer> {
+    config: PredictionConfig,
+    components: Vec<(&'static str, AddReconcileSystem, RewindComponent)>,
+    dep: &'a [&'a str],
+    _data: PhantomData<(T, S)>,
+}
+
+impl<'a, T> PredictionBundle<'a, T> {
+    /// Creates a new `PredictionBundle` with the given configuration.
+    pub fn new(config: PredictionConfig) -> Self {
+        PredictionBundle {
+            config,
+            components: Vec::new(),
+            dep: &[],
+            _data: PhantomData,
+        }
+    }
+}
+
+impl<'a, T, S> PredictionBundle<'a, T, S>
+where
+    S: NetSerializer,
+{
+    /// Decodes the authoritative components with the serializer `N` instead of `bincode`.
+    ///
+    /// `N` has to be the serializer passed to `NetworkBundle::with_serializer`.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if a component was already added, since its systems use the previous serializer.
+    pub fn with_serializer<N>(self) -> PredictionBundle<'a, T, N>
+    where
+        N: NetSerializer,
+    {
+        assert!(
+            self.components.is_empty(),
+            "The serializer has to be set before components are added"
+        );
+
+        PredictionBundle {
+            config: self.config,
+            components: Vec::new(),
+            dep: self.dep,
+            _data: PhantomData,
+        }
+    }
+
+    /// Sets the configuration of the prediction systems.
+    pub fn with_config(mut self, config: PredictionConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Predicts the component `C`, which is replicated under the given name.
+    pub fn with_component<C>(mut self, name: &'static str) -> Self
+    where
+        C: Component + Clone + PartialEq + DeserializeOwned + Send + Sync,
+    {
+        self.components.push((
+            name,
+            add_reconcile_system::<C, S> as AddReconcileSystem,
+            PredictedComponents::rewind::<C, S>(),
+        ));
+        self
+    }
+
+    /// Set dependencies for the prediction systems, usually the name of the `NetSocketSystem`.
+    pub fn with_dep(mut self, dep: &'a [&'a str]) -> Self {
+        self.dep = dep;
+        self
+    }
+}
+
+impl<'a, T> Default for PredictionBundle<'a, T> {
+    fn default() -> Self {
+        Self::new(PredictionConfig::default())
+    }
+}
+
+impl<'a, 'b, 'c, T, S> SystemBundle<'a, 'b> for PredictionBundle<'c, T, S>
+where
+    T: BindingTypes,
+{
+    fn build(
+        self,
+        world: &mut World,
+        builder: &mut DispatcherBuilder<'a, 'b>,
+    ) -> Result<(), Error> {
+        let client_dep = self
+            .dep
+            .iter()
+            .cloned()
+            .chain(Some("replication_client"))
+            .collect::<Vec<_>>();
+        builder.add(
+            PredictionClientSystem::<T>::new(),
+            "prediction_client",
+            &client_dep,
+        );
+
+        let mut predicted = PredictedComponents::default();
+        let dep = ["prediction_client", "replication_client"];
+
+        for (name, add_system, rewind) in self.components {
+            let id = world
+                .try_fetch::<ReplicationRegistry>()
+                .and_then(|registry| registry.id(name))
+                .ok_or_else(|| {
+                    Error::from_string(format!(
+                        "Predicted component `{}` is not replicated, add it to the `ReplicationBundle` first",
+                        name
+                    ))
+                })?;
+
+            add_system(builder, id, name, &dep);
+            predicted.register(id, rewind);
+        }
+
+        world.insert(predicted);
+
+        Ok(())
+    }
+}
+
+fn add_reconcile_system<C, S>(
+    builder: &mut DispatcherBuilder<'_, '_>,
+    id: ComponentId,
+    name: &'static str,
+    dep: &[&str],
+) where
+    C: Component + PartialEq + DeserializeOwned + Send + Sync,
+    S: NetSerializer,
+{
+    builder.add(
+        ReconcileSystem::<C, S>::new(id),
+        &format!("prediction_reconcile_{}", name),
+        dep,
+    );
+}
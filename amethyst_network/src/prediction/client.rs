@@ -0,0 +1,177 @@
+//! The client side of input prediction.
+
+use std::marker::PhantomData;
+
+use log::error;
+use serde::Serialize;
+
+use amethyst_core::ecs::{Join, Read, ReadExpect, System, Write, WriteStorage};
+use amethyst_input::{BindingTypes, InputHandler};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use super::{
+    ActionState, InputFrame, InputHistory, PredictedInput, PredictionConfig, PredictionMessage,
+    Reconciliation, SimulationTick,
+};
+use crate::{
+    replication::AuthoritativeState,
+    serializer::{BincodeSerializer, NetSerializer},
+    ConnectionState, NetConnection, NetEvent, NetPacket,
+};
+
+/// Samples the local input at the start of every simulation tick and sends it to the server.
+///
+/// This system has a few responsibilities:
+///
+/// - Advancing the `SimulationTick`.
+/// - Sampling the `InputHandler` into `PredictedInput` and recording it in the `InputHistory`.
+/// - Sending the most recent unacknowledged inputs to every `NetConnection`.
+///
+/// Add this system at the start of the dispatcher running the simulation on every fixed update.
+/// It does nothing while a replay is running, since replayed ticks use the recorded input.
+///
+/// - `E` corresponds to the network event type.
+/// - `T` corresponds to the binding types of the `InputHandler`.
+/// - `S` corresponds to the serializer of the `NetSocketSystem`, with which the inputs are encoded.
+#[allow(missing_debug_implementations)]
+pub struct InputSamplingSystem<E, T, S = BincodeSerializer> {
+    config: PredictionConfig,
+    _marker: PhantomData<(E, T, S)>,
+}
+
+impl<E, T, S> InputSamplingSystem<E, T, S> {
+    /// Creates a new `InputSamplingSystem` with the given configuration.
+    pub fn new(config: PredictionConfig) -> Self {
+        InputSamplingSystem {
+            config,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, E, T, S> System<'a> for InputSamplingSystem<E, T, S>
+where
+    E: Send + Sync + 'static,
+    T: BindingTypes,
+    T::Action: Serialize,
+    T::Axis: Serialize,
+    S: NetSerializer,
+{
+    type SystemData = (
+        Read<'a, InputHandler<T>>,
+        ReadExpect<'a, S>,
+        Read<'a, Reconciliation>,
+        Write<'a, SimulationTick>,
+        Write<'a, InputHistory<T>>,
+        Write<'a, PredictedInput<T>>,
+        WriteStorage<'a, NetConnection<E>>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            handler,
+            serializer,
+            reconciliation,
+            mut simulation_tick,
+            mut history,
+            mut predicted_input,
+            mut connections,
+        ): Self::SystemData,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("input_sampling_system");
+
+        if reconciliation.is_replaying() {
+            return;
+        }
+
+        let tick = simulation_tick.advance();
+        let state = ActionState::from_handler(&handler);
+
+        history.push(tick, state.clone(), self.config.input_history);
+        predicted_input.set(tick, state);
+
+        let skip = history.len().saturating_sub(self.config.input_redundancy);
+        let frames = history
+            .iter()
+            .skip(skip)
+            .filter_map(|(tick, state)| match serializer.serialize(state) {
+                Ok(payload) => Some(InputFrame {
+                    tick: *tick,
+                    payload,
+                }),
+                Err(e) => {
+                    error!("Failed to serialize the input of tick {}: {}", tick, e);
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        for connection in (&mut connections).join() {
+            if connection.state == ConnectionState::Disconnected {
+                continue;
+            }
+
+            connection.queue(NetEvent::Prediction(NetPacket::unreliable_sequenced(
+                PredictionMessage::Inputs(frames.clone()),
+                self.config.input_stream,
+            )));
+        }
+    }
+}
+
+/// Hands the client tick reflected by the latest authoritative state to the reconciliation.
+///
+/// Every snapshot received from the server is stamped with the latest tick whose input the server
+/// simulated before producing it. When a new snapshot arrived, that tick is handed to the
+/// `ReconcileSystem`s through the `Reconciliation`, and the inputs up to it are dropped from the `InputHistory`.
+/// Add this system once per frame, after the `ReplicationClientSystem`.
+///
+/// - `T` corresponds to the binding types of the `InputHandler`.
+#[allow(missing_debug_implementations)]
+pub struct PredictionClientSystem<T> {
+    // the sequence number of the snapshot which was reconciled last.
+    sequence: Option<u32>,
+    _marker: PhantomData<T>,
+}
+
+impl<T> PredictionClientSystem<T> {
+    /// Creates a new `PredictionClientSystem`.
+    pub fn new() -> Self {
+        PredictionClientSystem {
+            sequence: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> System<'a> for PredictionClientSystem<T>
+where
+    T: BindingTypes,
+{
+    type SystemData = (
+        Read<'a, AuthoritativeState>,
+        Write<'a, Reconciliation>,
+        Write<'a, InputHistory<T>>,
+    );
+
+    fn run(&mut self, (authoritative, mut reconciliation, mut history): Self::SystemData) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("prediction_client_system");
+
+        reconciliation.clear_pending();
+
+        if authoritative.sequence() != self.sequence {
+            self.sequence = authoritative.sequence();
+            if let Some(tick) = authoritative.client_tick() {
+                reconciliation.acknowledge(tick);
+            }
+        }
+
+        if let Some(tick) = reconciliation.pending_tick() {
+            history.acknowledge(tick);
+        }
+    }
+}
@@ -0,0 +1,32 @@
+/// The configuration of the prediction systems.
+#[derive(Clone, Debug)]
+pub struct PredictionConfig {
+    /// The maximal number of unacknowledged inputs kept by the client,
+    /// which also limits how many ticks can be replayed on a misprediction.
+    /// This value is by default 128.
+    pub input_history: usize,
+    /// The number of most recent inputs sent in every input message.
+    /// This value is by default 8.
+    pub input_redundancy: usize,
+    /// The number of ticks of predicted state kept for reconciliation.
+    /// This value is by default 128.
+    pub prediction_history: usize,
+    /// The maximal number of received inputs the server buffers per client.
+    /// This value is by default 32.
+    pub server_input_buffer: usize,
+    /// The stream on which input and acknowledgement messages are sequenced.
+    /// This value is by default `Some(2)`.
+    pub input_stream: Option<u8>,
+}
+
+impl Default for PredictionConfig {
+    fn default() -> Self {
+        PredictionConfig {
+            input_history: 128,
+            input_redundancy: 8,
+            prediction_history: 128,
+            server_input_buffer: 32,
+            input_stream: Some(2),
+        }
+    }
+}
@@ -0,0 +1,277 @@
+//! Sampled input states and the buffers holding them per simulation tick.
+
+use std::{
+    borrow::Borrow,
+    collections::{HashMap, VecDeque},
+    fmt::{Debug, Formatter},
+    hash::Hash,
+    net::SocketAddr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use amethyst_input::{BindingTypes, InputHandler};
+
+/// The state of the actions and axes of an `InputHandler` at a single simulation tick.
+///
+/// Simulation systems which should be predicted and replayed read their input from this type,
+/// through `PredictedInput` on the client and `ClientInputs` on the server, instead of reading the `InputHandler`.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "T::Action: Serialize, T::Axis: Serialize",
+    deserialize = "T::Action: Deserialize<'de>, T::Axis: Deserialize<'de>"
+))]
+pub struct ActionState<T: BindingTypes> {
+    actions: Vec<T::Action>,
+    axes: Vec<(T::Axis, f32)>,
+}
+
+impl<T: BindingTypes> ActionState<T> {
+    /// Samples the actions that are down and the values of all axes of the given `InputHandler`.
+    pub fn from_handler(handler: &InputHandler<T>) -> Self {
+        let actions = handler
+            .bindings
+            .actions()
+            .filter(|action| handler.action_is_down(*action).unwrap_or(false))
+            .cloned()
+            .collect();
+
+        let axes = handler
+            .bindings
+            .axes()
+            .filter_map(|axis| handler.axis_value(axis).map(|value| (axis.clone(), value)))
+            .collect();
+
+        ActionState { actions, axes }
+    }
+
+    /// Returns `true` if the given action was down.
+    pub fn action_is_down<A>(&self, action: &A) -> bool
+    where
+        T::Action: Borrow<A>,
+        A: Hash + Eq + ?Sized,
+    {
+        self.actions
+            .iter()
+            .any(|down| <T::Action as Borrow<A>>::borrow(down) == action)
+    }
+
+    /// Returns the value the given axis had, `0.0` if the axis is unknown.
+    pub fn axis_value<A>(&self, axis: &A) -> f32
+    where
+        T::Axis: Borrow<A>,
+        A: Hash + Eq + ?Sized,
+    {
+        self.axes
+            .iter()
+            .find(|(id, _)| <T::Axis as Borrow<A>>::borrow(id) == axis)
+            .map_or(0.0, |(_, value)| *value)
+    }
+
+    /// Returns an iterator over the actions that were down.
+    pub fn actions(&self) -> impl Iterator<Item = &T::Action> {
+        self.actions.iter()
+    }
+}
+
+impl<T: BindingTypes> Clone for ActionState<T> {
+    fn clone(&self) -> Self {
+        ActionState {
+            actions: self.actions.clone(),
+            axes: self.axes.clone(),
+        }
+    }
+}
+
+impl<T: BindingTypes> Debug for ActionState<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ActionState")
+            .field("actions", &self.actions)
+            .field("axes", &self.axes)
+            .finish()
+    }
+}
+
+impl<T: BindingTypes> Default for ActionState<T> {
+    fn default() -> Self {
+        ActionState {
+            actions: Vec::new(),
+            axes: Vec::new(),
+        }
+    }
+}
+
+impl<T: BindingTypes> PartialEq for ActionState<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.actions == other.actions && self.axes == other.axes
+    }
+}
+
+/// Resource holding the local input of the tick that is currently simulated on the client.
+///
+/// During a replay it holds the recorded input of the replayed tick.
+#[derive(Debug)]
+pub struct PredictedInput<T: BindingTypes> {
+    tick: u32,
+    state: ActionState<T>,
+}
+
+impl<T: BindingTypes> PredictedInput<T> {
+    /// Returns the tick this input belongs to.
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    /// Returns the input state.
+    pub fn state(&self) -> &ActionState<T> {
+        &self.state
+    }
+
+    pub(crate) fn set(&mut self, tick: u32, state: ActionState<T>) {
+        self.tick = tick;
+        self.state = state;
+    }
+}
+
+impl<T: BindingTypes> Default for PredictedInput<T> {
+    fn default() -> Self {
+        PredictedInput {
+            tick: 0,
+            state: ActionState::default(),
+        }
+    }
+}
+
+/// Resource holding the local inputs the server has not acknowledged yet, by tick.
+///
+/// These are the inputs that are sent to the server and replayed on a misprediction.
+#[derive(Debug)]
+pub struct InputHistory<T: BindingTypes> {
+    inputs: VecDeque<(u32, ActionState<T>)>,
+}
+
+impl<T: BindingTypes> InputHistory<T> {
+    /// Returns the recorded input of the given tick.
+    pub fn get(&self, tick: u32) -> Option<&ActionState<T>> {
+        self.inputs
+            .iter()
+            .find(|(recorded, _)| *recorded == tick)
+            .map(|(_, state)| state)
+    }
+
+    /// Returns an iterator over the unacknowledged inputs, oldest first.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &(u32, ActionState<T>)> {
+        self.inputs.iter()
+    }
+
+    /// Returns the number of unacknowledged inputs.
+    pub fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    /// Returns `true` if all inputs were acknowledged.
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    pub(crate) fn push(&mut self, tick: u32, state: ActionState<T>, capacity: usize) {
+        self.inputs.push_back((tick, state));
+        while self.inputs.len() > capacity {
+            self.inputs.pop_front();
+        }
+    }
+
+    /// Drops the inputs up to and including the given tick.
+    pub(crate) fn acknowledge(&mut self, tick: u32) {
+        while self
+            .inputs
+            .front()
+            .map_or(false, |(recorded, _)| *recorded <= tick)
+        {
+            self.inputs.pop_front();
+        }
+    }
+}
+
+impl<T: BindingTypes> Default for InputHistory<T> {
+    fn default() -> Self {
+        InputHistory {
+            inputs: VecDeque::new(),
+        }
+    }
+}
+
+/// Resource holding the input each connected client sent for the tick that is currently simulated on the server.
+///
+/// When the input of a client did not arrive in time, its previous input is repeated.
+#[derive(Debug)]
+pub struct ClientInputs<T: BindingTypes> {
+    inputs: HashMap<SocketAddr, (u32, ActionState<T>)>,
+}
+
+impl<T: BindingTypes> ClientInputs<T> {
+    /// Returns the input of the client with the given address.
+    pub fn input(&self, addr: SocketAddr) -> Option<&ActionState<T>> {
+        self.inputs.get(&addr).map(|(_, state)| state)
+    }
+
+    /// Returns the client tick of the input of the client with the given address.
+    pub fn tick(&self, addr: SocketAddr) -> Option<u32> {
+        self.inputs.get(&addr).map(|(tick, _)| *tick)
+    }
+
+    /// Returns an iterator over the clients and their input.
+    pub fn iter(&self) -> impl Iterator<Item = (SocketAddr, &ActionState<T>)> {
+        self.inputs.iter().map(|(addr, (_, state))| (*addr, state))
+    }
+
+    pub(crate) fn set(&mut self, addr: SocketAddr, tick: u32, state: ActionState<T>) {
+        self.inputs.insert(addr, (tick, state));
+    }
+
+    pub(crate) fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(SocketAddr) -> bool,
+    {
+        self.inputs.retain(|addr, _| f(*addr));
+    }
+}
+
+impl<T: BindingTypes> Default for ClientInputs<T> {
+    fn default() -> Self {
+        ClientInputs {
+            inputs: HashMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ActionState, InputHistory};
+    use amethyst_input::StringBindings;
+
+    #[test]
+    fn acknowledge_drops_older_inputs() {
+        let mut history = InputHistory::<StringBindings>::default();
+        for tick in 1..=5 {
+            history.push(tick, ActionState::default(), 16);
+        }
+
+        history.acknowledge(3);
+
+        assert_eq!(history.len(), 2);
+        assert!(history.get(3).is_none());
+        assert!(history.get(4).is_some());
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let mut history = InputHistory::<StringBindings>::default();
+        for tick in 1..=5 {
+            history.push(tick, ActionState::default(), 3);
+        }
+
+        assert_eq!(history.len(), 3);
+        assert!(history.get(2).is_none());
+    }
+}
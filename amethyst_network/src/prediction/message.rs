@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+
+/// The messages exchanged by the prediction systems.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PredictionMessage {
+    /// The latest unacknowledged inputs of a client, oldest first.
+    ///
+    /// Every message repeats the most recent inputs, so a lost packet does not lose input.
+    Inputs(Vec<InputFrame>),
+}
+
+/// The serialized `ActionState` of a single client tick.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputFrame {
+    /// The client tick the input was sampled at.
+    pub tick: u32,
+    /// The serialized `ActionState`.
+    pub payload: Vec<u8>,
+}
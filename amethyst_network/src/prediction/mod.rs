@@ -0,0 +1,42 @@
+//! Client side prediction of locally controlled entities, reconciled with the server.
+//!
+//! The client simulates its own entities ahead of the server on a fixed timestep.
+//! Every simulation tick is numbered by the `SimulationTick` and uses the input sampled for it,
+//! which is kept in the `InputHistory` until the server acknowledges it.
+//!
+//! On the client, the dispatcher running the simulation in `State::fixed_update` should:
+//!
+//! - Start with the `InputSamplingSystem`, and have the gameplay systems read `PredictedInput`
+//!   instead of the `InputHandler`.
+//! - End with a `RecordPredictionSystem<C>` for every predicted component.
+//!
+//! Call `replay` before running that dispatcher, so mispredicted ticks are simulated again
+//! starting from the authoritative state. The `PredictionBundle` adds the per frame systems
+//! which compare every received snapshot with the prediction of the tick it is stamped with.
+//!
+//! On the server, the `PredictionServerSystem` hands the input of every client to the simulation
+//! through `ClientInputs`, one tick at a time, and records the processed ticks in `ProcessedTicks`,
+//! which the replication stamps on the snapshots.
+
+pub use self::{
+    bundle::PredictionBundle,
+    client::{InputSamplingSystem, PredictionClientSystem},
+    config::PredictionConfig,
+    input::{ActionState, ClientInputs, InputHistory, PredictedInput},
+    message::{InputFrame, PredictionMessage},
+    reconcile::{
+        replay, Predicted, PredictedComponents, PredictionHistory, ReconcileSystem,
+        RecordPredictionSystem,
+    },
+    server::PredictionServerSystem,
+    tick::{ProcessedTicks, Reconciliation, SimulationTick},
+};
+
+mod bundle;
+mod client;
+mod config;
+mod input;
+mod message;
+mod reconcile;
+mod server;
+mod tick;
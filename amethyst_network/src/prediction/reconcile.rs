@@ -0,0 +1,467 @@
+//! The history of predicted state and the reconciliation against the authoritative state.
+
+use std::{
+    collections::VecDeque,
+    fmt::{Debug, Formatter},
+    marker::PhantomData,
+};
+
+use log::error;
+use serde::de::DeserializeOwned;
+
+use amethyst_core::{
+    ecs::{
+        Component, Entities, Entity, Join, NullStorage, Read, ReadExpect, ReadStorage, System,
+        SystemData, World, Write, WriteStorage,
+    },
+    shred::Dispatcher,
+};
+use amethyst_input::BindingTypes;
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use super::{InputHistory, PredictedInput, Reconciliation, SimulationTick};
+use crate::{
+    replication::{AuthoritativeState, ComponentId, NetworkId},
+    serializer::{BincodeSerializer, NetSerializer},
+};
+
+/// Marker component for entities that are simulated locally ahead of the server.
+///
+/// The replicated components of predicted entities are not overwritten by every snapshot.
+/// Instead they are compared against the predicted state of the tick the server processed last,
+/// and only reset to the authoritative state when the prediction was wrong.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Predicted;
+
+impl Component for Predicted {
+    type Storage = NullStorage<Self>;
+}
+
+/// Resource holding the predicted value of the `C` component of every `Predicted` entity, by tick.
+#[derive(Debug)]
+pub struct PredictionHistory<C> {
+    ticks: VecDeque<(u32, Vec<(Entity, C)>)>,
+}
+
+impl<C> PredictionHistory<C> {
+    /// Returns the predicted value of the component of the given entity at the given tick.
+    pub fn get(&self, tick: u32, entity: Entity) -> Option<&C> {
+        self.ticks
+            .iter()
+            .find(|(recorded, _)| *recorded == tick)
+            .and_then(|(_, values)| {
+                values
+                    .iter()
+                    .find(|(recorded, _)| *recorded == entity)
+                    .map(|(_, value)| value)
+            })
+    }
+
+    pub(crate) fn record(&mut self, tick: u32, values: Vec<(Entity, C)>, capacity: usize) {
+        // A replayed tick overwrites its prediction, and every later tick is replayed after it.
+        while self
+            .ticks
+            .back()
+            .map_or(false, |(recorded, _)| *recorded >= tick)
+        {
+            self.ticks.pop_back();
+        }
+
+        self.ticks.push_back((tick, values));
+        while self.ticks.len() > capacity {
+            self.ticks.pop_front();
+        }
+    }
+
+    pub(crate) fn forget_before(&mut self, tick: u32) {
+        while self
+            .ticks
+            .front()
+            .map_or(false, |(recorded, _)| *recorded < tick)
+        {
+            self.ticks.pop_front();
+        }
+    }
+}
+
+impl<C> Default for PredictionHistory<C> {
+    fn default() -> Self {
+        PredictionHistory {
+            ticks: VecDeque::new(),
+        }
+    }
+}
+
+/// Records the `C` component of every `Predicted` entity into the `PredictionHistory<C>`.
+///
+/// Add this system at the end of the dispatcher running the simulation on every fixed update.
+#[derive(Debug)]
+pub struct RecordPredictionSystem<C> {
+    capacity: usize,
+    _marker: PhantomData<C>,
+}
+
+impl<C> RecordPredictionSystem<C> {
+    /// Creates a new `RecordPredictionSystem` keeping the prediction of the given number of ticks.
+    pub fn new(capacity: usize) -> Self {
+        RecordPredictionSystem {
+            capacity,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, C> System<'a> for RecordPredictionSystem<C>
+where
+    C: Component + Clone + Send + Sync,
+{
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Predicted>,
+        ReadStorage<'a, C>,
+        Read<'a, SimulationTick>,
+        Write<'a, PredictionHistory<C>>,
+    );
+
+    fn run(&mut self, (entities, predicted, components, tick, mut history): Self::SystemData) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("record_prediction_system");
+
+        let values = (&entities, &predicted, &components)
+            .join()
+            .map(|(entity, _, component)| (entity, component.clone()))
+            .collect();
+
+        history.record(tick.tick(), values, self.capacity);
+    }
+}
+
+/// Rewinds a predicted component to its state at the given tick.
+pub(crate) type RewindComponent = fn(&World, u32, ComponentId);
+
+/// Resource listing the predicted component types and how to rewind them before a replay.
+#[derive(Default)]
+pub struct PredictedComponents {
+    rewinds: Vec<(ComponentId, RewindComponent)>,
+}
+
+impl Debug for PredictedComponents {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PredictedComponents")
+            .field(
+                "components",
+                &self.rewinds.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl PredictedComponents {
+    pub(crate) fn rewind<C, S>() -> RewindComponent
+    where
+        C: Component + Clone + DeserializeOwned + Send + Sync,
+        S: NetSerializer,
+    {
+        rewind_component::<C, S> as RewindComponent
+    }
+
+    pub(crate) fn register(&mut self, id: ComponentId, rewind: RewindComponent) {
+        self.rewinds.push((id, rewind));
+    }
+}
+
+/// Resets the `C` component of every `Predicted` entity to its authoritative value at the given tick,
+/// or to its predicted value when there is no authoritative one.
+fn rewind_component<C, S>(world: &World, tick: u32, id: ComponentId)
+where
+    C: Component + Clone + DeserializeOwned + Send + Sync,
+    S: NetSerializer,
+{
+    let (entities, predicted, network_ids, mut components, serializer, authoritative, history) =
+        <(
+            Entities<'_>,
+            ReadStorage<'_, Predicted>,
+            ReadStorage<'_, NetworkId>,
+            WriteStorage<'_, C>,
+            ReadExpect<'_, S>,
+            Read<'_, AuthoritativeState>,
+            Read<'_, PredictionHistory<C>>,
+        )>::fetch(world);
+
+    for (entity, _, network_id) in (&entities, &predicted, &network_ids).join() {
+        let value = authoritative
+            .component::<C, S>(&serializer, *network_id, id)
+            .or_else(|| history.get(tick, entity).cloned());
+
+        if let Some(value) = value {
+            if let Err(e) = components.insert(entity, value) {
+                error!("Failed to rewind a predicted component: {:?}", e);
+            }
+        }
+    }
+}
+
+/// Compares the predicted `C` component of every `Predicted` entity with the authoritative state.
+///
+/// When the server acknowledged a new tick, the authoritative value is compared with the value predicted for that tick.
+/// On a mismatch a replay of the following ticks is requested on the `Reconciliation`, which `replay` carries out.
+///
+/// The authoritative values are decoded with the `NetSerializer` `S` of the `NetSocketSystem`.
+#[derive(Debug)]
+pub struct ReconcileSystem<C, S = BincodeSerializer> {
+    id: ComponentId,
+    _marker: PhantomData<(C, S)>,
+}
+
+impl<C, S> ReconcileSystem<C, S> {
+    /// Creates a new `ReconcileSystem` for the component replicated under the given id.
+    pub fn new(id: ComponentId) -> Self {
+        ReconcileSystem {
+            id,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, C, S> System<'a> for ReconcileSystem<C, S>
+where
+    C: Component + PartialEq + DeserializeOwned + Send + Sync,
+    S: NetSerializer,
+{
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Predicted>,
+        ReadStorage<'a, NetworkId>,
+        ReadExpect<'a, S>,
+        Read<'a, AuthoritativeState>,
+        Write<'a, PredictionHistory<C>>,
+        Write<'a, Reconciliation>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            predicted,
+            network_ids,
+            serializer,
+            authoritative,
+            mut history,
+            mut reconciliation,
+        ): Self::SystemData,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("reconcile_system");
+
+        let tick = match reconciliation.pending_tick() {
+            Some(tick) => tick,
+            None => return,
+        };
+
+        for (entity, _, id) in (&entities, &predicted, &network_ids).join() {
+            let actual = match authoritative.component::<C, S>(&serializer, *id, self.id) {
+                Some(actual) => actual,
+                None => continue,
+            };
+
+            if history.get(tick, entity) != Some(&actual) {
+                reconciliation.request_replay(tick + 1);
+            }
+        }
+
+        history.forget_before(tick);
+    }
+}
+
+/// Replays the ticks after a misprediction, if the reconciliation requested it.
+///
+/// All predicted components are first rewound to their state at the last acknowledged tick.
+/// Then every tick up to the current one is simulated again by running the given dispatcher,
+/// with the `PredictedInput` set to the input recorded for that tick.
+///
+/// Call this before simulating the next tick, usually at the start of `State::fixed_update`,
+/// with the dispatcher that runs the simulation.
+pub fn replay<T>(world: &mut World, dispatcher: &mut Dispatcher<'_, '_>)
+where
+    T: BindingTypes,
+{
+    let from = match world.write_resource::<Reconciliation>().take_replay() {
+        Some(from) => from,
+        None => return,
+    };
+    let current = world.read_resource::<SimulationTick>().tick();
+
+    let rewinds = world.read_resource::<PredictedComponents>().rewinds.clone();
+    for (id, rewind) in rewinds {
+        rewind(world, from.saturating_sub(1), id);
+    }
+
+    world.write_resource::<Reconciliation>().set_replaying(true);
+
+    for tick in from..=current {
+        let input = world
+            .read_resource::<InputHistory<T>>()
+            .get(tick)
+            .cloned()
+            .unwrap_or_default();
+
+        world.write_resource::<SimulationTick>().set(tick);
+        world.write_resource::<PredictedInput<T>>().set(tick, input);
+
+        dispatcher.dispatch(world);
+    }
+
+    world.write_resource::<SimulationTick>().set(current);
+    world
+        .write_resource::<Reconciliation>()
+        .set_replaying(false);
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use amethyst_core::{
+        ecs::{
+            Builder, Component, DenseVecStorage, DispatcherBuilder, Join, ReadStorage, RunNow,
+            System, World, WorldExt, WriteStorage,
+        },
+        shred::SystemData,
+    };
+    use amethyst_input::StringBindings;
+
+    use super::{
+        replay, Predicted, PredictedComponents, PredictionHistory, ReconcileSystem,
+        RecordPredictionSystem,
+    };
+    use crate::{
+        prediction::{PredictionClientSystem, Reconciliation, SimulationTick},
+        replication::{AuthoritativeState, NetworkId, StateTable},
+        serializer::{BincodeSerializer, NetSerializer},
+    };
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Position(i32);
+
+    impl Component for Position {
+        type Storage = DenseVecStorage<Self>;
+    }
+
+    struct MoveSystem;
+
+    impl<'a> System<'a> for MoveSystem {
+        type SystemData = (ReadStorage<'a, Predicted>, WriteStorage<'a, Position>);
+
+        fn run(&mut self, (predicted, mut positions): Self::SystemData) {
+            for (_, position) in (&predicted, &mut positions).join() {
+                position.0 += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn misprediction_is_rewound_and_replayed() {
+        let mut world = World::new();
+        world.insert(BincodeSerializer);
+        let mut simulation = DispatcherBuilder::new()
+            .with(MoveSystem, "move", &[])
+            .with(
+                RecordPredictionSystem::<Position>::new(16),
+                "record",
+                &["move"],
+            )
+            .build();
+        simulation.setup(&mut world);
+        let mut client = PredictionClientSystem::<StringBindings>::new();
+        let mut reconcile = ReconcileSystem::<Position>::new(0);
+        <PredictionClientSystem<StringBindings> as System<'_>>::SystemData::setup(&mut world);
+        <ReconcileSystem<Position> as System<'_>>::SystemData::setup(&mut world);
+        world.write_resource::<PredictedComponents>().register(
+            0,
+            PredictedComponents::rewind::<Position, BincodeSerializer>(),
+        );
+
+        let entity = world
+            .create_entity()
+            .with(Predicted)
+            .with(NetworkId(0))
+            .with(Position(0))
+            .build();
+
+        for _ in 0..3 {
+            world.write_resource::<SimulationTick>().advance();
+            simulation.dispatch(&world);
+        }
+        assert_eq!(
+            world.read_storage::<Position>().get(entity),
+            Some(&Position(3))
+        );
+
+        // the server was pushed back at tick 2, which was predicted at 2
+        let mut state = StateTable::new();
+        state.insert(
+            (NetworkId(0), 0),
+            BincodeSerializer.serialize(&Position(10)).unwrap(),
+        );
+        world
+            .write_resource::<AuthoritativeState>()
+            .set(1, Some(2), state);
+
+        client.run_now(&world);
+        reconcile.run_now(&world);
+        world.maintain();
+        assert_eq!(
+            world.read_resource::<Reconciliation>().replay_from(),
+            Some(3)
+        );
+
+        replay::<StringBindings>(&mut world, &mut simulation);
+
+        assert_eq!(
+            world.read_storage::<Position>().get(entity),
+            Some(&Position(11))
+        );
+        assert_eq!(world.read_resource::<SimulationTick>().tick(), 3);
+        assert_eq!(
+            world
+                .read_resource::<PredictionHistory<Position>>()
+                .get(3, entity),
+            Some(&Position(11))
+        );
+        assert_eq!(world.read_resource::<Reconciliation>().replay_from(), None);
+    }
+
+    #[test]
+    fn replayed_tick_overwrites_later_predictions() {
+        let mut world = World::new();
+        let entity = world.create_entity().build();
+
+        let mut history = PredictionHistory::<u32>::default();
+        for tick in 1..=4 {
+            history.record(tick, vec![(entity, tick)], 16);
+        }
+
+        history.record(2, vec![(entity, 20)], 16);
+
+        assert_eq!(history.get(2, entity), Some(&20));
+        assert_eq!(history.get(3, entity), None);
+        assert_eq!(history.get(1, entity), Some(&1));
+    }
+
+    #[test]
+    fn forget_before_drops_older_ticks() {
+        let mut world = World::new();
+        let entity = world.create_entity().build();
+
+        let mut history = PredictionHistory::<u32>::default();
+        for tick in 1..=4 {
+            history.record(tick, vec![(entity, tick)], 16);
+        }
+
+        history.forget_before(3);
+
+        assert_eq!(history.get(2, entity), None);
+        assert_eq!(history.get(3, entity), Some(&3));
+    }
+}
@@ -0,0 +1,152 @@
+//! The server side of input prediction.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    marker::PhantomData,
+    net::SocketAddr,
+};
+
+use log::warn;
+use serde::de::DeserializeOwned;
+
+use amethyst_core::ecs::{Join, ReadExpect, System, SystemData, World, Write, WriteStorage};
+use amethyst_input::BindingTypes;
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use super::{ActionState, ClientInputs, PredictionConfig, PredictionMessage, ProcessedTicks};
+use crate::{
+    connection::register_consumer,
+    serializer::{BincodeSerializer, NetSerializer},
+    ConnectionState, NetConnection, NetEvent,
+};
+
+/// The inputs the server received from a single client.
+struct ClientState<T: BindingTypes> {
+    // received inputs which were not simulated yet, by client tick.
+    pending: BTreeMap<u32, ActionState<T>>,
+    // the latest client tick whose input was simulated.
+    processed: Option<u32>,
+}
+
+/// Hands the input of every client to the server simulation, one tick at a time.
+///
+/// This system has a few responsibilities:
+///
+/// - Buffering the inputs received from every `NetConnection` by client tick.
+/// - Setting the next buffered input of every client on the `ClientInputs` resource.
+/// - Recording the processed tick of every client in the `ProcessedTicks`, which the replication
+///   stamps on the snapshots sent to that client so it can reconcile its prediction.
+///
+/// Add this system at the start of the dispatcher running the simulation on every fixed update.
+/// A snapshot should reflect the state produced by the ticks it is stamped with,
+/// so run the replication after the simulation.
+///
+/// - `E` corresponds to the network event type.
+/// - `T` corresponds to the binding types of the `InputHandler`.
+/// - `S` corresponds to the serializer of the `NetSocketSystem`, with which the inputs are decoded.
+#[allow(missing_debug_implementations)]
+pub struct PredictionServerSystem<E: 'static, T: BindingTypes, S = BincodeSerializer> {
+    config: PredictionConfig,
+    clients: HashMap<SocketAddr, ClientState<T>>,
+    _marker: PhantomData<(E, T, S)>,
+}
+
+impl<E: 'static, T: BindingTypes, S> PredictionServerSystem<E, T, S> {
+    /// Creates a new `PredictionServerSystem` with the given configuration.
+    pub fn new(config: PredictionConfig) -> Self {
+        PredictionServerSystem {
+            config,
+            clients: HashMap::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, E, T, S> System<'a> for PredictionServerSystem<E, T, S>
+where
+    E: Send + Sync + 'static,
+    T: BindingTypes,
+    T::Action: DeserializeOwned,
+    T::Axis: DeserializeOwned,
+    S: NetSerializer,
+{
+    type SystemData = (
+        ReadExpect<'a, S>,
+        Write<'a, ClientInputs<T>>,
+        Write<'a, ProcessedTicks>,
+        WriteStorage<'a, NetConnection<E>>,
+    );
+
+    fn run(&mut self, (serializer, mut inputs, mut processed, mut connections): Self::SystemData) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("prediction_server_system");
+
+        let mut active_clients = HashSet::new();
+
+        for connection in (&mut connections).join() {
+            if connection.state == ConnectionState::Disconnected {
+                continue;
+            }
+
+            let addr = connection.target_addr;
+            active_clients.insert(addr);
+
+            let client = self.clients.entry(addr).or_insert_with(|| ClientState {
+                pending: BTreeMap::new(),
+                processed: None,
+            });
+
+            for event in connection.received_events_for("prediction_server") {
+                let frames = match event {
+                    NetEvent::Prediction(packet) => match packet.content() {
+                        PredictionMessage::Inputs(frames) => frames,
+                    },
+                    _ => continue,
+                };
+
+                for frame in frames {
+                    if client
+                        .processed
+                        .map_or(false, |processed| frame.tick <= processed)
+                        || client.pending.contains_key(&frame.tick)
+                    {
+                        continue;
+                    }
+
+                    match serializer.deserialize::<ActionState<T>>(&frame.payload) {
+                        Ok(state) => {
+                            client.pending.insert(frame.tick, state);
+                        }
+                        Err(e) => warn!(
+                            "Dropping undecodable input of tick {} from {}: {}",
+                            frame.tick, addr, e
+                        ),
+                    }
+                }
+            }
+
+            while client.pending.len() > self.config.server_input_buffer {
+                let oldest = *client.pending.keys().next().expect("Buffer is not empty");
+                client.pending.remove(&oldest);
+            }
+
+            let next = client.pending.keys().next().cloned();
+            if let Some(tick) = next {
+                let state = client.pending.remove(&tick).expect("Key was just read");
+                client.processed = Some(tick);
+                inputs.set(addr, tick, state);
+                processed.set(addr, tick);
+            }
+        }
+
+        self.clients.retain(|addr, _| active_clients.contains(addr));
+        inputs.retain(|addr| active_clients.contains(&addr));
+        processed.retain(|addr| active_clients.contains(&addr));
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        register_consumer::<E>(world, "prediction_server");
+    }
+}
@@ -0,0 +1,114 @@
+use std::{collections::HashMap, net::SocketAddr};
+
+/// Resource counting the fixed simulation steps of the local simulation.
+///
+/// On the client the tick is advanced by the `InputSamplingSystem` at the start of every fixed update,
+/// and rewound while replaying mispredicted ticks.
+/// Ticks start at `1`, a tick of `0` means that no simulation step has run yet.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SimulationTick {
+    tick: u32,
+}
+
+impl SimulationTick {
+    /// Returns the current tick.
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    /// Advances to the next tick and returns it.
+    pub fn advance(&mut self) -> u32 {
+        self.tick += 1;
+        self.tick
+    }
+
+    pub(crate) fn set(&mut self, tick: u32) {
+        self.tick = tick;
+    }
+}
+
+/// Resource holding, on the server, the latest tick of every client whose input was simulated.
+///
+/// The replication stamps the snapshots sent to a client with its tick,
+/// so the client knows which of its predicted ticks a snapshot has to be compared with.
+#[derive(Clone, Debug, Default)]
+pub struct ProcessedTicks {
+    ticks: HashMap<SocketAddr, u32>,
+}
+
+impl ProcessedTicks {
+    /// Returns the latest tick of the client with the given address whose input was simulated.
+    pub fn tick(&self, addr: SocketAddr) -> Option<u32> {
+        self.ticks.get(&addr).cloned()
+    }
+
+    pub(crate) fn set(&mut self, addr: SocketAddr, tick: u32) {
+        self.ticks.insert(addr, tick);
+    }
+
+    pub(crate) fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(SocketAddr) -> bool,
+    {
+        self.ticks.retain(|addr, _| f(*addr));
+    }
+}
+
+/// Resource tracking which local tick the server has processed and which ticks have to be replayed.
+#[derive(Clone, Debug, Default)]
+pub struct Reconciliation {
+    acknowledged: Option<u32>,
+    pending: Option<u32>,
+    replay_from: Option<u32>,
+    replaying: bool,
+}
+
+impl Reconciliation {
+    /// Returns the latest local tick the server has processed the input of.
+    pub fn acknowledged_tick(&self) -> Option<u32> {
+        self.acknowledged
+    }
+
+    /// Returns the tick the snapshot received during this frame was stamped with,
+    /// whose prediction still has to be checked against the authoritative state.
+    pub fn pending_tick(&self) -> Option<u32> {
+        self.pending
+    }
+
+    /// Returns the first tick that has to be replayed, if a misprediction was detected.
+    pub fn replay_from(&self) -> Option<u32> {
+        self.replay_from
+    }
+
+    /// Returns `true` while mispredicted ticks are being replayed.
+    pub fn is_replaying(&self) -> bool {
+        self.replaying
+    }
+
+    /// Requests that the ticks starting at the given tick are replayed.
+    pub fn request_replay(&mut self, tick: u32) {
+        self.replay_from = Some(self.replay_from.map_or(tick, |from| from.min(tick)));
+    }
+
+    pub(crate) fn acknowledge(&mut self, tick: u32) {
+        if self
+            .acknowledged
+            .map_or(true, |acknowledged| tick > acknowledged)
+        {
+            self.acknowledged = Some(tick);
+            self.pending = Some(tick);
+        }
+    }
+
+    pub(crate) fn clear_pending(&mut self) {
+        self.pending = None;
+    }
+
+    pub(crate) fn take_replay(&mut self) -> Option<u32> {
+        self.replay_from.take()
+    }
+
+    pub(crate) fn set_replaying(&mut self, replaying: bool) {
+        self.replaying = replaying;
+    }
+}
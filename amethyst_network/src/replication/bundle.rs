@@ -0,0 +1,222 @@
+use std::marker::PhantomData;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use amethyst_core::{
+    bundle::SystemBundle,
+    ecs::{Component, World},
+    shred::DispatcherBuilder,
+};
+use amethyst_error::Error;
+
+use super::{
+    registry::{ApplyComponentSystem, CollectComponentSystem},
+    ComponentId, ReplicationClientSystem, ReplicationConfig, ReplicationRegistry,
+    ReplicationServerSystem,
+};
+use crate::serializer::{BincodeSerializer, NetSerializer};
+
+/// Adds the systems replicating a single component type to the dispatcher.
+type AddComponentSystems = for<'a, 'b, 'c> fn(
+    &mut DispatcherBuilder<'a, 'b>,
+    ReplicationRole,
+    ComponentId,
+    &'static str,
+    &[&'c str],
+);
+
+/// The side of the replication a `ReplicationBundle` sets up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplicationRole {
+    /// The authoritative side which sends the state of its `Replicated` entities.
+    Server,
+    /// The side which mirrors the entities replicated by the server.
+    Client,
+}
+
+/// A bundle adding the systems needed to replicate entities between a server and its clients.
+///
+/// Components are opted into replication with `with_component`.
+/// Both sides have to register the same components in the same order, since that order decides
+/// the `ComponentId` under which the components are sent.
+///
+/// On the server `ReplicationServerSystem` will be registered with name "replication_server",
+/// on the client `ReplicationClientSystem` will be registered with name "replication_client".
+///
+/// The components are encoded with the `NetSerializer` `S`, which has to be the serializer
+/// of the `NetSocketSystem`.
+///
+/// ## Errors
+///
+/// No errors will be returned by this bundle.
+#[allow(missing_debug_implementations)]
+pub struct ReplicationBundle<'a, E, S = BincodeSerializer> {
+    role: ReplicationRole,
+    config: ReplicationConfig,
+    components: Vec<(&'static str, AddComponentSystems)>,
+    dep: &'a [&'a str],
+    _data: PhantomData<(E, S)>,
+}
+
+impl<'a, E> ReplicationBundle<'a, E> {
+    /// Creates a new `ReplicationBundle` for the given side of the replication.
+    pub fn new(role: ReplicationRole) -> Self {
+        ReplicationBundle {
+            role,
+            config: ReplicationConfig::default(),
+            components: Vec::new(),
+            dep: &[],
+            _data: PhantomData,
+        }
+    }
+
+    /// Creates a new `ReplicationBundle` for the server.
+    pub fn server() -> Self {
+        Self::new(ReplicationRole::Server)
+    }
+
+    /// Creates a new `ReplicationBundle` for a client.
+    pub fn client() -> Self {
+        Self::new(ReplicationRole::Client)
+    }
+}
+
+impl<'a, E, S> ReplicationBundle<'a, E, S>
+where
+    S: NetSerializer,
+{
+    /// Encodes the components with the serializer `N` instead of `bincode`.
+    ///
+    /// `N` has to be the serializer passed to `NetworkBundle::with_serializer`.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if a component was already added, since its systems use the previous serializer.
+    pub fn with_serializer<N>(self) -> ReplicationBundle<'a, E, N>
+    where
+        N: NetSerializer,
+    {
+        assert!(
+            self.components.is_empty(),
+            "The serializer has to be set before components are added"
+        );
+
+        ReplicationBundle {
+            role: self.role,
+            config: self.config,
+            components: Vec::new(),
+            dep: self.dep,
+            _data: PhantomData,
+        }
+    }
+
+    /// Sets the configuration of the replication systems.
+    pub fn with_config(mut self, config: ReplicationConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Replicates the component `C` under the given name.
+    ///
+    /// The name is used for diagnostics and to name the systems of this component,
+    /// so it should be unique among the replicated components.
+    pub fn with_component<C>(mut self, name: &'static str) -> Self
+    where
+        C: Component + Serialize + DeserializeOwned + Send + Sync,
+    {
+        self.components
+            .push((name, add_component_systems::<C, S> as AddComponentSystems));
+        self
+    }
+
+    /// Set dependencies for the replication systems, usually the name of the `NetSocketSystem`.
+    pub fn with_dep(mut self, dep: &'a [&'a str]) -> Self {
+        self.dep = dep;
+        self
+    }
+}
+
+impl<'a, 'b, 'c, E, S> SystemBundle<'a, 'b> for ReplicationBundle<'c, E, S>
+where
+    E: Send + Sync + 'static,
+{
+    fn build(
+        self,
+        world: &mut World,
+        builder: &mut DispatcherBuilder<'a, 'b>,
+    ) -> Result<(), Error> {
+        let mut registry = ReplicationRegistry::default();
+
+        match self.role {
+            ReplicationRole::Server => {
+                let mut collect_systems = Vec::new();
+
+                for (name, add_systems) in self.components {
+                    let id = registry.register(name);
+                    add_systems(builder, self.role, id, name, self.dep);
+                    collect_systems.push(collect_system_name(name));
+                }
+
+                let dep = collect_systems
+                    .iter()
+                    .map(String::as_str)
+                    .chain(self.dep.iter().cloned())
+                    .collect::<Vec<_>>();
+
+                builder.add(
+                    ReplicationServerSystem::<E>::new(self.config),
+                    "replication_server",
+                    &dep,
+                );
+            }
+            ReplicationRole::Client => {
+                builder.add(
+                    ReplicationClientSystem::<E>::new(self.config),
+                    "replication_client",
+                    self.dep,
+                );
+
+                for (name, add_systems) in self.components {
+                    let id = registry.register(name);
+                    add_systems(builder, self.role, id, name, &["replication_client"]);
+                }
+            }
+        }
+
+        world.insert(registry);
+
+        Ok(())
+    }
+}
+
+fn collect_system_name(name: &str) -> String {
+    format!("replication_collect_{}", name)
+}
+
+fn apply_system_name(name: &str) -> String {
+    format!("replication_apply_{}", name)
+}
+
+fn add_component_systems<C, S>(
+    builder: &mut DispatcherBuilder<'_, '_>,
+    role: ReplicationRole,
+    id: ComponentId,
+    name: &'static str,
+    dep: &[&str],
+) where
+    C: Component + Serialize + DeserializeOwned + Send + Sync,
+    S: NetSerializer,
+{
+    match role {
+        ReplicationRole::Server => builder.add(
+            CollectComponentSystem::<C, S>::new(id, name),
+            &collect_system_name(name),
+            dep,
+        ),
+        ReplicationRole::Client => builder.add(
+            ApplyComponentSystem::<C, S>::new(id, name),
+            &apply_system_name(name),
+            dep,
+        ),
+    }
+}
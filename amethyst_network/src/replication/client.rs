@@ -0,0 +1,248 @@
+//! The client side of entity replication.
+
+use std::{collections::VecDeque, marker::PhantomData};
+
+use log::{debug, error};
+
+use amethyst_core::ecs::{Entities, Join, System, SystemData, World, Write, WriteStorage};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use super::{
+    registry::ReplicationInbox, sequence_newer, AuthoritativeState, NetworkEntities, NetworkId,
+    ReplicationConfig, ReplicationMessage, Snapshot, StateTable,
+};
+use crate::{connection::register_consumer, ConnectionState, NetConnection, NetEvent, NetPacket};
+
+/// The snapshots received from the server and the state that was applied to the world.
+#[derive(Default)]
+struct MirrorState {
+    received: VecDeque<(u32, StateTable)>,
+    // the client tick the latest received snapshot was stamped with.
+    client_tick: Option<u32>,
+    applied: StateTable,
+}
+
+impl MirrorState {
+    /// Decodes the given snapshot and returns its sequence number if it should be acknowledged.
+    fn receive(&mut self, snapshot: &Snapshot, history_size: usize) -> Option<u32> {
+        if let Some((latest, _)) = self.received.back() {
+            if !sequence_newer(snapshot.sequence(), *latest) {
+                return None;
+            }
+        }
+
+        let state = match snapshot.baseline() {
+            None => snapshot.apply(&StateTable::new()),
+            Some(baseline) => match self
+                .received
+                .iter()
+                .find(|(sequence, _)| *sequence == baseline)
+            {
+                Some((_, state)) => snapshot.apply(state),
+                None => {
+                    debug!(
+                        "Dropping snapshot {}, its baseline {} is no longer known.",
+                        snapshot.sequence(),
+                        baseline
+                    );
+                    return None;
+                }
+            },
+        };
+
+        self.received.push_back((snapshot.sequence(), state));
+        self.client_tick = snapshot.client_tick();
+        while self.received.len() > history_size {
+            self.received.pop_front();
+        }
+
+        Some(snapshot.sequence())
+    }
+
+    /// Forgets the applied state of a despawned entity.
+    fn forget(&mut self, id: NetworkId) {
+        let keys = self
+            .applied
+            .keys()
+            .filter(|(entity_id, _)| *entity_id == id)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for key in keys {
+            self.applied.remove(&key);
+        }
+    }
+
+    /// Queues the differences between the latest received state and the applied state into the inbox.
+    fn apply(
+        &mut self,
+        network_entities: &NetworkEntities,
+        inbox: &mut ReplicationInbox,
+        authoritative: &mut AuthoritativeState,
+    ) {
+        let (sequence, latest) = match self.received.back() {
+            Some((sequence, state)) => (*sequence, state),
+            None => return,
+        };
+
+        if authoritative.sequence() != Some(sequence) {
+            authoritative.set(sequence, self.client_tick, latest.clone());
+        }
+
+        for (&(id, component), data) in latest {
+            if let Some(entity) = network_entities.entity(id) {
+                if self.applied.get(&(id, component)) != Some(data) {
+                    inbox
+                        .components
+                        .entry(component)
+                        .or_insert_with(Vec::new)
+                        .push((entity, Some(data.clone())));
+                }
+            }
+        }
+
+        for &(id, component) in self.applied.keys() {
+            if !latest.contains_key(&(id, component)) {
+                if let Some(entity) = network_entities.entity(id) {
+                    inbox
+                        .components
+                        .entry(component)
+                        .or_insert_with(Vec::new)
+                        .push((entity, None));
+                }
+            }
+        }
+
+        self.applied = latest
+            .iter()
+            .filter(|((id, _), _)| network_entities.contains(*id))
+            .map(|(key, data)| (*key, data.clone()))
+            .collect();
+    }
+}
+
+/// Mirrors the entities replicated by the server into the local world.
+///
+/// This system has a few responsibilities:
+///
+/// - Creating and deleting the mirrored entities on spawn and despawn messages, and keeping `NetworkEntities` up to date.
+/// - Decoding the received snapshots against their baseline and acknowledging them.
+/// - Handing the changed components to the systems which apply them to the mirrored entities.
+/// - Keeping the `AuthoritativeState` resource up to date with the latest decoded snapshot.
+///
+/// The client expects a single authoritative server, all connections are treated as the same source.
+///
+/// - `E` corresponds to the network event type.
+#[allow(missing_debug_implementations)]
+pub struct ReplicationClientSystem<E: 'static> {
+    config: ReplicationConfig,
+    state: MirrorState,
+    _marker: PhantomData<E>,
+}
+
+impl<E: 'static> ReplicationClientSystem<E> {
+    /// Creates a new `ReplicationClientSystem` with the given configuration.
+    pub fn new(config: ReplicationConfig) -> Self {
+        ReplicationClientSystem {
+            config,
+            state: MirrorState::default(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, E> System<'a> for ReplicationClientSystem<E>
+where
+    E: Send + Sync + 'static,
+{
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, NetworkId>,
+        Write<'a, NetworkEntities>,
+        Write<'a, ReplicationInbox>,
+        Write<'a, AuthoritativeState>,
+        WriteStorage<'a, NetConnection<E>>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut network_ids,
+            mut network_entities,
+            mut inbox,
+            mut authoritative,
+            mut connections,
+        ): Self::SystemData,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("replication_client_system");
+
+        let mut changed = false;
+
+        for connection in (&mut connections).join() {
+            if connection.state == ConnectionState::Disconnected {
+                continue;
+            }
+
+            let mut acks = Vec::new();
+
+            for event in connection.received_events_for("replication_client") {
+                let message = match event {
+                    NetEvent::Replication(packet) => packet.content(),
+                    _ => continue,
+                };
+
+                match message {
+                    ReplicationMessage::Spawn(id) => {
+                        if !network_entities.contains(*id) {
+                            let entity = entities.create();
+                            if let Err(e) = network_ids.insert(entity, *id) {
+                                error!(
+                                    "Failed to assign a network id to a mirrored entity: {:?}",
+                                    e
+                                );
+                            }
+                            network_entities.insert(*id, entity);
+                            changed = true;
+                        }
+                    }
+                    ReplicationMessage::Despawn(id) => {
+                        if let Some(entity) = network_entities.remove(*id) {
+                            if let Err(e) = entities.delete(entity) {
+                                error!("Failed to delete a mirrored entity: {:?}", e);
+                            }
+                            self.state.forget(*id);
+                        }
+                    }
+                    ReplicationMessage::Snapshot(snapshot) => {
+                        if let Some(sequence) =
+                            self.state.receive(snapshot, self.config.snapshot_history)
+                        {
+                            acks.push(sequence);
+                            changed = true;
+                        }
+                    }
+                    ReplicationMessage::Ack(_) => {}
+                }
+            }
+
+            for sequence in acks {
+                connection.queue(NetEvent::Replication(NetPacket::unreliable(
+                    ReplicationMessage::Ack(sequence),
+                )));
+            }
+        }
+
+        if changed {
+            self.state
+                .apply(&network_entities, &mut inbox, &mut authoritative);
+        }
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        register_consumer::<E>(world, "replication_client");
+    }
+}
@@ -0,0 +1,131 @@
+//! Components and resources identifying replicated entities.
+
+use std::collections::HashMap;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use amethyst_core::ecs::{Component, DenseVecStorage, Entity, NullStorage};
+
+use super::{ComponentId, StateTable};
+use crate::serializer::NetSerializer;
+
+/// Marker component for entities whose registered components should be replicated.
+///
+/// Only entities with this component are picked up by the server side replication systems.
+/// Which components of the entity are sent is decided by the components registered on the `ReplicationBundle`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Replicated;
+
+impl Component for Replicated {
+    type Storage = NullStorage<Self>;
+}
+
+/// A stable identifier for a replicated entity which is the same on every peer.
+///
+/// `Entity` ids are local to a `World`, so the server assigns a `NetworkId` to every replicated entity
+/// and clients attach the same id to the entity mirroring it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct NetworkId(pub u64);
+
+impl Component for NetworkId {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Resource mapping the `NetworkId` of each replicated entity to its local `Entity`.
+///
+/// On the server it contains every entity that is currently replicated,
+/// on the client every entity that was spawned by the server and not yet despawned.
+#[derive(Debug, Default)]
+pub struct NetworkEntities {
+    entities: HashMap<NetworkId, Entity>,
+}
+
+impl NetworkEntities {
+    /// Returns the local entity for the given network id, if any.
+    pub fn entity(&self, id: NetworkId) -> Option<Entity> {
+        self.entities.get(&id).cloned()
+    }
+
+    /// Returns `true` if an entity is known for the given network id.
+    pub fn contains(&self, id: NetworkId) -> bool {
+        self.entities.contains_key(&id)
+    }
+
+    /// Returns an iterator over all known network ids and their local entities.
+    pub fn iter(&self) -> impl Iterator<Item = (NetworkId, Entity)> + '_ {
+        self.entities.iter().map(|(id, entity)| (*id, *entity))
+    }
+
+    /// Returns the number of known entities.
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Returns `true` if no entity is known.
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    pub(crate) fn insert(&mut self, id: NetworkId, entity: Entity) {
+        self.entities.insert(id, entity);
+    }
+
+    pub(crate) fn remove(&mut self, id: NetworkId) -> Option<Entity> {
+        self.entities.remove(&id)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entities.clear();
+    }
+}
+
+/// Resource holding the latest state received from the server, as decoded from the snapshots.
+///
+/// Unlike the components of the mirrored entities, this state is never overwritten locally,
+/// which makes it the reference to compare locally predicted state against.
+#[derive(Debug, Default)]
+pub struct AuthoritativeState {
+    sequence: Option<u32>,
+    client_tick: Option<u32>,
+    state: StateTable,
+}
+
+impl AuthoritativeState {
+    /// Returns the sequence number of the snapshot this state was decoded from.
+    pub fn sequence(&self) -> Option<u32> {
+        self.sequence
+    }
+
+    /// Returns the latest local tick whose input the server simulated before producing this state.
+    ///
+    /// This is the tick the locally predicted state has to be compared with,
+    /// `None` if the server does not simulate the input of this client.
+    pub fn client_tick(&self) -> Option<u32> {
+        self.client_tick
+    }
+
+    /// Returns the authoritative value of a component of the entity with the given network id,
+    /// decoded with the serializer of the `NetSocketSystem`.
+    ///
+    /// Returns `None` if the entity does not have the component or if it could not be deserialized.
+    pub fn component<C, S>(
+        &self,
+        serializer: &S,
+        id: NetworkId,
+        component: ComponentId,
+    ) -> Option<C>
+    where
+        C: DeserializeOwned,
+        S: NetSerializer,
+    {
+        self.state
+            .get(&(id, component))
+            .and_then(|data| serializer.deserialize(data).ok())
+    }
+
+    pub(crate) fn set(&mut self, sequence: u32, client_tick: Option<u32>, state: StateTable) {
+        self.sequence = Some(sequence);
+        self.client_tick = client_tick;
+        self.state = state;
+    }
+}
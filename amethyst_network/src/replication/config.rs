@@ -0,0 +1,24 @@
+/// The configuration of the replication systems.
+#[derive(Clone, Debug)]
+pub struct ReplicationConfig {
+    /// The number of snapshots that are kept per connection to serve as a baseline for delta compression.
+    /// When the last snapshot acknowledged by a peer is older than this, a full snapshot is sent.
+    /// This value is by default 32.
+    pub snapshot_history: usize,
+    /// The stream on which snapshots are sequenced.
+    /// This value is by default `Some(1)`, so snapshots do not supersede sequenced packets sent by the user.
+    pub snapshot_stream: Option<u8>,
+    /// The stream on which spawn and despawn messages are ordered.
+    /// This value is by default `Some(1)`.
+    pub spawn_stream: Option<u8>,
+}
+
+impl Default for ReplicationConfig {
+    fn default() -> Self {
+        ReplicationConfig {
+            snapshot_history: 32,
+            snapshot_stream: Some(1),
+            spawn_stream: Some(1),
+        }
+    }
+}
@@ -0,0 +1,33 @@
+//! Replication of entity state from a server to its clients.
+//!
+//! Entities are opted into replication with the `Replicated` marker component,
+//! and component types are opted in by registering them on the `ReplicationBundle`.
+//!
+//! The server assigns every replicated entity a `NetworkId` and sends each connection:
+//!
+//! - `Spawn` and `Despawn` messages on a reliable ordered stream.
+//! - A `Snapshot` of the registered components each frame on an unreliable sequenced stream,
+//!   delta compressed against the last snapshot the connection acknowledged.
+//!
+//! The client mirrors every spawned entity locally, applies the received components to it
+//! and keeps `NetworkEntities` up to date, so game code can map network ids to local entities.
+
+pub use self::{
+    bundle::{ReplicationBundle, ReplicationRole},
+    client::ReplicationClientSystem,
+    component::{AuthoritativeState, NetworkEntities, NetworkId, Replicated},
+    config::ReplicationConfig,
+    registry::ReplicationRegistry,
+    server::ReplicationServerSystem,
+    snapshot::{ComponentId, ReplicationMessage, Snapshot},
+};
+
+pub(crate) use self::snapshot::{sequence_newer, StateTable};
+
+mod bundle;
+mod client;
+mod component;
+mod config;
+mod registry;
+mod server;
+mod snapshot;
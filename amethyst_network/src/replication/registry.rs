@@ -0,0 +1,187 @@
+//! The registry of replicated component types and the systems moving their data in and out of the world.
+
+use std::{collections::HashMap, marker::PhantomData};
+
+use log::error;
+use serde::{de::DeserializeOwned, Serialize};
+
+use amethyst_core::ecs::{
+    Component, Entities, Entity, Join, ReadExpect, ReadStorage, System, Write, WriteStorage,
+};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use super::{ComponentId, Replicated};
+use crate::{prediction::Predicted, serializer::NetSerializer};
+
+/// Resource listing the replicated component types, indexed by their `ComponentId`.
+#[derive(Debug, Default)]
+pub struct ReplicationRegistry {
+    names: Vec<&'static str>,
+}
+
+impl ReplicationRegistry {
+    /// Registers a component type under the given name and returns the id under which it is replicated.
+    pub(crate) fn register(&mut self, name: &'static str) -> ComponentId {
+        self.names.push(name);
+        (self.names.len() - 1) as ComponentId
+    }
+
+    /// Returns the id under which the component with the given name is replicated.
+    pub fn id(&self, name: &str) -> Option<ComponentId> {
+        self.names
+            .iter()
+            .position(|registered| *registered == name)
+            .map(|index| index as ComponentId)
+    }
+
+    /// Returns the name of the component that is replicated under the given id.
+    pub fn name(&self, id: ComponentId) -> Option<&'static str> {
+        self.names.get(id as usize).cloned()
+    }
+
+    /// Returns the number of registered components.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Returns `true` if no component is registered.
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+/// Serialized component state collected on the server, waiting to be put into snapshots.
+#[derive(Debug, Default)]
+pub(crate) struct ReplicationOutbox {
+    pub(crate) components: Vec<(Entity, ComponentId, Vec<u8>)>,
+}
+
+/// Component updates received by the client, waiting to be applied to the mirrored entities.
+///
+/// `None` means that the component was removed on the server.
+#[derive(Debug, Default)]
+pub(crate) struct ReplicationInbox {
+    pub(crate) components: HashMap<ComponentId, Vec<(Entity, Option<Vec<u8>>)>>,
+}
+
+/// Serializes the `C` component of every `Replicated` entity into the `ReplicationOutbox`,
+/// with the `NetSerializer` `S`.
+#[derive(Debug)]
+pub(crate) struct CollectComponentSystem<C, S> {
+    id: ComponentId,
+    name: &'static str,
+    _marker: PhantomData<(C, S)>,
+}
+
+impl<C, S> CollectComponentSystem<C, S> {
+    pub(crate) fn new(id: ComponentId, name: &'static str) -> Self {
+        CollectComponentSystem {
+            id,
+            name,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, C, S> System<'a> for CollectComponentSystem<C, S>
+where
+    C: Component + Serialize + Send + Sync,
+    S: NetSerializer,
+{
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Replicated>,
+        ReadStorage<'a, C>,
+        ReadExpect<'a, S>,
+        Write<'a, ReplicationOutbox>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, replicated, components, serializer, mut outbox): Self::SystemData,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("replication_collect_component_system");
+
+        for (entity, _, component) in (&entities, &replicated, &components).join() {
+            match serializer.serialize(component) {
+                Ok(data) => outbox.components.push((entity, self.id, data)),
+                Err(e) => error!(
+                    "Failed to serialize replicated component {}: {}",
+                    self.name, e
+                ),
+            }
+        }
+    }
+}
+
+/// Applies the received updates of the `C` component to the mirrored entities,
+/// decoding them with the `NetSerializer` `S`.
+///
+/// `Predicted` entities are skipped, their state is reconciled by the prediction systems instead.
+#[derive(Debug)]
+pub(crate) struct ApplyComponentSystem<C, S> {
+    id: ComponentId,
+    name: &'static str,
+    _marker: PhantomData<(C, S)>,
+}
+
+impl<C, S> ApplyComponentSystem<C, S> {
+    pub(crate) fn new(id: ComponentId, name: &'static str) -> Self {
+        ApplyComponentSystem {
+            id,
+            name,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, C, S> System<'a> for ApplyComponentSystem<C, S>
+where
+    C: Component + DeserializeOwned + Send + Sync,
+    S: NetSerializer,
+{
+    type SystemData = (
+        WriteStorage<'a, C>,
+        ReadStorage<'a, Predicted>,
+        ReadExpect<'a, S>,
+        Write<'a, ReplicationInbox>,
+    );
+
+    fn run(&mut self, (mut components, predicted, serializer, mut inbox): Self::SystemData) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("replication_apply_component_system");
+
+        let updates = match inbox.components.get_mut(&self.id) {
+            Some(updates) => updates,
+            None => return,
+        };
+
+        for (entity, data) in updates.drain(..) {
+            if predicted.contains(entity) {
+                continue;
+            }
+
+            match data {
+                Some(data) => match serializer.deserialize::<C>(&data) {
+                    Ok(component) => {
+                        if let Err(e) = components.insert(entity, component) {
+                            error!(
+                                "Failed to insert replicated component {}: {:?}",
+                                self.name, e
+                            );
+                        }
+                    }
+                    Err(e) => error!(
+                        "Failed to deserialize replicated component {}: {}",
+                        self.name, e
+                    ),
+                },
+                None => {
+                    components.remove(entity);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,274 @@
+//! The server side of entity replication.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    marker::PhantomData,
+    net::SocketAddr,
+};
+
+use log::error;
+
+use amethyst_core::ecs::{
+    Entities, Join, Read, ReadStorage, System, SystemData, World, Write, WriteStorage,
+};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use super::{
+    registry::ReplicationOutbox, sequence_newer, NetworkEntities, NetworkId, Replicated,
+    ReplicationConfig, ReplicationMessage, Snapshot, StateTable,
+};
+use crate::{
+    connection::register_consumer, interest::Relevancy, prediction::ProcessedTicks,
+    ConnectionState, NetConnection, NetEvent, NetPacket,
+};
+
+/// The replication state the server keeps for every connection.
+struct PeerState {
+    // the entities the peer was told to spawn.
+    spawned: BTreeSet<NetworkId>,
+    // the last snapshot the peer acknowledged.
+    acked: Option<u32>,
+    // the snapshots sent to the peer which are still usable as a baseline.
+    history: VecDeque<(u32, StateTable)>,
+}
+
+impl PeerState {
+    fn new() -> Self {
+        PeerState {
+            spawned: BTreeSet::new(),
+            acked: None,
+            history: VecDeque::new(),
+        }
+    }
+
+    fn acknowledge(&mut self, sequence: u32) {
+        if self
+            .acked
+            .map_or(true, |acked| sequence_newer(sequence, acked))
+        {
+            self.acked = Some(sequence);
+        }
+    }
+
+    /// Creates the snapshot of `current` for this peer, delta compressed against the last acknowledged snapshot.
+    fn snapshot(&mut self, sequence: u32, current: &StateTable, history_size: usize) -> Snapshot {
+        let acked = self.acked;
+
+        // Snapshots older than the acknowledged one will never be used as a baseline again.
+        self.history
+            .retain(|(sequence, _)| acked.map_or(true, |acked| !sequence_newer(acked, *sequence)));
+
+        let baseline = self
+            .history
+            .iter()
+            .find(|(sequence, _)| Some(*sequence) == acked)
+            .map(|(sequence, state)| (*sequence, state));
+
+        let snapshot = Snapshot::delta(sequence, baseline, current);
+
+        self.history.push_back((sequence, current.clone()));
+        while self.history.len() > history_size {
+            self.history.pop_front();
+        }
+
+        snapshot
+    }
+}
+
+/// Sends the state of all `Replicated` entities to every `NetConnection`.
+///
+/// This system has a few responsibilities:
+///
+/// - Assigning a `NetworkId` to every entity that became `Replicated`.
+/// - Telling every connection which entities to spawn and despawn, on a reliable ordered stream.
+/// - Sending a delta compressed snapshot of the registered components each frame, on an unreliable sequenced stream.
+///
+/// Snapshots are compressed against the last snapshot a connection acknowledged,
+/// so only the components that changed since then are sent.
+/// Connections which are filtered by the `Relevancy` only receive the entities relevant to them.
+/// Every snapshot is stamped with the latest tick of the connection found in the `ProcessedTicks`,
+/// which predicting clients reconcile against.
+///
+/// - `E` corresponds to the network event type.
+#[allow(missing_debug_implementations)]
+pub struct ReplicationServerSystem<E: 'static> {
+    config: ReplicationConfig,
+    next_id: u64,
+    sequence: u32,
+    peers: HashMap<SocketAddr, PeerState>,
+    _marker: PhantomData<E>,
+}
+
+impl<E: 'static> ReplicationServerSystem<E> {
+    /// Creates a new `ReplicationServerSystem` with the given configuration.
+    pub fn new(config: ReplicationConfig) -> Self {
+        ReplicationServerSystem {
+            config,
+            next_id: 0,
+            sequence: 0,
+            peers: HashMap::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, E> System<'a> for ReplicationServerSystem<E>
+where
+    E: Send + Sync + 'static,
+{
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Replicated>,
+        WriteStorage<'a, NetworkId>,
+        Write<'a, NetworkEntities>,
+        Write<'a, ReplicationOutbox>,
+        Read<'a, Relevancy>,
+        Read<'a, ProcessedTicks>,
+        WriteStorage<'a, NetConnection<E>>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            replicated,
+            mut network_ids,
+            mut network_entities,
+            mut outbox,
+            relevancy,
+            processed,
+            mut connections,
+        ): Self::SystemData,
+    ) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("replication_server_system");
+
+        let new_entities = (&entities, &replicated, !&network_ids)
+            .join()
+            .map(|(entity, _, _)| entity)
+            .collect::<Vec<_>>();
+
+        for entity in new_entities {
+            let id = NetworkId(self.next_id);
+            self.next_id += 1;
+
+            if let Err(e) = network_ids.insert(entity, id) {
+                error!(
+                    "Failed to assign a network id to a replicated entity: {:?}",
+                    e
+                );
+            }
+        }
+
+        network_entities.clear();
+        for (entity, _, id) in (&entities, &replicated, &network_ids).join() {
+            network_entities.insert(*id, entity);
+        }
+
+        let mut current = StateTable::new();
+        for (entity, component, data) in outbox.components.drain(..) {
+            if let Some(id) = network_ids.get(entity) {
+                current.insert((*id, component), data);
+            }
+        }
+
+        // wraps around in long sessions, the receiving end compares sequence numbers accordingly.
+        self.sequence = self.sequence.wrapping_add(1);
+
+        let replicated_ids = network_entities
+            .iter()
+            .map(|(id, _)| id)
+            .collect::<BTreeSet<_>>();
+        let mut active_peers = HashSet::new();
+
+        for connection in (&mut connections).join() {
+            if connection.state == ConnectionState::Disconnected {
+                continue;
+            }
+
+            active_peers.insert(connection.target_addr);
+
+            let peer = self
+                .peers
+                .entry(connection.target_addr)
+                .or_insert_with(PeerState::new);
+
+            let acks = connection
+                .received_events_for("replication_server")
+                .filter_map(|event| match event {
+                    NetEvent::Replication(packet) => match packet.content() {
+                        ReplicationMessage::Ack(sequence) => Some(*sequence),
+                        _ => None,
+                    },
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+
+            for sequence in acks {
+                peer.acknowledge(sequence);
+            }
+
+            let addr = connection.target_addr;
+            let (visible_ids, visible_state) = if relevancy.is_filtered(addr) {
+                let visible_ids = replicated_ids
+                    .iter()
+                    .filter(|id| {
+                        network_entities
+                            .entity(**id)
+                            .map_or(false, |entity| relevancy.is_relevant(addr, entity))
+                    })
+                    .cloned()
+                    .collect::<BTreeSet<_>>();
+                let visible_state = current
+                    .iter()
+                    .filter(|((id, _), _)| visible_ids.contains(id))
+                    .map(|(key, data)| (*key, data.clone()))
+                    .collect::<StateTable>();
+
+                (visible_ids, Some(visible_state))
+            } else {
+                (replicated_ids.clone(), None)
+            };
+
+            let mut events = Vec::new();
+
+            for id in visible_ids.difference(&peer.spawned) {
+                events.push(NetEvent::Replication(NetPacket::reliable_ordered(
+                    ReplicationMessage::Spawn(*id),
+                    self.config.spawn_stream,
+                )));
+            }
+
+            for id in peer.spawned.difference(&visible_ids) {
+                events.push(NetEvent::Replication(NetPacket::reliable_ordered(
+                    ReplicationMessage::Despawn(*id),
+                    self.config.spawn_stream,
+                )));
+            }
+
+            peer.spawned = visible_ids;
+
+            let snapshot = peer
+                .snapshot(
+                    self.sequence,
+                    visible_state.as_ref().unwrap_or(&current),
+                    self.config.snapshot_history,
+                )
+                .with_client_tick(processed.tick(addr));
+            events.push(NetEvent::Replication(NetPacket::unreliable_sequenced(
+                ReplicationMessage::Snapshot(snapshot),
+                self.config.snapshot_stream,
+            )));
+
+            connection.queue_vec(&mut events);
+        }
+
+        self.peers.retain(|addr, _| active_peers.contains(addr));
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        register_consumer::<E>(world, "replication_server");
+    }
+}
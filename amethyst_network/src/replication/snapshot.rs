@@ -0,0 +1,192 @@
+//! Replication messages and the delta compression of snapshots.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::NetworkId;
+
+/// Identifies a replicated component type on the wire.
+///
+/// Ids are handed out in registration order by the `ReplicationBundle`,
+/// so the server and the clients have to register their components in the same order.
+pub type ComponentId = u16;
+
+/// The serialized state of every replicated component, keyed by entity and component type.
+pub(crate) type StateTable = BTreeMap<(NetworkId, ComponentId), Vec<u8>>;
+
+/// The messages exchanged by the replication systems.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ReplicationMessage {
+    /// The entity with the given id is now replicated to the receiving peer.
+    ///
+    /// Sent reliable ordered.
+    Spawn(NetworkId),
+    /// The entity with the given id is no longer replicated to the receiving peer.
+    ///
+    /// Sent reliable ordered.
+    Despawn(NetworkId),
+    /// The component state of the replicated entities, relative to a snapshot acknowledged earlier.
+    ///
+    /// Sent unreliable sequenced.
+    Snapshot(Snapshot),
+    /// Acknowledges that the snapshot with the given sequence number was received.
+    ///
+    /// Sent unreliable, the client acknowledges every snapshot it decodes.
+    Ack(u32),
+}
+
+/// A delta compressed snapshot of the replicated component state.
+///
+/// A snapshot only contains the components which changed compared to its baseline,
+/// which is a snapshot the receiving peer has acknowledged before.
+/// Snapshots without a baseline contain the full state.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    sequence: u32,
+    baseline: Option<u32>,
+    client_tick: Option<u32>,
+    changed: Vec<(NetworkId, ComponentId, Vec<u8>)>,
+    removed: Vec<(NetworkId, ComponentId)>,
+}
+
+impl Snapshot {
+    /// Builds the snapshot of `current` that is delta compressed against the given baseline.
+    pub(crate) fn delta(
+        sequence: u32,
+        baseline: Option<(u32, &StateTable)>,
+        current: &StateTable,
+    ) -> Snapshot {
+        let empty = StateTable::new();
+        let (baseline_sequence, baseline_state) = match baseline {
+            Some((sequence, state)) => (Some(sequence), state),
+            None => (None, &empty),
+        };
+
+        let changed = current
+            .iter()
+            .filter(|(key, data)| baseline_state.get(*key) != Some(*data))
+            .map(|(&(id, component), data)| (id, component, data.clone()))
+            .collect();
+
+        let removed = baseline_state
+            .keys()
+            .filter(|key| !current.contains_key(*key))
+            .cloned()
+            .collect();
+
+        Snapshot {
+            sequence,
+            baseline: baseline_sequence,
+            client_tick: None,
+            changed,
+            removed,
+        }
+    }
+
+    /// Stamps the snapshot with the latest tick of the receiving client whose input the server simulated.
+    pub(crate) fn with_client_tick(mut self, client_tick: Option<u32>) -> Snapshot {
+        self.client_tick = client_tick;
+        self
+    }
+
+    /// Reconstructs the full state described by this snapshot from its baseline state.
+    pub(crate) fn apply(&self, baseline: &StateTable) -> StateTable {
+        let mut state = baseline.clone();
+
+        for key in &self.removed {
+            state.remove(key);
+        }
+
+        for (id, component, data) in &self.changed {
+            state.insert((*id, *component), data.clone());
+        }
+
+        state
+    }
+
+    /// Returns the sequence number of this snapshot.
+    pub fn sequence(&self) -> u32 {
+        self.sequence
+    }
+
+    /// Returns the sequence number of the snapshot this one is relative to.
+    ///
+    /// `None` if this snapshot contains the full state.
+    pub fn baseline(&self) -> Option<u32> {
+        self.baseline
+    }
+
+    /// Returns the latest tick of the receiving client whose input is reflected by this snapshot.
+    ///
+    /// `None` if the server does not simulate the input of the client.
+    pub fn client_tick(&self) -> Option<u32> {
+        self.client_tick
+    }
+
+    /// Returns `true` if this snapshot does not contain any change compared to its baseline.
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Returns `true` if the snapshot sequence number `sequence` was handed out after `other`.
+///
+/// Sequence numbers wrap around in long sessions, so a number is considered newer
+/// if it is less than half the range of `u32` ahead of the other one.
+pub(crate) fn sequence_newer(sequence: u32, other: u32) -> bool {
+    (sequence.wrapping_sub(other) as i32) > 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sequence_newer, Snapshot, StateTable};
+    use crate::replication::NetworkId;
+
+    #[test]
+    fn full_snapshot_without_baseline() {
+        let current = state(&[(1, 0, b"a"), (2, 0, b"b")]);
+        let snapshot = Snapshot::delta(1, None, &current);
+
+        assert_eq!(snapshot.baseline(), None);
+        assert_eq!(snapshot.changed.len(), 2);
+        assert_eq!(snapshot.apply(&StateTable::new()), current);
+    }
+
+    #[test]
+    fn delta_only_contains_changes() {
+        let baseline = state(&[(1, 0, b"a"), (1, 1, b"x"), (2, 0, b"b")]);
+        let current = state(&[(1, 0, b"a"), (2, 0, b"c"), (3, 0, b"d")]);
+        let snapshot = Snapshot::delta(2, Some((1, &baseline)), &current);
+
+        assert_eq!(snapshot.baseline(), Some(1));
+        assert_eq!(snapshot.changed.len(), 2);
+        assert_eq!(snapshot.removed, vec![(NetworkId(1), 1)]);
+        assert_eq!(snapshot.apply(&baseline), current);
+    }
+
+    #[test]
+    fn unchanged_state_gives_empty_delta() {
+        let baseline = state(&[(1, 0, b"a")]);
+        let snapshot = Snapshot::delta(2, Some((1, &baseline)), &baseline);
+
+        assert!(snapshot.is_empty());
+    }
+
+    #[test]
+    fn sequence_comparison_wraps_around() {
+        assert!(sequence_newer(2, 1));
+        assert!(!sequence_newer(1, 2));
+        assert!(!sequence_newer(1, 1));
+        assert!(sequence_newer(0, u32::max_value()));
+        assert!(sequence_newer(3, u32::max_value() - 3));
+        assert!(!sequence_newer(u32::max_value(), 0));
+    }
+
+    fn state(entries: &[(u64, u16, &[u8])]) -> StateTable {
+        entries
+            .iter()
+            .map(|(id, component, data)| ((NetworkId(*id), *component), data.to_vec()))
+            .collect()
+    }
+}
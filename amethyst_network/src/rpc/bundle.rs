@@ -0,0 +1,137 @@
+use std::marker::PhantomData;
+
+use amethyst_core::{bundle::SystemBundle, ecs::World, shred::DispatcherBuilder};
+use amethyst_error::Error;
+
+use super::{Rpc, RpcContext, RpcHandlerSystem, RpcResponseSystem};
+use crate::serializer::{BincodeSerializer, NetSerializer};
+
+/// Adds a system handling a single procedure, or its responses, to the dispatcher.
+type AddRpcSystem = Box<dyn FnOnce(&mut DispatcherBuilder<'_, '_>, &[&str])>;
+
+/// A bundle adding the systems which handle remote procedure calls and their responses.
+///
+/// For every procedure registered with `with_handler` a `RpcHandlerSystem` will be registered
+/// with name "rpc_handler_{name}", for every procedure registered with `with_response`
+/// a `RpcResponseSystem` will be registered with name "rpc_response_{name}".
+///
+/// Calls and responses are encoded with the `NetSerializer` `S`, which has to be the serializer
+/// of the `NetSocketSystem`.
+///
+/// ## Errors
+///
+/// No errors will be returned by this bundle.
+#[allow(missing_debug_implementations)]
+pub struct RpcBundle<'a, E, S = BincodeSerializer> {
+    systems: Vec<AddRpcSystem>,
+    dep: &'a [&'a str],
+    _data: PhantomData<(E, S)>,
+}
+
+impl<'a, E> RpcBundle<'a, E>
+where
+    E: Send + Sync + 'static,
+{
+    /// Creates a new `RpcBundle` without any procedures.
+    pub fn new() -> Self {
+        RpcBundle {
+            systems: Vec::new(),
+            dep: &[],
+            _data: PhantomData,
+        }
+    }
+}
+
+impl<'a, E, S> RpcBundle<'a, E, S>
+where
+    E: Send + Sync + 'static,
+    S: NetSerializer,
+{
+    /// Encodes the calls and responses with the serializer `N` instead of `bincode`.
+    ///
+    /// `N` has to be the serializer passed to `NetworkBundle::with_serializer`.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if a procedure was already added, since its systems use the previous serializer.
+    pub fn with_serializer<N>(self) -> RpcBundle<'a, E, N>
+    where
+        N: NetSerializer,
+    {
+        assert!(
+            self.systems.is_empty(),
+            "The serializer has to be set before procedures are added"
+        );
+
+        RpcBundle {
+            systems: Vec::new(),
+            dep: self.dep,
+            _data: PhantomData,
+        }
+    }
+
+    /// Handles the procedure `R` invoked by remote ends with the given handler.
+    pub fn with_handler<R, F>(mut self, handler: F) -> Self
+    where
+        R: Rpc,
+        F: FnMut(R, &RpcContext<'_>) -> R::Response + Send + 'static,
+    {
+        self.systems.push(Box::new(
+            move |builder: &mut DispatcherBuilder<'_, '_>, dep: &[&str]| {
+                builder.add(
+                    RpcHandlerSystem::<E, R, F, S>::new(handler),
+                    &format!("rpc_handler_{}", R::NAME),
+                    dep,
+                );
+            },
+        ));
+        self
+    }
+
+    /// Writes the responses to the procedure `R`, invoked with `NetConnection::call`,
+    /// to the `EventChannel<RpcResponse<R>>`.
+    pub fn with_response<R: Rpc>(mut self) -> Self {
+        self.systems.push(Box::new(
+            |builder: &mut DispatcherBuilder<'_, '_>, dep: &[&str]| {
+                builder.add(
+                    RpcResponseSystem::<E, R, S>::new(),
+                    &format!("rpc_response_{}", R::NAME),
+                    dep,
+                );
+            },
+        ));
+        self
+    }
+
+    /// Set dependencies for the rpc systems, usually the name of the `NetSocketSystem`.
+    pub fn with_dep(mut self, dep: &'a [&'a str]) -> Self {
+        self.dep = dep;
+        self
+    }
+}
+
+impl<'a, E> Default for RpcBundle<'a, E>
+where
+    E: Send + Sync + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, 'b, 'c, E, S> SystemBundle<'a, 'b> for RpcBundle<'c, E, S>
+where
+    E: Send + Sync + 'static,
+{
+    fn build(
+        self,
+        _world: &mut World,
+        builder: &mut DispatcherBuilder<'a, 'b>,
+    ) -> Result<(), Error> {
+        for add_system in self.systems {
+            add_system(builder, self.dep);
+        }
+
+        Ok(())
+    }
+}
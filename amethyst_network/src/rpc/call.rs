@@ -0,0 +1,101 @@
+use super::{RequestId, Rpc, RpcMessage};
+use crate::{
+    error::Result, serializer::NetSerializer, ConnectionState, DeliveryGuarantee, NetConnection,
+    NetEvent, NetPacket,
+};
+
+impl<E: Send + Sync + 'static> NetConnection<E> {
+    /// Invokes the procedure on the remote end and asks for its response.
+    ///
+    /// The procedure is encoded with the given serializer, which has to be the serializer of the
+    /// `NetSocketSystem` and can be read from the world as a resource.
+    ///
+    /// The response will be written to the `EventChannel<RpcResponse<R>>` with the returned id,
+    /// if a `RpcResponseSystem<E, R>` is running. Unreliable calls, or calls to a remote end
+    /// which does not handle the procedure, may never be answered.
+    pub fn call<R, S>(
+        &mut self,
+        rpc: &R,
+        delivery: DeliveryGuarantee,
+        serializer: &S,
+    ) -> Result<RequestId>
+    where
+        R: Rpc,
+        S: NetSerializer,
+    {
+        let id = RequestId(self.next_request_id);
+        self.next_request_id += 1;
+
+        self.queue(request(rpc, Some(id), delivery, serializer)?);
+        Ok(id)
+    }
+
+    /// Invokes the procedure on the remote end without asking for a response.
+    ///
+    /// The procedure is encoded with the given serializer, see `call`.
+    pub fn notify<R, S>(
+        &mut self,
+        rpc: &R,
+        delivery: DeliveryGuarantee,
+        serializer: &S,
+    ) -> Result<()>
+    where
+        R: Rpc,
+        S: NetSerializer,
+    {
+        self.queue(request(rpc, None, delivery, serializer)?);
+        Ok(())
+    }
+}
+
+/// Invokes the procedure on all connections which are not disconnected, without asking for a response.
+///
+/// The procedure is serialized only once for all connections, with the given serializer.
+pub fn broadcast<'a, E, R, S, I>(
+    connections: I,
+    rpc: &R,
+    delivery: DeliveryGuarantee,
+    serializer: &S,
+) -> Result<()>
+where
+    E: Clone + Send + Sync + 'static,
+    R: Rpc,
+    S: NetSerializer,
+    I: IntoIterator<Item = &'a mut NetConnection<E>>,
+{
+    let event = request(rpc, None, delivery, serializer)?;
+
+    for connection in connections {
+        if connection.state != ConnectionState::Disconnected {
+            connection.queue(event.clone());
+        }
+    }
+
+    Ok(())
+}
+
+fn request<E, R: Rpc, S: NetSerializer>(
+    rpc: &R,
+    id: Option<RequestId>,
+    delivery: DeliveryGuarantee,
+    serializer: &S,
+) -> Result<NetEvent<E>> {
+    let message = RpcMessage::Request {
+        id,
+        method: R::NAME.to_string(),
+        payload: serializer.serialize(rpc)?,
+    };
+
+    Ok(NetEvent::Rpc(rpc_packet(message, delivery)))
+}
+
+/// Wraps the message in a packet with the given delivery guarantee, reliable messages are ordered.
+pub(crate) fn rpc_packet(
+    message: RpcMessage,
+    delivery: DeliveryGuarantee,
+) -> NetPacket<RpcMessage> {
+    match delivery {
+        DeliveryGuarantee::Unreliable => NetPacket::unreliable(message),
+        DeliveryGuarantee::Reliable => NetPacket::reliable_ordered(message, None),
+    }
+}
@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+/// Identifies a remote procedure call made on a `NetConnection`, to correlate it with its response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct RequestId(pub u64);
+
+/// The messages exchanged by the rpc systems.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RpcMessage {
+    /// Invokes the procedure with the given name.
+    Request {
+        /// The id of the call, `None` if the caller does not want a response.
+        id: Option<RequestId>,
+        /// The name of the procedure.
+        method: String,
+        /// The serialized procedure.
+        payload: Vec<u8>,
+    },
+    /// The return value of a procedure.
+    Response {
+        /// The id of the call this is the response to.
+        id: RequestId,
+        /// The name of the procedure.
+        method: String,
+        /// The serialized return value.
+        payload: Vec<u8>,
+    },
+}
@@ -0,0 +1,45 @@
+//! Remote procedure calls on top of `NetConnection`.
+//!
+//! A procedure is a message type implementing `Rpc`, identified on the wire by its name.
+//! Procedures are invoked on a remote end with `NetConnection::call`, `NetConnection::notify` or `broadcast`.
+//!
+//! The receiving end handles a procedure with a `RpcHandlerSystem`, which runs the registered handler
+//! and sends its return value back if the caller asked for a response.
+//! The caller receives the response, correlated by its `RequestId`, as a `RpcResponse` on an `EventChannel`
+//! written by a `RpcResponseSystem`. Both systems are added by the `RpcBundle`.
+
+pub use self::{
+    bundle::RpcBundle,
+    call::broadcast,
+    message::{RequestId, RpcMessage},
+    systems::{RpcContext, RpcHandlerSystem, RpcResponse, RpcResponseSystem},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+mod bundle;
+mod call;
+mod message;
+mod systems;
+
+/// A message type which can be invoked as a procedure on a remote end.
+///
+/// ```
+/// use amethyst_network::Rpc;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Ping(u32);
+///
+/// impl Rpc for Ping {
+///     const NAME: &'static str = "ping";
+///     type Response = u32;
+/// }
+/// ```
+pub trait Rpc: Serialize + DeserializeOwned + Send + Sync + 'static {
+    /// The name identifying this procedure on the wire, which should be unique among all procedures.
+    const NAME: &'static str;
+
+    /// The type returned to the caller, `()` if the procedure returns nothing.
+    type Response: Serialize + DeserializeOwned + Send + Sync + 'static;
+}
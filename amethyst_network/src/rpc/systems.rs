@@ -0,0 +1,261 @@
+//! The systems handling remote procedure calls and their responses.
+
+use std::{
+    fmt::{Debug, Formatter},
+    marker::PhantomData,
+    net::SocketAddr,
+};
+
+use log::{error, warn};
+
+use amethyst_core::{
+    ecs::{Join, LazyUpdate, Read, ReadExpect, System, SystemData, World, Write, WriteStorage},
+    shrev::EventChannel,
+};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use super::{call::rpc_packet, RequestId, Rpc, RpcMessage};
+use crate::{
+    connection::register_consumer,
+    serializer::{BincodeSerializer, NetSerializer},
+    ConnectionState, NetConnection, NetEvent,
+};
+
+/// The context a procedure is handled in.
+#[allow(missing_debug_implementations)]
+pub struct RpcContext<'a> {
+    sender: SocketAddr,
+    lazy: &'a LazyUpdate,
+}
+
+impl<'a> RpcContext<'a> {
+    /// Returns the address of the end which invoked the procedure.
+    pub fn sender(&self) -> SocketAddr {
+        self.sender
+    }
+
+    /// Returns the `LazyUpdate`, through which the handler can modify the world.
+    pub fn lazy(&self) -> &LazyUpdate {
+        self.lazy
+    }
+}
+
+/// The response to a procedure invoked with `NetConnection::call`.
+pub struct RpcResponse<R: Rpc> {
+    /// The address of the end which handled the procedure.
+    pub addr: SocketAddr,
+    /// The id returned by `NetConnection::call`.
+    pub id: RequestId,
+    /// The value returned by the procedure.
+    pub response: R::Response,
+}
+
+impl<R: Rpc> Debug for RpcResponse<R>
+where
+    R::Response: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RpcResponse")
+            .field("addr", &self.addr)
+            .field("id", &self.id)
+            .field("response", &self.response)
+            .finish()
+    }
+}
+
+/// Handles the procedure `R` invoked by remote ends with the given handler.
+///
+/// If the caller asked for a response, the value returned by the handler is sent back
+/// with the same delivery guarantee the procedure was invoked with.
+///
+/// - `E` corresponds to the network event type.
+/// - `R` corresponds to the handled procedure.
+/// - `F` corresponds to the handler.
+/// - `S` corresponds to the serializer of the `NetSocketSystem`, with which calls and responses are encoded.
+#[allow(missing_debug_implementations)]
+pub struct RpcHandlerSystem<E: 'static, R, F, S = BincodeSerializer> {
+    handler: F,
+    // the name under which the received events are read.
+    consumer: String,
+    _marker: PhantomData<(E, R, S)>,
+}
+
+impl<E: 'static, R, F, S> RpcHandlerSystem<E, R, F, S>
+where
+    R: Rpc,
+    F: FnMut(R, &RpcContext<'_>) -> R::Response,
+{
+    /// Creates a new `RpcHandlerSystem` running the given handler for every invocation.
+    pub fn new(handler: F) -> Self {
+        RpcHandlerSystem {
+            handler,
+            consumer: format!("rpc_handler_{}", R::NAME),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, E, R, F, S> System<'a> for RpcHandlerSystem<E, R, F, S>
+where
+    E: Send + Sync + 'static,
+    R: Rpc,
+    F: FnMut(R, &RpcContext<'_>) -> R::Response + Send,
+    S: NetSerializer,
+{
+    type SystemData = (
+        WriteStorage<'a, NetConnection<E>>,
+        ReadExpect<'a, S>,
+        Read<'a, LazyUpdate>,
+    );
+
+    fn run(&mut self, (mut connections, serializer, lazy): Self::SystemData) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("rpc_handler_system");
+
+        for connection in (&mut connections).join() {
+            if connection.state == ConnectionState::Disconnected {
+                continue;
+            }
+
+            let addr = connection.target_addr;
+            let mut responses = Vec::new();
+
+            for event in connection.received_events_for(&self.consumer) {
+                let packet = match event {
+                    NetEvent::Rpc(packet) => packet,
+                    _ => continue,
+                };
+
+                let (id, payload) = match packet.content() {
+                    RpcMessage::Request {
+                        id,
+                        method,
+                        payload,
+                    } if method == R::NAME => (*id, payload),
+                    _ => continue,
+                };
+
+                let rpc = match serializer.deserialize::<R>(payload) {
+                    Ok(rpc) => rpc,
+                    Err(e) => {
+                        warn!(
+                            "Dropping undecodable call of `{}` from {}: {}",
+                            R::NAME,
+                            addr,
+                            e
+                        );
+                        continue;
+                    }
+                };
+
+                let context = RpcContext {
+                    sender: addr,
+                    lazy: &lazy,
+                };
+                let response = (self.handler)(rpc, &context);
+
+                if let Some(id) = id {
+                    match serializer.serialize(&response) {
+                        Ok(payload) => responses.push(NetEvent::Rpc(rpc_packet(
+                            RpcMessage::Response {
+                                id,
+                                method: R::NAME.to_string(),
+                                payload,
+                            },
+                            packet.delivery_guarantee(),
+                        ))),
+                        Err(e) => {
+                            error!("Failed to serialize the response of `{}`: {}", R::NAME, e)
+                        }
+                    }
+                }
+            }
+
+            for response in responses {
+                connection.queue(response);
+            }
+        }
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        register_consumer::<E>(world, self.consumer.clone());
+    }
+}
+
+/// Writes the responses to the procedure `R` to the `EventChannel<RpcResponse<R>>`.
+///
+/// - `E` corresponds to the network event type.
+/// - `R` corresponds to the procedure the responses are read for.
+/// - `S` corresponds to the serializer of the `NetSocketSystem`, with which the responses are decoded.
+#[allow(missing_debug_implementations)]
+pub struct RpcResponseSystem<E: 'static, R, S = BincodeSerializer> {
+    // the name under which the received events are read.
+    consumer: String,
+    _marker: PhantomData<(E, R, S)>,
+}
+
+impl<E: 'static, R: Rpc, S> RpcResponseSystem<E, R, S> {
+    /// Creates a new `RpcResponseSystem`.
+    pub fn new() -> Self {
+        RpcResponseSystem {
+            consumer: format!("rpc_response_{}", R::NAME),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, E, R, S> System<'a> for RpcResponseSystem<E, R, S>
+where
+    E: Send + Sync + 'static,
+    R: Rpc,
+    S: NetSerializer,
+{
+    type SystemData = (
+        WriteStorage<'a, NetConnection<E>>,
+        ReadExpect<'a, S>,
+        Write<'a, EventChannel<RpcResponse<R>>>,
+    );
+
+    fn run(&mut self, (mut connections, serializer, mut responses): Self::SystemData) {
+        #[cfg(feature = "profiler")]
+        profile_scope!("rpc_response_system");
+
+        for connection in (&mut connections).join() {
+            if connection.state == ConnectionState::Disconnected {
+                continue;
+            }
+
+            let addr = connection.target_addr;
+            for event in connection.received_events_for(&self.consumer) {
+                let (id, payload) = match event {
+                    NetEvent::Rpc(packet) => match packet.content() {
+                        RpcMessage::Response {
+                            id,
+                            method,
+                            payload,
+                        } if method == R::NAME => (*id, payload),
+                        _ => continue,
+                    },
+                    _ => continue,
+                };
+
+                match serializer.deserialize::<R::Response>(payload) {
+                    Ok(response) => responses.single_write(RpcResponse { addr, id, response }),
+                    Err(e) => warn!(
+                        "Dropping undecodable response of `{}` from {}: {}",
+                        R::NAME,
+                        addr,
+                        e
+                    ),
+                }
+            }
+        }
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        register_consumer::<E>(world, self.consumer.clone());
+    }
+}
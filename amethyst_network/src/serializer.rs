@@ -0,0 +1,215 @@
+//! The wire format of the events exchanged between connections.
+
+use std::{
+    io::{Read, Write},
+    net::SocketAddr,
+};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::error::{Error, Result};
+
+/// The largest payload `Compressed` will inflate, protecting against decompression bombs.
+const MAX_DECOMPRESSED_SIZE: u64 = 16 * 1024 * 1024;
+
+// The header byte of an uncompressed payload.
+const RAW: u8 = 0;
+// The header byte of a deflated payload.
+const DEFLATED: u8 = 1;
+
+/// Encodes the events sent to remote ends and decodes the events received from them.
+///
+/// Both ends of a connection have to use the same serializer, a payload which can not be decoded
+/// is reported through an `UndecodablePayload` event.
+///
+/// `BincodeSerializer` is used by default. A custom codec, for example one packing values
+/// bitwise, can be used by implementing this trait and passing it to `NetworkBundle::with_serializer`.
+///
+/// The `NetSocketSystem` inserts its serializer as a resource, which the replication, prediction
+/// and rpc systems use to encode the payloads nested in their messages.
+pub trait NetSerializer: Clone + Send + Sync + 'static {
+    /// Encodes the given value.
+    fn serialize<T: Serialize>(&self, value: &T) -> Result<Vec<u8>>;
+
+    /// Decodes a value from the given payload.
+    ///
+    /// ## Errors
+    ///
+    /// Returns `Error::DecodeError` if the payload is not a valid encoding of `T`.
+    fn deserialize<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T>;
+}
+
+/// Encodes events with `bincode`, this is the default `NetSerializer`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BincodeSerializer;
+
+impl NetSerializer for BincodeSerializer {
+    fn serialize<T: Serialize>(&self, value: &T) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(value)?)
+    }
+
+    fn deserialize<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T> {
+        bincode::deserialize(data).map_err(|e| Error::DecodeError(e.to_string()))
+    }
+}
+
+/// Encodes events with MessagePack.
+///
+/// This is only available with the `msgpack` feature.
+#[cfg(feature = "msgpack")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MessagePackSerializer;
+
+#[cfg(feature = "msgpack")]
+impl NetSerializer for MessagePackSerializer {
+    fn serialize<T: Serialize>(&self, value: &T) -> Result<Vec<u8>> {
+        rmp_serde::to_vec(value).map_err(|e| Error::EncodeError(e.to_string()))
+    }
+
+    fn deserialize<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T> {
+        rmp_serde::from_slice(data).map_err(|e| Error::DecodeError(e.to_string()))
+    }
+}
+
+/// Deflates the payloads of another `NetSerializer` which exceed a size threshold.
+///
+/// Every payload is prefixed with a single byte telling whether it was compressed,
+/// small payloads are sent as they are since compressing them does not pay off.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Compressed<S> {
+    inner: S,
+    threshold: usize,
+}
+
+impl<S> Compressed<S> {
+    /// Compresses the payloads of `inner` which are at least `threshold` bytes large.
+    pub fn new(inner: S, threshold: usize) -> Self {
+        Compressed { inner, threshold }
+    }
+
+    /// Returns the size from which payloads are compressed.
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+}
+
+impl<S: NetSerializer> NetSerializer for Compressed<S> {
+    fn serialize<T: Serialize>(&self, value: &T) -> Result<Vec<u8>> {
+        let payload = self.inner.serialize(value)?;
+
+        if payload.len() < self.threshold {
+            let mut data = Vec::with_capacity(payload.len() + 1);
+            data.push(RAW);
+            data.extend_from_slice(&payload);
+            return Ok(data);
+        }
+
+        let mut encoder = DeflateEncoder::new(vec![DEFLATED], Compression::fast());
+        encoder.write_all(&payload)?;
+        Ok(encoder.finish()?)
+    }
+
+    fn deserialize<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T> {
+        match data.split_first() {
+            Some((&RAW, payload)) => self.inner.deserialize(payload),
+            Some((&DEFLATED, payload)) => {
+                let mut inflated = Vec::new();
+                DeflateDecoder::new(payload)
+                    .take(MAX_DECOMPRESSED_SIZE + 1)
+                    .read_to_end(&mut inflated)
+                    .map_err(|e| Error::DecodeError(format!("Invalid deflate stream: {}", e)))?;
+
+                if inflated.len() as u64 > MAX_DECOMPRESSED_SIZE {
+                    return Err(Error::DecodeError(format!(
+                        "Decompressed payload exceeds {} bytes",
+                        MAX_DECOMPRESSED_SIZE
+                    )));
+                }
+
+                self.inner.deserialize(&inflated)
+            }
+            Some((header, _)) => Err(Error::DecodeError(format!(
+                "Unknown compression header {}",
+                header
+            ))),
+            None => Err(Error::DecodeError("Empty payload".to_string())),
+        }
+    }
+}
+
+/// Written to the `EventChannel<UndecodablePayload>` when a payload received from a remote end
+/// could not be decoded and was dropped.
+///
+/// This usually means the remote end uses another `NetSerializer` or protocol version.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UndecodablePayload {
+    /// The address the payload was received from.
+    pub addr: SocketAddr,
+    /// The size of the payload in bytes.
+    pub size: usize,
+    /// Why the payload could not be decoded.
+    pub reason: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NetEvent, NetPacket};
+
+    fn event(content: &str) -> NetEvent<String> {
+        NetEvent::Packet(NetPacket::reliable_ordered(content.to_string(), None))
+    }
+
+    #[test]
+    fn compressed_roundtrips_small_and_large_payloads() {
+        let serializer = Compressed::new(BincodeSerializer, 64);
+        let small = event("abc");
+        let large = event(&"a".repeat(4096));
+
+        let small_data = serializer.serialize(&small).unwrap();
+        let large_data = serializer.serialize(&large).unwrap();
+
+        assert_eq!(small_data[0], RAW);
+        assert_eq!(large_data[0], DEFLATED);
+        assert!(large_data.len() < 4096);
+
+        assert_eq!(
+            serializer
+                .deserialize::<NetEvent<String>>(&small_data)
+                .unwrap(),
+            small
+        );
+        assert_eq!(
+            serializer
+                .deserialize::<NetEvent<String>>(&large_data)
+                .unwrap(),
+            large
+        );
+    }
+
+    #[test]
+    fn undecodable_payload_is_a_decode_error() {
+        let plain = BincodeSerializer.deserialize::<NetEvent<String>>(&[255, 255, 255]);
+        let compressed = Compressed::new(BincodeSerializer, 0).deserialize::<String>(&[7, 1, 2]);
+
+        match (plain, compressed) {
+            (Err(Error::DecodeError(_)), Err(Error::DecodeError(_))) => {}
+            other => panic!("Expected decode errors, got {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn message_pack_roundtrips_events() {
+        let event = event("abc");
+        let data = MessagePackSerializer.serialize(&event).unwrap();
+
+        assert_eq!(
+            MessagePackSerializer
+                .deserialize::<NetEvent<String>>(&data)
+                .unwrap(),
+            event
+        );
+    }
+}
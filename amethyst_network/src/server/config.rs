@@ -1,5 +1,11 @@
+use crate::transport::NetworkConditions;
 use laminar::Config;
-use std::net::SocketAddr;
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+/// Decides whether the token of a connecting end is accepted.
+///
+/// The token is `None` when the connecting end did not send one.
+pub type TokenValidator = Arc<dyn Fn(SocketAddr, Option<&[u8]>) -> bool + Send + Sync>;
 
 #[derive(Clone)]
 /// The configuration used for the networking system.
@@ -17,10 +23,39 @@ pub struct ServerConfig {
     pub create_net_connection_on_connect: bool,
     /// Allows you to configure laminar its behaviour.
     pub laminar_config: Config,
+    /// The version of the game protocol, handshakes from ends with another version are refused.
+    /// This value is by default 0.
+    pub protocol_version: u32,
+    /// The token sent in the handshake of every connection this end opens.
+    /// This value is by default `None`.
+    pub auth_token: Option<Vec<u8>>,
+    /// Validates the token of every handshake this end receives, all tokens are accepted if this is `None`.
+    /// This value is by default `None`.
+    pub token_validator: Option<TokenValidator>,
+    /// The interval at which heartbeats are sent over idle connections and unanswered handshakes are repeated.
+    /// This value is by default 1 second.
+    pub heartbeat_interval: Duration,
+    /// The interval at which pings measuring the round trip time are sent.
+    /// This value is by default 2 seconds.
+    pub ping_interval: Duration,
+    /// The time after which a connection is dropped when nothing was received from the remote end.
+    /// This value is by default 5 seconds.
+    pub connection_timeout: Duration,
+    /// The time after which a connection is dropped when its handshake was not answered.
+    /// This value is by default 5 seconds.
+    pub handshake_timeout: Duration,
+    /// Simulates the given network conditions on all packets sent and received, for testing purposes.
+    /// This value is by default `None`.
+    pub network_conditions: Option<NetworkConditions>,
+    /// The interval over which the rates of the `NetworkStats` are averaged.
+    /// This value is by default 1 second.
+    pub stats_interval: Duration,
 }
 
 impl ServerConfig {
     /// Construct the config with the specified configuration options.
+    ///
+    /// The session options are set to their defaults.
     pub fn new(
         ip: SocketAddr,
         max_throughput: u16,
@@ -32,6 +67,7 @@ impl ServerConfig {
             max_throughput,
             create_net_connection_on_connect,
             laminar_config,
+            ..Default::default()
         }
     }
 }
@@ -44,6 +80,15 @@ impl Default for ServerConfig {
             max_throughput: 5000,
             create_net_connection_on_connect: true,
             laminar_config: Config::default(),
+            protocol_version: 0,
+            auth_token: None,
+            token_validator: None,
+            heartbeat_interval: Duration::from_secs(1),
+            ping_interval: Duration::from_secs(2),
+            connection_timeout: Duration::from_secs(5),
+            handshake_timeout: Duration::from_secs(5),
+            network_conditions: None,
+            stats_interval: Duration::from_secs(1),
         }
     }
 }
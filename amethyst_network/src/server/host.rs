@@ -4,12 +4,14 @@
 //! 2. Receiving Data
 //! 3. Broadcasting
 
-use crate::{error::Result, server::ServerConfig};
+use crate::{error::Result, server::ServerConfig, transport::NetworkTransport};
 use crossbeam_channel::{Receiver, Sender};
 use laminar::{Packet, Socket, SocketEvent};
 use std::thread;
 
 /// 'Host' abstracts Laminar udp sockets away.
+///
+/// This is the `NetworkTransport` used by the `NetSocketSystem` by default.
 #[allow(missing_debug_implementations)] // TODO: Revisit this, laminar doesn't implement debug anywhere
 pub struct Host {
     packet_sender: Sender<Packet>,
@@ -52,3 +54,13 @@ impl Host {
         Ok(())
     }
 }
+
+impl NetworkTransport for Host {
+    fn send(&mut self, packet: Packet) -> Result<()> {
+        self.send_udp(packet)
+    }
+
+    fn receive(&mut self) -> Option<SocketEvent> {
+        self.packet_receiver.try_recv().ok()
+    }
+}
@@ -1,4 +1,7 @@
 mod config;
 mod host;
 
-pub use self::{config::ServerConfig, host::Host};
+pub use self::{
+    config::{ServerConfig, TokenValidator},
+    host::Host,
+};
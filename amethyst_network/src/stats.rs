@@ -0,0 +1,161 @@
+//! Statistics about the traffic of every connection.
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use amethyst_core::timing::duration_to_secs;
+
+/// The weight of the previous value in the rolling averages, out of 8.
+const SMOOTHING: u64 = 7;
+
+/// The traffic statistics of a single connection.
+///
+/// The round trip time is measured with pings sent every `ping_interval` of the `ServerConfig`,
+/// the rates are averaged over the `stats_interval`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConnectionStats {
+    /// The rolling average of the round trip time, `None` until the first ping was answered.
+    pub rtt: Option<Duration>,
+    /// The rolling average of the fraction of pings which were not answered, from 0 to 1.
+    pub packet_loss: f32,
+    /// The number of bytes sent per second over the last `stats_interval`.
+    pub bytes_sent_per_second: f32,
+    /// The number of bytes received per second over the last `stats_interval`.
+    pub bytes_received_per_second: f32,
+    /// The number of bytes sent since the connection was established.
+    pub bytes_sent: u64,
+    /// The number of bytes received since the connection was established.
+    pub bytes_received: u64,
+    /// The number of packets sent since the connection was established.
+    pub packets_sent: u64,
+    /// The number of packets received since the connection was established.
+    pub packets_received: u64,
+    /// The number of events which were queued for sending in the last frame.
+    pub queued: usize,
+    /// The number of events which could not be serialized or handed to the transport.
+    pub dropped: u64,
+    /// The number of events the transport failed to send, which are counted as dropped as well.
+    pub send_errors: u64,
+    /// The number of handshakes which were sent again because they were not answered in time.
+    ///
+    /// Only the handshakes are counted, the packets resent by the reliability layer of the transport are not.
+    pub resent: u64,
+    /// The number of payloads received from the remote end which could not be decoded.
+    pub undecodable: u64,
+    window_start: Option<Instant>,
+    window_sent: u64,
+    window_received: u64,
+}
+
+impl ConnectionStats {
+    pub(crate) fn record_sent(&mut self, bytes: usize) {
+        self.packets_sent += 1;
+        self.bytes_sent += bytes as u64;
+        self.window_sent += bytes as u64;
+    }
+
+    pub(crate) fn record_received(&mut self, bytes: usize) {
+        self.packets_received += 1;
+        self.bytes_received += bytes as u64;
+        self.window_received += bytes as u64;
+    }
+
+    pub(crate) fn record_rtt(&mut self, sample: Duration) {
+        self.rtt = Some(match self.rtt {
+            Some(rtt) => {
+                let nanos = (rtt.as_nanos() as u64 * SMOOTHING + sample.as_nanos() as u64)
+                    / (SMOOTHING + 1);
+                Duration::from_nanos(nanos)
+            }
+            None => sample,
+        });
+    }
+
+    pub(crate) fn record_ping(&mut self, lost: bool) {
+        let sample = if lost { 1.0 } else { 0.0 };
+        self.packet_loss = (self.packet_loss * SMOOTHING as f32 + sample) / (SMOOTHING + 1) as f32;
+    }
+
+    /// Computes the rates once the given interval has passed since they were last computed.
+    pub(crate) fn update_rates(&mut self, now: Instant, interval: Duration) {
+        let start = *self.window_start.get_or_insert(now);
+        let elapsed = now.duration_since(start);
+        if elapsed < interval || elapsed == Duration::from_secs(0) {
+            return;
+        }
+
+        let seconds = duration_to_secs(elapsed);
+        self.bytes_sent_per_second = self.window_sent as f32 / seconds;
+        self.bytes_received_per_second = self.window_received as f32 / seconds;
+        self.window_start = Some(now);
+        self.window_sent = 0;
+        self.window_received = 0;
+    }
+}
+
+/// The traffic statistics of every connection which is not disconnected, updated by the `NetSocketSystem`.
+#[derive(Clone, Debug, Default)]
+pub struct NetworkStats {
+    pub(crate) connections: HashMap<SocketAddr, ConnectionStats>,
+    pub(crate) undecodable: u64,
+}
+
+impl NetworkStats {
+    /// Returns the statistics of the connection to the given address.
+    pub fn get(&self, addr: SocketAddr) -> Option<&ConnectionStats> {
+        self.connections.get(&addr)
+    }
+
+    /// Returns an iterator over the statistics of all connections.
+    pub fn iter(&self) -> impl Iterator<Item = (SocketAddr, &ConnectionStats)> {
+        self.connections.iter().map(|(addr, stats)| (*addr, stats))
+    }
+
+    /// Returns the number of payloads which could not be decoded, including the ones received
+    /// from addresses without a connection.
+    pub fn undecodable_payloads(&self) -> u64 {
+        self.undecodable
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)] // the expected values are exactly representable
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rates_are_averaged_over_the_interval() {
+        let start = Instant::now();
+        let mut stats = ConnectionStats::default();
+
+        stats.update_rates(start, Duration::from_secs(1));
+        stats.record_sent(500);
+        stats.record_sent(1500);
+        stats.record_received(1000);
+
+        stats.update_rates(start + Duration::from_millis(500), Duration::from_secs(1));
+        assert_eq!(stats.bytes_sent_per_second, 0.0);
+
+        stats.update_rates(start + Duration::from_secs(2), Duration::from_secs(1));
+        assert_eq!(stats.bytes_sent_per_second, 1000.0);
+        assert_eq!(stats.bytes_received_per_second, 500.0);
+        assert_eq!(stats.packets_sent, 2);
+        assert_eq!(stats.bytes_sent, 2000);
+    }
+
+    #[test]
+    fn rtt_and_loss_are_smoothed() {
+        let mut stats = ConnectionStats::default();
+
+        stats.record_rtt(Duration::from_millis(80));
+        assert_eq!(stats.rtt, Some(Duration::from_millis(80)));
+        stats.record_rtt(Duration::from_millis(160));
+        assert_eq!(stats.rtt, Some(Duration::from_millis(90)));
+
+        stats.record_ping(true);
+        assert_eq!(stats.packet_loss, 0.125);
+    }
+}
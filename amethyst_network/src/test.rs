@@ -1,18 +1,33 @@
 #![cfg(test)]
 
-use std::{net::SocketAddr, thread::sleep, time::Duration};
+use std::{
+    io,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::sleep,
+    time::Duration,
+};
 
 use amethyst_core::{
     ecs::{Builder, Join, World, WorldExt, WriteStorage},
     shred::{Dispatcher, DispatcherBuilder, SystemData},
+    shrev::EventChannel,
 };
 
 use crate::{
-    net_event::{NetEvent, NetPacket},
+    net_event::{DisconnectReason, NetEvent, NetPacket, RefusedReason},
     server::ServerConfig,
-    NetConnection, NetSocketSystem,
+    BincodeSerializer, Compressed, ConnectionState, DeliveryGuarantee, DiscoveredServers,
+    DiscoveryConfig, DiscoveryEvent, HostedServer, LanAnnounceSystem, LanDiscoverySystem,
+    LobbyClientSystem, LobbyEvent, LobbyMessage, LobbyServerSystem, LobbyView, LoopbackNetwork,
+    LoopbackTransport, NetConnection, NetSocketSystem, NetworkStats, NetworkTransport, Result, Rpc,
+    RpcContext, RpcHandlerSystem, RpcResponse, RpcResponseSystem, UndecodablePayload,
 };
-use laminar::Config;
+use laminar::{Config, Packet, SocketEvent};
+use serde::{Deserialize, Serialize};
 
 #[test]
 fn single_packet_early() {
@@ -42,9 +57,13 @@ fn single_packet_early() {
     let storage = world_sv.read_storage::<NetConnection<String>>();
     let comp = storage.get(conn_to_client_entity).unwrap();
 
-    assert_eq!(comp.receive_buffer.read(&mut rcv).next(), Some(&packet));
-    // We should have consumed the only event in the iterator by calling next().
-    assert!(comp.receive_buffer.read(&mut rcv).count() == 0);
+    let mut packets = comp
+        .receive_buffer
+        .read(&mut rcv)
+        .filter(|event| is_packet(event));
+    assert_eq!(packets.next(), Some(&packet));
+    // We should have consumed the only packet in the iterator by calling next().
+    assert!(packets.count() == 0);
 }
 
 #[test]
@@ -86,7 +105,729 @@ fn send_receive_100_packets() {
 
     let storage = world_sv.read_storage::<NetConnection<String>>();
     let comp = storage.get(conn_to_client_entity).unwrap();
-    assert_eq!(comp.receive_buffer.read(&mut rcv).count(), 100);
+    assert_eq!(
+        comp.receive_buffer
+            .read(&mut rcv)
+            .filter(|event| is_packet(event))
+            .count(),
+        100
+    );
+}
+
+#[test]
+fn handshake_connects_both_ends() {
+    let server_addr: SocketAddr = "127.0.0.1:21208".parse().unwrap();
+    let client_addr: SocketAddr = "127.0.0.1:21210".parse().unwrap();
+
+    let (mut world_cl, mut cl_dispatch, mut world_sv, mut sv_dispatch) =
+        build(client_addr, server_addr);
+
+    let mut conn_to_server = NetConnection::<String>::new(server_addr);
+    let mut conn_to_client = NetConnection::<String>::new(client_addr);
+
+    let mut cl_rcv = conn_to_server.receive_buffer.register_reader();
+    let mut sv_rcv = conn_to_client.receive_buffer.register_reader();
+    let conn_to_server_entity = world_cl.create_entity().with(conn_to_server).build();
+    let conn_to_client_entity = world_sv.create_entity().with(conn_to_client).build();
+
+    cl_dispatch.dispatch(&world_cl);
+    sleep(Duration::from_millis(200));
+    sv_dispatch.dispatch(&world_sv);
+    sleep(Duration::from_millis(200));
+    cl_dispatch.dispatch(&world_cl);
+
+    let storage = world_sv.read_storage::<NetConnection<String>>();
+    let comp = storage.get(conn_to_client_entity).unwrap();
+    assert_eq!(comp.state, ConnectionState::Connected);
+    assert_eq!(
+        comp.receive_buffer.read(&mut sv_rcv).next(),
+        Some(&NetEvent::Connected(client_addr))
+    );
+
+    let storage = world_cl.read_storage::<NetConnection<String>>();
+    let comp = storage.get(conn_to_server_entity).unwrap();
+    assert_eq!(comp.state, ConnectionState::Connected);
+    assert_eq!(
+        comp.receive_buffer.read(&mut cl_rcv).next(),
+        Some(&NetEvent::Connected(server_addr))
+    );
+}
+
+#[test]
+fn handshake_with_other_protocol_version_is_refused() {
+    let server_addr: SocketAddr = "127.0.0.1:21212".parse().unwrap();
+    let client_addr: SocketAddr = "127.0.0.1:21214".parse().unwrap();
+
+    let mut world_cl = World::new();
+    let mut cl_dispatch = dispatcher(
+        &mut world_cl,
+        ServerConfig {
+            udp_socket_addr: client_addr,
+            create_net_connection_on_connect: false,
+            protocol_version: 1,
+            ..Default::default()
+        },
+    );
+    let mut world_sv = World::new();
+    let mut sv_dispatch = dispatcher(
+        &mut world_sv,
+        ServerConfig {
+            udp_socket_addr: server_addr,
+            create_net_connection_on_connect: true,
+            protocol_version: 2,
+            ..Default::default()
+        },
+    );
+
+    let mut conn_to_server = NetConnection::<String>::new(server_addr);
+    let mut cl_rcv = conn_to_server.receive_buffer.register_reader();
+    let conn_to_server_entity = world_cl.create_entity().with(conn_to_server).build();
+
+    cl_dispatch.dispatch(&world_cl);
+    sleep(Duration::from_millis(200));
+    sv_dispatch.dispatch(&world_sv);
+    sleep(Duration::from_millis(200));
+    cl_dispatch.dispatch(&world_cl);
+
+    let storage = world_cl.read_storage::<NetConnection<String>>();
+    let comp = storage.get(conn_to_server_entity).unwrap();
+    assert_eq!(comp.state, ConnectionState::Disconnected);
+    assert_eq!(
+        comp.receive_buffer.read(&mut cl_rcv).next(),
+        Some(&NetEvent::ConnectionRefused {
+            reason: RefusedReason::ProtocolMismatch {
+                expected: 2,
+                received: 1,
+            }
+        })
+    );
+}
+
+#[test]
+fn disconnect_is_propagated_to_both_ends() {
+    let server_addr: SocketAddr = "127.0.0.1:21216".parse().unwrap();
+    let client_addr: SocketAddr = "127.0.0.1:21218".parse().unwrap();
+
+    let (mut world_cl, mut cl_dispatch, mut world_sv, mut sv_dispatch) =
+        build(client_addr, server_addr);
+
+    let mut conn_to_server = NetConnection::<String>::new(server_addr);
+    let mut conn_to_client = NetConnection::<String>::new(client_addr);
+
+    let mut cl_rcv = conn_to_server.receive_buffer.register_reader();
+    let mut sv_rcv = conn_to_client.receive_buffer.register_reader();
+    let conn_to_server_entity = world_cl.create_entity().with(conn_to_server).build();
+    let conn_to_client_entity = world_sv.create_entity().with(conn_to_client).build();
+
+    cl_dispatch.dispatch(&world_cl);
+    sleep(Duration::from_millis(200));
+    sv_dispatch.dispatch(&world_sv);
+    sleep(Duration::from_millis(200));
+    cl_dispatch.dispatch(&world_cl);
+
+    world_cl
+        .write_storage::<NetConnection<String>>()
+        .get_mut(conn_to_server_entity)
+        .unwrap()
+        .disconnect(DisconnectReason::Kicked("bye".to_string()));
+
+    cl_dispatch.dispatch(&world_cl);
+    sleep(Duration::from_millis(200));
+    sv_dispatch.dispatch(&world_sv);
+
+    let expected = |addr| NetEvent::Disconnected {
+        addr,
+        reason: DisconnectReason::Kicked("bye".to_string()),
+    };
+
+    let storage = world_cl.read_storage::<NetConnection<String>>();
+    let comp = storage.get(conn_to_server_entity).unwrap();
+    assert_eq!(comp.state, ConnectionState::Disconnected);
+    assert_eq!(
+        comp.receive_buffer.read(&mut cl_rcv).last(),
+        Some(&expected(server_addr))
+    );
+
+    let storage = world_sv.read_storage::<NetConnection<String>>();
+    let comp = storage.get(conn_to_client_entity).unwrap();
+    assert_eq!(comp.state, ConnectionState::Disconnected);
+    assert_eq!(
+        comp.receive_buffer.read(&mut sv_rcv).last(),
+        Some(&expected(client_addr))
+    );
+}
+
+#[test]
+fn loopback_transport_connects_client_and_server() {
+    let server_addr: SocketAddr = "127.0.0.1:1".parse().unwrap();
+    let client_addr: SocketAddr = "127.0.0.1:2".parse().unwrap();
+
+    let network = LoopbackNetwork::new();
+    let mut world_cl = World::new();
+    let mut cl_dispatch = transport_dispatcher(&mut world_cl, network.bind(client_addr).unwrap());
+    let mut world_sv = World::new();
+    let mut sv_dispatch = transport_dispatcher(&mut world_sv, network.bind(server_addr).unwrap());
+
+    let packet = NetEvent::Packet(NetPacket::reliable_ordered(
+        "Test Message From Client1".to_string(),
+        None,
+    ));
+
+    let mut conn_to_server = NetConnection::<String>::new(server_addr);
+    let mut conn_to_client = NetConnection::<String>::new(client_addr);
+    conn_to_server.queue(packet.clone());
+
+    let mut cl_rcv = conn_to_server.receive_buffer.register_reader();
+    let mut sv_rcv = conn_to_client.receive_buffer.register_reader();
+    let conn_to_server_entity = world_cl.create_entity().with(conn_to_server).build();
+    let conn_to_client_entity = world_sv.create_entity().with(conn_to_client).build();
+
+    // no sleeping required, the loopback transport delivers packets immediately.
+    cl_dispatch.dispatch(&world_cl);
+    sv_dispatch.dispatch(&world_sv);
+    cl_dispatch.dispatch(&world_cl);
+
+    let storage = world_sv.read_storage::<NetConnection<String>>();
+    let comp = storage.get(conn_to_client_entity).unwrap();
+    assert_eq!(comp.state, ConnectionState::Connected);
+    assert_eq!(
+        comp.receive_buffer.read(&mut sv_rcv).collect::<Vec<_>>(),
+        vec![&NetEvent::Connected(client_addr), &packet]
+    );
+
+    let storage = world_cl.read_storage::<NetConnection<String>>();
+    let comp = storage.get(conn_to_server_entity).unwrap();
+    assert_eq!(comp.state, ConnectionState::Connected);
+    assert_eq!(
+        comp.receive_buffer.read(&mut cl_rcv).collect::<Vec<_>>(),
+        vec![&NetEvent::Connected(server_addr)]
+    );
+}
+
+#[test]
+fn packets_of_unauthenticated_peer_are_not_delivered() {
+    let server_addr: SocketAddr = "127.0.0.1:1".parse().unwrap();
+    let peer_addr: SocketAddr = "127.0.0.1:2".parse().unwrap();
+
+    let network = LoopbackNetwork::new();
+    let mut peer = network.bind(peer_addr).unwrap();
+    let mut world_sv = World::new();
+    let mut sv_dispatch = transport_dispatcher_with_config(
+        &mut world_sv,
+        network.bind(server_addr).unwrap(),
+        ServerConfig {
+            create_net_connection_on_connect: false,
+            token_validator: Some(Arc::new(|_: SocketAddr, token: Option<&[u8]>| {
+                token == Some(&b"secret"[..])
+            })),
+            ..Default::default()
+        },
+    );
+
+    let mut conn_to_peer = NetConnection::<String>::new(peer_addr);
+    let mut sv_rcv = conn_to_peer.receive_buffer.register_reader();
+    let conn_to_peer_entity = world_sv.create_entity().with(conn_to_peer).build();
+
+    // the peer skips the handshake and sends packets right away.
+    let packet = NetPacket::reliable_ordered("Forged".to_string(), None);
+    peer.send(crate::serialize_packet(packet, server_addr, &BincodeSerializer).unwrap())
+        .unwrap();
+    sv_dispatch.dispatch(&world_sv);
+
+    {
+        let storage = world_sv.read_storage::<NetConnection<String>>();
+        let comp = storage.get(conn_to_peer_entity).unwrap();
+        assert_eq!(comp.state, ConnectionState::Connecting);
+        assert_eq!(comp.receive_buffer.read(&mut sv_rcv).count(), 0);
+    }
+
+    let handshake = NetEvent::<String>::Connect {
+        protocol_version: 0,
+        token: Some(b"forged".to_vec()),
+    };
+    peer.send(crate::serialize_event(handshake, server_addr, &BincodeSerializer).unwrap())
+        .unwrap();
+    sv_dispatch.dispatch(&world_sv);
+
+    let storage = world_sv.read_storage::<NetConnection<String>>();
+    let comp = storage.get(conn_to_peer_entity).unwrap();
+    assert_eq!(comp.state, ConnectionState::Disconnected);
+    assert_eq!(
+        comp.receive_buffer.read(&mut sv_rcv).collect::<Vec<_>>(),
+        vec![&NetEvent::ConnectionRefused {
+            reason: RefusedReason::Unauthorized
+        }]
+    );
+}
+
+#[test]
+fn refused_peer_can_connect_again() {
+    let server_addr: SocketAddr = "127.0.0.1:1".parse().unwrap();
+    let client_addr: SocketAddr = "127.0.0.1:2".parse().unwrap();
+
+    let accept = Arc::new(AtomicBool::new(false));
+    let validator_accept = accept.clone();
+
+    let network = LoopbackNetwork::new();
+    let mut world_cl = World::new();
+    let mut cl_dispatch = transport_dispatcher(&mut world_cl, network.bind(client_addr).unwrap());
+    let mut world_sv = World::new();
+    let mut sv_dispatch = transport_dispatcher_with_config(
+        &mut world_sv,
+        network.bind(server_addr).unwrap(),
+        ServerConfig {
+            create_net_connection_on_connect: true,
+            token_validator: Some(Arc::new(move |_: SocketAddr, _: Option<&[u8]>| {
+                validator_accept.load(Ordering::SeqCst)
+            })),
+            ..Default::default()
+        },
+    );
+
+    let conn_to_server_entity = world_cl
+        .create_entity()
+        .with(NetConnection::<String>::new(server_addr))
+        .build();
+
+    cl_dispatch.dispatch(&world_cl);
+    sv_dispatch.dispatch(&world_sv);
+    cl_dispatch.dispatch(&world_cl);
+    assert_eq!(
+        world_cl
+            .read_storage::<NetConnection<String>>()
+            .get(conn_to_server_entity)
+            .unwrap()
+            .state,
+        ConnectionState::Disconnected
+    );
+
+    accept.store(true, Ordering::SeqCst);
+    world_cl.delete_entity(conn_to_server_entity).unwrap();
+    world_cl.maintain();
+    let conn_to_server_entity = world_cl
+        .create_entity()
+        .with(NetConnection::<String>::new(server_addr))
+        .build();
+
+    cl_dispatch.dispatch(&world_cl);
+    sv_dispatch.dispatch(&world_sv);
+    cl_dispatch.dispatch(&world_cl);
+
+    let storage = world_sv.read_storage::<NetConnection<String>>();
+    let states = storage
+        .join()
+        .map(|connection| connection.state.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(states, vec![ConnectionState::Connected]);
+
+    let storage = world_cl.read_storage::<NetConnection<String>>();
+    let comp = storage.get(conn_to_server_entity).unwrap();
+    assert_eq!(comp.state, ConnectionState::Connected);
+}
+
+#[test]
+fn network_stats_measure_round_trip_and_traffic() {
+    let server_addr: SocketAddr = "127.0.0.1:1".parse().unwrap();
+    let client_addr: SocketAddr = "127.0.0.1:2".parse().unwrap();
+
+    let network = LoopbackNetwork::new();
+    let mut world_cl = World::new();
+    let mut cl_dispatch = transport_dispatcher(&mut world_cl, network.bind(client_addr).unwrap());
+    let mut world_sv = World::new();
+    let mut sv_dispatch = transport_dispatcher(&mut world_sv, network.bind(server_addr).unwrap());
+
+    world_cl
+        .create_entity()
+        .with(NetConnection::<String>::new(server_addr))
+        .build();
+    world_sv
+        .create_entity()
+        .with(NetConnection::<String>::new(client_addr))
+        .build();
+
+    // the client completes the handshake in its second run, pings the server in its third
+    // and receives the answer in its fourth.
+    for _ in 0..3 {
+        cl_dispatch.dispatch(&world_cl);
+        sv_dispatch.dispatch(&world_sv);
+    }
+    cl_dispatch.dispatch(&world_cl);
+
+    let stats = world_cl.read_resource::<NetworkStats>();
+    let connection = stats.get(server_addr).expect("No stats for the connection");
+    assert!(connection.rtt.is_some());
+    assert!(connection.packets_sent >= 2);
+    assert!(connection.bytes_received > 0);
+    assert_eq!(connection.undecodable, 0);
+}
+
+#[test]
+#[allow(clippy::float_cmp)]
+fn idle_connections_send_heartbeats_between_pings() {
+    let server_addr: SocketAddr = "127.0.0.1:1".parse().unwrap();
+    let client_addr: SocketAddr = "127.0.0.1:2".parse().unwrap();
+
+    let network = LoopbackNetwork::new();
+    let mut world_cl = World::new();
+    let mut cl_dispatch = transport_dispatcher_with_config(
+        &mut world_cl,
+        network.bind(client_addr).unwrap(),
+        ServerConfig {
+            create_net_connection_on_connect: false,
+            heartbeat_interval: Duration::from_secs(0),
+            ping_interval: Duration::from_secs(3600),
+            ..Default::default()
+        },
+    );
+    let mut world_sv = World::new();
+    let mut sv_dispatch = transport_dispatcher(&mut world_sv, network.bind(server_addr).unwrap());
+
+    world_cl
+        .create_entity()
+        .with(NetConnection::<String>::new(server_addr))
+        .build();
+    world_sv
+        .create_entity()
+        .with(NetConnection::<String>::new(client_addr))
+        .build();
+
+    // connect and answer the first ping
+    for _ in 0..3 {
+        cl_dispatch.dispatch(&world_cl);
+        sv_dispatch.dispatch(&world_sv);
+    }
+    cl_dispatch.dispatch(&world_cl);
+    let sent = world_cl
+        .read_resource::<NetworkStats>()
+        .get(server_addr)
+        .unwrap()
+        .packets_sent;
+
+    // the server doesn't answer anymore, so another ping would count as lost
+    for _ in 0..3 {
+        cl_dispatch.dispatch(&world_cl);
+    }
+
+    let stats = world_cl.read_resource::<NetworkStats>();
+    let connection = stats.get(server_addr).expect("No stats for the connection");
+    assert_eq!(connection.packets_sent, sent + 3);
+    assert_eq!(connection.packet_loss, 0.0);
+}
+
+#[test]
+fn send_failures_are_counted_as_send_errors() {
+    struct FailingTransport;
+
+    impl NetworkTransport for FailingTransport {
+        fn send(&mut self, _: Packet) -> Result<()> {
+            Err(io::Error::new(io::ErrorKind::Other, "unreachable").into())
+        }
+
+        fn receive(&mut self) -> Option<SocketEvent> {
+            None
+        }
+    }
+
+    let server_addr: SocketAddr = "127.0.0.1:1".parse().unwrap();
+
+    let mut world = World::new();
+    let mut dispatch = DispatcherBuilder::new()
+        .with(
+            NetSocketSystem::<String>::with_transport(ServerConfig::default(), FailingTransport),
+            "s",
+            &[],
+        )
+        .build();
+    dispatch.setup(&mut world);
+
+    world
+        .create_entity()
+        .with(NetConnection::<String>::new(server_addr))
+        .build();
+    dispatch.dispatch(&world);
+
+    let stats = world.read_resource::<NetworkStats>();
+    let connection = stats.get(server_addr).expect("No stats for the connection");
+    assert_eq!(connection.send_errors, 1);
+    assert_eq!(connection.dropped, 1);
+    assert_eq!(connection.undecodable, 0);
+    assert_eq!(stats.undecodable_payloads(), 0);
+}
+
+#[derive(Serialize, Deserialize)]
+struct Add(u32, u32);
+
+impl Rpc for Add {
+    const NAME: &'static str = "add";
+    type Response = u32;
+}
+
+#[test]
+fn rpc_response_is_delivered_to_caller() {
+    let server_addr: SocketAddr = "127.0.0.1:1".parse().unwrap();
+    let client_addr: SocketAddr = "127.0.0.1:2".parse().unwrap();
+
+    let network = LoopbackNetwork::new();
+    let config = ServerConfig {
+        create_net_connection_on_connect: false,
+        ..Default::default()
+    };
+    // the procedure and its response are encoded with the serializer of the sockets.
+    let serializer = Compressed::new(BincodeSerializer, 0);
+
+    let mut world_cl = World::new();
+    let mut cl_dispatch = DispatcherBuilder::new()
+        .with(
+            NetSocketSystem::<String>::with_transport(
+                config.clone(),
+                network.bind(client_addr).unwrap(),
+            )
+            .with_serializer(serializer),
+            "net_socket",
+            &[],
+        )
+        .with(
+            RpcResponseSystem::<String, Add, Compressed<BincodeSerializer>>::new(),
+            "rpc_response",
+            &["net_socket"],
+        )
+        .build();
+    cl_dispatch.setup(&mut world_cl);
+
+    let mut world_sv = World::new();
+    let mut sv_dispatch = DispatcherBuilder::new()
+        .with(
+            NetSocketSystem::<String>::with_transport(config, network.bind(server_addr).unwrap())
+                .with_serializer(serializer),
+            "net_socket",
+            &[],
+        )
+        .with(
+            RpcHandlerSystem::<String, Add, _, Compressed<BincodeSerializer>>::new(
+                |Add(a, b), _: &RpcContext<'_>| a + b,
+            ),
+            "rpc_handler",
+            &["net_socket"],
+        )
+        .build();
+    sv_dispatch.setup(&mut world_sv);
+
+    let mut conn_to_server = NetConnection::<String>::new(server_addr);
+    let id = conn_to_server
+        .call(&Add(2, 3), DeliveryGuarantee::Reliable, &serializer)
+        .unwrap();
+    world_cl.create_entity().with(conn_to_server).build();
+    world_sv
+        .create_entity()
+        .with(NetConnection::<String>::new(client_addr))
+        .build();
+
+    let mut responses = world_cl
+        .write_resource::<EventChannel<RpcResponse<Add>>>()
+        .register_reader();
+
+    cl_dispatch.dispatch(&world_cl);
+    // the server handles the call in this run and sends the response in the next one.
+    sv_dispatch.dispatch(&world_sv);
+    sv_dispatch.dispatch(&world_sv);
+    cl_dispatch.dispatch(&world_cl);
+
+    let channel = world_cl.read_resource::<EventChannel<RpcResponse<Add>>>();
+    let response = channel.read(&mut responses).next().expect("No response");
+    assert_eq!(response.id, id);
+    assert_eq!(response.addr, server_addr);
+    assert_eq!(response.response, 5);
+}
+
+#[test]
+fn undecodable_payload_is_reported() {
+    let server_addr: SocketAddr = "127.0.0.1:1".parse().unwrap();
+    let client_addr: SocketAddr = "127.0.0.1:2".parse().unwrap();
+
+    let network = LoopbackNetwork::new();
+    let config = ServerConfig {
+        create_net_connection_on_connect: false,
+        ..Default::default()
+    };
+
+    // the client compresses its events while the server expects plain bincode.
+    let mut world_cl = World::new();
+    let mut cl_dispatch = DispatcherBuilder::new()
+        .with(
+            NetSocketSystem::<String>::with_transport(config, network.bind(client_addr).unwrap())
+                .with_serializer(Compressed::new(BincodeSerializer, 0)),
+            "s",
+            &[],
+        )
+        .build();
+    cl_dispatch.setup(&mut world_cl);
+    let mut world_sv = World::new();
+    let mut sv_dispatch = transport_dispatcher(&mut world_sv, network.bind(server_addr).unwrap());
+
+    world_cl
+        .create_entity()
+        .with(NetConnection::<String>::new(server_addr))
+        .build();
+    let mut reader = world_sv
+        .write_resource::<EventChannel<UndecodablePayload>>()
+        .register_reader();
+
+    cl_dispatch.dispatch(&world_cl);
+    sv_dispatch.dispatch(&world_sv);
+
+    let channel = world_sv.read_resource::<EventChannel<UndecodablePayload>>();
+    let undecodable = channel
+        .read(&mut reader)
+        .next()
+        .expect("No undecodable payload");
+    assert_eq!(undecodable.addr, client_addr);
+    assert!(undecodable.size > 0);
+}
+
+#[test]
+fn lobby_room_is_synchronized_to_client() {
+    let server_addr: SocketAddr = "127.0.0.1:1".parse().unwrap();
+    let client_addr: SocketAddr = "127.0.0.1:2".parse().unwrap();
+
+    let network = LoopbackNetwork::new();
+    let config = ServerConfig {
+        create_net_connection_on_connect: false,
+        ..Default::default()
+    };
+
+    let mut world_cl = World::new();
+    let mut cl_dispatch = DispatcherBuilder::new()
+        .with(
+            NetSocketSystem::<String>::with_transport(
+                config.clone(),
+                network.bind(client_addr).unwrap(),
+            ),
+            "net_socket",
+            &[],
+        )
+        .with(
+            LobbyClientSystem::<String>::new(),
+            "lobby_client",
+            &["net_socket"],
+        )
+        .build();
+    cl_dispatch.setup(&mut world_cl);
+
+    let mut world_sv = World::new();
+    let mut sv_dispatch = DispatcherBuilder::new()
+        .with(
+            NetSocketSystem::<String>::with_transport(config, network.bind(server_addr).unwrap()),
+            "net_socket",
+            &[],
+        )
+        .with(
+            LobbyServerSystem::<String>::new(),
+            "lobby_server",
+            &["net_socket"],
+        )
+        .build();
+    sv_dispatch.setup(&mut world_sv);
+
+    let mut conn_to_server = NetConnection::<String>::new(server_addr);
+    conn_to_server.lobby_request(LobbyMessage::Create {
+        name: "room".to_string(),
+        max_members: None,
+    });
+    conn_to_server.lobby_request(LobbyMessage::SetReady(true));
+    world_cl.create_entity().with(conn_to_server).build();
+    world_sv
+        .create_entity()
+        .with(NetConnection::<String>::new(client_addr))
+        .build();
+
+    let mut events = world_cl
+        .write_resource::<EventChannel<LobbyEvent>>()
+        .register_reader();
+
+    cl_dispatch.dispatch(&world_cl);
+    // the server handles the requests in this run and sends the room in the next one.
+    sv_dispatch.dispatch(&world_sv);
+    sv_dispatch.dispatch(&world_sv);
+    cl_dispatch.dispatch(&world_cl);
+
+    let view = world_cl.read_resource::<LobbyView>();
+    let room = view.room.as_ref().expect("Not in a room");
+    assert_eq!(view.you, Some(client_addr));
+    assert!(view.is_leader());
+    assert!(room.all_ready());
+
+    let channel = world_cl.read_resource::<EventChannel<LobbyEvent>>();
+    assert_eq!(
+        channel.read(&mut events).cloned().collect::<Vec<_>>(),
+        vec![LobbyEvent::Joined {
+            room: room.id,
+            addr: client_addr
+        }]
+    );
+}
+
+#[test]
+fn lan_discovery_finds_announced_server() {
+    let config = DiscoveryConfig {
+        port: 23461,
+        broadcast_addr: "127.0.0.1".parse().unwrap(),
+        ..Default::default()
+    };
+
+    let mut world_sv = World::new();
+    let mut sv_dispatch = DispatcherBuilder::new()
+        .with(
+            LanAnnounceSystem::<String>::new(config.clone(), 3457).unwrap(),
+            "lan_announce",
+            &[],
+        )
+        .build();
+    sv_dispatch.setup(&mut world_sv);
+    world_sv.insert(HostedServer {
+        info: Some("server".to_string()),
+    });
+
+    let mut world_cl = World::new();
+    let mut cl_dispatch = DispatcherBuilder::new()
+        .with(
+            LanDiscoverySystem::<String>::new(config).unwrap(),
+            "lan_discovery",
+            &[],
+        )
+        .build();
+    cl_dispatch.setup(&mut world_cl);
+    let mut events = world_cl
+        .write_resource::<EventChannel<DiscoveryEvent<String>>>()
+        .register_reader();
+
+    cl_dispatch.dispatch(&world_cl);
+    sleep(Duration::from_millis(50));
+    sv_dispatch.dispatch(&world_sv);
+    sleep(Duration::from_millis(50));
+    cl_dispatch.dispatch(&world_cl);
+
+    let addr: SocketAddr = "127.0.0.1:3457".parse().unwrap();
+    let servers = world_cl.read_resource::<DiscoveredServers<String>>();
+    assert_eq!(
+        servers.get(addr).map(|server| server.info.as_str()),
+        Some("server")
+    );
+
+    let channel = world_cl.read_resource::<EventChannel<DiscoveryEvent<String>>>();
+    assert_eq!(
+        channel.read(&mut events).cloned().collect::<Vec<_>>(),
+        vec![DiscoveryEvent::Found {
+            addr,
+            info: "server".to_string()
+        }]
+    );
+}
+
+fn is_packet(event: &NetEvent<String>) -> bool {
+    match event {
+        NetEvent::Packet(_) => true,
+        _ => false,
+    }
 }
 
 fn build<'a, 'b>(
@@ -102,6 +843,7 @@ fn build<'a, 'b>(
         max_throughput: 10000,
         create_net_connection_on_connect: false,
         laminar_config: Config::default(),
+        ..Default::default()
     };
 
     // server config
@@ -110,24 +852,47 @@ fn build<'a, 'b>(
         max_throughput: 10000,
         create_net_connection_on_connect: false,
         laminar_config: Config::default(),
+        ..Default::default()
     };
 
-    let mut cl_dispatch = DispatcherBuilder::new()
-        .with(
-            NetSocketSystem::<String>::new(client_config).unwrap(),
-            "s",
-            &[],
-        )
+    let cl_dispatch = dispatcher(&mut world_cl, client_config);
+    let sv_dispatch = dispatcher(&mut world_sv, server_config);
+
+    (world_cl, cl_dispatch, world_sv, sv_dispatch)
+}
+
+fn dispatcher<'a, 'b>(world: &mut World, config: ServerConfig) -> Dispatcher<'a, 'b> {
+    let mut dispatch = DispatcherBuilder::new()
+        .with(NetSocketSystem::<String>::new(config).unwrap(), "s", &[])
         .build();
-    cl_dispatch.setup(&mut world_cl);
-    let mut sv_dispatch = DispatcherBuilder::new()
+    dispatch.setup(world);
+    dispatch
+}
+
+fn transport_dispatcher<'a, 'b>(
+    world: &mut World,
+    transport: LoopbackTransport,
+) -> Dispatcher<'a, 'b> {
+    let config = ServerConfig {
+        create_net_connection_on_connect: false,
+        ..Default::default()
+    };
+
+    transport_dispatcher_with_config(world, transport, config)
+}
+
+fn transport_dispatcher_with_config<'a, 'b>(
+    world: &mut World,
+    transport: LoopbackTransport,
+    config: ServerConfig,
+) -> Dispatcher<'a, 'b> {
+    let mut dispatch = DispatcherBuilder::new()
         .with(
-            NetSocketSystem::<String>::new(server_config).unwrap(),
+            NetSocketSystem::<String>::with_transport(config, transport),
             "s",
             &[],
         )
         .build();
-    sv_dispatch.setup(&mut world_sv);
-
-    (world_cl, cl_dispatch, world_sv, sv_dispatch)
+    dispatch.setup(world);
+    dispatch
 }
@@ -0,0 +1,410 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use laminar::{DeliveryGuarantee, OrderingGuarantee, Packet, SocketEvent};
+use log::error;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::NetworkTransport;
+use crate::error::Result;
+
+/// The number of times a lost reliable packet is resent before it is given up.
+const MAX_RESENDS: u32 = 32;
+
+/// The network conditions simulated by a `ConditionedTransport`.
+///
+/// The conditions are applied to sent and received packets alike,
+/// so the round trip of a packet is subject to them twice.
+///
+/// They are simulated on the datagrams below the delivery guarantees of the packets,
+/// the way a reliability layer experiences them: a lost reliable packet is resent after the `resend_delay`,
+/// the packets of an ordered stream wait for the packets before them, an older packet of a sequenced stream
+/// is dropped once a newer one was delivered, and only unreliable unordered packets are ever delivered twice.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetworkConditions {
+    /// The time every datagram is held back.
+    /// This value is by default 0.
+    pub latency: Duration,
+    /// The maximal random time every datagram is held back in addition to the latency.
+    /// This value is by default 0.
+    pub jitter: Duration,
+    /// The probability between 0 and 1 that a datagram is dropped.
+    /// This value is by default 0.
+    pub loss: f32,
+    /// The probability between 0 and 1 that a datagram is delivered twice.
+    /// This value is by default 0.
+    pub duplication: f32,
+    /// The probability between 0 and 1 that a datagram is held back by the `reorder_delay`,
+    /// so the datagrams following it overtake it.
+    /// This value is by default 0.
+    pub reordering: f32,
+    /// The time a reordered datagram is held back in addition to its latency and jitter.
+    /// This value is by default 50 milliseconds.
+    pub reorder_delay: Duration,
+    /// The time after which a lost reliable packet is resent.
+    /// This value is by default 100 milliseconds.
+    pub resend_delay: Duration,
+    /// The seed of the random number generator, the same seed reproduces the same conditions.
+    /// This value is by default 0.
+    pub seed: u64,
+}
+
+impl Default for NetworkConditions {
+    fn default() -> Self {
+        NetworkConditions {
+            latency: Duration::from_millis(0),
+            jitter: Duration::from_millis(0),
+            loss: 0.0,
+            duplication: 0.0,
+            reordering: 0.0,
+            reorder_delay: Duration::from_millis(50),
+            resend_delay: Duration::from_millis(100),
+            seed: 0,
+        }
+    }
+}
+
+/// The packets held back in one direction of a `ConditionedTransport`.
+#[derive(Default)]
+struct Pipe {
+    // packets which are held back, by the time they are due and the order they were scheduled in.
+    packets: BTreeMap<(Instant, u64), Packet>,
+    // the time the last packet of every ordered stream is due, which the following packets wait for.
+    ordered: HashMap<(SocketAddr, Option<u8>), Instant>,
+    // the order of the newest packet delivered on every sequenced stream.
+    sequenced: HashMap<(SocketAddr, Option<u8>), u64>,
+}
+
+impl Pipe {
+    /// Removes and returns the earliest packet if it is due,
+    /// skipping the packets a newer packet of their sequenced stream was delivered before.
+    fn next_due(&mut self, now: Instant) -> Option<Packet> {
+        loop {
+            let key = self
+                .packets
+                .keys()
+                .next()
+                .filter(|(due, _)| *due <= now)
+                .cloned()?;
+            let packet = self.packets.remove(&key).expect("Key was just read");
+
+            if let OrderingGuarantee::Sequenced(stream) = packet.order_guarantee() {
+                let newest = self.sequenced.entry((packet.addr(), stream)).or_insert(0);
+                if key.1 < *newest {
+                    continue;
+                }
+                *newest = key.1;
+            }
+
+            return Some(packet);
+        }
+    }
+}
+
+/// A transport wrapping another transport, simulating bad network conditions on it.
+///
+/// The `NetSocketSystem` wraps its transport in this one when `ServerConfig::network_conditions` is set.
+/// Events which are not packets, like connects and timeouts, are passed through as they are.
+#[allow(missing_debug_implementations)]
+pub struct ConditionedTransport<T> {
+    inner: T,
+    conditions: NetworkConditions,
+    rng: StdRng,
+    outgoing: Pipe,
+    incoming: Pipe,
+    scheduled: u64,
+}
+
+impl<T: NetworkTransport> ConditionedTransport<T> {
+    /// Wraps the given transport, simulating the given conditions.
+    pub fn new(inner: T, conditions: NetworkConditions) -> Self {
+        ConditionedTransport {
+            rng: StdRng::seed_from_u64(conditions.seed),
+            inner,
+            conditions,
+            outgoing: Pipe::default(),
+            incoming: Pipe::default(),
+            scheduled: 0,
+        }
+    }
+
+    /// Returns the wrapped transport.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns the simulated conditions.
+    pub fn conditions(&self) -> &NetworkConditions {
+        &self.conditions
+    }
+
+    /// Returns the time a single datagram takes to arrive.
+    fn delay(&mut self) -> Duration {
+        let mut delay = self.conditions.latency;
+
+        let jitter = self.conditions.jitter.as_nanos() as u64;
+        if jitter > 0 {
+            delay += Duration::from_nanos(self.rng.gen_range(0, jitter + 1));
+        }
+
+        if self.rng.gen::<f32>() < self.conditions.reordering {
+            delay += self.conditions.reorder_delay;
+        }
+
+        delay
+    }
+
+    /// Holds back the copies of a received or sent packet until they are due, none if it is lost.
+    fn schedule(&mut self, incoming: bool, packet: Packet, now: Instant) {
+        let reliable = packet.delivery_guarantee() == DeliveryGuarantee::Reliable;
+
+        // a lost reliable datagram is resent once the loss is noticed.
+        let mut sent = now;
+        let mut resends = 0;
+        while self.rng.gen::<f32>() < self.conditions.loss {
+            if !reliable || resends == MAX_RESENDS {
+                return;
+            }
+            sent += self.conditions.resend_delay;
+            resends += 1;
+        }
+
+        let mut due = vec![sent + self.delay()];
+
+        // duplicates of all other packets are discarded by the reliability layer.
+        let unordered = packet.order_guarantee() == OrderingGuarantee::None;
+        if !reliable && unordered && self.rng.gen::<f32>() < self.conditions.duplication {
+            due.push(sent + self.delay());
+        }
+
+        let pipe = if incoming {
+            &mut self.incoming
+        } else {
+            &mut self.outgoing
+        };
+
+        if let OrderingGuarantee::Ordered(stream) = packet.order_guarantee() {
+            let last = pipe
+                .ordered
+                .entry((packet.addr(), stream))
+                .or_insert(due[0]);
+            due[0] = due[0].max(*last);
+            *last = due[0];
+        }
+
+        for due in due {
+            self.scheduled += 1;
+            pipe.packets.insert((due, self.scheduled), packet.clone());
+        }
+    }
+
+    /// Sends all due packets, logging every packet which could not be sent.
+    ///
+    /// Returns an error if any packet could not be sent.
+    fn flush_outgoing(&mut self, now: Instant) -> Result<()> {
+        let mut failed = 0;
+        while let Some(packet) = self.outgoing.next_due(now) {
+            let addr = packet.addr();
+            if let Err(e) = self.inner.send(packet) {
+                error!("Failed to send a delayed packet to {}: {}", addr, e);
+                failed += 1;
+            }
+        }
+
+        if failed > 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("Failed to send {} delayed packets", failed),
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: NetworkTransport> NetworkTransport for ConditionedTransport<T> {
+    fn send(&mut self, packet: Packet) -> Result<()> {
+        let now = Instant::now();
+
+        self.schedule(false, packet, now);
+        self.flush_outgoing(now)
+    }
+
+    fn receive(&mut self) -> Option<SocketEvent> {
+        let now = Instant::now();
+
+        // held back packets are sent once due, even if nothing new is sent.
+        // the failures were logged already.
+        let _ = self.flush_outgoing(now);
+
+        while let Some(event) = self.inner.receive() {
+            match event {
+                SocketEvent::Packet(packet) => self.schedule(true, packet, now),
+                event => return Some(event),
+            }
+        }
+
+        self.incoming.next_due(now).map(SocketEvent::Packet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConditionedTransport, NetworkConditions};
+    use crate::transport::{LoopbackNetwork, NetworkTransport};
+    use laminar::{Packet, SocketEvent};
+    use std::{net::SocketAddr, thread::sleep, time::Duration};
+
+    fn received_payloads<T: NetworkTransport>(transport: &mut T) -> Vec<u8> {
+        let mut payloads = Vec::new();
+        while let Some(event) = transport.receive() {
+            if let SocketEvent::Packet(packet) = event {
+                payloads.push(packet.payload()[0]);
+            }
+        }
+        payloads
+    }
+
+    fn send_packets(conditions: NetworkConditions, count: u8) -> Vec<u8> {
+        let network = LoopbackNetwork::new();
+        let client_addr: SocketAddr = "127.0.0.1:1".parse().unwrap();
+        let server_addr: SocketAddr = "127.0.0.1:2".parse().unwrap();
+
+        let mut client = ConditionedTransport::new(network.bind(client_addr).unwrap(), conditions);
+        let mut server = network.bind(server_addr).unwrap();
+
+        for payload in 0..count {
+            client
+                .send(Packet::unreliable(server_addr, vec![payload]))
+                .unwrap();
+        }
+
+        received_payloads(&mut server)
+    }
+
+    #[test]
+    fn same_seed_reproduces_loss_and_duplication() {
+        let conditions = NetworkConditions {
+            loss: 0.3,
+            duplication: 0.3,
+            seed: 42,
+            ..Default::default()
+        };
+
+        let first = send_packets(conditions.clone(), 100);
+        let second = send_packets(conditions, 100);
+
+        assert_eq!(first, second);
+        assert_ne!(first, (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn full_loss_drops_all_packets() {
+        let conditions = NetworkConditions {
+            loss: 1.0,
+            ..Default::default()
+        };
+
+        assert!(send_packets(conditions, 10).is_empty());
+    }
+
+    #[test]
+    fn full_duplication_delivers_packets_twice() {
+        let conditions = NetworkConditions {
+            duplication: 1.0,
+            ..Default::default()
+        };
+
+        assert_eq!(send_packets(conditions, 2), vec![0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn lost_reliable_packets_are_resent() {
+        let network = LoopbackNetwork::new();
+        let client_addr: SocketAddr = "127.0.0.1:1".parse().unwrap();
+        let server_addr: SocketAddr = "127.0.0.1:2".parse().unwrap();
+
+        let mut client = ConditionedTransport::new(
+            network.bind(client_addr).unwrap(),
+            NetworkConditions {
+                loss: 0.5,
+                resend_delay: Duration::from_millis(10),
+                seed: 7,
+                ..Default::default()
+            },
+        );
+        let mut server = network.bind(server_addr).unwrap();
+
+        for payload in 0..20 {
+            client
+                .send(Packet::reliable_unordered(server_addr, vec![payload]))
+                .unwrap();
+        }
+        sleep(Duration::from_millis(400));
+        // the resent packets are sent once the client polls its transport.
+        assert!(client.receive().is_none());
+
+        let mut received = received_payloads(&mut server);
+        received.sort();
+        assert_eq!(received, (0..20).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn ordered_packets_are_not_reordered() {
+        let network = LoopbackNetwork::new();
+        let client_addr: SocketAddr = "127.0.0.1:1".parse().unwrap();
+        let server_addr: SocketAddr = "127.0.0.1:2".parse().unwrap();
+
+        let mut client = network.bind(client_addr).unwrap();
+        let mut server = ConditionedTransport::new(
+            network.bind(server_addr).unwrap(),
+            NetworkConditions {
+                jitter: Duration::from_millis(20),
+                reordering: 0.5,
+                reorder_delay: Duration::from_millis(20),
+                seed: 3,
+                ..Default::default()
+            },
+        );
+
+        for payload in 0..20 {
+            client
+                .send(Packet::reliable_ordered(server_addr, vec![payload], None))
+                .unwrap();
+        }
+        let mut received = received_payloads(&mut server);
+        sleep(Duration::from_millis(100));
+        received.extend(received_payloads(&mut server));
+
+        assert_eq!(received, (0..20).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn latency_holds_back_received_packets() {
+        let network = LoopbackNetwork::new();
+        let client_addr: SocketAddr = "127.0.0.1:1".parse().unwrap();
+        let server_addr: SocketAddr = "127.0.0.1:2".parse().unwrap();
+
+        let mut client = network.bind(client_addr).unwrap();
+        let mut server = ConditionedTransport::new(
+            network.bind(server_addr).unwrap(),
+            NetworkConditions {
+                latency: Duration::from_millis(50),
+                ..Default::default()
+            },
+        );
+
+        client
+            .send(Packet::unreliable(server_addr, vec![7]))
+            .unwrap();
+
+        assert!(received_payloads(&mut server).is_empty());
+        sleep(Duration::from_millis(60));
+        assert_eq!(received_payloads(&mut server), vec![7]);
+    }
+}
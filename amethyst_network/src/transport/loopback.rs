@@ -0,0 +1,198 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use crossbeam_channel::{Receiver, Sender};
+use laminar::{Packet, SocketEvent};
+
+use super::NetworkTransport;
+use crate::{
+    error::Result,
+    into_laminar_packet,
+    net_event::{DeliveryGuarantee, OrderingGuarantee},
+};
+
+/// The receiving side of a transport bound on a `LoopbackNetwork`.
+struct Endpoint {
+    events: Sender<SocketEvent>,
+    // the addresses this endpoint received packets from before.
+    peers: HashSet<SocketAddr>,
+}
+
+/// An in-process network on which `LoopbackTransport`s exchange packets without sockets.
+///
+/// Packets are delivered immediately and in order, which makes tests running a client and a server
+/// in the same process deterministic. Cloning the network returns a handle to the same network.
+#[derive(Clone, Default)]
+pub struct LoopbackNetwork {
+    endpoints: Arc<Mutex<HashMap<SocketAddr, Endpoint>>>,
+}
+
+impl std::fmt::Debug for LoopbackNetwork {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let endpoints = self
+            .endpoints
+            .lock()
+            .expect("Loopback network lock poisoned");
+
+        f.debug_struct("LoopbackNetwork")
+            .field("endpoints", &endpoints.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl LoopbackNetwork {
+    /// Creates a new, empty network.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Binds a transport on the given address of this network.
+    ///
+    /// Returns an error if a transport is bound on this address already.
+    pub fn bind(&self, addr: SocketAddr) -> Result<LoopbackTransport> {
+        let mut endpoints = self
+            .endpoints
+            .lock()
+            .expect("Loopback network lock poisoned");
+
+        if endpoints.contains_key(&addr) {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("{} is bound on the loopback network already", addr),
+            )
+            .into());
+        }
+
+        let (events, receiver) = crossbeam_channel::unbounded();
+        endpoints.insert(
+            addr,
+            Endpoint {
+                events,
+                peers: HashSet::new(),
+            },
+        );
+
+        Ok(LoopbackTransport {
+            addr,
+            network: self.clone(),
+            receiver,
+        })
+    }
+}
+
+/// A transport bound on a `LoopbackNetwork`.
+///
+/// The first packet sent to a transport by some address is preceded by a `SocketEvent::Connect`,
+/// the way laminar reports new remote ends. Packets to addresses nothing is bound on are dropped.
+/// Dropping the transport unbinds its address.
+#[allow(missing_debug_implementations)]
+pub struct LoopbackTransport {
+    addr: SocketAddr,
+    network: LoopbackNetwork,
+    receiver: Receiver<SocketEvent>,
+}
+
+impl LoopbackTransport {
+    /// Returns the address this transport is bound on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl NetworkTransport for LoopbackTransport {
+    fn send(&mut self, packet: Packet) -> Result<()> {
+        let mut endpoints = self
+            .network
+            .endpoints
+            .lock()
+            .expect("Loopback network lock poisoned");
+
+        let endpoint = match endpoints.get_mut(&packet.addr()) {
+            Some(endpoint) => endpoint,
+            None => return Ok(()),
+        };
+
+        // A receiving transport which is being dropped ignores the packet, like an unbound UDP port.
+        if endpoint.peers.insert(self.addr) {
+            let _ = endpoint.events.send(SocketEvent::Connect(self.addr));
+        }
+
+        let delivered = into_laminar_packet(
+            self.addr,
+            packet.payload().to_vec(),
+            DeliveryGuarantee::from(packet.delivery_guarantee()),
+            OrderingGuarantee::from(packet.order_guarantee()),
+        );
+        let _ = endpoint.events.send(SocketEvent::Packet(delivered));
+
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Option<SocketEvent> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+impl Drop for LoopbackTransport {
+    fn drop(&mut self) {
+        if let Ok(mut endpoints) = self.network.endpoints.lock() {
+            endpoints.remove(&self.addr);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LoopbackNetwork;
+    use crate::transport::NetworkTransport;
+    use laminar::{Packet, SocketEvent};
+    use std::net::SocketAddr;
+
+    #[test]
+    fn first_packet_is_preceded_by_connect() {
+        let network = LoopbackNetwork::new();
+        let client_addr: SocketAddr = "127.0.0.1:1".parse().unwrap();
+        let server_addr: SocketAddr = "127.0.0.1:2".parse().unwrap();
+
+        let mut client = network.bind(client_addr).unwrap();
+        let mut server = network.bind(server_addr).unwrap();
+
+        client
+            .send(Packet::reliable_ordered(server_addr, vec![1], None))
+            .unwrap();
+        client
+            .send(Packet::reliable_ordered(server_addr, vec![2], None))
+            .unwrap();
+
+        match server.receive() {
+            Some(SocketEvent::Connect(addr)) => assert_eq!(addr, client_addr),
+            _ => panic!("Expected a connect event"),
+        }
+        for payload in &[1, 2] {
+            match server.receive() {
+                Some(SocketEvent::Packet(packet)) => {
+                    assert_eq!(packet.addr(), client_addr);
+                    assert_eq!(packet.payload(), &[*payload]);
+                }
+                _ => panic!("Expected a packet"),
+            }
+        }
+        assert!(server.receive().is_none());
+    }
+
+    #[test]
+    fn address_can_only_be_bound_once() {
+        let network = LoopbackNetwork::new();
+        let addr: SocketAddr = "127.0.0.1:1".parse().unwrap();
+
+        let transport = network.bind(addr).unwrap();
+        assert!(network.bind(addr).is_err());
+
+        drop(transport);
+        assert!(network.bind(addr).is_ok());
+    }
+}
@@ -0,0 +1,45 @@
+//! The transports over which the `NetSocketSystem` exchanges packets with remote ends.
+//!
+//! - `Host`: UDP through laminar, which is used by default.
+//! - `TcpTransport`: length-prefixed frames over TCP, for environments where UDP is blocked.
+//! - `LoopbackTransport`: an in-process transport, to run clients and a server in the same process without sockets.
+//!
+//! Any transport can be wrapped in a `ConditionedTransport` to simulate latency, jitter, loss, duplication and reordering.
+
+pub use self::{
+    conditions::{ConditionedTransport, NetworkConditions},
+    loopback::{LoopbackNetwork, LoopbackTransport},
+    tcp::TcpTransport,
+};
+
+use laminar::{Packet, SocketEvent};
+
+use crate::error::Result;
+
+mod conditions;
+mod loopback;
+mod tcp;
+
+/// A transport moving packets between this end and remote ends.
+///
+/// The `NetSocketSystem` sends the packets of all connections through its transport and reads
+/// the received packets, connects and timeouts from it every frame.
+/// Transports may drop the delivery and ordering guarantees of a packet if the medium already
+/// guarantees them, which is why the guarantees of the received packets should not be relied upon.
+pub trait NetworkTransport: Send {
+    /// Sends the packet to the address it carries.
+    fn send(&mut self, packet: Packet) -> Result<()>;
+
+    /// Returns the next event received by this transport, or `None` if there is no event pending.
+    fn receive(&mut self) -> Option<SocketEvent>;
+}
+
+impl NetworkTransport for Box<dyn NetworkTransport> {
+    fn send(&mut self, packet: Packet) -> Result<()> {
+        (**self).send(packet)
+    }
+
+    fn receive(&mut self) -> Option<SocketEvent> {
+        (**self).receive()
+    }
+}
@@ -0,0 +1,365 @@
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crossbeam_channel::{Receiver, Sender};
+use laminar::{Packet, SocketEvent};
+use log::error;
+
+use super::NetworkTransport;
+use crate::error::Result;
+
+/// The largest frame accepted from a remote end, larger frames close the connection.
+const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// The time the accepting thread waits before it checks for new connections again.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(10);
+
+/// An open connection, whose frames are written by its own thread.
+struct Peer {
+    // tells apart the connections opened to the same address one after another.
+    id: u64,
+    frames: Sender<Vec<u8>>,
+}
+
+#[derive(Clone, Default)]
+struct Peers {
+    peers: Arc<Mutex<HashMap<SocketAddr, Peer>>>,
+    next_id: Arc<Mutex<u64>>,
+}
+
+impl Peers {
+    /// Adds a connection to the given address and returns its id and the receiving end of its frames.
+    fn insert(&self, addr: SocketAddr) -> (u64, Receiver<Vec<u8>>) {
+        let id = {
+            let mut next_id = self.next_id.lock().expect("TCP peers lock poisoned");
+            *next_id += 1;
+            *next_id
+        };
+
+        let (frames, receiver) = crossbeam_channel::unbounded();
+        self.peers
+            .lock()
+            .expect("TCP peers lock poisoned")
+            .insert(addr, Peer { id, frames });
+        (id, receiver)
+    }
+
+    /// Removes the connection with the given id, if it was not replaced by a newer one.
+    fn remove(&self, addr: SocketAddr, id: u64) {
+        let mut peers = self.peers.lock().expect("TCP peers lock poisoned");
+        if peers.get(&addr).map_or(false, |peer| peer.id == id) {
+            peers.remove(&addr);
+        }
+    }
+}
+
+/// A transport sending every packet as a length-prefixed frame over TCP.
+///
+/// A TCP connection is opened the first time a packet is sent to an address, and connections
+/// opened by remote ends are accepted on the address this transport is bound on.
+/// Accepted connections are reported with a `SocketEvent::Connect`, closed ones and
+/// connections which could not be opened with a `SocketEvent::Timeout`.
+///
+/// Connecting, reading and writing happen on a thread per connection, so sending never blocks.
+/// Dropping the transport stops accepting connections and closes all open ones.
+///
+/// Since TCP is reliable and ordered all received packets are reliable ordered,
+/// regardless of the guarantees they were sent with.
+#[allow(missing_debug_implementations)]
+pub struct TcpTransport {
+    local_addr: SocketAddr,
+    peers: Peers,
+    event_sender: Sender<SocketEvent>,
+    event_receiver: Receiver<SocketEvent>,
+    running: Arc<AtomicBool>,
+    accept_thread: Option<JoinHandle<()>>,
+}
+
+impl TcpTransport {
+    /// Binds the transport on the given address and starts accepting connections.
+    pub fn bind(addr: SocketAddr) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        // polled, so the accepting thread notices when the transport is dropped.
+        listener.set_nonblocking(true)?;
+
+        let (event_sender, event_receiver) = crossbeam_channel::unbounded();
+        let peers = Peers::default();
+        let running = Arc::new(AtomicBool::new(true));
+
+        let accept_thread = {
+            let peers = peers.clone();
+            let event_sender = event_sender.clone();
+            let running = running.clone();
+
+            thread::spawn(move || {
+                while running.load(Ordering::Relaxed) {
+                    let accepted = listener.accept().and_then(|(stream, addr)| {
+                        stream.set_nonblocking(false)?;
+                        open(&peers, &event_sender, addr, Some(stream));
+                        Ok(addr)
+                    });
+
+                    match accepted {
+                        Ok(addr) => {
+                            let _ = event_sender.send(SocketEvent::Connect(addr));
+                        }
+                        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                            thread::sleep(ACCEPT_INTERVAL);
+                        }
+                        Err(e) => error!("Failed to accept a TCP connection: {}", e),
+                    }
+                }
+            })
+        };
+
+        Ok(TcpTransport {
+            local_addr,
+            peers,
+            event_sender,
+            event_receiver,
+            running,
+            accept_thread: Some(accept_thread),
+        })
+    }
+
+    /// Returns the address this transport is bound on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl NetworkTransport for TcpTransport {
+    fn send(&mut self, packet: Packet) -> Result<()> {
+        let addr = packet.addr();
+        if packet.payload().len() > MAX_FRAME_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("A frame of {} bytes is too large", packet.payload().len()),
+            )
+            .into());
+        }
+
+        let connected = self
+            .peers
+            .peers
+            .lock()
+            .expect("TCP peers lock poisoned")
+            .contains_key(&addr);
+        if !connected {
+            open(&self.peers, &self.event_sender, addr, None);
+        }
+
+        let peers = self.peers.peers.lock().expect("TCP peers lock poisoned");
+        let sent = peers.get(&addr).map_or(false, |peer| {
+            peer.frames.send(packet.payload().to_vec()).is_ok()
+        });
+
+        if !sent {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                format!("The TCP connection to {} was closed", addr),
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Option<SocketEvent> {
+        self.event_receiver.try_recv().ok()
+    }
+}
+
+impl Drop for TcpTransport {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(accept_thread) = self.accept_thread.take() {
+            if accept_thread.join().is_err() {
+                error!("The thread accepting TCP connections panicked");
+            }
+        }
+
+        // the writing threads close their connection once their frames channel is dropped.
+        self.peers
+            .peers
+            .lock()
+            .expect("TCP peers lock poisoned")
+            .clear();
+    }
+}
+
+/// Adds a connection to the given address and starts writing and reading frames on it.
+///
+/// The connection is opened by the writing thread if no accepted stream is given.
+fn open(peers: &Peers, events: &Sender<SocketEvent>, addr: SocketAddr, stream: Option<TcpStream>) {
+    let (id, frames) = peers.insert(addr);
+    let peers = peers.clone();
+    let events = events.clone();
+
+    thread::spawn(move || {
+        let stream = match stream {
+            Some(stream) => Ok(stream),
+            None => TcpStream::connect(addr),
+        };
+        let mut stream =
+            match stream.and_then(|stream| start_reading(&peers, &events, addr, id, stream)) {
+                Ok(stream) => stream,
+                Err(e) => {
+                    error!("Failed to open a TCP connection to {}: {}", addr, e);
+                    peers.remove(addr, id);
+                    let _ = events.send(SocketEvent::Timeout(addr));
+                    return;
+                }
+            };
+
+        for frame in frames.iter() {
+            if let Err(e) = write_frame(&mut stream, &frame) {
+                error!("Failed to send a frame to {}: {}", addr, e);
+                break;
+            }
+        }
+
+        // ends the reading thread, which reports the closed connection.
+        let _ = stream.shutdown(Shutdown::Both);
+    });
+}
+
+/// Starts reading frames from the stream on a new thread and returns the stream to write to.
+fn start_reading(
+    peers: &Peers,
+    events: &Sender<SocketEvent>,
+    addr: SocketAddr,
+    id: u64,
+    stream: TcpStream,
+) -> io::Result<TcpStream> {
+    stream.set_nodelay(true)?;
+    let mut reader = stream.try_clone()?;
+    let peers = peers.clone();
+    let events = events.clone();
+
+    thread::spawn(move || loop {
+        match read_frame(&mut reader) {
+            Ok(payload) => {
+                let packet = Packet::reliable_ordered(addr, payload, None);
+                if events.send(SocketEvent::Packet(packet)).is_err() {
+                    break;
+                }
+            }
+            Err(_) => {
+                peers.remove(addr, id);
+                let _ = events.send(SocketEvent::Timeout(addr));
+                break;
+            }
+        }
+    });
+
+    Ok(stream)
+}
+
+/// Writes the payload prefixed with its length as big endian `u32`.
+fn write_frame<W: Write>(writer: &mut W, payload: &[u8]) -> io::Result<()> {
+    if payload.len() > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("A frame of {} bytes is too large", payload.len()),
+        ));
+    }
+
+    writer.write_all(&(payload.len() as u32).to_be_bytes())?;
+    writer.write_all(payload)?;
+    writer.flush()
+}
+
+/// Reads a single length-prefixed frame.
+fn read_frame<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut length = [0; 4];
+    reader.read_exact(&mut length)?;
+
+    let length = u32::from_be_bytes(length) as usize;
+    if length > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("A frame of {} bytes is too large", length),
+        ));
+    }
+
+    let mut payload = vec![0; length];
+    reader.read_exact(&mut payload)?;
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_frame, write_frame, TcpTransport, MAX_FRAME_SIZE};
+    use crate::transport::NetworkTransport;
+    use laminar::{Packet, SocketEvent};
+    use std::{io::Cursor, thread::sleep, time::Duration};
+
+    #[test]
+    fn can_roundtrip_frames() {
+        let mut buffer = Vec::new();
+        write_frame(&mut buffer, b"abc").unwrap();
+        write_frame(&mut buffer, b"").unwrap();
+
+        let mut reader = Cursor::new(buffer);
+        assert_eq!(read_frame(&mut reader).unwrap(), b"abc".to_vec());
+        assert_eq!(read_frame(&mut reader).unwrap(), Vec::<u8>::new());
+        assert!(read_frame(&mut reader).is_err());
+    }
+
+    #[test]
+    fn oversized_frames_are_rejected() {
+        let length = (MAX_FRAME_SIZE as u32 + 1).to_be_bytes();
+        assert!(read_frame(&mut Cursor::new(length.to_vec())).is_err());
+    }
+
+    #[test]
+    fn packets_are_exchanged_both_ways() {
+        let mut client = TcpTransport::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let mut server = TcpTransport::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+
+        client
+            .send(Packet::unreliable(server.local_addr(), vec![1, 2, 3]))
+            .unwrap();
+        let request = next_packet(&mut server);
+        assert_eq!(request.payload(), &[1, 2, 3]);
+
+        // answered over the connection the client opened.
+        server
+            .send(Packet::unreliable(request.addr(), vec![4, 5]))
+            .unwrap();
+        let response = next_packet(&mut client);
+        assert_eq!(response.addr(), server.local_addr());
+        assert_eq!(response.payload(), &[4, 5]);
+    }
+
+    #[test]
+    fn dropping_the_transport_releases_its_address() {
+        let transport = TcpTransport::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = transport.local_addr();
+
+        drop(transport);
+        assert!(TcpTransport::bind(addr).is_ok());
+    }
+
+    fn next_packet(transport: &mut TcpTransport) -> Packet {
+        for _ in 0..500 {
+            match transport.receive() {
+                Some(SocketEvent::Packet(packet)) => return packet,
+                Some(_) => {}
+                None => sleep(Duration::from_millis(10)),
+            }
+        }
+        panic!("No packet was received");
+    }
+}
@@ -11,10 +11,13 @@ use crate::{
     types::{Backend, Mesh, Texture},
     visibility::Visibility,
 };
-use amethyst_assets::{AssetStorage, Handle, HotReloadStrategy, ProcessingState, ThreadPool};
+use amethyst_assets::{
+    AssetEvent, AssetStorage, Handle, HotReloadStrategy, ProcessingState, ThreadPool,
+};
 use amethyst_core::{
     components::Transform,
     ecs::{Read, ReadExpect, ReadStorage, RunNow, System, SystemData, World, Write, WriteExpect},
+    shrev::EventChannel,
     timing::Time,
     Hidden, HiddenPropagate,
 };
@@ -194,16 +197,18 @@ impl<'a, B: Backend> System<'a> for MeshProcessor<B> {
         ReadExpect<'a, Arc<ThreadPool>>,
         Option<Read<'a, HotReloadStrategy>>,
         ReadExpect<'a, Factory<B>>,
+        Write<'a, EventChannel<AssetEvent<Mesh>>>,
     );
 
     fn run(
         &mut self,
-        (mut mesh_storage, queue_id, time, pool, strategy, factory): Self::SystemData,
+        (mut mesh_storage, queue_id, time, pool, strategy, factory, mut events): Self::SystemData,
     ) {
         #[cfg(feature = "profiler")]
         profile_scope!("mesh_processor");
 
         use std::ops::Deref;
+        mesh_storage.track_events();
         mesh_storage.process(
             |b| {
                 #[cfg(feature = "profiler")]
@@ -218,6 +223,7 @@ impl<'a, B: Backend> System<'a> for MeshProcessor<B> {
             &**pool,
             strategy.as_ref().map(Deref::deref),
         );
+        mesh_storage.write_events(&mut events);
     }
 }
 
@@ -233,16 +239,18 @@ impl<'a, B: Backend> System<'a> for TextureProcessor<B> {
         ReadExpect<'a, Arc<ThreadPool>>,
         Option<Read<'a, HotReloadStrategy>>,
         WriteExpect<'a, Factory<B>>,
+        Write<'a, EventChannel<AssetEvent<Texture>>>,
     );
 
     fn run(
         &mut self,
-        (mut texture_storage, queue_id, time, pool, strategy, mut factory): Self::SystemData,
+        (mut texture_storage, queue_id, time, pool, strategy, mut factory, mut events): Self::SystemData,
     ) {
         #[cfg(feature = "profiler")]
         profile_scope!("texture_processor");
 
         use std::ops::Deref;
+        texture_storage.track_events();
         texture_storage.process(
             |b| {
                 #[cfg(feature = "profiler")]
@@ -266,6 +274,7 @@ impl<'a, B: Backend> System<'a> for TextureProcessor<B> {
             &**pool,
             strategy.as_ref().map(Deref::deref),
         );
+        texture_storage.write_events(&mut events);
     }
 }
 
@@ -56,6 +56,28 @@ macro_rules! impl_backends {
             )*
         }
 
+        impl Mesh {
+            fn estimated_size(&self) -> usize {
+                match *self {
+                    $(
+                        #[cfg(feature = $feature)]
+                        Mesh::$variant(ref mesh) => mesh_size(mesh),
+                    )*
+                }
+            }
+        }
+
+        impl Texture {
+            fn estimated_size(&self) -> usize {
+                match *self {
+                    $(
+                        #[cfg(feature = $feature)]
+                        Texture::$variant(ref texture) => texture_size(texture),
+                    )*
+                }
+            }
+        }
+
         $(
             #[cfg(feature = $feature)]
             impl Backend for $backend {
@@ -122,12 +144,51 @@ impl Asset for Mesh {
     const NAME: &'static str = "Mesh";
     type Data = MeshData;
     type HandleStorage = DenseVecStorage<Handle<Self>>;
+
+    fn size_hint(&self) -> usize {
+        self.estimated_size()
+    }
 }
 
 impl Asset for Texture {
-    const NAME: &'static str = "Mesh";
+    const NAME: &'static str = "Texture";
     type Data = TextureData;
     type HandleStorage = DenseVecStorage<Handle<Self>>;
+
+    fn size_hint(&self) -> usize {
+        self.estimated_size()
+    }
+}
+
+/// Estimates the buffer memory of a mesh from its number of vertices or indices.
+///
+/// The vertex layouts of a built mesh are unknown, so every vertex is assumed to have
+/// a position, normal, tangent and texture coordinates.
+fn mesh_size<B: rendy::hal::Backend>(mesh: &rendy::mesh::Mesh<B>) -> usize {
+    let len = mesh.len() as usize;
+    let indices = match mesh.index_type() {
+        Some(rendy::hal::IndexType::U16) => len * 2,
+        Some(rendy::hal::IndexType::U32) => len * 4,
+        None => 0,
+    };
+    std::mem::size_of_val(mesh) + indices + len * std::mem::size_of::<rendy::mesh::PosNormTangTex>()
+}
+
+/// Computes the image memory of a texture from its extent, format, layers and mip levels.
+fn texture_size<B: rendy::hal::Backend>(texture: &rendy::texture::Texture<B>) -> usize {
+    let info = texture.image().info();
+    let desc = info.format.surface_desc();
+    let (block_width, block_height) = (u32::from(desc.dim.0), u32::from(desc.dim.1));
+    let level_bytes = (0..info.levels)
+        .map(|level| {
+            let extent = info.kind.extent().at_level(level);
+            let blocks = ((extent.width + block_width - 1) / block_width)
+                * ((extent.height + block_height - 1) / block_height)
+                * extent.depth;
+            blocks as usize * usize::from(desc.bits) / 8
+        })
+        .sum::<usize>();
+    std::mem::size_of_val(texture) + level_bytes * usize::from(info.kind.num_layers())
 }
 
 /// Newtype for MeshBuilder prefab usage.
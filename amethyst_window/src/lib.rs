@@ -22,7 +22,7 @@ pub use crate::{
     bundle::WindowBundle,
     config::DisplayConfig,
     monitor::{MonitorIdent, MonitorsAccess},
-    resources::ScreenDimensions,
+    resources::{ScreenDimensions, SuppressInput},
     system::{EventsLoopSystem, WindowSystem},
 };
 pub use winit::{Icon, Window};
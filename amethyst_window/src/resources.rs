@@ -1,5 +1,12 @@
 use amethyst_core::math::Vector2;
 
+/// World resource that stops the `EventsLoopSystem` from forwarding input events while `true`.
+///
+/// The events are still polled, and the other window events like `CloseRequested` are still
+/// forwarded. It is set while a recording is replayed, so live input does not mix with the recorded one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SuppressInput(pub bool);
+
 /// World resource that stores screen dimensions.
 #[derive(Debug, PartialEq, Clone)]
 pub struct ScreenDimensions {
@@ -1,11 +1,14 @@
-use crate::{config::DisplayConfig, resources::ScreenDimensions};
+use crate::{
+    config::DisplayConfig,
+    resources::{ScreenDimensions, SuppressInput},
+};
 use amethyst_config::Config;
 use amethyst_core::{
     ecs::{ReadExpect, RunNow, System, SystemData, World, Write, WriteExpect},
     shrev::EventChannel,
 };
 use std::path::Path;
-use winit::{Event, EventsLoop, Window};
+use winit::{Event, EventsLoop, Window, WindowEvent};
 
 /// System for opening and managing the window.
 #[derive(Debug)]
@@ -87,6 +90,8 @@ impl<'a> System<'a> for WindowSystem {
 ///
 /// This system must be active for any `GameState` to receive
 /// any `StateEvent::Window` event into it's `handle_event` method.
+///
+/// Input events are dropped while the `SuppressInput` resource is set.
 #[derive(Debug)]
 pub struct EventsLoopSystem {
     events_loop: EventsLoop,
@@ -107,9 +112,15 @@ impl<'a> RunNow<'a> for EventsLoopSystem {
     fn run_now(&mut self, world: &'a World) {
         let mut event_handler = <Write<'a, EventChannel<Event>>>::fetch(world);
 
+        let suppress_input = world
+            .try_fetch::<SuppressInput>()
+            .map_or(false, |suppress| suppress.0);
+
         let events = &mut self.events;
         self.events_loop.poll_events(|event| {
-            events.push(event);
+            if !suppress_input || !is_input(&event) {
+                events.push(event);
+            }
         });
         event_handler.drain_vec_write(events);
     }
@@ -118,3 +129,24 @@ impl<'a> RunNow<'a> for EventsLoopSystem {
         <Write<'a, EventChannel<Event>>>::setup(world);
     }
 }
+
+/// Returns `true` for the events caused by the keyboard, mouse and other input devices.
+fn is_input(event: &Event) -> bool {
+    match *event {
+        Event::DeviceEvent { .. } => true,
+        Event::WindowEvent { ref event, .. } => match *event {
+            WindowEvent::ReceivedCharacter(_)
+            | WindowEvent::KeyboardInput { .. }
+            | WindowEvent::CursorMoved { .. }
+            | WindowEvent::CursorEntered { .. }
+            | WindowEvent::CursorLeft { .. }
+            | WindowEvent::MouseWheel { .. }
+            | WindowEvent::MouseInput { .. }
+            | WindowEvent::TouchpadPressure { .. }
+            | WindowEvent::AxisMotion { .. }
+            | WindowEvent::Touch(_) => true,
+            _ => false,
+        },
+        _ => false,
+    }
+}
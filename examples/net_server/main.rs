@@ -79,7 +79,8 @@ impl<'a> System<'a> for SpamReceiveSystem {
                 match ev {
                     NetEvent::Packet(packet) => info!("{}", packet.content()),
                     NetEvent::Connected(addr) => info!("New Client Connection: {}", addr),
-                    NetEvent::Disconnected(_addr) => {
+                    NetEvent::Disconnected { addr, reason } => {
+                        info!("Client {} disconnected: {:?}", addr, reason);
                         client_disconnected = true;
                     }
                     _ => {}
@@ -25,7 +25,8 @@ use crate::{
     ecs::prelude::{Component, Read, World, WorldExt, Write},
     error::Error,
     game_data::{DataDispose, DataInit},
-    state::{State, StateData, StateMachine, TransEvent},
+    replay::{Recording, Replay},
+    state::{State, StateData, StateMachine, Trans, TransEvent},
     state_event::{StateEvent, StateEventReader},
     ui::UiEvent,
 };
@@ -60,9 +61,51 @@ where
     trans_reader_id: ReaderId<TransEvent<T, E>>,
     states: StateMachine<'a, T, E>,
     ignore_window_close: bool,
+    #[derivative(Debug = "ignore")]
+    replay: Option<Replay>,
+    // the transitions a replay holds back until the frame they were recorded in.
+    #[derivative(Debug = "ignore")]
+    held_transitions: Vec<Trans<T, E>>,
+    limits: RunLimits,
     data: T,
 }
 
+/// Why `CoreApplication::run_with_status` returned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// The game state stopped running, or the window was closed.
+    Stopped,
+    /// The number of frames set with `ApplicationBuilder::with_max_frames` ran.
+    FrameLimit,
+    /// The stop condition set with `ApplicationBuilder::with_stop_condition` returned a code.
+    Condition(i32),
+    /// The replay set with `ApplicationBuilder::with_replay` diverged from the recording
+    /// in the given frame.
+    ReplayDesync(usize),
+}
+
+impl ExitStatus {
+    /// The exit code of the process, `0` unless the stop condition returned another code
+    /// or the replay desynced, which exits with `1`.
+    pub fn code(self) -> i32 {
+        match self {
+            ExitStatus::Stopped | ExitStatus::FrameLimit => 0,
+            ExitStatus::Condition(code) => code,
+            ExitStatus::ReplayDesync(_) => 1,
+        }
+    }
+}
+
+/// The fixed step and limits of a run, set on the `ApplicationBuilder`.
+#[derive(Derivative, Default)]
+#[derivative(Debug)]
+struct RunLimits {
+    step: Option<Duration>,
+    max_frames: Option<u64>,
+    #[derivative(Debug = "ignore")]
+    stop_condition: Option<Box<dyn FnMut(&World) -> Option<i32>>>,
+}
+
 /// An Application is the root object of the game engine. It binds the OS
 /// event loop, state machines, timers and other core components in a central place.
 ///
@@ -214,6 +257,11 @@ where
         ApplicationBuilder::new(path, initial_state)
     }
 
+    /// Returns the state machine, to inspect the state stack.
+    pub fn state_machine(&self) -> &StateMachine<'a, T, E> {
+        &self.states
+    }
+
     /// Run the gameloop until the game state indicates that the game is no
     /// longer running. This is done via the `State` returning `Trans::Quit` or
     /// `Trans::Pop` on the last state in from the stack. See full
@@ -224,6 +272,39 @@ where
     /// See the example supplied in the
     /// [`new`](struct.Application.html#examples) method.
     pub fn run(&mut self)
+    where
+        for<'b> R: EventReader<'b, Event = E>,
+    {
+        self.run_with_status();
+    }
+
+    /// Run the gameloop like `run`, and return why it stopped.
+    ///
+    /// Besides the game state, the loop is stopped by the frame limit and stop condition set
+    /// on the `ApplicationBuilder`, in which case the states are stopped after the last frame.
+    ///
+    /// # Examples
+    ///
+    /// ~~~no_run
+    /// use std::time::Duration;
+    ///
+    /// use amethyst::prelude::*;
+    ///
+    /// struct NullState;
+    /// impl EmptyState for NullState {}
+    ///
+    /// # fn main() -> amethyst::Result<()> {
+    /// let step = Duration::from_millis(16);
+    /// let mut game = Application::build("assets/", NullState)?
+    ///     .with_fixed_step_length(step)
+    ///     .with_simulation_step(step)
+    ///     .with_max_frames(600)
+    ///     .build(())?;
+    /// let status = game.run_with_status();
+    /// std::process::exit(status.code());
+    /// # }
+    /// ~~~
+    pub fn run_with_status(&mut self) -> ExitStatus
     where
         for<'b> R: EventReader<'b, Event = E>,
     {
@@ -238,31 +319,71 @@ where
 
         self.initialize();
         self.world.write_resource::<Stopwatch>().start();
-        while self.states.is_running() {
+        let mut frames = 0;
+        let status = loop {
+            if !self.states.is_running() {
+                break ExitStatus::Stopped;
+            }
             self.advance_frame();
-            {
+            if self.limits.step.is_none() {
                 #[cfg(feature = "profiler")]
                 profile_scope!("frame_limiter wait");
                 self.world.write_resource::<FrameLimiter>().wait();
             }
             {
-                let elapsed = self.world.read_resource::<Stopwatch>().elapsed();
+                let mut elapsed = match self.limits.step {
+                    Some(step) => step,
+                    None => self.world.read_resource::<Stopwatch>().elapsed(),
+                };
+                if let Some(ref mut replay) = self.replay {
+                    elapsed = replay.end_frame(&self.world, elapsed);
+                }
                 let mut time = self.world.write_resource::<Time>();
                 time.increment_frame_number();
                 time.set_delta_time(elapsed);
             }
-            let mut stopwatch = self.world.write_resource::<Stopwatch>();
-            stopwatch.stop();
-            stopwatch.restart();
-        }
+            {
+                let mut stopwatch = self.world.write_resource::<Stopwatch>();
+                stopwatch.stop();
+                stopwatch.restart();
+            }
+
+            frames += 1;
+            if !self.states.is_running() {
+                continue;
+            }
+            let status = self
+                .replay
+                .as_ref()
+                .and_then(Replay::desync)
+                .map(ExitStatus::ReplayDesync);
+            let status = status.or_else(|| match self.limits.stop_condition {
+                Some(ref mut condition) => condition(&self.world).map(ExitStatus::Condition),
+                None => None,
+            });
+            let status = status.or_else(|| match self.limits.max_frames {
+                Some(max_frames) if frames >= max_frames => Some(ExitStatus::FrameLimit),
+                _ => None,
+            });
+            if let Some(status) = status {
+                info!("Stopping after {} frames: {:?}", frames, status);
+                self.states
+                    .stop(StateData::new(&mut self.world, &mut self.data));
+                break status;
+            }
+        };
 
         self.shutdown();
+        status
     }
 
     /// Sets up the application.
     fn initialize(&mut self) {
         #[cfg(feature = "profiler")]
         profile_scope!("initialize");
+        if let Some(ref mut replay) = self.replay {
+            replay.initialize(&mut self.world);
+        }
         self.states
             .start(StateData::new(&mut self.world, &mut self.data))
             .expect("Tried to start state machine without any states present");
@@ -308,7 +429,11 @@ where
         for<'b> R: EventReader<'b, Event = E>,
     {
         trace!("Advancing frame (`Application::advance_frame`)");
-        if self.should_close() {
+        let replay_finished = self.replay.as_ref().map_or(false, Replay::is_finished);
+        if replay_finished {
+            info!("Replay finished");
+        }
+        if self.should_close() || replay_finished {
             let world = &mut self.world;
             let states = &mut self.states;
             states.stop(StateData::new(world, &mut self.data));
@@ -320,11 +445,14 @@ where
             let states = &mut self.states;
             let reader = &mut self.trans_reader_id;
 
-            let trans = world
+            let mut trans = world
                 .read_resource::<EventChannel<TransEvent<T, E>>>()
                 .read(reader)
                 .map(|e| e())
                 .collect::<Vec<_>>();
+            if let Some(ref mut replay) = self.replay {
+                trans = replay.transitions(&mut self.held_transitions, trans);
+            }
             for tr in trans {
                 states.transition(tr, StateData::new(&mut world, &mut self.data));
             }
@@ -357,6 +485,9 @@ where
                 }
             }
         }
+        if let Some(ref mut replay) = self.replay {
+            replay.send_events(&self.world);
+        }
         {
             #[cfg(feature = "profiler")]
             profile_scope!("fixed_update");
@@ -417,6 +548,8 @@ pub struct ApplicationBuilder<S, T, E, R> {
     /// Used by bundles to access the world directly
     pub world: World,
     ignore_window_close: bool,
+    replay: Option<Replay>,
+    limits: RunLimits,
     phantom: PhantomData<(T, E, R)>,
 }
 
@@ -557,6 +690,8 @@ where
             initial_state,
             world,
             ignore_window_close: false,
+            replay: None,
+            limits: RunLimits::default(),
             phantom: PhantomData,
         })
     }
@@ -854,6 +989,97 @@ where
         self
     }
 
+    /// Runs the application as a simulation, advancing `Time` by `step` every frame.
+    ///
+    /// The frame limiter does not wait, so frames run as fast as possible and the `Time` deltas
+    /// do not depend on the speed of the machine. Together with `with_fixed_step_length` set to
+    /// the same duration, every frame runs exactly one fixed update. Leave out the window and
+    /// render bundles to run headless, e.g. for dedicated servers or in CI.
+    ///
+    /// # Parameters
+    ///
+    /// `step`: The delta time of every frame.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    pub fn with_simulation_step(mut self, step: Duration) -> Self {
+        self.limits.step = Some(step);
+        self
+    }
+
+    /// Stops the application after the given number of frames, with `ExitStatus::FrameLimit`.
+    ///
+    /// # Parameters
+    ///
+    /// `frames`: The number of frames to run.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    pub fn with_max_frames(mut self, frames: u64) -> Self {
+        self.limits.max_frames = Some(frames);
+        self
+    }
+
+    /// Stops the application once `condition` returns an exit code after a frame, with
+    /// `ExitStatus::Condition`.
+    ///
+    /// # Parameters
+    ///
+    /// `condition`: Checks the world after every frame.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    pub fn with_stop_condition<F>(mut self, condition: F) -> Self
+    where
+        F: FnMut(&World) -> Option<i32> + 'static,
+    {
+        self.limits.stop_condition = Some(Box::new(condition));
+        self
+    }
+
+    /// Records the frames of the application to a file, to replay them with `with_replay`.
+    ///
+    /// Every frame, the winit events sent to `EventChannel<Event>`, the `Time` delta and the
+    /// number of `TransEvent`s are recorded. The file is written in the RON format once the
+    /// application is dropped, which also happens when it panics.
+    ///
+    /// # Parameters
+    ///
+    /// `path`: The file to write the `Recording` to.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    pub fn with_recording<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.replay = Some(Replay::record(path.as_ref().to_owned()));
+        self
+    }
+
+    /// Replays a `Recording` made with `with_recording`.
+    ///
+    /// The recorded events are sent to `EventChannel<Event>` before the states are updated, and
+    /// the `Time` delta of every frame is the recorded one instead of the measured one. Input
+    /// events of the window are suppressed while replaying, see `SuppressInput`. The
+    /// `TransEvent`s sent during the replay are applied in the frames they were recorded in.
+    ///
+    /// The application stops once all frames were replayed, or with `ExitStatus::ReplayDesync`
+    /// when a recorded transition was not sent again.
+    ///
+    /// # Parameters
+    ///
+    /// `recording`: The recording to replay, see `Recording` for loading it from a file.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    pub fn with_replay(mut self, recording: Recording) -> Self {
+        self.replay = Some(Replay::play(recording));
+        self
+    }
+
     /// Build an `Application` object using the `ApplicationBuilder` as configured.
     ///
     /// # Returns
@@ -906,9 +1132,58 @@ where
             reader,
             events: Vec::new(),
             ignore_window_close: self.ignore_window_close,
+            replay: self.replay,
+            held_transitions: Vec::new(),
+            limits: self.limits,
             data,
             event_reader_id,
             trans_reader_id,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::ExitStatus;
+    use crate::{core::Time, prelude::*};
+
+    struct NullState;
+    impl EmptyState for NullState {}
+
+    #[test]
+    fn simulation_stops_after_max_frames() {
+        let mut app = Application::build(".", NullState)
+            .unwrap()
+            .with_simulation_step(Duration::from_millis(10))
+            .with_max_frames(3)
+            .build(())
+            .unwrap();
+
+        assert_eq!(app.run_with_status(), ExitStatus::FrameLimit);
+        let time = app.world.read_resource::<Time>();
+        assert_eq!(time.frame_number(), 3);
+        assert_eq!(time.delta_real_time(), Duration::from_millis(10));
+    }
+
+    #[test]
+    fn simulation_stops_on_condition() {
+        let mut app = Application::build(".", NullState)
+            .unwrap()
+            .with_simulation_step(Duration::from_millis(10))
+            .with_stop_condition(|world| {
+                if world.read_resource::<Time>().frame_number() == 2 {
+                    Some(3)
+                } else {
+                    None
+                }
+            })
+            .build(())
+            .unwrap();
+
+        let status = app.run_with_status();
+        assert_eq!(status, ExitStatus::Condition(3));
+        assert_eq!(status.code(), 3);
+    }
+}
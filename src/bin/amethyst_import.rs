@@ -0,0 +1,52 @@
+//! Imports the textures and meshes of an asset directory ahead of time.
+//!
+//! Usage: `amethyst_import <assets dir> <cache dir>`
+//!
+//! A game using the same cache with `Loader::set_import_cache` loads the prebuilt data
+//! instead of decoding the files.
+
+use std::{env, process};
+
+use amethyst::{
+    assets::{ImportCache, ImportPipeline},
+    error::Error,
+    renderer::{
+        formats::{mesh::ObjFormat, texture::ImageFormat},
+        types::{MeshData, TextureData},
+    },
+};
+use log::{error, info};
+
+fn main() -> Result<(), Error> {
+    amethyst::start_logger(Default::default());
+
+    let mut args = env::args().skip(1);
+    let (assets_dir, cache_dir) = match (args.next(), args.next(), args.next()) {
+        (Some(assets_dir), Some(cache_dir), None) => (assets_dir, cache_dir),
+        _ => {
+            eprintln!("Usage: amethyst_import <assets dir> <cache dir>");
+            process::exit(2);
+        }
+    };
+
+    let report = ImportPipeline::new(ImportCache::new(cache_dir))
+        .with_format::<TextureData, _>("png", "TextureData", ImageFormat::default())
+        .with_format::<TextureData, _>("jpg", "TextureData", ImageFormat::default())
+        .with_format::<MeshData, _>("obj", "MeshData", ObjFormat)
+        .build_directory(&assets_dir)?;
+
+    info!(
+        "Imported {} assets, {} were up to date, skipped {} files",
+        report.imported.len(),
+        report.up_to_date.len(),
+        report.skipped.len()
+    );
+    for (name, e) in &report.failed {
+        error!("Failed to import {:?}: {}", name, e);
+    }
+
+    if !report.failed.is_empty() {
+        process::exit(1);
+    }
+    Ok(())
+}
@@ -91,11 +91,12 @@ pub use crate::core::{ecs, shred, shrev};
 pub use crate::derive::*;
 
 pub use self::{
-    app::{Application, ApplicationBuilder, CoreApplication},
+    app::{Application, ApplicationBuilder, CoreApplication, ExitStatus},
     callback_queue::{Callback, CallbackQueue},
     error::Error,
     game_data::{DataDispose, DataInit, GameData, GameDataBuilder},
     logger::{start_logger, LevelFilter as LogLevelFilter, Logger, LoggerConfig, StdoutLog},
+    replay::{RecordedEvent, RecordedFrame, Recording},
     state::{
         EmptyState, EmptyTrans, SimpleState, SimpleTrans, State, StateData, StateMachine, Trans,
         TransEvent,
@@ -112,5 +113,6 @@ mod app;
 mod callback_queue;
 mod game_data;
 mod logger;
+mod replay;
 mod state;
 mod state_event;
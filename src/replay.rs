@@ -0,0 +1,525 @@
+//! Recording and replaying the frames of an application.
+
+use std::{path::PathBuf, time::Duration};
+
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use winit::{
+    dpi::{LogicalPosition, LogicalSize},
+    DeviceEvent, DeviceId, ElementState, Event, KeyboardInput, ModifiersState, MouseButton,
+    MouseScrollDelta, TouchPhase, WindowEvent, WindowId,
+};
+
+use crate::{
+    config::Config,
+    core::{
+        shrev::{EventChannel, ReaderId},
+        Time,
+    },
+    ecs::prelude::{World, WorldExt},
+    window::SuppressInput,
+};
+
+/// A window or device event sent by winit, as stored in a `Recording`.
+///
+/// Only the events used by the engine are recorded, the window and device ids are not.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RecordedEvent {
+    /// `WindowEvent::Resized`
+    Resized(LogicalSize),
+    /// `WindowEvent::Moved`
+    Moved(LogicalPosition),
+    /// `WindowEvent::CloseRequested`
+    CloseRequested,
+    /// `WindowEvent::Destroyed`
+    Destroyed,
+    /// `WindowEvent::ReceivedCharacter`
+    ReceivedCharacter(char),
+    /// `WindowEvent::Focused`
+    Focused(bool),
+    /// `WindowEvent::KeyboardInput`
+    KeyboardInput(KeyboardInput),
+    /// `WindowEvent::CursorMoved`
+    CursorMoved {
+        /// The position of the cursor
+        position: LogicalPosition,
+        /// The modifier keys held
+        modifiers: ModifiersState,
+    },
+    /// `WindowEvent::CursorEntered`
+    CursorEntered,
+    /// `WindowEvent::CursorLeft`
+    CursorLeft,
+    /// `WindowEvent::MouseWheel`
+    MouseWheel {
+        /// The scrolled distance
+        delta: MouseScrollDelta,
+        /// The touch phase of the scroll
+        phase: TouchPhase,
+        /// The modifier keys held
+        modifiers: ModifiersState,
+    },
+    /// `WindowEvent::MouseInput`
+    MouseInput {
+        /// Whether the button was pressed or released
+        state: ElementState,
+        /// The mouse button
+        button: MouseButton,
+        /// The modifier keys held
+        modifiers: ModifiersState,
+    },
+    /// `WindowEvent::HiDpiFactorChanged`
+    HiDpiFactorChanged(f64),
+    /// `DeviceEvent::MouseMotion`
+    MouseMotion {
+        /// The moved distance
+        delta: (f64, f64),
+    },
+    /// `DeviceEvent::MouseWheel`
+    DeviceMouseWheel {
+        /// The scrolled distance
+        delta: MouseScrollDelta,
+    },
+    /// `DeviceEvent::Key`
+    DeviceKey(KeyboardInput),
+}
+
+impl RecordedEvent {
+    /// Converts a winit event, returns `None` for events which are not recorded.
+    pub fn from_event(event: &Event) -> Option<Self> {
+        match *event {
+            Event::WindowEvent { ref event, .. } => match *event {
+                WindowEvent::Resized(size) => Some(RecordedEvent::Resized(size)),
+                WindowEvent::Moved(position) => Some(RecordedEvent::Moved(position)),
+                WindowEvent::CloseRequested => Some(RecordedEvent::CloseRequested),
+                WindowEvent::Destroyed => Some(RecordedEvent::Destroyed),
+                WindowEvent::ReceivedCharacter(c) => Some(RecordedEvent::ReceivedCharacter(c)),
+                WindowEvent::Focused(focused) => Some(RecordedEvent::Focused(focused)),
+                WindowEvent::KeyboardInput { input, .. } => {
+                    Some(RecordedEvent::KeyboardInput(input))
+                }
+                WindowEvent::CursorMoved {
+                    position,
+                    modifiers,
+                    ..
+                } => Some(RecordedEvent::CursorMoved {
+                    position,
+                    modifiers,
+                }),
+                WindowEvent::CursorEntered { .. } => Some(RecordedEvent::CursorEntered),
+                WindowEvent::CursorLeft { .. } => Some(RecordedEvent::CursorLeft),
+                WindowEvent::MouseWheel {
+                    delta,
+                    phase,
+                    modifiers,
+                    ..
+                } => Some(RecordedEvent::MouseWheel {
+                    delta,
+                    phase,
+                    modifiers,
+                }),
+                WindowEvent::MouseInput {
+                    state,
+                    button,
+                    modifiers,
+                    ..
+                } => Some(RecordedEvent::MouseInput {
+                    state,
+                    button,
+                    modifiers,
+                }),
+                WindowEvent::HiDpiFactorChanged(factor) => {
+                    Some(RecordedEvent::HiDpiFactorChanged(factor))
+                }
+                _ => None,
+            },
+            Event::DeviceEvent { ref event, .. } => match *event {
+                DeviceEvent::MouseMotion { delta } => Some(RecordedEvent::MouseMotion { delta }),
+                DeviceEvent::MouseWheel { delta } => {
+                    Some(RecordedEvent::DeviceMouseWheel { delta })
+                }
+                DeviceEvent::Key(input) => Some(RecordedEvent::DeviceKey(input)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Converts the event back to a winit event, with dummy window and device ids.
+    pub fn to_event(&self) -> Event {
+        // the ids are only compared with each other by the engine
+        let window_id = unsafe { WindowId::dummy() };
+        let device_id = unsafe { DeviceId::dummy() };
+        let window = |event| Event::WindowEvent { window_id, event };
+        let device = |event| Event::DeviceEvent { device_id, event };
+        match *self {
+            RecordedEvent::Resized(size) => window(WindowEvent::Resized(size)),
+            RecordedEvent::Moved(position) => window(WindowEvent::Moved(position)),
+            RecordedEvent::CloseRequested => window(WindowEvent::CloseRequested),
+            RecordedEvent::Destroyed => window(WindowEvent::Destroyed),
+            RecordedEvent::ReceivedCharacter(c) => window(WindowEvent::ReceivedCharacter(c)),
+            RecordedEvent::Focused(focused) => window(WindowEvent::Focused(focused)),
+            RecordedEvent::KeyboardInput(input) => {
+                window(WindowEvent::KeyboardInput { device_id, input })
+            }
+            RecordedEvent::CursorMoved {
+                position,
+                modifiers,
+            } => window(WindowEvent::CursorMoved {
+                device_id,
+                position,
+                modifiers,
+            }),
+            RecordedEvent::CursorEntered => window(WindowEvent::CursorEntered { device_id }),
+            RecordedEvent::CursorLeft => window(WindowEvent::CursorLeft { device_id }),
+            RecordedEvent::MouseWheel {
+                delta,
+                phase,
+                modifiers,
+            } => window(WindowEvent::MouseWheel {
+                device_id,
+                delta,
+                phase,
+                modifiers,
+            }),
+            RecordedEvent::MouseInput {
+                state,
+                button,
+                modifiers,
+            } => window(WindowEvent::MouseInput {
+                device_id,
+                state,
+                button,
+                modifiers,
+            }),
+            RecordedEvent::HiDpiFactorChanged(factor) => {
+                window(WindowEvent::HiDpiFactorChanged(factor))
+            }
+            RecordedEvent::MouseMotion { delta } => device(DeviceEvent::MouseMotion { delta }),
+            RecordedEvent::DeviceMouseWheel { delta } => device(DeviceEvent::MouseWheel { delta }),
+            RecordedEvent::DeviceKey(input) => device(DeviceEvent::Key(input)),
+        }
+    }
+}
+
+/// A single frame of a `Recording`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    /// The time the frame took, used as the `Time` delta of the next frame.
+    pub delta: Duration,
+    /// The winit events sent during the frame.
+    pub events: Vec<RecordedEvent>,
+    /// The number of `TransEvent`s applied at the start of the frame.
+    ///
+    /// `TransEvent`s are closures and can't be recorded. During a replay the transitions sent
+    /// again by the states and systems are held back until the frames they were recorded in,
+    /// and the replay desyncs if one is missing.
+    pub transitions: usize,
+}
+
+/// The frames of an application run, recorded with `ApplicationBuilder::with_recording` and
+/// replayed with `ApplicationBuilder::with_replay`.
+///
+/// Recordings are stored in the RON format, use `Config::load_no_fallback` to load one.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    /// The duration between fixed updates of the run.
+    pub fixed_time: Duration,
+    /// The recorded frames.
+    pub frames: Vec<RecordedFrame>,
+}
+
+/// Records or replays the frames of a `CoreApplication`.
+pub(crate) enum Replay {
+    Record {
+        path: PathBuf,
+        recording: Recording,
+        current: RecordedFrame,
+        reader: Option<ReaderId<Event>>,
+    },
+    Play {
+        recording: Recording,
+        frame: usize,
+        // the first frame whose recorded transitions were not sent.
+        desync: Option<usize>,
+    },
+}
+
+impl Replay {
+    /// Records the frames of the application to the file at `path`, which is written once the
+    /// application is dropped.
+    pub(crate) fn record(path: PathBuf) -> Self {
+        Replay::Record {
+            path,
+            recording: Recording::default(),
+            current: RecordedFrame::default(),
+            reader: None,
+        }
+    }
+
+    /// Replays the given recording.
+    pub(crate) fn play(recording: Recording) -> Self {
+        Replay::Play {
+            recording,
+            frame: 0,
+            desync: None,
+        }
+    }
+
+    /// Called before the first frame.
+    pub(crate) fn initialize(&mut self, world: &mut World) {
+        match *self {
+            Replay::Record {
+                ref mut recording,
+                ref mut reader,
+                ..
+            } => {
+                recording.fixed_time = world.read_resource::<Time>().fixed_time();
+                *reader = Some(
+                    world
+                        .write_resource::<EventChannel<Event>>()
+                        .register_reader(),
+                );
+            }
+            Replay::Play { ref recording, .. } => {
+                world
+                    .write_resource::<Time>()
+                    .set_fixed_time(recording.fixed_time);
+                world.insert(SuppressInput(true));
+            }
+        }
+    }
+
+    /// Returns `true` once all recorded frames were replayed.
+    pub(crate) fn is_finished(&self) -> bool {
+        match *self {
+            Replay::Record { .. } => false,
+            Replay::Play {
+                ref recording,
+                frame,
+                ..
+            } => frame >= recording.frames.len(),
+        }
+    }
+
+    /// Returns the first replayed frame which diverged from the recording.
+    pub(crate) fn desync(&self) -> Option<usize> {
+        match *self {
+            Replay::Record { .. } => None,
+            Replay::Play { desync, .. } => desync,
+        }
+    }
+
+    /// Called with the transitions sent since the last frame, returns the transitions to apply
+    /// at the start of this frame.
+    ///
+    /// While replaying, the transitions are held back in `held` until the frame they were recorded in.
+    pub(crate) fn transitions<T>(&mut self, held: &mut Vec<T>, sent: Vec<T>) -> Vec<T> {
+        match *self {
+            Replay::Record {
+                ref mut current, ..
+            } => {
+                current.transitions = sent.len();
+                sent
+            }
+            Replay::Play {
+                ref recording,
+                frame,
+                ref mut desync,
+            } => {
+                held.extend(sent);
+
+                let recorded = recording.frames.get(frame).map_or(0, |f| f.transitions);
+                let last_frame = frame + 1 >= recording.frames.len();
+                if recorded > held.len() || (last_frame && recorded < held.len()) {
+                    warn!(
+                        "Replay desync in frame {}: {} transitions were recorded, {} were sent",
+                        frame,
+                        recorded,
+                        held.len()
+                    );
+                    if desync.is_none() {
+                        *desync = Some(frame);
+                    }
+                }
+
+                let due = recorded.min(held.len());
+                held.drain(..due).collect()
+            }
+        }
+    }
+
+    /// Called before the states are updated, sends the recorded events of the frame.
+    pub(crate) fn send_events(&mut self, world: &World) {
+        if let Replay::Play {
+            ref recording,
+            frame,
+            ..
+        } = *self
+        {
+            if let Some(recorded) = recording.frames.get(frame) {
+                world
+                    .write_resource::<EventChannel<Event>>()
+                    .iter_write(recorded.events.iter().map(RecordedEvent::to_event));
+            }
+        }
+    }
+
+    /// Called at the end of the frame with the time it took,
+    /// returns the delta time to use for the next frame.
+    pub(crate) fn end_frame(&mut self, world: &World, elapsed: Duration) -> Duration {
+        match *self {
+            Replay::Record {
+                ref mut recording,
+                ref mut current,
+                reader: Some(ref mut reader),
+                ..
+            } => {
+                current.delta = elapsed;
+                current.events.extend(
+                    world
+                        .read_resource::<EventChannel<Event>>()
+                        .read(reader)
+                        .filter_map(RecordedEvent::from_event),
+                );
+                recording
+                    .frames
+                    .push(std::mem::replace(current, RecordedFrame::default()));
+                elapsed
+            }
+            Replay::Play {
+                ref recording,
+                ref mut frame,
+                ..
+            } => {
+                let delta = recording
+                    .frames
+                    .get(*frame)
+                    .map_or(elapsed, |recorded| recorded.delta);
+                *frame += 1;
+                delta
+            }
+            Replay::Record { reader: None, .. } => elapsed,
+        }
+    }
+}
+
+impl Drop for Replay {
+    fn drop(&mut self) {
+        if let Replay::Record {
+            ref path,
+            ref recording,
+            reader: Some(_),
+            ..
+        } = *self
+        {
+            // also runs when unwinding, so a recording of a crash is kept
+            match recording.write(path) {
+                Ok(()) => info!(
+                    "Saved recording of {} frames to {:?}",
+                    recording.frames.len(),
+                    path
+                ),
+                Err(e) => error!("Failed to save recording to {:?}: {}", path, e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use winit::{ElementState, KeyboardInput, ModifiersState, VirtualKeyCode};
+
+    use crate::ecs::prelude::{World, WorldExt};
+
+    use super::{RecordedEvent, RecordedFrame, Recording, Replay};
+
+    #[test]
+    fn recording_round_trips() {
+        let key = RecordedEvent::KeyboardInput(KeyboardInput {
+            scancode: 104,
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::Up),
+            modifiers: ModifiersState::default(),
+        });
+        assert_eq!(
+            RecordedEvent::from_event(&key.to_event()),
+            Some(key.clone())
+        );
+
+        let recording = Recording {
+            fixed_time: Duration::from_millis(10),
+            frames: vec![
+                RecordedFrame {
+                    delta: Duration::from_millis(16),
+                    events: vec![key],
+                    transitions: 1,
+                },
+                RecordedFrame {
+                    delta: Duration::from_millis(17),
+                    events: vec![RecordedEvent::CloseRequested],
+                    transitions: 0,
+                },
+            ],
+        };
+        let ron = ron::ser::to_string(&recording).unwrap();
+        assert_eq!(ron::de::from_str::<Recording>(&ron).unwrap(), recording);
+    }
+
+    #[test]
+    fn replay_uses_recorded_deltas() {
+        let world = World::new();
+        let mut replay = Replay::play(Recording {
+            fixed_time: Duration::from_millis(10),
+            frames: vec![
+                RecordedFrame {
+                    delta: Duration::from_millis(16),
+                    ..Default::default()
+                },
+                RecordedFrame {
+                    delta: Duration::from_millis(17),
+                    ..Default::default()
+                },
+            ],
+        });
+
+        let measured = Duration::from_millis(1);
+        assert_eq!(
+            replay.end_frame(&world, measured),
+            Duration::from_millis(16)
+        );
+        assert!(!replay.is_finished());
+        assert_eq!(
+            replay.end_frame(&world, measured),
+            Duration::from_millis(17)
+        );
+        assert!(replay.is_finished());
+    }
+
+    #[test]
+    fn replay_holds_transitions_until_their_frame() {
+        let frame = |transitions| RecordedFrame {
+            transitions,
+            ..Default::default()
+        };
+        let mut replay = Replay::play(Recording {
+            fixed_time: Duration::from_millis(10),
+            frames: vec![frame(0), frame(2), frame(1)],
+        });
+        let world = World::new();
+        let mut held = Vec::new();
+
+        // sent one frame early, so both are held back
+        assert!(replay.transitions(&mut held, vec!["a", "b"]).is_empty());
+        replay.end_frame(&world, Duration::from_millis(1));
+        assert_eq!(replay.transitions(&mut held, vec![]), vec!["a", "b"]);
+        replay.end_frame(&world, Duration::from_millis(1));
+        assert_eq!(replay.desync(), None);
+
+        // the transition recorded in the last frame is missing
+        assert!(replay.transitions(&mut held, vec![]).is_empty());
+        assert_eq!(replay.desync(), Some(2));
+    }
+}
@@ -3,7 +3,9 @@
 use amethyst_input::is_close_requested;
 
 use derivative::Derivative;
+use log::warn;
 
+use self::private::StateType;
 use crate::{ecs::World, GameData, StateEvent};
 
 use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
@@ -62,6 +64,26 @@ pub enum Trans<T, E> {
     Switch(Box<dyn State<T, E>>),
     /// Stop and remove all states and shut down the engine.
     Quit,
+    /// Remove all states on the stack and push the given ones, the last one becomes active.
+    ///
+    /// Stops the state machine if no states are given.
+    Replace(Vec<Box<dyn State<T, E>>>),
+    /// Remove the given number of states and resume the next state on the stack or stop
+    /// if there are none.
+    PopN(usize),
+    /// Remove states until the active state has the given `State::name`.
+    ///
+    /// Does nothing if no state on the stack has the name.
+    PopUntil(String),
+    /// Remove states until the active state has the given type name, see `Trans::pop_until`.
+    ///
+    /// Does nothing if no state on the stack has the type.
+    PopUntilType(&'static str),
+    /// Apply the given transitions in order, as a single transition.
+    ///
+    /// Only the state active at the end is resumed, and the stack may become empty in between
+    /// without stopping the state machine.
+    Sequence(Vec<Trans<T, E>>),
 }
 impl<T, E> Debug for Trans<T, E> {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
@@ -71,10 +93,27 @@ impl<T, E> Debug for Trans<T, E> {
             Trans::Push(_) => f.write_str("Push"),
             Trans::Switch(_) => f.write_str("Switch"),
             Trans::Quit => f.write_str("Quit"),
+            Trans::Replace(ref states) => write!(f, "Replace({})", states.len()),
+            Trans::PopN(count) => write!(f, "PopN({})", count),
+            Trans::PopUntil(ref name) => write!(f, "PopUntil({:?})", name),
+            Trans::PopUntilType(type_name) => write!(f, "PopUntilType({})", type_name),
+            Trans::Sequence(ref transitions) => {
+                f.debug_tuple("Sequence").field(transitions).finish()
+            }
         }
     }
 }
 
+impl<T, E: Send + Sync + 'static> Trans<T, E> {
+    /// Remove states until the active state is of type `S`.
+    ///
+    /// States are not required to be `'static`, so they are matched by `std::any::type_name`
+    /// rather than by `TypeId`.
+    pub fn pop_until<S: State<T, E>>() -> Self {
+        Trans::PopUntilType(std::any::type_name::<S>())
+    }
+}
+
 /// Event queue to trigger state `Trans` from other places than a `State`'s methods.
 /// # Example:
 /// ```rust, ignore
@@ -92,7 +131,7 @@ pub type EmptyTrans = Trans<(), StateEvent>;
 pub type SimpleTrans = Trans<GameData<'static, 'static>, StateEvent>;
 
 /// A trait which defines game states that can be used by the state machine.
-pub trait State<T, E: Send + Sync + 'static> {
+pub trait State<T, E: Send + Sync + 'static>: private::StateType {
     /// Executed when the game state begins.
     fn on_start(&mut self, _data: StateData<'_, T>) {}
 
@@ -133,6 +172,30 @@ pub trait State<T, E: Send + Sync + 'static> {
     /// even when this is not the active state,
     /// as long as this state is on the [StateMachine](struct.StateMachine.html)'s state-stack.
     fn shadow_update(&mut self, _data: StateData<'_, T>) {}
+
+    /// The name of the state, as returned by `StateMachine::state_names` and matched by
+    /// `Trans::PopUntil`.
+    ///
+    /// Defaults to the name of the state type.
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+mod private {
+    /// Gives every state the name of its type, matched by `Trans::PopUntilType`.
+    ///
+    /// Implemented for every type, so states can't override it.
+    pub trait StateType {
+        /// The name of the state type.
+        fn type_name(&self) -> &'static str;
+    }
+
+    impl<S> StateType for S {
+        fn type_name(&self) -> &'static str {
+            std::any::type_name::<S>()
+        }
+    }
 }
 
 /// An empty `State` trait. It contains no `StateData` or custom `StateEvent`.
@@ -183,6 +246,14 @@ pub trait EmptyState {
     /// even when this is not the active state,
     /// as long as this state is on the [StateMachine](struct.StateMachine.html)'s state-stack.
     fn shadow_update(&mut self, _data: StateData<'_, ()>) {}
+
+    /// The name of the state, as returned by `StateMachine::state_names` and matched by
+    /// `Trans::PopUntil`.
+    ///
+    /// Defaults to the name of the state type.
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
 }
 
 impl<T: EmptyState> State<(), StateEvent> for T {
@@ -236,6 +307,10 @@ impl<T: EmptyState> State<(), StateEvent> for T {
     fn shadow_update(&mut self, data: StateData<'_, ()>) {
         self.shadow_update(data);
     }
+    /// The name of the state.
+    fn name(&self) -> &str {
+        EmptyState::name(self)
+    }
 }
 
 /// A simple `State` trait. It contains `GameData` as its `StateData` and no custom `StateEvent`.
@@ -290,6 +365,14 @@ pub trait SimpleState {
     /// even when this is not the active state,
     /// as long as this state is on the [StateMachine](struct.StateMachine.html)'s state-stack.
     fn shadow_update(&mut self, _data: StateData<'_, GameData<'_, '_>>) {}
+
+    /// The name of the state, as returned by `StateMachine::state_names` and matched by
+    /// `Trans::PopUntil`.
+    ///
+    /// Defaults to the name of the state type.
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
 }
 
 impl<T: SimpleState> State<GameData<'static, 'static>, StateEvent> for T {
@@ -351,6 +434,10 @@ impl<T: SimpleState> State<GameData<'static, 'static>, StateEvent> for T {
     fn shadow_update(&mut self, data: StateData<'_, GameData<'_, '_>>) {
         self.shadow_update(data);
     }
+    /// The name of the state.
+    fn name(&self) -> &str {
+        SimpleState::name(self)
+    }
 }
 
 /// A simple stack-based state machine (pushdown automaton).
@@ -460,63 +547,124 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
     /// sequentially in the order of insertion.
     pub fn transition(&mut self, request: Trans<T, E>, data: StateData<'_, T>) {
         if self.running {
-            match request {
-                Trans::None => (),
-                Trans::Pop => self.pop(data),
-                Trans::Push(state) => self.push(state, data),
-                Trans::Switch(state) => self.switch(state, data),
-                Trans::Quit => self.stop(data),
+            let StateData { world, data } = data;
+            let mut top_active = true;
+            self.apply(request, &mut top_active, StateData { world, data });
+
+            if self.running {
+                match self.state_stack.last_mut() {
+                    Some(state) if !top_active => state.on_resume(StateData { world, data }),
+                    Some(_) => (),
+                    None => self.running = false,
+                }
             }
         }
     }
 
-    /// Removes the current state on the stack and inserts a different one.
-    fn switch(&mut self, state: Box<dyn State<T, E>>, data: StateData<'_, T>) {
-        if self.running {
-            let StateData { world, data } = data;
-            if let Some(mut state) = self.state_stack.pop() {
-                state.on_stop(StateData { world, data });
-            }
+    /// Returns the number of states on the stack.
+    pub fn depth(&self) -> usize {
+        self.state_stack.len()
+    }
+
+    /// Returns the `State::name` of every state on the stack, from the bottom to the active state.
+    pub fn state_names(&self) -> Vec<&str> {
+        self.state_stack.iter().map(|state| state.name()).collect()
+    }
+
+    /// Returns the `State::name` of the active state.
+    pub fn active_state_name(&self) -> Option<&str> {
+        self.state_stack.last().map(|state| state.name())
+    }
 
-            self.state_stack.push(state);
+    /// Applies a transition without resuming the state on top of the stack, `top_active` tracks
+    /// whether that state is active.
+    fn apply(&mut self, request: Trans<T, E>, top_active: &mut bool, data: StateData<'_, T>) {
+        let StateData { world, data } = data;
+        match request {
+            Trans::None => (),
+            Trans::Pop => self.pop(top_active, StateData { world, data }),
+            Trans::Push(state) => self.push(state, top_active, StateData { world, data }),
+            Trans::Switch(state) => {
+                self.pop(top_active, StateData { world, data });
+                self.push(state, top_active, StateData { world, data });
+            }
+            Trans::Quit => self.stop(StateData { world, data }),
+            Trans::Replace(states) => {
+                while !self.state_stack.is_empty() {
+                    self.pop(top_active, StateData { world, data });
+                }
+                for state in states {
+                    self.push(state, top_active, StateData { world, data });
+                }
+            }
+            Trans::PopN(count) => {
+                for _ in 0..count.min(self.state_stack.len()) {
+                    self.pop(top_active, StateData { world, data });
+                }
+            }
+            Trans::PopUntil(name) => {
+                match self
+                    .state_stack
+                    .iter()
+                    .rposition(|state| state.name() == name)
+                {
+                    Some(index) => self.truncate(index + 1, top_active, StateData { world, data }),
+                    None => warn!("No state named {:?} to pop until", name),
+                }
+            }
+            Trans::PopUntilType(type_name) => {
+                match self
+                    .state_stack
+                    .iter()
+                    .rposition(|state| StateType::type_name(&**state) == type_name)
+                {
+                    Some(index) => self.truncate(index + 1, top_active, StateData { world, data }),
+                    None => warn!("No state of type {} to pop until", type_name),
+                }
+            }
+            Trans::Sequence(transitions) => {
+                for request in transitions {
+                    // a `Trans::Quit` stops the remaining transitions
+                    if self.running {
+                        self.apply(request, top_active, StateData { world, data });
+                    }
+                }
+            }
+        }
+    }
 
-            //State was just pushed, thus pop will always succeed
-            let new_state = self.state_stack.last_mut().unwrap();
-            new_state.on_start(StateData { world, data });
+    /// Pops states until the given number of states is left on the stack.
+    fn truncate(&mut self, len: usize, top_active: &mut bool, data: StateData<'_, T>) {
+        let StateData { world, data } = data;
+        while self.state_stack.len() > len {
+            self.pop(top_active, StateData { world, data });
         }
     }
 
     /// Pauses the active state and pushes a new state onto the state stack.
-    fn push(&mut self, state: Box<dyn State<T, E>>, data: StateData<'_, T>) {
-        if self.running {
-            let StateData { world, data } = data;
-            if let Some(state) = self.state_stack.last_mut() {
+    fn push(&mut self, state: Box<dyn State<T, E>>, top_active: &mut bool, data: StateData<'_, T>) {
+        let StateData { world, data } = data;
+        if let Some(state) = self.state_stack.last_mut() {
+            if *top_active {
                 state.on_pause(StateData { world, data });
             }
+        }
 
-            self.state_stack.push(state);
+        self.state_stack.push(state);
 
-            //State was just pushed, thus pop will always succeed
-            let new_state = self.state_stack.last_mut().unwrap();
-            new_state.on_start(StateData { world, data });
-        }
+        //State was just pushed, thus pop will always succeed
+        let new_state = self.state_stack.last_mut().unwrap();
+        new_state.on_start(StateData { world, data });
+        *top_active = true;
     }
 
-    /// Stops and removes the active state and un-pauses the next state on the
-    /// stack (if any).
-    fn pop(&mut self, data: StateData<'_, T>) {
-        if self.running {
-            let StateData { world, data } = data;
-            if let Some(mut state) = self.state_stack.pop() {
-                state.on_stop(StateData { world, data });
-            }
-
-            if let Some(state) = self.state_stack.last_mut() {
-                state.on_resume(StateData { world, data });
-            } else {
-                self.running = false;
-            }
+    /// Stops and removes the active state, the next state on the stack (if any) stays paused.
+    fn pop(&mut self, top_active: &mut bool, data: StateData<'_, T>) {
+        let StateData { world, data } = data;
+        if let Some(mut state) = self.state_stack.pop() {
+            state.on_stop(StateData { world, data });
         }
+        *top_active = false;
     }
 
     /// Shuts the state machine down.
@@ -534,6 +682,8 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
 
 #[cfg(test)]
 mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
     use super::*;
 
     struct State1(u8);
@@ -574,4 +724,117 @@ mod tests {
         sm.update(StateData::new(&mut world, &mut ()));
         assert!(!sm.is_running());
     }
+
+    struct Logged(&'static str, Rc<RefCell<Vec<String>>>);
+
+    impl Logged {
+        fn boxed(name: &'static str, log: &Rc<RefCell<Vec<String>>>) -> Box<dyn State<(), ()>> {
+            Box::new(Logged(name, log.clone()))
+        }
+
+        fn log(&self, event: &str) {
+            self.1.borrow_mut().push(format!("{} {}", event, self.0));
+        }
+    }
+
+    impl State<(), ()> for Logged {
+        fn on_start(&mut self, _: StateData<'_, ()>) {
+            self.log("start");
+        }
+
+        fn on_stop(&mut self, _: StateData<'_, ()>) {
+            self.log("stop");
+        }
+
+        fn on_pause(&mut self, _: StateData<'_, ()>) {
+            self.log("pause");
+        }
+
+        fn on_resume(&mut self, _: StateData<'_, ()>) {
+            self.log("resume");
+        }
+
+        fn name(&self) -> &str {
+            self.0
+        }
+    }
+
+    #[test]
+    fn replace_pop_until_and_sequence() {
+        use crate::ecs::prelude::{World, WorldExt};
+
+        let mut world = World::new();
+        let log = Rc::new(RefCell::new(Vec::new()));
+
+        let mut sm = StateMachine::new(Logged("loading", log.clone()));
+        sm.start(StateData::new(&mut world, &mut ())).unwrap();
+        sm.transition(
+            Trans::Replace(vec![
+                Logged::boxed("game", &log),
+                Logged::boxed("pause", &log),
+                Logged::boxed("options", &log),
+            ]),
+            StateData::new(&mut world, &mut ()),
+        );
+        assert_eq!(sm.depth(), 3);
+        assert_eq!(sm.state_names(), vec!["game", "pause", "options"]);
+
+        sm.transition(
+            Trans::PopUntil("game".into()),
+            StateData::new(&mut world, &mut ()),
+        );
+        assert_eq!(sm.active_state_name(), Some("game"));
+
+        log.borrow_mut().clear();
+        sm.transition(
+            Trans::Sequence(vec![Trans::Pop, Trans::Push(Logged::boxed("menu", &log))]),
+            StateData::new(&mut world, &mut ()),
+        );
+        assert!(sm.is_running());
+        assert_eq!(sm.state_names(), vec!["menu"]);
+        assert_eq!(*log.borrow(), vec!["stop game", "start menu"]);
+
+        sm.transition(Trans::PopN(2), StateData::new(&mut world, &mut ()));
+        assert!(!sm.is_running());
+    }
+
+    struct Game;
+
+    impl State<(), ()> for Game {}
+
+    #[test]
+    fn pop_until_type_and_default_name() {
+        use crate::ecs::prelude::{World, WorldExt};
+
+        let mut world = World::new();
+        let log = Rc::new(RefCell::new(Vec::new()));
+
+        let mut sm = StateMachine::new(Game);
+        sm.start(StateData::new(&mut world, &mut ())).unwrap();
+        sm.transition(
+            Trans::Sequence(vec![
+                Trans::Push(Logged::boxed("pause", &log)),
+                Trans::Push(Logged::boxed("options", &log)),
+            ]),
+            StateData::new(&mut world, &mut ()),
+        );
+        assert_eq!(sm.depth(), 3);
+
+        sm.transition(
+            Trans::pop_until::<Game>(),
+            StateData::new(&mut world, &mut ()),
+        );
+        assert_eq!(sm.depth(), 1);
+        assert_eq!(sm.active_state_name(), Some(std::any::type_name::<Game>()));
+        assert_eq!(
+            *log.borrow(),
+            vec![
+                "start pause",
+                "pause pause",
+                "start options",
+                "stop options",
+                "stop pause"
+            ]
+        );
+    }
 }